@@ -0,0 +1,345 @@
+//! Generic Stream Encapsulation out of baseband frames (TS 102 606-1).
+//!
+//! A generic continuous stream carries GSE packets back to back in the
+//! data field of its frames. A PDU either fits one GSE packet or is cut into
+//! fragments that share a fragment id and may run over several frames; the
+//! first fragment carries the total length and the last one a CRC-32 over
+//! the total length and the reassembled PDU. A GSE header that starts with four zero bits ends
+//! the useful part of the data field.
+
+use libmpegts::utils::crc32b;
+
+use super::{
+    BbFrame,
+    StreamFormat,
+};
+
+/// Label of a GSE PDU, the address of the receiver it is for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GseLabel {
+    /// 6-byte label, a MAC address.
+    Six([u8; 6]),
+    /// 3-byte label.
+    Three([u8; 3]),
+    /// No label: the PDU is for every receiver.
+    Broadcast,
+}
+
+/// A PDU taken out of GSE.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GsePdu {
+    /// Protocol type: an EtherType such as `0x0800` for IPv4 from `0x0600`
+    /// up, the type of the first extension header below.
+    pub protocol_type: u16,
+    /// Label of the receiver.
+    pub label: GseLabel,
+    /// The PDU, with any extension headers ahead of it.
+    pub data: Vec<u8>,
+}
+
+/// A PDU whose fragments are arriving.
+#[derive(Debug)]
+struct Fragments {
+    label_type: u8,
+    label: GseLabel,
+    total_length: u16,
+    /// Protocol type, label and the PDU so far: what the total length
+    /// counts and the CRC-32 covers after it.
+    data: Vec<u8>,
+}
+
+/// Cuts `size` bytes off the front of `data`.
+fn take<'a>(data: &mut &'a [u8], size: usize) -> Option<&'a [u8]> {
+    let field = data.get(.. size)?;
+    *data = &data[size ..];
+    Some(field)
+}
+
+/// Decodes the protocol type and label at the start of `data`, and returns
+/// the PDU after them. Label type 3 re-uses `last_label`.
+fn pdu(label_type: u8, mut data: &[u8], last_label: &mut Option<GseLabel>) -> Option<GsePdu> {
+    let protocol_type = take(&mut data, 2)?;
+    let label = match label_type {
+        0 => GseLabel::Six(take(&mut data, 6)?.try_into().ok()?),
+        1 => GseLabel::Three(take(&mut data, 3)?.try_into().ok()?),
+        2 => GseLabel::Broadcast,
+        _ => (*last_label)?,
+    };
+    *last_label = Some(label);
+
+    Some(GsePdu {
+        protocol_type: u16::from_be_bytes([protocol_type[0], protocol_type[1]]),
+        label,
+        data: data.to_vec(),
+    })
+}
+
+/// Reassembles the PDUs of one generic stream from its baseband frames.
+#[derive(Debug)]
+pub struct GseReassembler {
+    isi: Option<u8>,
+    fragments: Vec<Option<Fragments>>,
+    errors: usize,
+}
+
+impl GseReassembler {
+    /// Reassembler of the stream `isi`, or of any stream with `None`.
+    /// Frames of other streams, and those that carry no GSE, are passed
+    /// over.
+    pub fn new(isi: Option<u8>) -> GseReassembler {
+        GseReassembler {
+            isi,
+            fragments: (0 .. 256).map(|_| None).collect(),
+            errors: 0,
+        }
+    }
+
+    /// GSE packets that could not be decoded, and PDUs dropped for a lost
+    /// fragment, a wrong length or CRC-32, so far.
+    pub fn errors(&self) -> usize {
+        self.errors
+    }
+
+    /// Takes in a frame. Returns the PDUs it completes.
+    pub fn push(&mut self, frame: &BbFrame) -> Vec<GsePdu> {
+        let header = &frame.header;
+        let mut pdus = Vec::new();
+        if !matches!(
+            header.format,
+            StreamFormat::GenericContinuous | StreamFormat::GseLite
+        ) || (!header.single_stream && self.isi.is_some_and(|isi| isi != header.isi))
+        {
+            return pdus;
+        }
+
+        // label re-use refers to the packet before in the same frame
+        let mut last_label = None;
+        let mut data = &frame.data[..];
+        while data.len() >= 2 && data[0] & 0xF0 != 0 {
+            let length = 2 + usize::from(u16::from_be_bytes([data[0] & 0x0F, data[1]]));
+            let Some(packet) = take(&mut data, length) else {
+                self.errors += 1;
+                break;
+            };
+
+            match self.packet(packet, &mut last_label) {
+                Some(Some(pdu)) => pdus.push(pdu),
+                Some(None) => {}
+                None => self.errors += 1,
+            }
+        }
+
+        pdus
+    }
+
+    /// Decodes one GSE packet: a PDU when it is complete or completes one,
+    /// `None` on an error.
+    fn packet(
+        &mut self,
+        packet: &[u8],
+        last_label: &mut Option<GseLabel>,
+    ) -> Option<Option<GsePdu>> {
+        let start = packet[0] & 0x80 != 0;
+        let end = packet[0] & 0x40 != 0;
+        let label_type = (packet[0] >> 4) & 0x03;
+        let mut rest = &packet[2 ..];
+
+        if start && end {
+            return pdu(label_type, rest, last_label).map(Some);
+        }
+
+        let fragment_id = usize::from(take(&mut rest, 1)?[0]);
+        if start {
+            let total_length = take(&mut rest, 2)?;
+            let label = pdu(label_type, rest, last_label)?.label;
+            if self.fragments[fragment_id].is_some() {
+                // the PDU before never ended
+                self.errors += 1;
+            }
+            self.fragments[fragment_id] = Some(Fragments {
+                label_type,
+                label,
+                total_length: u16::from_be_bytes([total_length[0], total_length[1]]),
+                data: rest.to_vec(),
+            });
+            return Some(None);
+        }
+
+        let fragments = self.fragments[fragment_id].as_mut()?;
+        if !end {
+            fragments.data.extend_from_slice(rest);
+            return Some(None);
+        }
+
+        let fragments = self.fragments[fragment_id].take()?;
+        let (tail, crc) = rest.split_at(rest.len().checked_sub(4)?);
+        // the CRC-32 covers the total length field as well
+        let mut covered = fragments.total_length.to_be_bytes().to_vec();
+        covered.extend_from_slice(&fragments.data);
+        covered.extend_from_slice(tail);
+        let data = &covered[2 ..];
+        if data.len() != usize::from(fragments.total_length)
+            || crc32b(&covered) != u32::from_be_bytes([crc[0], crc[1], crc[2], crc[3]])
+        {
+            return None;
+        }
+
+        pdu(fragments.label_type, data, &mut Some(fragments.label)).map(Some)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bbframe::BbHeader;
+
+    /// A GSE packet: start and end flags, label type and the fields after
+    /// the GSE length.
+    fn gse_packet(start: bool, end: bool, label_type: u8, fields: &[u8]) -> Vec<u8> {
+        let length = fields.len() as u16;
+        let mut packet = vec![
+            (u8::from(start) << 7) | (u8::from(end) << 6) | (label_type << 4) | (length >> 8) as u8,
+            length as u8,
+        ];
+        packet.extend_from_slice(fields);
+        packet
+    }
+
+    fn frame(isi: u8, data: Vec<u8>) -> BbFrame {
+        BbFrame {
+            header: BbHeader {
+                format: StreamFormat::GenericContinuous,
+                single_stream: false,
+                ccm: false,
+                issy: false,
+                npd: false,
+                rolloff: None,
+                isi,
+                upl: 0,
+                dfl: (data.len() * 8) as u16,
+                sync: 0,
+                syncd: 0,
+                high_efficiency: false,
+            },
+            data,
+        }
+    }
+
+    const MAC: [u8; 6] = [0x02, 0x00, 0x5E, 0x10, 0x20, 0x30];
+
+    #[test]
+    fn complete_packets_and_label_reuse() {
+        let mut data = gse_packet(true, true, 0, &[&[0x08, 0x00][..], &MAC, b"first"].concat());
+        data.extend(gse_packet(
+            true,
+            true,
+            3,
+            &[&[0x86, 0xDD][..], b"second"].concat(),
+        ));
+        data.extend(gse_packet(
+            true,
+            true,
+            2,
+            &[&[0x08, 0x00][..], b"third"].concat(),
+        ));
+        // padding
+        data.extend([0x00; 20]);
+
+        let mut reassembler = GseReassembler::new(Some(1));
+        assert!(reassembler.push(&frame(2, data.clone())).is_empty());
+        let pdus = reassembler.push(&frame(1, data));
+        assert_eq!(
+            pdus,
+            [
+                GsePdu {
+                    protocol_type: 0x0800,
+                    label: GseLabel::Six(MAC),
+                    data: b"first".to_vec(),
+                },
+                GsePdu {
+                    protocol_type: 0x86DD,
+                    label: GseLabel::Six(MAC),
+                    data: b"second".to_vec(),
+                },
+                GsePdu {
+                    protocol_type: 0x0800,
+                    label: GseLabel::Broadcast,
+                    data: b"third".to_vec(),
+                },
+            ]
+        );
+        assert_eq!(reassembler.errors(), 0);
+
+        // label re-use without a label before it in the frame
+        let data = gse_packet(true, true, 3, &[0x08, 0x00, 0x45]);
+        assert!(reassembler.push(&frame(1, data)).is_empty());
+        assert_eq!(reassembler.errors(), 1);
+    }
+
+    #[test]
+    fn fragments_span_frames() {
+        let payload: Vec<u8> = (0 .. 300).map(|i| i as u8).collect();
+        let covered = [&[0x08, 0x00][..], &[0xAB, 0xCD, 0xEF], &payload].concat();
+        let total = (covered.len() as u16).to_be_bytes();
+        let crc = crc32b([&total[..], &covered].concat()).to_be_bytes();
+
+        let first = gse_packet(
+            true,
+            false,
+            1,
+            &[&[7][..], &total, &covered[.. 100]].concat(),
+        );
+        let middle = gse_packet(false, false, 1, &[&[7][..], &covered[100 .. 200]].concat());
+        let last =
+            |crc: [u8; 4]| gse_packet(false, true, 1, &[&[7][..], &covered[200 ..], &crc].concat());
+
+        let mut reassembler = GseReassembler::new(None);
+        assert!(reassembler.push(&frame(0, first.clone())).is_empty());
+        assert!(reassembler.push(&frame(0, middle.clone())).is_empty());
+        let pdus = reassembler.push(&frame(0, last(crc)));
+        assert_eq!(
+            pdus,
+            [GsePdu {
+                protocol_type: 0x0800,
+                label: GseLabel::Three([0xAB, 0xCD, 0xEF]),
+                data: payload,
+            }]
+        );
+
+        // a lost middle fragment fails the length check
+        reassembler.push(&frame(0, first.clone()));
+        assert!(reassembler.push(&frame(0, last(crc))).is_empty());
+        assert_eq!(reassembler.errors(), 1);
+
+        // and a corrupt one the CRC-32
+        reassembler.push(&frame(0, first));
+        reassembler.push(&frame(0, middle));
+        assert!(reassembler.push(&frame(0, last([0; 4]))).is_empty());
+        assert_eq!(reassembler.errors(), 2);
+    }
+
+    #[test]
+    fn fragment_crc_covers_the_total_length() {
+        // an IPv4 header start in two fragments, the CRC-32 computed apart
+        // over Total_Length, Protocol_Type and the PDU as TS 102 606-1 lays
+        // them out
+        let first = [
+            0xA0, 0x09, 0x09, 0x00, 0x0C, 0x08, 0x00, 0x45, 0x00, 0x00, 0x14,
+        ];
+        let last = [
+            0x60, 0x0B, 0x09, 0x00, 0x01, 0x00, 0x00, 0x40, 0x11, 0x7F, 0x4F, 0x96, 0x28,
+        ];
+
+        let mut reassembler = GseReassembler::new(None);
+        let pdus = reassembler.push(&frame(0, [&first[..], &last].concat()));
+        assert_eq!(
+            pdus,
+            [GsePdu {
+                protocol_type: 0x0800,
+                label: GseLabel::Broadcast,
+                data: vec![0x45, 0x00, 0x00, 0x14, 0x00, 0x01, 0x00, 0x00, 0x40, 0x11],
+            }]
+        );
+        assert_eq!(reassembler.errors(), 0);
+    }
+}
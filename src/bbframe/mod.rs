@@ -0,0 +1,426 @@
+//! DVB-S2 baseband frames.
+//!
+//! A DVB-S2 frontend switched to BBFrame delivery - the driver-specific bit
+//! of [`BBFRAME_STREAM_ID`] - hands over the baseband frames themselves
+//! instead of the transport stream decoded from them. The drivers wrap the
+//! frames in TS packets on one PID: the packet that starts a frame has the
+//! payload unit start indicator set, its payload opens with the BBHEADER -
+//! after a marker byte [`BBFRAME_MARKER`] on the drivers that add one - and
+//! the frame runs on over the payloads of the packets after it, as long as
+//! the data field length of the header says.
+//!
+//! [`BbFrameReader`] takes that pseudo transport stream apart into
+//! [`BbFrame`]s, and [`BbHeader`] decodes the header of one (EN 302 307-1,
+//! 5.1.6). [`TsReassembler`] rebuilds the transport stream of a stream
+//! from its frames, [`GseReassembler`] the PDUs of a generic stream.
+
+pub mod gse;
+pub mod ts;
+
+pub use self::{
+    gse::{
+        GseLabel,
+        GsePdu,
+        GseReassembler,
+    },
+    ts::TsReassembler,
+};
+use crate::{
+    error::{
+        Error,
+        Result,
+    },
+    fe::sys::Rolloff,
+};
+
+/// `DTV_STREAM_ID` bit that switches the frontends supporting it to BBFrame
+/// delivery.
+pub const BBFRAME_STREAM_ID: u32 = 0x8000_0000;
+
+/// Byte some drivers put before the BBHEADER in the packet that starts a
+/// frame.
+pub const BBFRAME_MARKER: u8 = 0xB8;
+
+/// Size of the BBHEADER.
+pub const BBHEADER_SIZE: usize = 10;
+
+const TS_PACKET_SIZE: usize = 188;
+const TS_SYNC_BYTE: u8 = 0x47;
+
+/// CRC-8 of the BBHEADER and the GSE, polynomial
+/// x^8 + x^7 + x^6 + x^4 + x^2 + 1.
+pub(crate) fn crc8(data: &[u8]) -> u8 {
+    let mut crc = 0u8;
+    for &byte in data {
+        crc ^= byte;
+        for _ in 0 .. 8 {
+            crc = if crc & 0x80 != 0 {
+                (crc << 1) ^ 0xD5
+            } else {
+                crc << 1
+            };
+        }
+    }
+
+    crc
+}
+
+/// Cuts a stream read in chunks of any size into TS packets.
+#[derive(Debug, Default)]
+pub(crate) struct TsPackets {
+    packet: Vec<u8>,
+}
+
+impl TsPackets {
+    /// Takes in a chunk and calls `f` with every packet it completes.
+    /// Bytes up to the next sync byte are skipped where a packet should
+    /// start.
+    pub(crate) fn push<F: FnMut(&[u8])>(&mut self, mut data: &[u8], mut f: F) {
+        while !data.is_empty() {
+            if self.packet.is_empty() {
+                let Some(start) = data.iter().position(|&b| b == TS_SYNC_BYTE) else {
+                    break;
+                };
+                data = &data[start ..];
+            }
+
+            let take = (TS_PACKET_SIZE - self.packet.len()).min(data.len());
+            self.packet.extend_from_slice(&data[.. take]);
+            data = &data[take ..];
+
+            if self.packet.len() == TS_PACKET_SIZE {
+                f(&self.packet);
+                self.packet.clear();
+            }
+        }
+    }
+}
+
+/// The payload of a TS packet, `None` when it has none.
+pub(crate) fn ts_payload(packet: &[u8]) -> Option<&[u8]> {
+    if packet[3] & 0x10 == 0 {
+        return None;
+    }
+
+    let offset = if packet[3] & 0x20 != 0 {
+        5 + usize::from(packet[4])
+    } else {
+        4
+    };
+    packet.get(offset ..)
+}
+
+/// What the data field of a baseband frame carries: the TS/GS field of
+/// MATYPE-1.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StreamFormat {
+    /// Generic packetized stream.
+    GenericPacketized,
+    /// Generic continuous stream, which GSE is carried as.
+    GenericContinuous,
+    /// GSE-Lite (EN 302 307-2).
+    GseLite,
+    /// Transport stream.
+    Transport,
+}
+
+/// A decoded BBHEADER.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BbHeader {
+    /// What the data field carries.
+    pub format: StreamFormat,
+    /// `true` on a single input stream, `false` on a multistream
+    /// transponder.
+    pub single_stream: bool,
+    /// `true` with constant coding and modulation, `false` with adaptive or
+    /// variable.
+    pub ccm: bool,
+    /// `true` when the user packets carry an ISSY field.
+    pub issy: bool,
+    /// `true` when null packet deletion is active.
+    pub npd: bool,
+    /// Rolloff, `None` for the value DVB-S2X reuses to tell the lower
+    /// rolloffs apart.
+    pub rolloff: Option<Rolloff>,
+    /// Input stream identifier, MATYPE-2. Reserved on a single input
+    /// stream.
+    pub isi: u8,
+    /// User packet length in bits, 0 for a continuous stream.
+    pub upl: u16,
+    /// Data field length in bits.
+    pub dfl: u16,
+    /// Copy of the sync byte of the user packets.
+    pub sync: u8,
+    /// Distance in bits from the start of the data field to the first user
+    /// packet that starts in it; `0xFFFF` when none does.
+    pub syncd: u16,
+    /// `true` in high efficiency mode, which the CRC-8 tells by its XOR
+    /// with 1.
+    pub high_efficiency: bool,
+}
+
+impl BbHeader {
+    /// Decodes the BBHEADER at the start of `data`. Fails when `data` is
+    /// shorter than a header or the CRC-8 does not match.
+    pub fn parse(data: &[u8]) -> Result<BbHeader> {
+        if data.len() < BBHEADER_SIZE {
+            return Err(Error::InvalidData(format!(
+                "BBHEADER is {} bytes, got {}",
+                BBHEADER_SIZE,
+                data.len()
+            )));
+        }
+
+        let high_efficiency = match crc8(&data[.. 9]) ^ data[9] {
+            0 => false,
+            1 => true,
+            _ => {
+                return Err(Error::InvalidData("BBHEADER CRC-8 mismatch".to_owned()));
+            }
+        };
+
+        let matype = data[0];
+        let format = match matype >> 6 {
+            0b00 => StreamFormat::GenericPacketized,
+            0b01 => StreamFormat::GenericContinuous,
+            0b10 => StreamFormat::GseLite,
+            _ => StreamFormat::Transport,
+        };
+        let rolloff = match matype & 0x03 {
+            0b00 => Some(Rolloff::R35),
+            0b01 => Some(Rolloff::R25),
+            0b10 => Some(Rolloff::R20),
+            _ => None,
+        };
+
+        Ok(BbHeader {
+            format,
+            single_stream: matype & 0x20 != 0,
+            ccm: matype & 0x10 != 0,
+            issy: matype & 0x08 != 0,
+            npd: matype & 0x04 != 0,
+            rolloff,
+            isi: data[1],
+            upl: u16::from_be_bytes([data[2], data[3]]),
+            dfl: u16::from_be_bytes([data[4], data[5]]),
+            sync: data[6],
+            syncd: u16::from_be_bytes([data[7], data[8]]),
+            high_efficiency,
+        })
+    }
+
+    /// Size of the frame in bytes: the header and the data field.
+    pub fn frame_size(&self) -> usize {
+        BBHEADER_SIZE + usize::from(self.dfl / 8)
+    }
+}
+
+/// A baseband frame.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BbFrame {
+    /// The decoded header.
+    pub header: BbHeader,
+    /// The data field, without the padding after it.
+    pub data: Vec<u8>,
+}
+
+/// Takes the baseband frames out of the pseudo transport stream of a
+/// frontend in BBFrame delivery.
+///
+/// The stream is pushed in chunks of any size as the DVR device hands them
+/// over. A frame with a bad header, or one that loses a packet on the way,
+/// is dropped.
+#[derive(Debug)]
+pub struct BbFrameReader {
+    pid: u16,
+    packets: TsPackets,
+    frame: Vec<u8>,
+    frame_size: Option<usize>,
+    continuity: Option<u8>,
+}
+
+impl BbFrameReader {
+    /// Reader of the frames on `pid`, or on any PID but the null packets
+    /// with `0x2000`.
+    pub fn new(pid: u16) -> BbFrameReader {
+        BbFrameReader {
+            pid,
+            packets: TsPackets::default(),
+            frame: Vec::new(),
+            frame_size: None,
+            continuity: None,
+        }
+    }
+
+    /// Takes in a chunk of the stream. Returns the frames it completes.
+    pub fn push(&mut self, data: &[u8]) -> Vec<BbFrame> {
+        let mut frames = Vec::new();
+        let mut packets = std::mem::take(&mut self.packets);
+        packets.push(data, |packet| {
+            if let Some(frame) = self.push_packet(packet) {
+                frames.push(frame);
+            }
+        });
+        self.packets = packets;
+
+        frames
+    }
+
+    fn push_packet(&mut self, packet: &[u8]) -> Option<BbFrame> {
+        let pid = u16::from_be_bytes([packet[1] & 0x1F, packet[2]]);
+        if pid == 0x1FFF || (self.pid != 0x2000 && pid != self.pid) {
+            return None;
+        }
+
+        let payload = ts_payload(packet)?;
+        let continuity = packet[3] & 0x0F;
+        let expected = self.continuity.map(|last| (last + 1) & 0x0F);
+        self.continuity = Some(continuity);
+        if packet[1] & 0x80 != 0 {
+            return None;
+        }
+
+        if packet[1] & 0x40 != 0 {
+            self.frame.clear();
+            self.frame_size = None;
+            for start in [0, 1] {
+                if start == 1 && payload.first() != Some(&BBFRAME_MARKER) {
+                    break;
+                }
+                let Some(header) = payload.get(start ..) else {
+                    break;
+                };
+                if let Ok(header) = BbHeader::parse(header) {
+                    self.frame_size = Some(header.frame_size());
+                    self.frame.extend_from_slice(&payload[start ..]);
+                    break;
+                }
+            }
+        } else if self.frame_size.is_some() {
+            if expected != Some(continuity) {
+                self.frame_size = None;
+                return None;
+            }
+            self.frame.extend_from_slice(payload);
+        }
+
+        let frame_size = self.frame_size?;
+        if self.frame.len() < frame_size {
+            return None;
+        }
+
+        self.frame_size = None;
+        let header = BbHeader::parse(&self.frame).ok()?;
+        Some(BbFrame {
+            header,
+            data: self.frame[BBHEADER_SIZE .. frame_size].to_vec(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A BBHEADER with its CRC-8.
+    fn header(matype: [u8; 2], upl: u16, dfl: u16, sync: u8, syncd: u16) -> Vec<u8> {
+        let mut header = vec![matype[0], matype[1]];
+        header.extend_from_slice(&upl.to_be_bytes());
+        header.extend_from_slice(&dfl.to_be_bytes());
+        header.push(sync);
+        header.extend_from_slice(&syncd.to_be_bytes());
+        header.push(crc8(&header));
+        header
+    }
+
+    /// The pseudo transport stream of `frames` on PID 0x118.
+    fn pseudo_ts(frames: &[Vec<u8>], marker: bool) -> Vec<u8> {
+        let mut ts = Vec::new();
+        let mut continuity = 0;
+        for frame in frames {
+            let mut data = Vec::new();
+            if marker {
+                data.push(BBFRAME_MARKER);
+            }
+            data.extend_from_slice(frame);
+            for (i, chunk) in data.chunks(184).enumerate() {
+                let start = if i == 0 { 0x40 } else { 0x00 };
+                ts.extend_from_slice(&[TS_SYNC_BYTE, start | 0x01, 0x18, 0x10 | continuity]);
+                ts.extend_from_slice(chunk);
+                ts.resize(ts.len() + 184 - chunk.len(), 0xFF);
+                continuity = (continuity + 1) & 0x0F;
+            }
+        }
+        ts
+    }
+
+    #[test]
+    fn parse_header() {
+        // multistream TS, ACM, NPD, rolloff 0.20, ISI 3
+        let data = header([0xC6, 0x03], 188 * 8, 7000 * 8, 0x47, 0x0120);
+        let header = BbHeader::parse(&data).unwrap();
+        assert_eq!(
+            header,
+            BbHeader {
+                format: StreamFormat::Transport,
+                single_stream: false,
+                ccm: false,
+                issy: false,
+                npd: true,
+                rolloff: Some(Rolloff::R20),
+                isi: 3,
+                upl: 1504,
+                dfl: 56000,
+                sync: 0x47,
+                syncd: 0x0120,
+                high_efficiency: false,
+            }
+        );
+        assert_eq!(header.frame_size(), 7010);
+
+        let mut high_efficiency = data.clone();
+        high_efficiency[9] ^= 1;
+        assert!(BbHeader::parse(&high_efficiency).unwrap().high_efficiency);
+
+        let mut corrupt = data.clone();
+        corrupt[1] = 4;
+        assert!(BbHeader::parse(&corrupt).is_err());
+        assert!(BbHeader::parse(&data[.. 9]).is_err());
+    }
+
+    #[test]
+    fn reader_reassembles_frames() {
+        let frame = |isi: u8, size: usize| {
+            let mut frame = header([0x70, isi], 0, (size * 8) as u16, 0, 0);
+            frame.extend((0 .. size).map(|i| i as u8));
+            frame
+        };
+        let frames = [frame(1, 500), frame(2, 100)];
+
+        for marker in [false, true] {
+            let ts = pseudo_ts(&frames, marker);
+            let mut reader = BbFrameReader::new(0x2000);
+            // chunks that split the packets, after some garbage
+            let mut decoded = reader.push(&[0x00, 0x12]);
+            for chunk in ts.chunks(100) {
+                decoded.extend(reader.push(chunk));
+            }
+            let sizes: Vec<(u8, usize)> = decoded
+                .iter()
+                .map(|frame| (frame.header.isi, frame.data.len()))
+                .collect();
+            assert_eq!(sizes, [(1, 500), (2, 100)]);
+            assert_eq!(decoded[0].data[499], (499 % 256) as u8);
+        }
+
+        // a lost packet drops the frame
+        let mut ts = pseudo_ts(&frames, false);
+        ts.drain(188 .. 376);
+        let mut reader = BbFrameReader::new(0x118);
+        let decoded = reader.push(&ts);
+        assert_eq!(decoded.len(), 1);
+        assert_eq!(decoded[0].header.isi, 2);
+
+        assert!(BbFrameReader::new(0x119).push(&ts).is_empty());
+    }
+}
@@ -0,0 +1,224 @@
+//! Transport stream out of baseband frames.
+//!
+//! In normal mode a baseband frame carries the TS packets of its stream as
+//! user packets with the sync byte replaced by the CRC-8 of the packet
+//! before, followed by the ISSY field and the count of deleted null packets
+//! when the header flags them. SYNCD points at the first packet that starts
+//! in the data field; the bytes before it end the packet the previous frame
+//! left open. In high efficiency mode the sync byte is dropped altogether.
+
+use super::{
+    BbFrame,
+    BbHeader,
+    StreamFormat,
+    TS_PACKET_SIZE,
+    TS_SYNC_BYTE,
+    crc8,
+};
+
+/// Rebuilds the transport stream of one input stream from its baseband
+/// frames.
+#[derive(Debug)]
+pub struct TsReassembler {
+    isi: Option<u8>,
+    packet: Vec<u8>,
+    crc: Option<u8>,
+    crc_errors: usize,
+}
+
+/// A null packet, as null packet deletion takes them out.
+const NULL_PACKET: [u8; 4] = [TS_SYNC_BYTE, 0x1F, 0xFF, 0x10];
+
+impl TsReassembler {
+    /// Reassembler of the stream `isi`, or of any stream with `None`.
+    /// Frames of other streams, and those that carry no transport stream,
+    /// are passed over.
+    pub fn new(isi: Option<u8>) -> TsReassembler {
+        TsReassembler {
+            isi,
+            packet: Vec::with_capacity(TS_PACKET_SIZE + 4),
+            crc: None,
+            crc_errors: 0,
+        }
+    }
+
+    /// User packets whose CRC-8 did not match, so far. The packets are
+    /// passed on all the same.
+    pub fn crc_errors(&self) -> usize {
+        self.crc_errors
+    }
+
+    /// Takes in a frame. Returns the TS packets it completes, in a row.
+    pub fn push(&mut self, frame: &BbFrame) -> Vec<u8> {
+        let header = &frame.header;
+        let mut ts = Vec::new();
+        if header.format != StreamFormat::Transport
+            || (!header.single_stream && self.isi.is_some_and(|isi| isi != header.isi))
+        {
+            return ts;
+        }
+
+        let size = packet_size(header);
+        let mut data = &frame.data[..];
+        if header.syncd == 0xFFFF {
+            if !self.packet.is_empty() {
+                self.fill(&mut data, size, header, &mut ts);
+            }
+            return ts;
+        }
+
+        let syncd = usize::from(header.syncd / 8).min(data.len());
+        if self.packet.len() + syncd == size {
+            let (end, rest) = data.split_at(syncd);
+            self.packet.extend_from_slice(end);
+            self.flush(header, &mut ts);
+            data = rest;
+        } else {
+            // the end of a lost frame
+            self.packet.clear();
+            self.crc = None;
+            data = &data[syncd ..];
+        }
+
+        while !data.is_empty() {
+            self.fill(&mut data, size, header, &mut ts);
+        }
+
+        ts
+    }
+
+    /// Moves `data` into the open packet, up to its size.
+    fn fill(&mut self, data: &mut &[u8], size: usize, header: &BbHeader, ts: &mut Vec<u8>) {
+        let take = (size - self.packet.len()).min(data.len());
+        self.packet.extend_from_slice(&data[.. take]);
+        *data = &data[take ..];
+        if self.packet.len() == size {
+            self.flush(header, ts);
+        }
+    }
+
+    /// Writes out the complete packet, after the null packets deleted
+    /// before it.
+    fn flush(&mut self, header: &BbHeader, ts: &mut Vec<u8>) {
+        let useful = if header.high_efficiency {
+            &self.packet[.. TS_PACKET_SIZE - 1]
+        } else {
+            if self.crc.is_some_and(|crc| crc != self.packet[0]) {
+                self.crc_errors += 1;
+            }
+            &self.packet[1 .. TS_PACKET_SIZE]
+        };
+
+        if header.npd {
+            let deleted = self.packet[self.packet.len() - 1];
+            for _ in 0 .. deleted {
+                ts.extend_from_slice(&NULL_PACKET);
+                ts.resize(ts.len() + TS_PACKET_SIZE - NULL_PACKET.len(), 0xFF);
+            }
+        }
+
+        ts.push(TS_SYNC_BYTE);
+        ts.extend_from_slice(useful);
+        self.crc = Some(crc8(useful));
+        self.packet.clear();
+    }
+}
+
+/// Size of a user packet in the data field.
+fn packet_size(header: &BbHeader) -> usize {
+    if header.high_efficiency {
+        // ISSY travels in the header, not in the packets
+        TS_PACKET_SIZE - 1 + usize::from(header.npd)
+    } else {
+        usize::from(header.upl / 8).max(TS_PACKET_SIZE)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ts_packet(pid: u16, fill: u8) -> Vec<u8> {
+        let mut packet = vec![TS_SYNC_BYTE, (pid >> 8) as u8, pid as u8, 0x10];
+        packet.resize(TS_PACKET_SIZE, fill);
+        packet
+    }
+
+    /// The user packets of `packets`, with the CRC-8 for the sync byte and
+    /// a deleted null packet count when `npd` is given.
+    fn user_packets(packets: &[Vec<u8>], npd: Option<&[u8]>) -> Vec<u8> {
+        let mut data = Vec::new();
+        let mut crc = 0;
+        for (i, packet) in packets.iter().enumerate() {
+            data.push(crc);
+            data.extend_from_slice(&packet[1 ..]);
+            crc = crc8(&packet[1 ..]);
+            if let Some(npd) = npd {
+                data.push(npd[i]);
+            }
+        }
+        data
+    }
+
+    fn frame(isi: u8, npd: bool, syncd: u16, data: &[u8]) -> BbFrame {
+        BbFrame {
+            header: BbHeader {
+                format: StreamFormat::Transport,
+                single_stream: false,
+                ccm: true,
+                issy: false,
+                npd,
+                rolloff: None,
+                isi,
+                upl: if npd { 189 * 8 } else { 188 * 8 },
+                dfl: (data.len() * 8) as u16,
+                sync: TS_SYNC_BYTE,
+                syncd,
+                high_efficiency: false,
+            },
+            data: data.to_vec(),
+        }
+    }
+
+    #[test]
+    fn packets_span_frames() {
+        let packets: Vec<Vec<u8>> = (0 .. 5).map(|i| ts_packet(0x100 + i, i as u8)).collect();
+        let data = user_packets(&packets, None);
+
+        // packet 0 and the start of packet 1, a frame of another stream,
+        // then the rest of packet 1 and packets 2 and 3
+        let mut reassembler = TsReassembler::new(Some(3));
+        let mut ts = reassembler.push(&frame(3, false, 0, &data[.. 288]));
+        ts.extend(reassembler.push(&frame(4, false, 0, &data[.. 188])));
+        ts.extend(reassembler.push(&frame(3, false, 88 * 8, &data[288 .. 752])));
+        assert_eq!(ts, packets[.. 4].concat());
+        assert_eq!(reassembler.crc_errors(), 0);
+
+        // a lost frame drops the open packet, and SYNCD finds the next one
+        let mut reassembler = TsReassembler::new(None);
+        let mut ts = reassembler.push(&frame(3, false, 0, &data[.. 288]));
+        ts.extend(reassembler.push(&frame(3, false, 12 * 8, &data[364 .. 752])));
+        assert_eq!(ts, [&packets[0][..], &packets[2], &packets[3]].concat());
+
+        // a corrupt packet is counted by the CRC-8 of the one after
+        let mut corrupt = data.clone();
+        corrupt[200] ^= 0xFF;
+        let mut reassembler = TsReassembler::new(None);
+        reassembler.push(&frame(3, false, 0, &corrupt[.. 564]));
+        assert_eq!(reassembler.crc_errors(), 1);
+    }
+
+    #[test]
+    fn deleted_null_packets_come_back() {
+        let packets: Vec<Vec<u8>> = (0 .. 2).map(|i| ts_packet(0x200, i)).collect();
+        let data = user_packets(&packets, Some(&[0, 2]));
+        let mut reassembler = TsReassembler::new(None);
+        let ts = reassembler.push(&frame(0, true, 0, &data));
+        assert_eq!(ts.len(), 4 * TS_PACKET_SIZE);
+        assert_eq!(&ts[.. TS_PACKET_SIZE], &packets[0][..]);
+        for null in ts[TS_PACKET_SIZE .. 3 * TS_PACKET_SIZE].chunks(TS_PACKET_SIZE) {
+            assert_eq!(&null[.. 4], &NULL_PACKET);
+        }
+        assert_eq!(&ts[3 * TS_PACKET_SIZE ..], &packets[1][..]);
+    }
+}
@@ -1122,7 +1122,7 @@ fn is_link_error(error: &Error) -> bool {
     match error {
         Error::Io(_) | Error::Nix(_) => true,
         Error::InvalidData(context) => context == "ca link frame short write",
-        Error::InvalidProperty(_) => false,
+        Error::InvalidProperty(_) | Error::InvalidTune(_) | Error::LnbOverload => false,
     }
 }
 
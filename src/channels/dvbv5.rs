@@ -0,0 +1,793 @@
+//! libdvbv5 channel files, as `dvbv5-scan` writes them.
+//!
+//! One block per channel, a name in brackets and then `KEY = VALUE` lines:
+//!
+//! ```text
+//! [Das Erste HD]
+//!     SERVICE_ID = 10301
+//!     VIDEO_PID = 5101
+//!     AUDIO_PID = 5102 5103 5106
+//!     FREQUENCY = 11494000
+//!     POLARIZATION = HORIZONTAL
+//!     SYMBOL_RATE = 22000000
+//!     INNER_FEC = 2/3
+//!     MODULATION = PSK/8
+//!     PILOT = AUTO
+//!     ROLLOFF = 35
+//!     INVERSION = AUTO
+//!     DELIVERY_SYSTEM = DVBS2
+//! ```
+//!
+//! Satellite frequencies are in kHz, the others in Hz. The keys of the
+//! tuning parameters are the names of the DVBv5 properties; a DVB-S2
+//! scrambling sequence is `SCRAMBLING_SEQUENCE_INDEX`, a Gold index. The
+//! LNB and DiSEqC keys, the ISDB-T layer keys and the `PID_xx` lists of
+//! other components are read over, unknown keys rejected.
+
+use super::{
+    Channel,
+    ComponentPid,
+    name_of,
+    value_of,
+};
+use crate::{
+    error::{
+        Error,
+        Result,
+    },
+    fe::{
+        AtscTune,
+        DvbCAnnex,
+        DvbCTune,
+        DvbS2Tune,
+        DvbSTune,
+        DvbT2Tune,
+        DvbTTune,
+        IsdbTTune,
+        Mis,
+        PlsMode,
+        Polarization,
+        TuneRequest,
+        sys::{
+            DeliverySystem,
+            Fec,
+            GuardInterval,
+            Hierarchy,
+            Inversion,
+            Modulation,
+            Pilot,
+            Rolloff,
+            TransmitMode,
+        },
+    },
+};
+
+const DELIVERY_SYSTEMS: &[(&str, DeliverySystem)] = &[
+    ("DVBC/ANNEX_A", DeliverySystem::DvbcAnnexA),
+    ("DVBC/ANNEX_AC", DeliverySystem::DvbcAnnexA),
+    ("DVBC/ANNEX_B", DeliverySystem::DvbcAnnexB),
+    ("DVBC/ANNEX_C", DeliverySystem::DvbcAnnexC),
+    ("DVBT", DeliverySystem::Dvbt),
+    ("DVBT2", DeliverySystem::Dvbt2),
+    ("DVBS", DeliverySystem::Dvbs),
+    ("DVBS2", DeliverySystem::Dvbs2),
+    ("ATSC", DeliverySystem::Atsc),
+    ("ISDBT", DeliverySystem::Isdbt),
+];
+
+const MODULATIONS: &[(&str, Modulation)] = &[
+    ("QPSK", Modulation::Qpsk),
+    ("QAM/16", Modulation::Qam16),
+    ("QAM/32", Modulation::Qam32),
+    ("QAM/64", Modulation::Qam64),
+    ("QAM/128", Modulation::Qam128),
+    ("QAM/256", Modulation::Qam256),
+    ("QAM/AUTO", Modulation::QamAuto),
+    ("VSB/8", Modulation::Vsb8),
+    ("VSB/16", Modulation::Vsb16),
+    ("PSK/8", Modulation::Psk8),
+    ("APSK/16", Modulation::Apsk16),
+    ("APSK/32", Modulation::Apsk32),
+    ("DQPSK", Modulation::Dqpsk),
+    ("QAM/4_NR", Modulation::Qam4Nr),
+    ("APSK/64", Modulation::Apsk64),
+    ("APSK/128", Modulation::Apsk128),
+    ("APSK/256", Modulation::Apsk256),
+];
+
+const FECS: &[(&str, Fec)] = &[
+    ("NONE", Fec::None),
+    ("1/2", Fec::Fec1_2),
+    ("2/3", Fec::Fec2_3),
+    ("3/4", Fec::Fec3_4),
+    ("4/5", Fec::Fec4_5),
+    ("5/6", Fec::Fec5_6),
+    ("6/7", Fec::Fec6_7),
+    ("7/8", Fec::Fec7_8),
+    ("8/9", Fec::Fec8_9),
+    ("AUTO", Fec::Auto),
+    ("3/5", Fec::Fec3_5),
+    ("9/10", Fec::Fec9_10),
+    ("2/5", Fec::Fec2_5),
+    ("1/4", Fec::Fec1_4),
+    ("1/3", Fec::Fec1_3),
+];
+
+const GUARD_INTERVALS: &[(&str, GuardInterval)] = &[
+    ("1/32", GuardInterval::Gi1_32),
+    ("1/16", GuardInterval::Gi1_16),
+    ("1/8", GuardInterval::Gi1_8),
+    ("1/4", GuardInterval::Gi1_4),
+    ("AUTO", GuardInterval::Auto),
+    ("1/128", GuardInterval::Gi1_128),
+    ("19/128", GuardInterval::Gi19_128),
+    ("19/256", GuardInterval::Gi19_256),
+    ("PN420", GuardInterval::Pn420),
+    ("PN595", GuardInterval::Pn595),
+    ("PN945", GuardInterval::Pn945),
+];
+
+const TRANSMISSION_MODES: &[(&str, TransmitMode)] = &[
+    ("2K", TransmitMode::Tm2K),
+    ("8K", TransmitMode::Tm8K),
+    ("AUTO", TransmitMode::Auto),
+    ("4K", TransmitMode::Tm4K),
+    ("1K", TransmitMode::Tm1K),
+    ("16K", TransmitMode::Tm16K),
+    ("32K", TransmitMode::Tm32K),
+    ("C1", TransmitMode::C1),
+    ("C3780", TransmitMode::C3780),
+];
+
+const HIERARCHIES: &[(&str, Hierarchy)] = &[
+    ("NONE", Hierarchy::None),
+    ("1", Hierarchy::H1),
+    ("2", Hierarchy::H2),
+    ("4", Hierarchy::H4),
+    ("AUTO", Hierarchy::Auto),
+];
+
+const INVERSIONS: &[(&str, Inversion)] = &[
+    ("OFF", Inversion::Off),
+    ("ON", Inversion::On),
+    ("AUTO", Inversion::Auto),
+];
+
+const PILOTS: &[(&str, Pilot)] = &[
+    ("ON", Pilot::On),
+    ("OFF", Pilot::Off),
+    ("AUTO", Pilot::Auto),
+];
+
+const ROLLOFFS: &[(&str, Rolloff)] = &[
+    ("35", Rolloff::R35),
+    ("20", Rolloff::R20),
+    ("25", Rolloff::R25),
+    ("AUTO", Rolloff::Auto),
+    ("15", Rolloff::R15),
+    ("10", Rolloff::R10),
+    ("5", Rolloff::R5),
+];
+
+const POLARIZATIONS: &[(&str, Polarization)] = &[
+    ("HORIZONTAL", Polarization::Horizontal),
+    ("VERTICAL", Polarization::Vertical),
+    ("LEFT", Polarization::Left),
+    ("RIGHT", Polarization::Right),
+];
+
+/// Keys of the libdvbv5 tools this crate has no use for.
+const IGNORED_KEYS: &[&str] = &["VCHANNEL", "LNB", "SAT_NUMBER", "DISEQC_WAIT", "FREQ_BPF"];
+
+/// ISDB-T keys with no field in [`IsdbTTune`](crate::IsdbTTune).
+const ISDBT_KEYS: &[&str] = &[
+    "ISDBT_PARTIAL_RECEPTION",
+    "ISDBT_SOUND_BROADCASTING",
+    "ISDBT_SB_SUBCHANNEL_ID",
+    "ISDBT_SB_SEGMENT_IDX",
+    "ISDBT_SB_SEGMENT_COUNT",
+    "ISDBT_LAYER_ENABLED",
+];
+
+/// Per-layer ISDB-T key suffixes, after `ISDBT_LAYERA_` to `ISDBT_LAYERC_`.
+const ISDBT_LAYER_KEYS: &[&str] = &["FEC", "MODULATION", "SEGMENT_COUNT", "TIME_INTERLEAVING"];
+
+/// `true` for the ISDB-T keys, read over since the frontend detects the
+/// layers from the TMCC.
+pub(crate) fn is_isdbt_key(key: &str) -> bool {
+    if ISDBT_KEYS.contains(&key) {
+        return true;
+    }
+
+    ["ISDBT_LAYERA_", "ISDBT_LAYERB_", "ISDBT_LAYERC_"]
+        .iter()
+        .filter_map(|prefix| key.strip_prefix(prefix))
+        .any(|suffix| ISDBT_LAYER_KEYS.contains(&suffix))
+}
+
+/// Tuning parameters of a block, as its keys set them.
+#[derive(Debug, Default)]
+pub(crate) struct Properties {
+    delivery_system: Option<DeliverySystem>,
+    frequency: Option<u32>,
+    symbolrate: Option<u32>,
+    bandwidth_hz: Option<u32>,
+    modulation: Option<Modulation>,
+    inner_fec: Option<Fec>,
+    code_rate_hp: Option<Fec>,
+    code_rate_lp: Option<Fec>,
+    guard_interval: Option<GuardInterval>,
+    transmission_mode: Option<TransmitMode>,
+    hierarchy: Option<Hierarchy>,
+    inversion: Option<Inversion>,
+    pilot: Option<Pilot>,
+    rolloff: Option<Rolloff>,
+    polarization: Option<Polarization>,
+    stream_id: Option<u32>,
+    scrambling_sequence_index: Option<u32>,
+}
+
+fn lookup<T: Copy>(table: &[(&'static str, T)], key: &str, value: &str) -> Result<T> {
+    value_of(table, value).ok_or_else(|| Error::InvalidData(format!("invalid {} {:?}", key, value)))
+}
+
+fn number<T: std::str::FromStr>(key: &str, value: &str) -> Result<T> {
+    value
+        .parse()
+        .map_err(|_| Error::InvalidData(format!("invalid {} {:?}", key, value)))
+}
+
+impl Properties {
+    /// Sets a tuning parameter. Returns `false` if `key` is not one.
+    pub(crate) fn set(&mut self, key: &str, value: &str) -> Result<bool> {
+        match key {
+            "DELIVERY_SYSTEM" => self.delivery_system = Some(lookup(DELIVERY_SYSTEMS, key, value)?),
+            "FREQUENCY" => self.frequency = Some(number(key, value)?),
+            "SYMBOL_RATE" => self.symbolrate = Some(number(key, value)?),
+            "BANDWIDTH_HZ" => self.bandwidth_hz = Some(number(key, value)?),
+            "MODULATION" => self.modulation = Some(lookup(MODULATIONS, key, value)?),
+            "INNER_FEC" => self.inner_fec = Some(lookup(FECS, key, value)?),
+            "CODE_RATE_HP" => self.code_rate_hp = Some(lookup(FECS, key, value)?),
+            "CODE_RATE_LP" => self.code_rate_lp = Some(lookup(FECS, key, value)?),
+            "GUARD_INTERVAL" => self.guard_interval = Some(lookup(GUARD_INTERVALS, key, value)?),
+            "TRANSMISSION_MODE" => {
+                self.transmission_mode = Some(lookup(TRANSMISSION_MODES, key, value)?)
+            }
+            "HIERARCHY" => self.hierarchy = Some(lookup(HIERARCHIES, key, value)?),
+            "INVERSION" => self.inversion = Some(lookup(INVERSIONS, key, value)?),
+            "PILOT" => self.pilot = Some(lookup(PILOTS, key, value)?),
+            "ROLLOFF" => self.rolloff = Some(lookup(ROLLOFFS, key, value)?),
+            "POLARIZATION" => self.polarization = Some(lookup(POLARIZATIONS, key, value)?),
+            // an empty stream id is how libdvbv5 writes none
+            "STREAM_ID" if value.is_empty() => self.stream_id = None,
+            "STREAM_ID" => self.stream_id = Some(number(key, value)?),
+            "SCRAMBLING_SEQUENCE_INDEX" => {
+                self.scrambling_sequence_index = Some(number(key, value)?)
+            }
+            _ => return Ok(false),
+        }
+
+        Ok(true)
+    }
+
+    /// The tune request and the polarization the parameters describe. A
+    /// satellite request is left with frequency 0; see
+    /// [`Properties::transponder_khz`].
+    pub(crate) fn request(&self) -> Result<(TuneRequest, Option<Polarization>)> {
+        let delivery_system = self
+            .delivery_system
+            .ok_or_else(|| Error::InvalidData("no DELIVERY_SYSTEM".to_owned()))?;
+        let frequency = self
+            .frequency
+            .ok_or_else(|| Error::InvalidData("no FREQUENCY".to_owned()))?;
+        let symbolrate = self.symbolrate.unwrap_or(0);
+        let inversion = self.inversion.unwrap_or(Inversion::Auto);
+
+        let request = match delivery_system {
+            DeliverySystem::Dvbs => TuneRequest::DvbS(DvbSTune {
+                frequency_khz: 0,
+                symbolrate,
+                modulation: self.modulation.unwrap_or(Modulation::Qpsk),
+                fec: self.inner_fec.unwrap_or(Fec::Auto),
+                inversion,
+            }),
+            DeliverySystem::Dvbs2 => {
+                let mis = match (self.stream_id, self.scrambling_sequence_index) {
+                    (None, None) => None,
+                    (stream_id, index) => Some(Mis {
+                        mode: PlsMode::Gold,
+                        code: index.unwrap_or(0),
+                        stream_id: stream_id.unwrap_or(0),
+                    }),
+                };
+                let defaults = DvbS2Tune::default();
+                TuneRequest::DvbS2(DvbS2Tune {
+                    frequency_khz: 0,
+                    symbolrate,
+                    modulation: self.modulation.unwrap_or(defaults.modulation),
+                    fec: self.inner_fec.unwrap_or(Fec::Auto),
+                    inversion,
+                    pilot: self.pilot.unwrap_or(Pilot::Auto),
+                    rolloff: self.rolloff.unwrap_or(defaults.rolloff),
+                    mis,
+                })
+            }
+            DeliverySystem::DvbcAnnexA
+            | DeliverySystem::DvbcAnnexB
+            | DeliverySystem::DvbcAnnexC => TuneRequest::DvbC(DvbCTune {
+                frequency_hz: frequency,
+                symbolrate,
+                annex: match delivery_system {
+                    DeliverySystem::DvbcAnnexB => DvbCAnnex::B,
+                    DeliverySystem::DvbcAnnexC => DvbCAnnex::C,
+                    _ => DvbCAnnex::A,
+                },
+                modulation: self.modulation.unwrap_or(Modulation::QamAuto),
+                fec: self.inner_fec.unwrap_or(Fec::Auto),
+                inversion,
+            }),
+            DeliverySystem::Dvbt => {
+                let defaults = DvbTTune::default();
+                TuneRequest::DvbT(DvbTTune {
+                    frequency_hz: frequency,
+                    bandwidth_hz: self.bandwidth_hz.unwrap_or(defaults.bandwidth_hz),
+                    modulation: self.modulation.unwrap_or(defaults.modulation),
+                    code_rate_hp: self.code_rate_hp.unwrap_or(Fec::Auto),
+                    code_rate_lp: self.code_rate_lp.unwrap_or(Fec::Auto),
+                    guard_interval: self.guard_interval.unwrap_or(GuardInterval::Auto),
+                    transmission_mode: self.transmission_mode.unwrap_or(TransmitMode::Auto),
+                    hierarchy: self.hierarchy.unwrap_or(defaults.hierarchy),
+                    inversion,
+                })
+            }
+            DeliverySystem::Dvbt2 => {
+                let defaults = DvbT2Tune::default();
+                TuneRequest::DvbT2(DvbT2Tune {
+                    frequency_hz: frequency,
+                    bandwidth_hz: self.bandwidth_hz.unwrap_or(defaults.bandwidth_hz),
+                    modulation: self.modulation.unwrap_or(defaults.modulation),
+                    code_rate: self.code_rate_hp.unwrap_or(Fec::Auto),
+                    guard_interval: self.guard_interval.unwrap_or(GuardInterval::Auto),
+                    transmission_mode: self.transmission_mode.unwrap_or(TransmitMode::Auto),
+                    stream_id: self.stream_id,
+                    inversion,
+                })
+            }
+            DeliverySystem::Atsc => TuneRequest::Atsc(AtscTune {
+                frequency_hz: frequency,
+                modulation: self.modulation.unwrap_or(Modulation::Vsb8),
+                inversion,
+            }),
+            DeliverySystem::Isdbt => TuneRequest::IsdbT(IsdbTTune {
+                frequency_hz: frequency,
+                bandwidth_hz: self
+                    .bandwidth_hz
+                    .unwrap_or(IsdbTTune::default().bandwidth_hz),
+                inversion,
+            }),
+            other => {
+                return Err(Error::InvalidData(format!(
+                    "unsupported delivery system {}",
+                    other
+                )));
+            }
+        };
+
+        let polarization = match (&request, self.polarization) {
+            (TuneRequest::DvbS(_) | TuneRequest::DvbS2(_), None) => {
+                return Err(Error::InvalidData("no POLARIZATION".to_owned()));
+            }
+            (_, polarization) => polarization,
+        };
+
+        Ok((request, polarization))
+    }
+
+    /// The transponder frequency in kHz of satellite parameters.
+    pub(crate) fn transponder_khz(&self) -> Option<u32> {
+        match self.delivery_system? {
+            DeliverySystem::Dvbs | DeliverySystem::Dvbs2 => self.frequency,
+            _ => None,
+        }
+    }
+
+    /// The keys and values of a tune request, `DELIVERY_SYSTEM` last, as
+    /// libdvbv5 writes them. A satellite request takes its `FREQUENCY` from
+    /// `transponder_khz`.
+    pub(crate) fn format(
+        request: &TuneRequest,
+        transponder_khz: Option<u32>,
+        polarization: Option<Polarization>,
+    ) -> Vec<(&'static str, String)> {
+        let mut keys = Vec::new();
+
+        match request {
+            TuneRequest::DvbS(tune) => {
+                keys.extend(transponder_khz.map(|khz| ("FREQUENCY", khz.to_string())));
+                keys.extend(polarization.map(|p| ("POLARIZATION", name(POLARIZATIONS, p))));
+                keys.push(("SYMBOL_RATE", tune.symbolrate.to_string()));
+                keys.push(("INNER_FEC", name(FECS, tune.fec)));
+                keys.push(("MODULATION", name(MODULATIONS, tune.modulation)));
+                keys.push(("INVERSION", name(INVERSIONS, tune.inversion)));
+            }
+            TuneRequest::DvbS2(tune) => {
+                keys.extend(transponder_khz.map(|khz| ("FREQUENCY", khz.to_string())));
+                keys.extend(polarization.map(|p| ("POLARIZATION", name(POLARIZATIONS, p))));
+                keys.push(("SYMBOL_RATE", tune.symbolrate.to_string()));
+                keys.push(("INNER_FEC", name(FECS, tune.fec)));
+                keys.push(("MODULATION", name(MODULATIONS, tune.modulation)));
+                keys.push(("PILOT", name(PILOTS, tune.pilot)));
+                keys.push(("ROLLOFF", name(ROLLOFFS, tune.rolloff)));
+                keys.push(("INVERSION", name(INVERSIONS, tune.inversion)));
+                if let Some(mis) = tune.mis {
+                    keys.push(("STREAM_ID", mis.stream_id.to_string()));
+                    if let Some(index) = mis.pls_code() {
+                        keys.push(("SCRAMBLING_SEQUENCE_INDEX", index.to_string()));
+                    }
+                }
+            }
+            TuneRequest::DvbC(tune) => {
+                keys.push(("FREQUENCY", tune.frequency_hz.to_string()));
+                keys.push(("SYMBOL_RATE", tune.symbolrate.to_string()));
+                keys.push(("INNER_FEC", name(FECS, tune.fec)));
+                keys.push(("MODULATION", name(MODULATIONS, tune.modulation)));
+                keys.push(("INVERSION", name(INVERSIONS, tune.inversion)));
+            }
+            TuneRequest::DvbT(tune) => {
+                keys.push(("FREQUENCY", tune.frequency_hz.to_string()));
+                keys.push(("BANDWIDTH_HZ", tune.bandwidth_hz.to_string()));
+                keys.push(("CODE_RATE_HP", name(FECS, tune.code_rate_hp)));
+                keys.push(("CODE_RATE_LP", name(FECS, tune.code_rate_lp)));
+                keys.push(("MODULATION", name(MODULATIONS, tune.modulation)));
+                keys.push((
+                    "TRANSMISSION_MODE",
+                    name(TRANSMISSION_MODES, tune.transmission_mode),
+                ));
+                keys.push(("GUARD_INTERVAL", name(GUARD_INTERVALS, tune.guard_interval)));
+                keys.push(("HIERARCHY", name(HIERARCHIES, tune.hierarchy)));
+                keys.push(("INVERSION", name(INVERSIONS, tune.inversion)));
+            }
+            TuneRequest::DvbT2(tune) => {
+                keys.push(("FREQUENCY", tune.frequency_hz.to_string()));
+                keys.push(("BANDWIDTH_HZ", tune.bandwidth_hz.to_string()));
+                keys.push(("CODE_RATE_HP", name(FECS, tune.code_rate)));
+                keys.push(("MODULATION", name(MODULATIONS, tune.modulation)));
+                keys.push((
+                    "TRANSMISSION_MODE",
+                    name(TRANSMISSION_MODES, tune.transmission_mode),
+                ));
+                keys.push(("GUARD_INTERVAL", name(GUARD_INTERVALS, tune.guard_interval)));
+                keys.push(("INVERSION", name(INVERSIONS, tune.inversion)));
+                if let Some(stream_id) = tune.stream_id {
+                    keys.push(("STREAM_ID", stream_id.to_string()));
+                }
+            }
+            TuneRequest::Atsc(tune) => {
+                keys.push(("FREQUENCY", tune.frequency_hz.to_string()));
+                keys.push(("MODULATION", name(MODULATIONS, tune.modulation)));
+                keys.push(("INVERSION", name(INVERSIONS, tune.inversion)));
+            }
+            TuneRequest::IsdbT(tune) => {
+                keys.push(("FREQUENCY", tune.frequency_hz.to_string()));
+                keys.push(("BANDWIDTH_HZ", tune.bandwidth_hz.to_string()));
+                keys.push(("INVERSION", name(INVERSIONS, tune.inversion)));
+            }
+        }
+
+        let delivery_system = name_of(DELIVERY_SYSTEMS, request.delivery_system());
+        keys.push(("DELIVERY_SYSTEM", delivery_system.unwrap_or("").to_owned()));
+        keys
+    }
+}
+
+/// Name of a value in the file.
+fn name<T: PartialEq + Copy>(table: &[(&'static str, T)], value: T) -> String {
+    name_of(table, value).unwrap_or("AUTO").to_owned()
+}
+
+/// A `KEY = VALUE` line, trimmed.
+pub(crate) fn key_value(line: &str) -> Result<(&str, &str)> {
+    let (key, value) = line
+        .split_once('=')
+        .ok_or_else(|| Error::InvalidData(format!("{:?} is not KEY = VALUE", line)))?;
+    Ok((key.trim(), value.trim()))
+}
+
+/// Block header `[name]`, if the line is one.
+pub(crate) fn block_name(line: &str) -> Option<&str> {
+    line.strip_prefix('[')?.strip_suffix(']')
+}
+
+fn pids(key: &str, value: &str) -> Result<Vec<u16>> {
+    value
+        .split_whitespace()
+        .map(|pid| {
+            pid.parse()
+                .map_err(|_| Error::InvalidData(format!("invalid {} {:?}", key, pid)))
+        })
+        .collect()
+}
+
+/// A channel block being read.
+struct Block {
+    line: usize,
+    name: String,
+    properties: Properties,
+    service_id: u16,
+    video: Vec<u16>,
+    audio: Vec<u16>,
+}
+
+impl Block {
+    fn set(&mut self, key: &str, value: &str) -> Result<()> {
+        match key {
+            "SERVICE_ID" => self.service_id = number(key, value)?,
+            "VIDEO_PID" => self.video = pids(key, value)?,
+            "AUDIO_PID" => self.audio = pids(key, value)?,
+            _ if IGNORED_KEYS.contains(&key) || key.starts_with("PID_") || is_isdbt_key(key) => {}
+            _ => {
+                if !self.properties.set(key, value)? {
+                    return Err(Error::InvalidData(format!("unknown key {}", key)));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn channel(self) -> Result<Channel> {
+        let (request, polarization) = self.properties.request().map_err(|e| {
+            Error::InvalidData(format!(
+                "line {}: channel {}: {}",
+                self.line,
+                self.name,
+                message(e)
+            ))
+        })?;
+
+        let mut channel = Channel::new(self.name, request);
+        channel.transponder_khz = self.properties.transponder_khz();
+        channel.polarization = polarization;
+        channel.service_id = self.service_id;
+        channel.video_pid = self.video.first().copied().unwrap_or(0);
+        channel.audio = self.audio.into_iter().map(ComponentPid::new).collect();
+        Ok(channel)
+    }
+}
+
+/// Strips the `Invalid data: ` prefix of an error message, so that the
+/// message can be wrapped with a line number.
+pub(crate) fn message(error: Error) -> String {
+    match error {
+        Error::InvalidData(message) => message,
+        other => other.to_string(),
+    }
+}
+
+/// Parses the text of a libdvbv5 channel file. An error names the line it
+/// was found on; a channel missing a parameter names the line of its
+/// header.
+pub fn parse_dvbv5_channels(input: &str) -> Result<Vec<Channel>> {
+    let mut channels = Vec::new();
+    let mut block: Option<Block> = None;
+
+    for (i, line) in input.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        if let Some(name) = block_name(line) {
+            if let Some(block) = block.take() {
+                channels.push(block.channel()?);
+            }
+            block = Some(Block {
+                line: i + 1,
+                name: name.to_owned(),
+                properties: Properties::default(),
+                service_id: 0,
+                video: Vec::new(),
+                audio: Vec::new(),
+            });
+            continue;
+        }
+
+        let error = |e: Error| Error::InvalidData(format!("line {}: {}", i + 1, message(e)));
+        let Some(block) = block.as_mut() else {
+            return Err(error(Error::InvalidData(
+                "parameter outside of a channel".to_owned(),
+            )));
+        };
+        let (key, value) = key_value(line).map_err(error)?;
+        block.set(key, value).map_err(error)?;
+    }
+
+    if let Some(block) = block {
+        channels.push(block.channel()?);
+    }
+
+    Ok(channels)
+}
+
+/// Formats channels as a libdvbv5 channel file. The video PID and every
+/// audio component, AC-3 included, are written; languages, teletext,
+/// subtitles and CA systems have no key.
+pub fn write_dvbv5_channels(channels: &[Channel]) -> String {
+    let mut output = String::new();
+    for channel in channels {
+        output.push_str(&format!("[{}]\n", channel.name));
+        if channel.service_id != 0 {
+            output.push_str(&format!("\tSERVICE_ID = {}\n", channel.service_id));
+        }
+        if channel.video_pid != 0 {
+            output.push_str(&format!("\tVIDEO_PID = {}\n", channel.video_pid));
+        }
+        let audio: Vec<String> = channel
+            .audio
+            .iter()
+            .chain(&channel.dolby)
+            .map(|component| component.pid.to_string())
+            .collect();
+        if !audio.is_empty() {
+            output.push_str(&format!("\tAUDIO_PID = {}\n", audio.join(" ")));
+        }
+        for (key, value) in Properties::format(
+            &channel.request,
+            channel.transponder_khz,
+            channel.polarization,
+        ) {
+            output.push_str(&format!("\t{} = {}\n", key, value));
+        }
+        output.push('\n');
+    }
+
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FILE: &str = "\
+[Das Erste HD]
+\tSERVICE_ID = 10301
+\tVIDEO_PID = 5101
+\tAUDIO_PID = 5102 5103 5106
+\tFREQUENCY = 11494000
+\tPOLARIZATION = HORIZONTAL
+\tSYMBOL_RATE = 22000000
+\tINNER_FEC = 2/3
+\tMODULATION = PSK/8
+\tPILOT = AUTO
+\tROLLOFF = 35
+\tINVERSION = AUTO
+\tDELIVERY_SYSTEM = DVBS2
+
+[MIS Feed]
+\tSERVICE_ID = 1
+\tFREQUENCY = 11013000
+\tPOLARIZATION = VERTICAL
+\tSYMBOL_RATE = 30000000
+\tINNER_FEC = 2/3
+\tMODULATION = PSK/8
+\tPILOT = ON
+\tROLLOFF = 20
+\tINVERSION = AUTO
+\tSTREAM_ID = 4
+\tSCRAMBLING_SEQUENCE_INDEX = 258
+\tDELIVERY_SYSTEM = DVBS2
+
+[ZDF]
+\tSERVICE_ID = 11110
+\tVIDEO_PID = 610
+\tAUDIO_PID = 620
+\tFREQUENCY = 474000000
+\tBANDWIDTH_HZ = 8000000
+\tCODE_RATE_HP = 2/3
+\tCODE_RATE_LP = 1/2
+\tMODULATION = QAM/64
+\tTRANSMISSION_MODE = 8K
+\tGUARD_INTERVAL = 1/4
+\tHIERARCHY = NONE
+\tINVERSION = OFF
+\tDELIVERY_SYSTEM = DVBT
+
+[QVC]
+\tSERVICE_ID = 12100
+\tVIDEO_PID = 512
+\tAUDIO_PID = 513
+\tFREQUENCY = 346000000
+\tSYMBOL_RATE = 6900000
+\tINNER_FEC = NONE
+\tMODULATION = QAM/256
+\tINVERSION = AUTO
+\tDELIVERY_SYSTEM = DVBC/ANNEX_A
+
+";
+
+    #[test]
+    fn parse_reads_every_block() {
+        let channels = parse_dvbv5_channels(FILE).unwrap();
+        assert_eq!(channels.len(), 4);
+
+        let first = &channels[0];
+        assert_eq!(first.name, "Das Erste HD");
+        assert_eq!(first.service_id, 10301);
+        assert_eq!(first.video_pid, 5101);
+        let audio: Vec<u16> = first.audio.iter().map(|component| component.pid).collect();
+        assert_eq!(audio, [5102, 5103, 5106]);
+        assert_eq!(first.transponder_khz, Some(11_494_000));
+        assert_eq!(first.polarization, Some(Polarization::Horizontal));
+        assert_eq!(
+            first.request,
+            TuneRequest::DvbS2(DvbS2Tune {
+                symbolrate: 22_000_000,
+                fec: Fec::Fec2_3,
+                ..Default::default()
+            })
+        );
+
+        let TuneRequest::DvbS2(tune) = &channels[1].request else {
+            panic!("not DVB-S2");
+        };
+        assert_eq!(
+            tune.mis,
+            Some(Mis {
+                mode: PlsMode::Gold,
+                code: 258,
+                stream_id: 4,
+            })
+        );
+
+        assert_eq!(channels[2].request.delivery_system(), DeliverySystem::Dvbt);
+        assert_eq!(
+            channels[3].request.delivery_system(),
+            DeliverySystem::DvbcAnnexA
+        );
+    }
+
+    #[test]
+    fn file_round_trips_and_converts_from_vdr() {
+        let channels = parse_dvbv5_channels(FILE).unwrap();
+        assert_eq!(write_dvbv5_channels(&channels), FILE);
+
+        // the same channels from VDR keep their tuning and main PIDs
+        let vdr = crate::channels::parse_vdr_channels(
+            "ZDF:474000:B8C23D12G4I0M64S0T8Y0:T:0:610=2:620=deu@4:630:0:11110:8468:769:0",
+        )
+        .unwrap();
+        let converted = parse_dvbv5_channels(&write_dvbv5_channels(&vdr)).unwrap();
+        assert_eq!(converted[0].request, channels[2].request);
+        assert_eq!(converted[0].audio[0].pid, 620);
+    }
+
+    #[test]
+    fn parse_errors_name_the_line() {
+        let error = parse_dvbv5_channels("[A]\n\tFREQUENCY = 474000000\n\tCOLOUR = RED\n")
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("line 3: unknown key COLOUR"), "{}", error);
+
+        let error = parse_dvbv5_channels("\n[A]\n\tFREQUENCY = 474000000\n")
+            .unwrap_err()
+            .to_string();
+        assert!(
+            error.contains("line 2: channel A: no DELIVERY_SYSTEM"),
+            "{}",
+            error
+        );
+
+        let error = parse_dvbv5_channels("[A]\n\tMODULATION = QAM/77\n")
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("line 2: invalid MODULATION"), "{}", error);
+
+        assert!(parse_dvbv5_channels("FREQUENCY = 1\n").is_err());
+        // LNB keys are read over
+        let channels = parse_dvbv5_channels(
+            "[A]\n\tLNB = UNIVERSAL\n\tFREQUENCY = 57000000\n\tDELIVERY_SYSTEM = ATSC\n",
+        )
+        .unwrap();
+        assert_eq!(channels.len(), 1);
+    }
+}
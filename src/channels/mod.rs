@@ -0,0 +1,139 @@
+//! Channel lists of other DVB software.
+//!
+//! [`vdr`] reads and writes the `channels.conf` of VDR, [`dvbv5`] the
+//! channel files of the libdvbv5 tools. Both map each channel to a
+//! [`Channel`]: the tune request of its transponder and the PIDs of its
+//! service, so that a list converts from one format to the other.
+//! [`scan_tables`] reads the initial tuning files a scan starts from.
+
+pub mod dvbv5;
+pub mod scan_tables;
+pub mod vdr;
+
+pub use self::{
+    dvbv5::{
+        parse_dvbv5_channels,
+        write_dvbv5_channels,
+    },
+    scan_tables::{
+        ScanTableEntry,
+        parse_scan_table,
+    },
+    vdr::{
+        parse_vdr_channels,
+        write_vdr_channels,
+    },
+};
+use crate::fe::{
+    Polarization,
+    TuneRequest,
+};
+
+/// An audio or subtitle component of a channel.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ComponentPid {
+    /// Elementary PID.
+    pub pid: u16,
+    /// ISO 639 language code, such as `deu`; VDR joins two with `+`. Empty
+    /// when unknown.
+    pub language: String,
+    /// ISO/IEC 13818-1 stream type, 0 when unknown.
+    pub stream_type: u8,
+}
+
+impl ComponentPid {
+    /// Component known only by its PID.
+    pub fn new(pid: u16) -> ComponentPid {
+        ComponentPid {
+            pid,
+            ..Default::default()
+        }
+    }
+}
+
+/// A channel of a channel list: where it is broadcast and which PIDs carry
+/// it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Channel {
+    /// Channel name. VDR appends a short name after a comma.
+    pub name: String,
+    /// Provider name, empty when unknown.
+    pub provider: String,
+    /// Tune request of the transponder. That of a satellite channel is a
+    /// template with frequency 0, which
+    /// [`TuneRequest::with_intermediate_khz`] completes once the SEC setup
+    /// for `transponder_khz` returns the intermediate frequency.
+    pub request: TuneRequest,
+    /// Frequency of a satellite transponder in kHz.
+    pub transponder_khz: Option<u32>,
+    /// Polarization of a satellite transponder.
+    pub polarization: Option<Polarization>,
+    /// VDR source of a satellite channel, such as `S19.2E`.
+    pub source: Option<String>,
+    /// Service id.
+    pub service_id: u16,
+    /// Video PID, 0 for a radio or data channel.
+    pub video_pid: u16,
+    /// PCR PID, 0 when it is the video PID.
+    pub pcr_pid: u16,
+    /// Stream type of the video, 0 when unknown.
+    pub video_type: u8,
+    /// Audio components.
+    pub audio: Vec<ComponentPid>,
+    /// AC-3 audio components, which VDR lists apart.
+    pub dolby: Vec<ComponentPid>,
+    /// Teletext PID, 0 without teletext.
+    pub teletext_pid: u16,
+    /// DVB subtitle components.
+    pub subtitles: Vec<ComponentPid>,
+    /// CA system ids, empty for a free channel.
+    pub ca_system_ids: Vec<u16>,
+    /// Original network id, 0 when unknown.
+    pub original_network_id: u16,
+    /// Transport stream id, 0 when unknown.
+    pub transport_stream_id: u16,
+    /// VDR radio id, telling apart channels that share the other ids.
+    pub radio_id: u16,
+}
+
+impl Channel {
+    /// Channel with a name and a tune request, and nothing else known.
+    pub fn new(name: impl Into<String>, request: TuneRequest) -> Channel {
+        Channel {
+            name: name.into(),
+            provider: String::new(),
+            request,
+            transponder_khz: None,
+            polarization: None,
+            source: None,
+            service_id: 0,
+            video_pid: 0,
+            pcr_pid: 0,
+            video_type: 0,
+            audio: Vec::new(),
+            dolby: Vec::new(),
+            teletext_pid: 0,
+            subtitles: Vec::new(),
+            ca_system_ids: Vec::new(),
+            original_network_id: 0,
+            transport_stream_id: 0,
+            radio_id: 0,
+        }
+    }
+}
+
+/// Looks up the name of a value in a table of names.
+fn name_of<T: PartialEq + Copy>(table: &[(&'static str, T)], value: T) -> Option<&'static str> {
+    table
+        .iter()
+        .find(|(_, entry)| *entry == value)
+        .map(|(name, _)| *name)
+}
+
+/// Looks up a value by its name in a table of names.
+fn value_of<T: Copy>(table: &[(&'static str, T)], name: &str) -> Option<T> {
+    table
+        .iter()
+        .find(|(entry, _)| entry.eq_ignore_ascii_case(name))
+        .map(|(_, value)| *value)
+}
@@ -0,0 +1,279 @@
+//! Initial tuning files of `dtv-scan-tables`.
+//!
+//! The files are in the libdvbv5 format, one block per transponder with
+//! the keys of its tuning parameters only:
+//!
+//! ```text
+//! # Astra 19.2E SDT info service transponder
+//! [CHANNEL]
+//!     DELIVERY_SYSTEM = DVBS
+//!     FREQUENCY = 12551500
+//!     POLARIZATION = VERTICAL
+//!     SYMBOL_RATE = 22000000
+//!     INNER_FEC = 5/6
+//!     INVERSION = AUTO
+//! ```
+//!
+//! The layer keys of ISDB-T are read over - the frontend detects the
+//! layers from the TMCC - and any other key that is not a tuning
+//! parameter is an error.
+
+use super::dvbv5::{
+    Properties,
+    block_name,
+    is_isdbt_key,
+    key_value,
+    message,
+};
+use crate::{
+    error::{
+        Error,
+        Result,
+    },
+    fe::{
+        Polarization,
+        TuneRequest,
+    },
+};
+
+/// A transponder of an initial tuning file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScanTableEntry {
+    /// Block name, `CHANNEL` in most files.
+    pub name: String,
+    /// Line of the block header.
+    pub line: usize,
+    /// Tune request. That of a satellite transponder is a template with
+    /// frequency 0, for [`TuneRequest::with_intermediate_khz`].
+    pub request: TuneRequest,
+    /// Frequency of a satellite transponder in kHz.
+    pub transponder_khz: Option<u32>,
+    /// Polarization of a satellite transponder.
+    pub polarization: Option<Polarization>,
+}
+
+/// A block being read.
+struct Block {
+    name: String,
+    line: usize,
+    properties: Properties,
+}
+
+impl Block {
+    fn entry(self) -> Result<ScanTableEntry> {
+        let (request, polarization) = self.properties.request().map_err(|e| {
+            Error::InvalidData(format!("line {}: {}: {}", self.line, self.name, message(e)))
+        })?;
+
+        Ok(ScanTableEntry {
+            name: self.name,
+            line: self.line,
+            request,
+            transponder_khz: self.properties.transponder_khz(),
+            polarization,
+        })
+    }
+}
+
+/// Parses the text of an initial tuning file. An error names the line it
+/// was found on: an unknown key or value its own line, a block missing a
+/// parameter the line of its header.
+pub fn parse_scan_table(input: &str) -> Result<Vec<ScanTableEntry>> {
+    let mut entries = Vec::new();
+    let mut block: Option<Block> = None;
+
+    for (i, line) in input.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        if let Some(name) = block_name(line) {
+            if let Some(block) = block.take() {
+                entries.push(block.entry()?);
+            }
+            block = Some(Block {
+                name: name.to_owned(),
+                line: i + 1,
+                properties: Properties::default(),
+            });
+            continue;
+        }
+
+        let error = |e: Error| Error::InvalidData(format!("line {}: {}", i + 1, message(e)));
+        let Some(block) = block.as_mut() else {
+            return Err(error(Error::InvalidData(
+                "parameter outside of a block".to_owned(),
+            )));
+        };
+        let (key, value) = key_value(line).map_err(error)?;
+        if !block.properties.set(key, value).map_err(error)? && !is_isdbt_key(key) {
+            return Err(error(Error::InvalidData(format!("unknown key {}", key))));
+        }
+    }
+
+    if let Some(block) = block {
+        entries.push(block.entry()?);
+    }
+
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fe::{
+        DvbS2Tune,
+        DvbSTune,
+        DvbT2Tune,
+        IsdbTTune,
+        Mis,
+        PlsMode,
+        sys::{
+            Fec,
+            Inversion,
+            Modulation,
+            Rolloff,
+        },
+    };
+
+    const TABLE: &str = "\
+# Astra 19.2E SDT info service transponder
+# freq pol sr fec
+[CHANNEL]
+\tDELIVERY_SYSTEM = DVBS
+\tFREQUENCY = 12551500
+\tPOLARIZATION = VERTICAL
+\tSYMBOL_RATE = 22000000
+\tINNER_FEC = 5/6
+\tINVERSION = AUTO
+
+[CHANNEL]
+\tDELIVERY_SYSTEM = DVBS2
+\tFREQUENCY = 11013000
+\tPOLARIZATION = HORIZONTAL
+\tSYMBOL_RATE = 30000000
+\tINNER_FEC = 3/4
+\tMODULATION = APSK/16
+\tROLLOFF = 20
+\tSTREAM_ID = 2
+\tSCRAMBLING_SEQUENCE_INDEX = 131070
+
+[C35]
+\tDELIVERY_SYSTEM = DVBT2
+\tFREQUENCY = 586000000
+\tBANDWIDTH_HZ = 8000000
+\tSTREAM_ID = 0
+
+[CHANNEL]
+\tDELIVERY_SYSTEM = ISDBT
+\tBANDWIDTH_HZ = 6000000
+\tFREQUENCY = 473142857
+\tISDBT_LAYER_ENABLED = 7
+\tISDBT_LAYERA_FEC = AUTO
+\tISDBT_LAYERB_SEGMENT_COUNT = 0
+";
+
+    #[test]
+    fn parse_reads_every_block() {
+        let entries = parse_scan_table(TABLE).unwrap();
+        assert_eq!(entries.len(), 4);
+
+        assert_eq!(entries[0].name, "CHANNEL");
+        assert_eq!(entries[0].line, 3);
+        assert_eq!(entries[0].transponder_khz, Some(12_551_500));
+        assert_eq!(entries[0].polarization, Some(Polarization::Vertical));
+        assert_eq!(
+            entries[0].request,
+            TuneRequest::DvbS(DvbSTune {
+                symbolrate: 22_000_000,
+                fec: Fec::Fec5_6,
+                inversion: Inversion::Auto,
+                ..Default::default()
+            })
+        );
+
+        assert_eq!(entries[1].transponder_khz, Some(11_013_000));
+        assert_eq!(
+            entries[1].request,
+            TuneRequest::DvbS2(DvbS2Tune {
+                symbolrate: 30_000_000,
+                modulation: Modulation::Apsk16,
+                fec: Fec::Fec3_4,
+                rolloff: Rolloff::R20,
+                mis: Some(Mis {
+                    mode: PlsMode::Gold,
+                    code: 131_070,
+                    stream_id: 2,
+                }),
+                ..Default::default()
+            })
+        );
+
+        assert_eq!(entries[2].name, "C35");
+        assert_eq!(entries[2].transponder_khz, None);
+        assert_eq!(
+            entries[2].request,
+            TuneRequest::DvbT2(DvbT2Tune {
+                frequency_hz: 586_000_000,
+                stream_id: Some(0),
+                ..Default::default()
+            })
+        );
+        assert_eq!(entries[2].polarization, None);
+
+        assert_eq!(
+            entries[3].request,
+            TuneRequest::IsdbT(IsdbTTune {
+                frequency_hz: 473_142_857,
+                ..Default::default()
+            })
+        );
+    }
+
+    #[test]
+    fn errors_name_the_line() {
+        let error = |input: &str| parse_scan_table(input).unwrap_err().to_string();
+
+        let message = error(
+            "[CHANNEL]\n\tDELIVERY_SYSTEM = DVBT\n\tFREQUENCY = 474000000\n\tSERVICE_ID = 1\n",
+        );
+        assert!(
+            message.contains("line 4: unknown key SERVICE_ID"),
+            "{}",
+            message
+        );
+
+        let message =
+            error("# header\n\n[CHANNEL]\n\tDELIVERY_SYSTEM = DVBS\n\tFREQUENCY = 10714000\n");
+        assert!(
+            message.contains("line 3: CHANNEL: no POLARIZATION"),
+            "{}",
+            message
+        );
+
+        let message = error("[CHANNEL]\n\tDELIVERY_SYSTEM = DVBS\n\tINNER_FEC = 4/7\n");
+        assert!(
+            message.contains("line 3: invalid INNER_FEC \"4/7\""),
+            "{}",
+            message
+        );
+
+        let message = error("[CHANNEL]\n\tFREQUENCY 474000000\n");
+        assert!(message.contains("line 2: "), "{}", message);
+
+        let message = error("\tFREQUENCY = 474000000\n");
+        assert!(
+            message.contains("line 1: parameter outside of a block"),
+            "{}",
+            message
+        );
+
+        let message = error("[CHANNEL]\n\tDELIVERY_SYSTEM = DVBT\n\tISDBT_LAYERD_FEC = AUTO\n");
+        assert!(
+            message.contains("line 3: unknown key ISDBT_LAYERD_FEC"),
+            "{}",
+            message
+        );
+    }
+}
@@ -0,0 +1,757 @@
+//! VDR `channels.conf` files.
+//!
+//! One line per channel, thirteen fields separated by colons:
+//!
+//! ```text
+//! Das Erste HD;ARD:11494:HC23M5O35S1:S19.2E:22000:5101=27:5102=deu@3,5103=mis@3;5106=deu@106:5104;5105=deu:0:10301:1:1019:0
+//! ```
+//!
+//! name and provider, frequency, parameters, source, symbol rate in kBd,
+//! video, audio and teletext PIDs, CA system ids, service id, network id,
+//! transport stream id and radio id. The parameters are letters with a
+//! number each: `B` bandwidth, `C` and `D` the high and low priority code
+//! rates, `G` guard interval, `I` inversion, `M` modulation, `N` pilot,
+//! `O` roll-off, `P` stream id, `S` the second generation system, `T`
+//! transmission mode and `Y` hierarchy - and a satellite polarization
+//! letter on its own. Values of 999 mean auto.
+//!
+//! VDR has no parameter for the PLS of a DVB-S2 multistream: a stream id
+//! reads back with the default scrambling sequence, and a PLS code is not
+//! written. Group separators, lines starting with a colon, are skipped.
+
+use super::{
+    Channel,
+    ComponentPid,
+};
+use crate::{
+    error::{
+        Error,
+        Result,
+    },
+    fe::{
+        AtscTune,
+        DvbCTune,
+        DvbS2Tune,
+        DvbSTune,
+        DvbT2Tune,
+        DvbTTune,
+        Mis,
+        PlsMode,
+        Polarization,
+        TuneRequest,
+        sys::{
+            Fec,
+            GuardInterval,
+            Hierarchy,
+            Inversion,
+            Modulation,
+            Pilot,
+            Rolloff,
+            TransmitMode,
+        },
+    },
+};
+
+const FECS: &[(u32, Fec)] = &[
+    (0, Fec::None),
+    (12, Fec::Fec1_2),
+    (23, Fec::Fec2_3),
+    (34, Fec::Fec3_4),
+    (35, Fec::Fec3_5),
+    (45, Fec::Fec4_5),
+    (56, Fec::Fec5_6),
+    (67, Fec::Fec6_7),
+    (78, Fec::Fec7_8),
+    (89, Fec::Fec8_9),
+    (910, Fec::Fec9_10),
+    (999, Fec::Auto),
+];
+
+const MODULATIONS: &[(u32, Modulation)] = &[
+    (2, Modulation::Qpsk),
+    (5, Modulation::Psk8),
+    (6, Modulation::Apsk16),
+    (7, Modulation::Apsk32),
+    (10, Modulation::Vsb8),
+    (11, Modulation::Vsb16),
+    (12, Modulation::Dqpsk),
+    (16, Modulation::Qam16),
+    (32, Modulation::Qam32),
+    (64, Modulation::Qam64),
+    (128, Modulation::Qam128),
+    (256, Modulation::Qam256),
+    (999, Modulation::QamAuto),
+];
+
+const GUARD_INTERVALS: &[(u32, GuardInterval)] = &[
+    (4, GuardInterval::Gi1_4),
+    (8, GuardInterval::Gi1_8),
+    (16, GuardInterval::Gi1_16),
+    (32, GuardInterval::Gi1_32),
+    (128, GuardInterval::Gi1_128),
+    (19128, GuardInterval::Gi19_128),
+    (19256, GuardInterval::Gi19_256),
+    (999, GuardInterval::Auto),
+];
+
+const TRANSMISSION_MODES: &[(u32, TransmitMode)] = &[
+    (1, TransmitMode::Tm1K),
+    (2, TransmitMode::Tm2K),
+    (4, TransmitMode::Tm4K),
+    (8, TransmitMode::Tm8K),
+    (16, TransmitMode::Tm16K),
+    (32, TransmitMode::Tm32K),
+    (999, TransmitMode::Auto),
+];
+
+const HIERARCHIES: &[(u32, Hierarchy)] = &[
+    (0, Hierarchy::None),
+    (1, Hierarchy::H1),
+    (2, Hierarchy::H2),
+    (4, Hierarchy::H4),
+    (999, Hierarchy::Auto),
+];
+
+const ROLLOFFS: &[(u32, Rolloff)] = &[
+    (0, Rolloff::Auto),
+    (35, Rolloff::R35),
+    (25, Rolloff::R25),
+    (20, Rolloff::R20),
+    (15, Rolloff::R15),
+    (10, Rolloff::R10),
+    (5, Rolloff::R5),
+];
+
+const INVERSIONS: &[(u32, Inversion)] = &[
+    (0, Inversion::Off),
+    (1, Inversion::On),
+    (999, Inversion::Auto),
+];
+
+const PILOTS: &[(u32, Pilot)] = &[(0, Pilot::Off), (1, Pilot::On), (999, Pilot::Auto)];
+
+const BANDWIDTHS: &[(u32, u32)] = &[
+    (5, 5_000_000),
+    (6, 6_000_000),
+    (7, 7_000_000),
+    (8, 8_000_000),
+    (10, 10_000_000),
+    (1712, 1_712_000),
+];
+
+/// The parameters field of a line.
+#[derive(Debug, Default)]
+struct Parameters {
+    polarization: Option<Polarization>,
+    values: Vec<(char, u32)>,
+}
+
+impl Parameters {
+    fn parse(text: &str) -> std::result::Result<Parameters, String> {
+        let mut parameters = Parameters::default();
+        let mut chars = text.chars().peekable();
+        while let Some(letter) = chars.next() {
+            let letter = letter.to_ascii_uppercase();
+            if let Some(polarization) = Polarization::from_code(letter) {
+                parameters.polarization = Some(polarization);
+                continue;
+            }
+            if !"BCDGIMNOPQSTXY".contains(letter) {
+                return Err(format!("unknown parameter {:?}", letter));
+            }
+
+            let mut digits = String::new();
+            while let Some(digit) = chars.next_if(char::is_ascii_digit) {
+                digits.push(digit);
+            }
+            let value = digits
+                .parse()
+                .map_err(|_| format!("parameter {} has no value", letter))?;
+            parameters.values.push((letter, value));
+        }
+
+        Ok(parameters)
+    }
+
+    fn get(&self, letter: char) -> Option<u32> {
+        self.values
+            .iter()
+            .rev()
+            .find(|(entry, _)| *entry == letter)
+            .map(|(_, value)| *value)
+    }
+
+    /// The value of a parameter through its table, `default` when the
+    /// line does not have it.
+    fn map<T: Copy>(
+        &self,
+        letter: char,
+        table: &[(u32, T)],
+        default: T,
+    ) -> std::result::Result<T, String> {
+        let Some(code) = self.get(letter) else {
+            return Ok(default);
+        };
+
+        table
+            .iter()
+            .find(|(entry, _)| *entry == code)
+            .map(|(_, value)| *value)
+            .ok_or_else(|| format!("invalid value {} of parameter {}", code, letter))
+    }
+
+    /// Appends a parameter unless it is auto.
+    fn push<T: PartialEq + Copy>(&mut self, letter: char, table: &[(u32, T)], value: T) {
+        if let Some((code, _)) = table.iter().find(|(_, entry)| *entry == value)
+            && *code != 999
+        {
+            self.values.push((letter, *code));
+        }
+    }
+}
+
+/// Parses a field of numbers.
+fn number<T: std::str::FromStr>(field: &str, name: &str) -> std::result::Result<T, String> {
+    field
+        .trim()
+        .parse()
+        .map_err(|_| format!("invalid {} {:?}", name, field))
+}
+
+/// Parses a list of components: `pid[=language][@type]`, comma separated.
+fn components(field: &str) -> std::result::Result<Vec<ComponentPid>, String> {
+    field
+        .split(',')
+        .filter(|item| !item.is_empty())
+        .map(|item| {
+            let (item, stream_type) = match item.split_once('@') {
+                Some((item, stream_type)) => (item, number(stream_type, "stream type")?),
+                None => (item, 0),
+            };
+            let (pid, language) = item.split_once('=').unwrap_or((item, ""));
+            Ok(ComponentPid {
+                pid: number(pid, "PID")?,
+                language: language.to_owned(),
+                stream_type,
+            })
+        })
+        .filter(|component| !matches!(component, Ok(ComponentPid { pid: 0, .. })))
+        .collect()
+}
+
+fn format_components(components: &[ComponentPid]) -> String {
+    components
+        .iter()
+        .map(|component| {
+            let mut item = component.pid.to_string();
+            if !component.language.is_empty() {
+                item.push('=');
+                item.push_str(&component.language);
+            }
+            if component.stream_type != 0 {
+                item.push_str(&format!("@{}", component.stream_type));
+            }
+            item
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Frequency in Hz from any of the units VDR accepts: MHz, kHz or Hz.
+fn frequency_hz(value: u32) -> u32 {
+    let mut frequency = u64::from(value);
+    while frequency != 0 && frequency < 1_000_000 {
+        frequency *= 1000;
+    }
+    frequency.min(u64::from(u32::MAX)) as u32
+}
+
+fn parse_line(line: &str) -> std::result::Result<Channel, String> {
+    let fields: Vec<&str> = line.split(':').collect();
+    let [
+        names,
+        frequency,
+        parameters,
+        source,
+        symbolrate,
+        video,
+        audio,
+        teletext,
+        ca,
+        service_id,
+        network_id,
+        transport_stream_id,
+        radio_id,
+    ] = fields[..]
+    else {
+        return Err(format!("{} fields instead of 13", fields.len()));
+    };
+
+    let frequency: u32 = number(frequency, "frequency")?;
+    let parameters = Parameters::parse(parameters)?;
+    let symbolrate = number::<u32>(symbolrate, "symbol rate")?.saturating_mul(1000);
+    let second_generation = parameters.get('S').unwrap_or(0) != 0;
+    let inversion = parameters.map('I', INVERSIONS, Inversion::Auto)?;
+
+    let mut satellite = None;
+    let request = match source.chars().next().map(|c| c.to_ascii_uppercase()) {
+        Some('S') => {
+            let polarization = parameters
+                .polarization
+                .ok_or_else(|| "satellite channel without polarization".to_owned())?;
+
+            let mut frequency_mhz = frequency;
+            while frequency_mhz > 999_999 {
+                frequency_mhz /= 1000;
+            }
+            satellite = Some((frequency_mhz * 1000, polarization, source.to_owned()));
+            let fec = parameters.map('C', FECS, Fec::Auto)?;
+
+            if second_generation {
+                TuneRequest::DvbS2(DvbS2Tune {
+                    frequency_khz: 0,
+                    symbolrate,
+                    modulation: parameters.map('M', MODULATIONS, Modulation::Psk8)?,
+                    fec,
+                    inversion,
+                    pilot: parameters.map('N', PILOTS, Pilot::Auto)?,
+                    rolloff: parameters.map('O', ROLLOFFS, Rolloff::Auto)?,
+                    mis: parameters.get('P').map(|stream_id| Mis {
+                        mode: PlsMode::Root,
+                        code: 0,
+                        stream_id,
+                    }),
+                })
+            } else {
+                TuneRequest::DvbS(DvbSTune {
+                    frequency_khz: 0,
+                    symbolrate,
+                    modulation: parameters.map('M', MODULATIONS, Modulation::Qpsk)?,
+                    fec,
+                    inversion,
+                })
+            }
+        }
+        Some('C') => TuneRequest::DvbC(DvbCTune {
+            frequency_hz: frequency_hz(frequency),
+            symbolrate,
+            modulation: parameters.map('M', MODULATIONS, Modulation::QamAuto)?,
+            fec: parameters.map('C', FECS, Fec::Auto)?,
+            inversion,
+            ..Default::default()
+        }),
+        Some('T') => {
+            let frequency_hz = frequency_hz(frequency);
+            let bandwidth_hz = parameters.map('B', BANDWIDTHS, 8_000_000)?;
+            let modulation = parameters.map('M', MODULATIONS, Modulation::QamAuto)?;
+            let guard_interval = parameters.map('G', GUARD_INTERVALS, GuardInterval::Auto)?;
+            let transmission_mode = parameters.map('T', TRANSMISSION_MODES, TransmitMode::Auto)?;
+
+            if second_generation {
+                TuneRequest::DvbT2(DvbT2Tune {
+                    frequency_hz,
+                    bandwidth_hz,
+                    modulation,
+                    code_rate: parameters.map('C', FECS, Fec::Auto)?,
+                    guard_interval,
+                    transmission_mode,
+                    stream_id: parameters.get('P'),
+                    inversion,
+                })
+            } else {
+                TuneRequest::DvbT(DvbTTune {
+                    frequency_hz,
+                    bandwidth_hz,
+                    modulation,
+                    code_rate_hp: parameters.map('C', FECS, Fec::Auto)?,
+                    code_rate_lp: parameters.map('D', FECS, Fec::Auto)?,
+                    guard_interval,
+                    transmission_mode,
+                    hierarchy: parameters.map('Y', HIERARCHIES, Hierarchy::None)?,
+                    inversion,
+                })
+            }
+        }
+        Some('A') => TuneRequest::Atsc(AtscTune {
+            frequency_hz: frequency_hz(frequency),
+            modulation: parameters.map('M', MODULATIONS, Modulation::Vsb8)?,
+            inversion,
+        }),
+        _ => return Err(format!("unsupported source {:?}", source)),
+    };
+
+    let (name, provider) = names.split_once(';').unwrap_or((names, ""));
+    let mut channel = Channel::new(name.replace('|', ":"), request);
+    channel.provider = provider.replace('|', ":");
+    if let Some((transponder_khz, polarization, source)) = satellite {
+        channel.transponder_khz = Some(transponder_khz);
+        channel.polarization = Some(polarization);
+        channel.source = Some(source);
+    }
+
+    let (video, video_type) = video.split_once('=').unwrap_or((video, "0"));
+    let (video, pcr) = video.split_once('+').unwrap_or((video, "0"));
+    channel.video_pid = number(video, "video PID")?;
+    channel.pcr_pid = number(pcr, "PCR PID")?;
+    channel.video_type = number(video_type, "video stream type")?;
+
+    let (audio, dolby) = audio.split_once(';').unwrap_or((audio, ""));
+    channel.audio = components(audio)?;
+    channel.dolby = components(dolby)?;
+
+    let (teletext, subtitles) = teletext.split_once(';').unwrap_or((teletext, ""));
+    channel.teletext_pid = number(teletext, "teletext PID")?;
+    channel.subtitles = components(subtitles)?;
+
+    channel.ca_system_ids = ca
+        .split(',')
+        .map(|id| {
+            u16::from_str_radix(id.trim(), 16).map_err(|_| format!("invalid CA system id {:?}", id))
+        })
+        .filter(|id| *id != Ok(0))
+        .collect::<std::result::Result<_, _>>()?;
+
+    channel.service_id = number(service_id, "service id")?;
+    channel.original_network_id = number(network_id, "network id")?;
+    channel.transport_stream_id = number(transport_stream_id, "transport stream id")?;
+    channel.radio_id = number(radio_id, "radio id")?;
+
+    Ok(channel)
+}
+
+/// Parses one line of a `channels.conf` file.
+pub fn parse_vdr_line(line: &str) -> Result<Channel> {
+    parse_line(line.trim()).map_err(Error::InvalidData)
+}
+
+/// Parses the text of a `channels.conf` file. Empty lines and group
+/// separators are skipped; an error names the line it was found on.
+pub fn parse_vdr_channels(input: &str) -> Result<Vec<Channel>> {
+    input
+        .lines()
+        .enumerate()
+        .map(|(i, line)| (i, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with(':'))
+        .map(|(i, line)| {
+            parse_line(line).map_err(|e| Error::InvalidData(format!("line {}: {}", i + 1, e)))
+        })
+        .collect()
+}
+
+/// Formats a channel as a `channels.conf` line, without the line end.
+///
+/// Satellite frequencies are written in MHz, cable ones in MHz when they
+/// are whole and terrestrial ones in kHz. A satellite channel needs its
+/// transponder frequency, polarization and source; ISDB-T has no VDR
+/// source.
+pub fn format_vdr_line(channel: &Channel) -> Result<String> {
+    let mut parameters = Parameters {
+        polarization: channel.polarization,
+        values: Vec::new(),
+    };
+
+    let transponder_mhz = channel.transponder_khz.unwrap_or(0) / 1000;
+    let (frequency, source, symbolrate) = match &channel.request {
+        TuneRequest::DvbS(tune) => {
+            parameters.push('C', FECS, tune.fec);
+            parameters.push('I', INVERSIONS, tune.inversion);
+            parameters.push('M', MODULATIONS, tune.modulation);
+            parameters.values.push(('S', 0));
+            (transponder_mhz, None, tune.symbolrate)
+        }
+        TuneRequest::DvbS2(tune) => {
+            parameters.push('C', FECS, tune.fec);
+            parameters.push('I', INVERSIONS, tune.inversion);
+            parameters.push('M', MODULATIONS, tune.modulation);
+            parameters.push('N', PILOTS, tune.pilot);
+            parameters.push('O', ROLLOFFS, tune.rolloff);
+            if let Some(mis) = tune.mis {
+                parameters.values.push(('P', mis.stream_id));
+            }
+            parameters.values.push(('S', 1));
+            (transponder_mhz, None, tune.symbolrate)
+        }
+        TuneRequest::DvbC(tune) => {
+            parameters.push('C', FECS, tune.fec);
+            parameters.push('I', INVERSIONS, tune.inversion);
+            parameters.push('M', MODULATIONS, tune.modulation);
+            let frequency = if tune.frequency_hz % 1_000_000 == 0 {
+                tune.frequency_hz / 1_000_000
+            } else {
+                tune.frequency_hz / 1000
+            };
+            (frequency, Some("C"), tune.symbolrate)
+        }
+        TuneRequest::DvbT(tune) => {
+            parameters.push('B', BANDWIDTHS, tune.bandwidth_hz);
+            parameters.push('C', FECS, tune.code_rate_hp);
+            parameters.push('D', FECS, tune.code_rate_lp);
+            parameters.push('G', GUARD_INTERVALS, tune.guard_interval);
+            parameters.push('I', INVERSIONS, tune.inversion);
+            parameters.push('M', MODULATIONS, tune.modulation);
+            parameters.values.push(('S', 0));
+            parameters.push('T', TRANSMISSION_MODES, tune.transmission_mode);
+            parameters.push('Y', HIERARCHIES, tune.hierarchy);
+            (tune.frequency_hz / 1000, Some("T"), 0)
+        }
+        TuneRequest::DvbT2(tune) => {
+            parameters.push('B', BANDWIDTHS, tune.bandwidth_hz);
+            parameters.push('C', FECS, tune.code_rate);
+            parameters.push('G', GUARD_INTERVALS, tune.guard_interval);
+            parameters.push('I', INVERSIONS, tune.inversion);
+            parameters.push('M', MODULATIONS, tune.modulation);
+            if let Some(stream_id) = tune.stream_id {
+                parameters.values.push(('P', stream_id));
+            }
+            parameters.values.push(('S', 1));
+            parameters.push('T', TRANSMISSION_MODES, tune.transmission_mode);
+            (tune.frequency_hz / 1000, Some("T"), 0)
+        }
+        TuneRequest::Atsc(tune) => {
+            parameters.push('I', INVERSIONS, tune.inversion);
+            parameters.push('M', MODULATIONS, tune.modulation);
+            (tune.frequency_hz / 1000, Some("A"), 0)
+        }
+        TuneRequest::IsdbT(_) => {
+            return Err(Error::InvalidData(format!(
+                "{}: VDR has no source for ISDB-T",
+                channel.name
+            )));
+        }
+    };
+
+    let source = match source {
+        Some(source) => source,
+        None => {
+            if channel.transponder_khz.is_none() {
+                return Err(Error::InvalidData(format!(
+                    "{}: satellite channel without transponder frequency",
+                    channel.name
+                )));
+            }
+            if channel.polarization.is_none() {
+                return Err(Error::InvalidData(format!(
+                    "{}: satellite channel without polarization",
+                    channel.name
+                )));
+            }
+            channel.source.as_deref().ok_or_else(|| {
+                Error::InvalidData(format!(
+                    "{}: satellite channel without source",
+                    channel.name
+                ))
+            })?
+        }
+    };
+    let mut parameters_field: String = parameters
+        .polarization
+        .filter(|_| source.starts_with(['S', 's']))
+        .map(Polarization::code)
+        .into_iter()
+        .collect();
+    for (letter, value) in &parameters.values {
+        parameters_field.push_str(&format!("{}{}", letter, value));
+    }
+
+    let mut names = channel.name.replace(':', "|");
+    if !channel.provider.is_empty() {
+        names.push(';');
+        names.push_str(&channel.provider.replace(':', "|"));
+    }
+
+    let mut video = channel.video_pid.to_string();
+    if channel.pcr_pid != 0 && channel.pcr_pid != channel.video_pid {
+        video.push_str(&format!("+{}", channel.pcr_pid));
+    }
+    if channel.video_type != 0 {
+        video.push_str(&format!("={}", channel.video_type));
+    }
+
+    let mut audio = format_components(&channel.audio);
+    if audio.is_empty() {
+        audio.push('0');
+    }
+    if !channel.dolby.is_empty() {
+        audio.push(';');
+        audio.push_str(&format_components(&channel.dolby));
+    }
+
+    let mut teletext = channel.teletext_pid.to_string();
+    if !channel.subtitles.is_empty() {
+        teletext.push(';');
+        teletext.push_str(&format_components(&channel.subtitles));
+    }
+
+    let ca = if channel.ca_system_ids.is_empty() {
+        "0".to_owned()
+    } else {
+        channel
+            .ca_system_ids
+            .iter()
+            .map(|id| format!("{:X}", id))
+            .collect::<Vec<_>>()
+            .join(",")
+    };
+
+    Ok(format!(
+        "{}:{}:{}:{}:{}:{}:{}:{}:{}:{}:{}:{}:{}",
+        names,
+        frequency,
+        parameters_field,
+        source,
+        symbolrate / 1000,
+        video,
+        audio,
+        teletext,
+        ca,
+        channel.service_id,
+        channel.original_network_id,
+        channel.transport_stream_id,
+        channel.radio_id
+    ))
+}
+
+/// Formats channels as a `channels.conf` file, one line each.
+pub fn write_vdr_channels(channels: &[Channel]) -> Result<String> {
+    let mut output = String::new();
+    for channel in channels {
+        output.push_str(&format_vdr_line(channel)?);
+        output.push('\n');
+    }
+
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONF: &str = "\
+:Satellite
+Das Erste HD;ARD:11494:HC23M5O35S1:S19.2E:22000:5101=27:5102=deu@3,5103=mis@3;5106=deu@106:5104;5105=deu:0:10301:1:1019:0
+Sky Cinema;Sky:11797:HC34M2S0:S19.2E:27500:255=2:256=deu@4;259=deu@106:32:1702,1722:10:133:2:0
+MIS Feed:11013:VC23M5N1O20P4S1:S5.0W:30000:0:0:0:0:1:0:0:0
+:Terrestrial and cable
+ZDF:474000:B8C23D12G4I0M64S0T8Y0:T:0:610=2:620=deu@4:630:0:11110:8468:769:0
+rbb HD:498000:B8G19128M256P1S1T32:T:0:2001=36:2002=deu@17:0:0:4:8468:3000:0
+QVC:346:M256:C:6900:512=2:513=ger@3:0:0:12100:61441:1003:0
+WCBS:557000:M10:A:0:49+48=2:52=eng@129:0:0:1:0:1537:0
+";
+
+    #[test]
+    fn parse_reads_every_field() {
+        let channels = parse_vdr_channels(CONF).unwrap();
+        assert_eq!(channels.len(), 7);
+
+        let first = &channels[0];
+        assert_eq!(first.name, "Das Erste HD");
+        assert_eq!(first.provider, "ARD");
+        assert_eq!(first.source.as_deref(), Some("S19.2E"));
+        assert_eq!(first.transponder_khz, Some(11_494_000));
+        assert_eq!(first.polarization, Some(Polarization::Horizontal));
+        assert_eq!(
+            first.request,
+            TuneRequest::DvbS2(DvbS2Tune {
+                symbolrate: 22_000_000,
+                modulation: Modulation::Psk8,
+                fec: Fec::Fec2_3,
+                rolloff: Rolloff::R35,
+                ..Default::default()
+            })
+        );
+        assert_eq!(
+            (first.video_pid, first.pcr_pid, first.video_type),
+            (5101, 0, 27)
+        );
+        assert_eq!(
+            first.audio,
+            [
+                ComponentPid {
+                    pid: 5102,
+                    language: "deu".to_owned(),
+                    stream_type: 3,
+                },
+                ComponentPid {
+                    pid: 5103,
+                    language: "mis".to_owned(),
+                    stream_type: 3,
+                },
+            ]
+        );
+        assert_eq!(first.dolby[0].pid, 5106);
+        assert_eq!(first.teletext_pid, 5104);
+        assert_eq!(first.subtitles[0].language, "deu");
+        assert_eq!(
+            (
+                first.service_id,
+                first.original_network_id,
+                first.transport_stream_id
+            ),
+            (10301, 1, 1019)
+        );
+
+        assert_eq!(channels[1].ca_system_ids, [0x1702, 0x1722]);
+        let TuneRequest::DvbS2(tune) = &channels[2].request else {
+            panic!("not DVB-S2");
+        };
+        assert_eq!(tune.mis.map(|mis| mis.stream_id), Some(4));
+        assert_eq!(tune.pilot, Pilot::On);
+
+        assert_eq!(
+            channels[3].request,
+            TuneRequest::DvbT(DvbTTune {
+                frequency_hz: 474_000_000,
+                modulation: Modulation::Qam64,
+                code_rate_hp: Fec::Fec2_3,
+                code_rate_lp: Fec::Fec1_2,
+                guard_interval: GuardInterval::Gi1_4,
+                transmission_mode: TransmitMode::Tm8K,
+                inversion: Inversion::Off,
+                ..Default::default()
+            })
+        );
+        let TuneRequest::DvbT2(tune) = &channels[4].request else {
+            panic!("not DVB-T2");
+        };
+        assert_eq!(tune.stream_id, Some(1));
+        assert_eq!(tune.guard_interval, GuardInterval::Gi19_128);
+        assert_eq!(channels[5].request.delivery_system().to_string(), "dvb-c");
+        assert_eq!(channels[6].pcr_pid, 48);
+    }
+
+    #[test]
+    fn lines_round_trip() {
+        let channels = parse_vdr_channels(CONF).unwrap();
+        let lines: Vec<&str> = CONF.lines().filter(|line| !line.starts_with(':')).collect();
+        for (channel, line) in channels.iter().zip(lines) {
+            assert_eq!(format_vdr_line(channel).unwrap(), line);
+        }
+
+        let written = write_vdr_channels(&channels).unwrap();
+        assert_eq!(parse_vdr_channels(&written).unwrap(), channels);
+    }
+
+    #[test]
+    fn parse_errors_name_the_line() {
+        let error = parse_vdr_channels("\nX:11494:HC23M5S1:S19.2E:22000:0:0:0:0:1:1:1\n")
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("line 2: 12 fields"), "{}", error);
+
+        let error = parse_vdr_channels("X:11494:C23M5S1:S19.2E:22000:0:0:0:0:1:1:1:0")
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("line 1: satellite channel without polarization"));
+
+        assert!(parse_vdr_line("X:11494:HZ1:S19.2E:22000:0:0:0:0:1:1:1:0").is_err());
+        assert!(parse_vdr_line("X:11494:HM3:S19.2E:22000:0:0:0:0:1:1:1:0").is_err());
+        assert!(parse_vdr_line("X:11494:H:I:22000:0:0:0:0:1:1:1:0").is_err());
+
+        let mut channel = parse_vdr_line(CONF.lines().nth(1).unwrap()).unwrap();
+        channel.source = None;
+        assert!(format_vdr_line(&channel).is_err());
+        let mut channel = parse_vdr_line(CONF.lines().nth(1).unwrap()).unwrap();
+        channel.transponder_khz = None;
+        assert!(format_vdr_line(&channel).is_err());
+    }
+}
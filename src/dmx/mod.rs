@@ -1,3 +1,4 @@
+mod section;
 pub mod sys;
 
 use std::{
@@ -5,7 +6,10 @@ use std::{
         File,
         OpenOptions,
     },
-    io::Read,
+    io::{
+        self,
+        Read,
+    },
     os::{
         fd::{
             AsFd,
@@ -16,14 +20,25 @@ use std::{
             RawFd,
         },
     },
+    time::{
+        Duration,
+        Instant,
+    },
 };
 
+pub use self::section::SectionCollector;
 use self::sys::*;
-use crate::error::{
-    Error,
-    Result,
+use crate::{
+    error::{
+        Error,
+        Result,
+    },
+    fd::wait_readable,
 };
 
+/// Largest PSI/SI section, header included.
+const MAX_SECTION_SIZE: usize = 4096;
+
 /// A reference to the demux device and device information
 #[derive(Debug)]
 pub struct DmxDevice {
@@ -108,6 +123,147 @@ impl DmxDevice {
         self.set_pes_filter(&filter)
     }
 
+    /// Sets up a section filter. Each read then returns one whole section.
+    pub fn set_section_filter(&self, filter: &DmxSctFilterParams) -> Result<()> {
+        // DMX_SET_FILTER
+        nix::ioctl_write_ptr!(
+            #[inline]
+            ioctl_call,
+            b'o',
+            43,
+            DmxSctFilterParams
+        );
+        unsafe { ioctl_call(self.as_raw_fd(), filter) }?;
+
+        Ok(())
+    }
+
+    /// Starts a section filter for the current tables `table_id` on `pid`,
+    /// the table id bits outside `table_id_mask` ignored, and only table id
+    /// extension `extension` when given. The demux checks the CRC.
+    pub fn set_table_filter(
+        &self,
+        pid: u16,
+        table_id: u8,
+        table_id_mask: u8,
+        extension: Option<u16>,
+    ) -> Result<()> {
+        let mut filter = DmxFilter::default();
+        filter.filter[0] = table_id;
+        filter.mask[0] = table_id_mask;
+        if let Some(extension) = extension {
+            filter.filter[1 .. 3].copy_from_slice(&extension.to_be_bytes());
+            filter.mask[1 .. 3].copy_from_slice(&[0xFF, 0xFF]);
+        }
+        // current_next_indicator
+        filter.filter[3] = 0x01;
+        filter.mask[3] = 0x01;
+
+        self.set_section_filter(&DmxSctFilterParams {
+            pid,
+            filter,
+            timeout: 0,
+            flags: (DmxFilterFlags::CHECK_CRC | DmxFilterFlags::IMMEDIATE_START).bits(),
+        })
+    }
+
+    /// Reads one section of a running section filter, waiting up to
+    /// `timeout` for it. Returns `None` if none arrived in time.
+    ///
+    /// Sections lost to a full buffer (`EOVERFLOW`) are skipped.
+    pub fn read_section(&mut self, timeout: Duration) -> Result<Option<Vec<u8>>> {
+        let deadline = Instant::now() + timeout;
+        let mut buf = vec![0; MAX_SECTION_SIZE];
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if !wait_readable(self.as_raw_fd(), remaining)? {
+                return Ok(None);
+            }
+
+            match self.read(&mut buf) {
+                Ok(size) => {
+                    buf.truncate(size);
+                    return Ok(Some(buf));
+                }
+                Err(e) if e.raw_os_error() == Some(::nix::libc::EOVERFLOW) => {}
+                Err(e) if e.raw_os_error() == Some(::nix::libc::ETIMEDOUT) => return Ok(None),
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+
+    /// Reads one table: every section of table `table_id` on `pid`, with
+    /// table id extension `extension` when given. Without one, every table
+    /// `table_id` that arrives before the read completes is part of it,
+    /// which suits the tables a transport stream carries once: the PAT, the
+    /// actual NIT and SDT. Fails with `ETIMEDOUT` when the table is not
+    /// complete within `timeout`.
+    ///
+    /// The filter is stopped before the call returns.
+    pub fn read_table(
+        &mut self,
+        pid: u16,
+        table_id: u8,
+        extension: Option<u16>,
+        timeout: Duration,
+    ) -> Result<Vec<Vec<u8>>> {
+        self.set_table_filter(pid, table_id, 0xFF, extension)?;
+        let collector = self.collect(timeout, SectionCollector::is_complete);
+        self.stop()?;
+
+        let collector = collector?;
+        if !collector.is_complete() {
+            return Err(Error::Nix(nix::errno::Errno::ETIMEDOUT));
+        }
+
+        Ok(collector.into_sections())
+    }
+
+    /// Reads every table on `pid` whose table id matches `table_id` in the
+    /// bits of `table_id_mask`, such as the NIT or SDT of all other
+    /// networks. The read goes on until each table is complete and the
+    /// carousel has come round once, or until `timeout`; what arrived by
+    /// then is returned.
+    ///
+    /// The filter is stopped before the call returns.
+    pub fn read_tables(
+        &mut self,
+        pid: u16,
+        table_id: u8,
+        table_id_mask: u8,
+        timeout: Duration,
+    ) -> Result<Vec<Vec<u8>>> {
+        self.set_table_filter(pid, table_id, table_id_mask, None)?;
+        let collector = self.collect(timeout, |collector| {
+            collector.is_complete() && collector.has_repeated()
+        });
+        self.stop()?;
+
+        Ok(collector?.into_sections())
+    }
+
+    /// Feeds the sections of the running filter into a collector until
+    /// `done` or `timeout`.
+    fn collect(
+        &mut self,
+        timeout: Duration,
+        done: impl Fn(&SectionCollector) -> bool,
+    ) -> Result<SectionCollector> {
+        let deadline = Instant::now() + timeout;
+        let mut collector = SectionCollector::new();
+        while !done(&collector) {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            let Some(section) = self.read_section(remaining)? else {
+                break;
+            };
+            // a short section on a PID of long ones is not part of a table
+            let _ = collector.push(section);
+        }
+
+        Ok(collector)
+    }
+
     /// Sets the size of the circular buffer used for filtered data.
     /// Recommended to use values that are multiples of 4096 bytes.
     /// The default size is 2 * 4096 bytes.
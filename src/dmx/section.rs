@@ -0,0 +1,171 @@
+//! Assembly of PSI/SI tables from the sections a section filter delivers.
+
+use std::collections::BTreeMap;
+
+use crate::error::{
+    Error,
+    Result,
+};
+
+/// Sections of one table version, by section number.
+#[derive(Debug)]
+struct Table {
+    version: u8,
+    sections: Vec<Option<Vec<u8>>>,
+}
+
+/// Gathers the sections of PSI/SI tables as a demux delivers them, and
+/// tells when the tables are complete.
+///
+/// A table is one table id and table id extension; it is complete when
+/// every section from 0 to the last section number has arrived. A new
+/// version starts the table over. Sections with the current-next indicator
+/// cleared describe a table that is not in force yet and are left out.
+#[derive(Debug, Default)]
+pub struct SectionCollector {
+    tables: BTreeMap<(u8, u16), Table>,
+    repeated: bool,
+}
+
+impl SectionCollector {
+    /// Empty collector.
+    pub fn new() -> SectionCollector {
+        SectionCollector::default()
+    }
+
+    /// Takes in a section with the long header. Returns `true` if it was
+    /// new, `false` if it was already there or is not current.
+    pub fn push(&mut self, section: Vec<u8>) -> Result<bool> {
+        if section.len() < 8 || section[1] & 0x80 == 0 {
+            return Err(Error::InvalidData("section has no long header".to_owned()));
+        }
+
+        let table_id = section[0];
+        let extension = u16::from_be_bytes([section[3], section[4]]);
+        let version = (section[5] >> 1) & 0x1F;
+        let number = usize::from(section[6]);
+        let last = usize::from(section[7]);
+        if number > last {
+            return Err(Error::InvalidData(format!(
+                "section number {} is past the last section {}",
+                number, last
+            )));
+        }
+        if section[5] & 0x01 == 0 {
+            return Ok(false);
+        }
+
+        let table = self
+            .tables
+            .entry((table_id, extension))
+            .or_insert_with(|| Table {
+                version,
+                sections: Vec::new(),
+            });
+        if table.version != version || table.sections.len() != last + 1 {
+            table.version = version;
+            table.sections = vec![None; last + 1];
+        }
+
+        let slot = &mut table.sections[number];
+        if slot.is_some() {
+            self.repeated = true;
+            return Ok(false);
+        }
+        *slot = Some(section);
+
+        Ok(true)
+    }
+
+    /// `true` once at least one table arrived and every table seen has all
+    /// its sections.
+    pub fn is_complete(&self) -> bool {
+        !self.tables.is_empty()
+            && self
+                .tables
+                .values()
+                .all(|table| table.sections.iter().all(Option::is_some))
+    }
+
+    /// `true` once a section arrived a second time: the carousel that
+    /// repeats the tables has come round, so every table on the PID has
+    /// been seen.
+    pub fn has_repeated(&self) -> bool {
+        self.repeated
+    }
+
+    /// Sections collected, by table id, table id extension and section
+    /// number.
+    pub fn into_sections(self) -> Vec<Vec<u8>> {
+        self.tables
+            .into_values()
+            .flat_map(|table| table.sections.into_iter().flatten())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn section(table_id: u8, extension: u16, version: u8, number: u8, last: u8) -> Vec<u8> {
+        let [high, low] = extension.to_be_bytes();
+        vec![
+            table_id,
+            0xB0,
+            0x09,
+            high,
+            low,
+            0xC1 | (version << 1),
+            number,
+            last,
+            0x00,
+            0x00,
+            0x00,
+            0x00,
+        ]
+    }
+
+    #[test]
+    fn table_completes_with_all_sections() {
+        let mut collector = SectionCollector::new();
+        assert!(!collector.is_complete());
+
+        assert!(collector.push(section(0x42, 1, 0, 1, 2)).unwrap());
+        assert!(collector.push(section(0x42, 1, 0, 0, 2)).unwrap());
+        assert!(!collector.is_complete());
+        assert!(!collector.push(section(0x42, 1, 0, 1, 2)).unwrap());
+        assert!(collector.has_repeated());
+        assert!(collector.push(section(0x42, 1, 0, 2, 2)).unwrap());
+        assert!(collector.is_complete());
+
+        // a second table is not complete yet
+        assert!(collector.push(section(0x46, 2, 0, 0, 1)).unwrap());
+        assert!(!collector.is_complete());
+
+        let numbers: Vec<(u8, u8)> = collector
+            .into_sections()
+            .iter()
+            .map(|section| (section[0], section[6]))
+            .collect();
+        assert_eq!(numbers, [(0x42, 0), (0x42, 1), (0x42, 2), (0x46, 0)]);
+    }
+
+    #[test]
+    fn new_version_starts_over() {
+        let mut collector = SectionCollector::new();
+        collector.push(section(0x40, 1, 3, 0, 1)).unwrap();
+        collector.push(section(0x40, 1, 4, 1, 1)).unwrap();
+        assert!(!collector.is_complete());
+        collector.push(section(0x40, 1, 4, 0, 1)).unwrap();
+        assert!(collector.is_complete());
+
+        // next version, not in force yet
+        let mut next = section(0x40, 1, 5, 0, 0);
+        next[5] &= !0x01;
+        assert!(!collector.push(next).unwrap());
+
+        assert!(collector.push(section(0x40, 1, 0, 2, 1)).is_err());
+        assert!(collector.push(vec![0x70, 0x70, 0x05]).is_err());
+    }
+}
@@ -107,3 +107,37 @@ pub struct DmxPesFilterParams {
     /// Demux PES flags
     pub flags: u32,
 }
+
+/// Size of the match, mask and mode arrays of a [`DmxFilter`]
+pub const DMX_FILTER_SIZE: usize = 16;
+
+/// Bytes a section filter matches.
+///
+/// Byte 0 is the table id, byte 1 on are the section from its fourth byte:
+/// the two section length bytes are left out.
+#[repr(C)]
+#[derive(Default, Debug, Copy, Clone)]
+pub struct DmxFilter {
+    /// Values to match
+    pub filter: [u8; DMX_FILTER_SIZE],
+    /// Bits of `filter` that take part in the match
+    pub mask: [u8; DMX_FILTER_SIZE],
+    /// Bits that match when they differ from `filter` rather than when
+    /// they are equal
+    pub mode: [u8; DMX_FILTER_SIZE],
+}
+
+/// Specifies section filter parameters
+#[repr(C)]
+#[derive(Default, Debug, Copy, Clone)]
+pub struct DmxSctFilterParams {
+    /// PID to be filtered
+    pub pid: u16,
+    /// Section filter
+    pub filter: DmxFilter,
+    /// Time in ms the first section may take before a read fails with
+    /// `ETIMEDOUT`; 0 waits forever
+    pub timeout: u32,
+    /// Demux section flags, [`DmxFilterFlags`]
+    pub flags: u32,
+}
@@ -3,7 +3,10 @@ use std::{
         File,
         OpenOptions,
     },
-    io::Read,
+    io::{
+        self,
+        Read,
+    },
     os::{
         fd::{
             AsFd,
@@ -14,9 +17,16 @@ use std::{
             RawFd,
         },
     },
+    time::{
+        Duration,
+        Instant,
+    },
 };
 
-use crate::error::Result;
+use crate::{
+    error::Result,
+    fd::wait_readable,
+};
 
 /// A reference to the logical DVR device.
 ///
@@ -71,4 +81,25 @@ impl DvrDevice {
 
         Ok(())
     }
+
+    /// Reads transport stream packets, waiting up to `timeout` for them.
+    /// Returns 0 if none arrived in time.
+    ///
+    /// Data lost to a full buffer (`EOVERFLOW`) is skipped.
+    pub fn read_timeout(&mut self, buf: &mut [u8], timeout: Duration) -> Result<usize> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if !wait_readable(self.as_raw_fd(), remaining)? {
+                return Ok(0);
+            }
+
+            match self.read(buf) {
+                Ok(size) => return Ok(size),
+                Err(e) if e.raw_os_error() == Some(::nix::libc::EOVERFLOW) => {}
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
 }
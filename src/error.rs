@@ -1,3 +1,5 @@
+use crate::fe::TuneViolation;
+
 pub type Result<T> = std::result::Result<T, Error>;
 
 #[derive(Debug, thiserror::Error)]
@@ -10,4 +12,16 @@ pub enum Error {
     InvalidProperty(String),
     #[error("Invalid data: {0}")]
     InvalidData(String),
+    #[error("Invalid tune request: {}", join_violations(.0))]
+    InvalidTune(Vec<TuneViolation>),
+    #[error("LNB supply failed: possible overload, or the controller did not answer")]
+    LnbOverload,
+}
+
+fn join_violations(violations: &[TuneViolation]) -> String {
+    violations
+        .iter()
+        .map(|v| v.to_string())
+        .collect::<Vec<_>>()
+        .join("; ")
 }
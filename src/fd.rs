@@ -1,6 +1,7 @@
 use std::{
     io,
     os::unix::io::RawFd,
+    time::Duration,
 };
 
 pub fn file_status_flags(fd: RawFd) -> io::Result<i32> {
@@ -22,3 +23,20 @@ pub fn set_file_status_flags(fd: RawFd, flags: i32) -> io::Result<()> {
         Ok(())
     }
 }
+
+/// Waits until `fd` has data to read, for at most `timeout`. Returns `false` on timeout.
+pub fn wait_readable(fd: RawFd, timeout: Duration) -> io::Result<bool> {
+    let mut pollfd = ::nix::libc::pollfd {
+        fd,
+        events: ::nix::libc::POLLIN,
+        revents: 0,
+    };
+    let timeout_ms = timeout.as_millis().min(i32::MAX as u128) as i32;
+
+    let result = unsafe { ::nix::libc::poll(&mut pollfd, 1, timeout_ms) };
+    if result == -1 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(result > 0)
+    }
+}
@@ -0,0 +1,450 @@
+//! Unicable bus arbitration.
+//!
+//! Every receiver on a Unicable cable sends its channel change commands on
+//! the same wire, and two commands on the wire at once are lost to both: the
+//! ODU keeps the user band on its old transponder and nothing tells the
+//! receiver. [`UnicableArbiter`] serializes the commands of the processes
+//! on one host with a lock file per cable, checks that the frontend locks
+//! after each command, and sends the command again after a random delay
+//! when it does not - the recovery EN 50494 recommends, since a receiver on
+//! another host can still collide. [`UserBandAllocator`] hands the user
+//! bands of a cable out to the frontends of one process.
+
+use std::{
+    fs::{
+        File,
+        OpenOptions,
+        TryLockError,
+    },
+    io,
+    path::{
+        Path,
+        PathBuf,
+    },
+    sync::{
+        Arc,
+        Mutex,
+        atomic::{
+            AtomicU64,
+            Ordering,
+        },
+    },
+    time::{
+        Duration,
+        Instant,
+        SystemTime,
+    },
+};
+
+use super::{
+    CalibratedLnb,
+    FeDevice,
+    SecConfig,
+    SecTimings,
+    TuneRequest,
+    UnicableConfig,
+    UserBand,
+    sec_sequence,
+    sys::SecVoltage,
+    wait_lock,
+};
+use crate::error::{
+    Error,
+    Result,
+};
+
+/// Timings and retry policy of [`UnicableArbiter`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnicableArbiterConfig {
+    /// Directory of the lock files. Every process sharing a cable must use
+    /// the same one.
+    pub lock_dir: PathBuf,
+    /// How long to wait for another process to release the cable.
+    pub lock_timeout: Duration,
+    /// How many times the command is sent before giving up.
+    pub attempts: u32,
+    /// Shortest random delay before the command is sent again.
+    pub retry_delay_min: Duration,
+    /// Longest random delay before the command is sent again.
+    pub retry_delay_max: Duration,
+    /// How long the frontend has to lock after the tune.
+    pub lock_deadline: Duration,
+    /// Waits placed in the SEC sequence.
+    pub timings: SecTimings,
+}
+
+impl Default for UnicableArbiterConfig {
+    fn default() -> Self {
+        let run_lock = Path::new("/run/lock");
+        UnicableArbiterConfig {
+            lock_dir: if run_lock.is_dir() {
+                run_lock.to_path_buf()
+            } else {
+                std::env::temp_dir()
+            },
+            lock_timeout: Duration::from_secs(2),
+            attempts: 4,
+            retry_delay_min: Duration::from_millis(20),
+            retry_delay_max: Duration::from_millis(500),
+            lock_deadline: Duration::from_secs(2),
+            timings: SecTimings::default(),
+        }
+    }
+}
+
+/// Serializes and verifies the Unicable commands sent on one cable.
+#[derive(Debug)]
+pub struct UnicableArbiter {
+    lock_path: PathBuf,
+    config: UnicableArbiterConfig,
+    seed: AtomicU64,
+}
+
+/// Exclusive hold of a cable, released on drop.
+#[derive(Debug)]
+pub struct CableLock {
+    file: File,
+}
+
+impl Drop for CableLock {
+    fn drop(&mut self) {
+        let _ = self.file.unlock();
+    }
+}
+
+impl UnicableArbiter {
+    /// Arbiter for the cable named `cable`, with the default configuration.
+    /// The name is the key the processes on the cable agree on.
+    pub fn new(cable: &str) -> Self {
+        Self::with_config(cable, UnicableArbiterConfig::default())
+    }
+
+    /// Arbiter for the cable named `cable`.
+    pub fn with_config(cable: &str, config: UnicableArbiterConfig) -> Self {
+        let name: String = cable
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+        let lock_path = config
+            .lock_dir
+            .join(format!("libdvb-unicable-{}.lock", name));
+
+        let nanos = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or(0);
+        let seed = nanos ^ (u64::from(std::process::id()) << 32) | 1;
+
+        UnicableArbiter {
+            lock_path,
+            config,
+            seed: AtomicU64::new(seed),
+        }
+    }
+
+    /// Lock file of the cable.
+    pub fn lock_path(&self) -> &Path {
+        &self.lock_path
+    }
+
+    /// Takes the cable, waiting up to
+    /// [`UnicableArbiterConfig::lock_timeout`] for another holder to let go.
+    pub fn lock(&self) -> Result<CableLock> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&self.lock_path)?;
+
+        let start = Instant::now();
+        loop {
+            match file.try_lock() {
+                Ok(()) => return Ok(CableLock { file }),
+                Err(TryLockError::WouldBlock) => {}
+                Err(TryLockError::Error(e)) => return Err(e.into()),
+            }
+
+            if start.elapsed() >= self.config.lock_timeout {
+                return Err(Error::Io(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    format!("Unicable cable {} is busy", self.lock_path.display()),
+                )));
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    /// Runs the SEC step and the tune, and checks the frontend locks.
+    ///
+    /// The SEC sequence runs with the cable locked; the tune and the lock
+    /// check run after it is released, so another receiver is not held up
+    /// for them. When the frontend does not lock within
+    /// [`UnicableArbiterConfig::lock_deadline`], the command may have
+    /// collided with one from another host: it is sent again after a random
+    /// delay, up to [`UnicableArbiterConfig::attempts`] times.
+    ///
+    /// `config` must be a Unicable configuration, anything else fails with
+    /// [`Error::InvalidData`]. `request` is a satellite tune request; its
+    /// frequency is replaced with the one the SEC step returns, which is
+    /// also returned. A transponder that is simply not there fails the same
+    /// way as a lost command, after every attempt.
+    pub fn tune(
+        &self,
+        fe: &FeDevice,
+        transponder_mhz: u32,
+        lnb: impl Into<CalibratedLnb>,
+        config: SecConfig,
+        request: &TuneRequest,
+    ) -> Result<u32> {
+        check_unicable(&config)?;
+        let setup = sec_sequence(transponder_mhz, lnb, config, self.config.timings)?;
+        let request = request.with_intermediate_khz(setup.frontend_frequency_khz)?;
+
+        for attempt in 0 .. self.config.attempts {
+            if attempt > 0 {
+                std::thread::sleep(self.retry_delay());
+            }
+
+            {
+                let _lock = self.lock()?;
+                fe.run_sec_sequence(&setup.sec_sequence)?;
+            }
+
+            fe.tune(&request)?;
+            if wait_lock(fe, self.config.lock_deadline)? {
+                return Ok(setup.frontend_frequency_khz);
+            }
+        }
+
+        Err(Error::Io(io::Error::new(
+            io::ErrorKind::TimedOut,
+            format!(
+                "no lock on {} MHz after {} attempts",
+                transponder_mhz, self.config.attempts
+            ),
+        )))
+    }
+
+    /// Random delay in the configured retry range.
+    fn retry_delay(&self) -> Duration {
+        // xorshift64, good enough to take receivers apart
+        let mut x = self.seed.load(Ordering::Relaxed);
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        self.seed.store(x, Ordering::Relaxed);
+
+        let min = self.config.retry_delay_min;
+        let max = self.config.retry_delay_max.max(min);
+        let span = (max - min).as_micros() as u64;
+        min + Duration::from_micros(x % (span + 1))
+    }
+}
+
+/// Fails unless `config` drives a Unicable ODU: only its channel change
+/// commands collide on a shared cable.
+fn check_unicable(config: &SecConfig) -> Result<()> {
+    match config {
+        SecConfig::Unicable1(_) | SecConfig::Unicable2(_) => Ok(()),
+        _ => Err(Error::InvalidData(
+            "cable arbitration needs a Unicable SEC configuration".to_owned(),
+        )),
+    }
+}
+
+/// Hands the user bands of one cable out to the frontends of a process, so
+/// no two of them tune on the same band. Clones share the pool.
+#[derive(Debug, Clone, Default)]
+pub struct UserBandAllocator {
+    pool: Arc<Mutex<Vec<(UserBand, bool)>>>,
+}
+
+/// A user band taken from a [`UserBandAllocator`]; it goes back to the pool
+/// on drop.
+#[derive(Debug)]
+pub struct UserBandLease {
+    band: UserBand,
+    pool: Arc<Mutex<Vec<(UserBand, bool)>>>,
+}
+
+impl UserBandAllocator {
+    /// Pool of the user bands of a cable, as listed by
+    /// [`FeDevice::unicable_user_bands`] or configured.
+    pub fn new(bands: impl IntoIterator<Item = UserBand>) -> Self {
+        UserBandAllocator {
+            pool: Arc::new(Mutex::new(
+                bands.into_iter().map(|band| (band, false)).collect(),
+            )),
+        }
+    }
+
+    /// Takes the first free user band, `None` when all are taken.
+    pub fn acquire(&self) -> Option<UserBandLease> {
+        let mut pool = self.pool.lock().unwrap_or_else(|e| e.into_inner());
+        let (band, taken) = pool.iter_mut().find(|(_, taken)| !*taken)?;
+        *taken = true;
+
+        Some(UserBandLease {
+            band: *band,
+            pool: self.pool.clone(),
+        })
+    }
+
+    /// Takes a given user band, `None` when it is taken or not in the pool.
+    pub fn acquire_slot(&self, slot: u8) -> Option<UserBandLease> {
+        let mut pool = self.pool.lock().unwrap_or_else(|e| e.into_inner());
+        let (band, taken) = pool
+            .iter_mut()
+            .find(|(band, taken)| band.slot == slot && !*taken)?;
+        *taken = true;
+
+        Some(UserBandLease {
+            band: *band,
+            pool: self.pool.clone(),
+        })
+    }
+
+    /// Number of user bands not taken.
+    pub fn available(&self) -> usize {
+        let pool = self.pool.lock().unwrap_or_else(|e| e.into_inner());
+        pool.iter().filter(|(_, taken)| !*taken).count()
+    }
+}
+
+impl UserBandLease {
+    /// The leased user band.
+    pub fn band(&self) -> UserBand {
+        self.band
+    }
+
+    /// Unicable configuration on the leased user band.
+    pub fn config(&self, position: u8, voltage: SecVoltage, pin: Option<u8>) -> UnicableConfig {
+        UnicableConfig {
+            slot: self.band.slot,
+            user_band_frequency_mhz: self.band.frequency_mhz,
+            position,
+            voltage,
+            pin,
+        }
+    }
+}
+
+impl Drop for UserBandLease {
+    fn drop(&mut self) {
+        let mut pool = self.pool.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some((_, taken)) = pool.iter_mut().find(|(band, _)| *band == self.band) {
+            *taken = false;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn arbiter(cable: &str, lock_timeout: Duration) -> UnicableArbiter {
+        UnicableArbiter::with_config(
+            cable,
+            UnicableArbiterConfig {
+                lock_dir: std::env::temp_dir(),
+                lock_timeout,
+                ..Default::default()
+            },
+        )
+    }
+
+    #[test]
+    fn lock_excludes_a_second_holder_until_dropped() {
+        let cable = format!("test {}", std::process::id());
+        let first = arbiter(&cable, Duration::from_millis(30));
+        let second = arbiter(&cable, Duration::from_millis(30));
+        assert_eq!(first.lock_path(), second.lock_path());
+        assert!(
+            first
+                .lock_path()
+                .ends_with(format!("libdvb-unicable-test_{}.lock", std::process::id()))
+        );
+
+        let lock = first.lock().unwrap();
+        assert!(second.lock().is_err());
+        drop(lock);
+        assert!(second.lock().is_ok());
+
+        let _ = std::fs::remove_file(first.lock_path());
+    }
+
+    #[test]
+    fn retry_delay_stays_in_range() {
+        let arbiter = UnicableArbiter::with_config(
+            "delay",
+            UnicableArbiterConfig {
+                retry_delay_min: Duration::from_millis(20),
+                retry_delay_max: Duration::from_millis(40),
+                ..Default::default()
+            },
+        );
+
+        let delays: Vec<Duration> = (0 .. 100).map(|_| arbiter.retry_delay()).collect();
+        assert!(
+            delays
+                .iter()
+                .all(|d| (Duration::from_millis(20) ..= Duration::from_millis(40)).contains(d))
+        );
+        assert!(delays.iter().any(|d| *d != delays[0]));
+    }
+
+    #[test]
+    fn only_unicable_takes_the_cable() {
+        let lease = UserBandAllocator::new([UserBand {
+            slot: 1,
+            frequency_mhz: 1210,
+        }])
+        .acquire()
+        .unwrap();
+        let unicable = lease.config(0, SecVoltage::V18, None);
+        assert!(check_unicable(&SecConfig::Unicable1(unicable)).is_ok());
+        assert!(check_unicable(&SecConfig::Unicable2(unicable)).is_ok());
+
+        let lnb = SecConfig::Lnb {
+            voltage: SecVoltage::V18,
+        };
+        assert!(matches!(check_unicable(&lnb), Err(Error::InvalidData(_))));
+    }
+
+    #[test]
+    fn allocator_hands_each_band_out_once() {
+        let allocator = UserBandAllocator::new([
+            UserBand {
+                slot: 1,
+                frequency_mhz: 1210,
+            },
+            UserBand {
+                slot: 2,
+                frequency_mhz: 1420,
+            },
+        ]);
+
+        let first = allocator.acquire().unwrap();
+        let second = allocator.clone().acquire().unwrap();
+        assert_eq!(first.band().slot, 1);
+        assert_eq!(second.band().slot, 2);
+        assert!(allocator.acquire().is_none());
+        assert!(allocator.acquire_slot(1).is_none());
+
+        drop(first);
+        assert_eq!(allocator.available(), 1);
+        let again = allocator.acquire_slot(1).unwrap();
+
+        let config = again.config(0, SecVoltage::V18, None);
+        assert_eq!(config.slot, 1);
+        assert_eq!(config.user_band_frequency_mhz, 1210);
+        assert!(allocator.acquire_slot(3).is_none());
+    }
+}
@@ -0,0 +1,550 @@
+//! Channel plans of the terrestrial and cable bands, and a sweep over them.
+//!
+//! Terrestrial and cable networks sit on a fixed channel raster: a channel
+//! number stands for a centre frequency and a bandwidth, set by the region.
+//! A [`BandPlan`] lists the channels of one raster together with what is
+//! transmitted on them - the delivery system, and for cable the symbol
+//! rates and QAM orders in use - so a sweep can tune them one by one
+//! without a transponder list.
+
+use std::{
+    fmt,
+    ops::{
+        ControlFlow,
+        RangeInclusive,
+    },
+    time::Duration,
+};
+
+use super::{
+    AtscTune,
+    DvbCAnnex,
+    DvbCTune,
+    DvbT2Tune,
+    DvbTTune,
+    FeLimits,
+    FeStats,
+    IsdbTTune,
+    LockBus,
+    TuneRequest,
+    sys::{
+        FeCaps,
+        Modulation,
+    },
+};
+use crate::error::Result;
+
+/// A channel of a [`BandPlan`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BandChannel {
+    /// Channel name as the region numbers it, e.g. `21` or `S21`.
+    pub name: String,
+    /// Centre frequency in Hz.
+    pub frequency_hz: u32,
+    /// Channel bandwidth in Hz.
+    pub bandwidth_hz: u32,
+}
+
+impl fmt::Display for BandChannel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} ({:.3} MHz)",
+            self.name,
+            f64::from(self.frequency_hz) / 1_000_000.0
+        )
+    }
+}
+
+/// What the channels of a [`BandPlan`] carry.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BandSystem {
+    /// DVB-T or DVB-T2; a sweep tries both.
+    Dvbt,
+    /// ATSC 8-VSB.
+    Atsc,
+    /// ISDB-T.
+    IsdbT,
+    /// DVB-C on one annex, with the QAM orders and symbol rates in baud
+    /// the operators of the region use, most common first.
+    Cable {
+        annex: DvbCAnnex,
+        modes: Vec<(Modulation, u32)>,
+    },
+}
+
+/// A regional channel raster.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BandPlan {
+    /// Short name of the plan, as [`BandPlan::builtin`] takes it.
+    pub name: String,
+    /// What the channels carry.
+    pub system: BandSystem,
+    /// Channels, in frequency order.
+    pub channels: Vec<BandChannel>,
+}
+
+/// Channels `numbers` of a raster: `prefix` and the number as the name, the
+/// first one centred on `first_hz`, each `step_hz` above the one before.
+fn raster(
+    prefix: &str,
+    numbers: RangeInclusive<u32>,
+    first_hz: u32,
+    step_hz: u32,
+    bandwidth_hz: u32,
+) -> impl Iterator<Item = BandChannel> + '_ {
+    let first = *numbers.start();
+    numbers.map(move |number| BandChannel {
+        name: format!("{}{}", prefix, number),
+        frequency_hz: first_hz + (number - first) * step_hz,
+        bandwidth_hz,
+    })
+}
+
+/// 64-QAM and 256-QAM at the two symbol rates of European cable networks.
+fn eu_cable_modes() -> Vec<(Modulation, u32)> {
+    vec![
+        (Modulation::Qam256, 6_900_000),
+        (Modulation::Qam64, 6_900_000),
+        (Modulation::Qam256, 6_875_000),
+        (Modulation::Qam64, 6_875_000),
+    ]
+}
+
+impl BandPlan {
+    /// Names of the built-in plans.
+    pub const BUILTIN: [&'static str; 8] = [
+        "eu-vhf", "eu-uhf", "eu-cable", "us-atsc", "us-cable", "jp-isdbt", "br-isdbt", "au-dvbt",
+    ];
+
+    /// A built-in plan by its name, see [`BandPlan::BUILTIN`].
+    pub fn builtin(name: &str) -> Option<BandPlan> {
+        match name {
+            "eu-vhf" => Some(BandPlan::eu_vhf()),
+            "eu-uhf" => Some(BandPlan::eu_uhf()),
+            "eu-cable" => Some(BandPlan::eu_cable()),
+            "us-atsc" => Some(BandPlan::us_atsc()),
+            "us-cable" => Some(BandPlan::us_cable()),
+            "jp-isdbt" => Some(BandPlan::jp_isdbt()),
+            "br-isdbt" => Some(BandPlan::br_isdbt()),
+            "au-dvbt" => Some(BandPlan::au_dvbt()),
+            _ => None,
+        }
+    }
+
+    /// European VHF band III, channels 5 to 12 at 7 MHz.
+    pub fn eu_vhf() -> BandPlan {
+        BandPlan {
+            name: "eu-vhf".to_owned(),
+            system: BandSystem::Dvbt,
+            channels: raster("", 5 ..= 12, 177_500_000, 7_000_000, 7_000_000).collect(),
+        }
+    }
+
+    /// European UHF bands IV and V, channels 21 to 69 at 8 MHz.
+    pub fn eu_uhf() -> BandPlan {
+        BandPlan {
+            name: "eu-uhf".to_owned(),
+            system: BandSystem::Dvbt,
+            channels: raster("", 21 ..= 69, 474_000_000, 8_000_000, 8_000_000).collect(),
+        }
+    }
+
+    /// European DVB-C: the 8 MHz channels of the hyperband, S21 to S41,
+    /// and of UHF, 21 to 69.
+    pub fn eu_cable() -> BandPlan {
+        BandPlan {
+            name: "eu-cable".to_owned(),
+            system: BandSystem::Cable {
+                annex: DvbCAnnex::A,
+                modes: eu_cable_modes(),
+            },
+            channels: raster("S", 21 ..= 41, 306_000_000, 8_000_000, 8_000_000)
+                .chain(raster("", 21 ..= 69, 474_000_000, 8_000_000, 8_000_000))
+                .collect(),
+        }
+    }
+
+    /// United States broadcast, channels 2 to 36 at 6 MHz. Channels above 36
+    /// were cleared by the 600 MHz repack.
+    pub fn us_atsc() -> BandPlan {
+        BandPlan {
+            name: "us-atsc".to_owned(),
+            system: BandSystem::Atsc,
+            channels: raster("", 2 ..= 4, 57_000_000, 6_000_000, 6_000_000)
+                .chain(raster("", 5 ..= 6, 79_000_000, 6_000_000, 6_000_000))
+                .chain(raster("", 7 ..= 13, 177_000_000, 6_000_000, 6_000_000))
+                .chain(raster("", 14 ..= 36, 473_000_000, 6_000_000, 6_000_000))
+                .collect(),
+        }
+    }
+
+    /// United States cable, EIA-542 standard plan, channels 2 to 158 in
+    /// frequency order, with 256-QAM and 64-QAM on annex B.
+    pub fn us_cable() -> BandPlan {
+        BandPlan {
+            name: "us-cable".to_owned(),
+            system: BandSystem::Cable {
+                annex: DvbCAnnex::B,
+                modes: vec![
+                    (Modulation::Qam256, 5_360_537),
+                    (Modulation::Qam64, 5_056_941),
+                ],
+            },
+            channels: raster("", 2 ..= 4, 57_000_000, 6_000_000, 6_000_000)
+                .chain(raster("", 5 ..= 6, 79_000_000, 6_000_000, 6_000_000))
+                .chain(raster("", 95 ..= 99, 93_000_000, 6_000_000, 6_000_000))
+                .chain(raster("", 14 ..= 22, 123_000_000, 6_000_000, 6_000_000))
+                .chain(raster("", 7 ..= 13, 177_000_000, 6_000_000, 6_000_000))
+                .chain(raster("", 23 ..= 94, 219_000_000, 6_000_000, 6_000_000))
+                .chain(raster("", 100 ..= 158, 651_000_000, 6_000_000, 6_000_000))
+                .collect(),
+        }
+    }
+
+    /// Japan, UHF channels 13 to 52 at 6 MHz, centred 1/7 MHz above the
+    /// raster.
+    pub fn jp_isdbt() -> BandPlan {
+        BandPlan {
+            name: "jp-isdbt".to_owned(),
+            system: BandSystem::IsdbT,
+            channels: raster("", 13 ..= 52, 473_142_857, 6_000_000, 6_000_000).collect(),
+        }
+    }
+
+    /// Brazil and the ISDB-Tb countries, UHF channels 14 to 51 at 6 MHz,
+    /// centred 1/7 MHz above the raster.
+    pub fn br_isdbt() -> BandPlan {
+        BandPlan {
+            name: "br-isdbt".to_owned(),
+            system: BandSystem::IsdbT,
+            channels: raster("", 14 ..= 51, 473_142_857, 6_000_000, 6_000_000).collect(),
+        }
+    }
+
+    /// Australia, VHF channels 6 to 12 and UHF channels 28 to 51 at 7 MHz.
+    pub fn au_dvbt() -> BandPlan {
+        BandPlan {
+            name: "au-dvbt".to_owned(),
+            system: BandSystem::Dvbt,
+            channels: raster("", 6 ..= 12, 177_500_000, 7_000_000, 7_000_000)
+                .chain(raster("", 28 ..= 51, 529_500_000, 7_000_000, 7_000_000))
+                .collect(),
+        }
+    }
+
+    /// A channel by its name.
+    pub fn channel(&self, name: &str) -> Option<&BandChannel> {
+        self.channels.iter().find(|channel| channel.name == name)
+    }
+
+    /// Tune requests for a channel, in the order a sweep tries them, as far
+    /// as the frontend of `limits` tunes them.
+    ///
+    /// DVB-T comes before DVB-T2 unless `prefer_t2`. A cable channel is
+    /// tried with each mode of the plan; a frontend with `FE_CAN_QAM_AUTO`
+    /// finds the QAM order itself and is tried once per symbol rate.
+    pub fn requests(
+        &self,
+        channel: &BandChannel,
+        limits: &FeLimits,
+        prefer_t2: bool,
+    ) -> Vec<TuneRequest> {
+        let frequency_hz = channel.frequency_hz;
+        let bandwidth_hz = channel.bandwidth_hz;
+
+        let mut requests = match &self.system {
+            BandSystem::Dvbt => {
+                let t = TuneRequest::DvbT(DvbTTune {
+                    frequency_hz,
+                    bandwidth_hz,
+                    ..Default::default()
+                });
+                let t2 = TuneRequest::DvbT2(DvbT2Tune {
+                    frequency_hz,
+                    bandwidth_hz,
+                    ..Default::default()
+                });
+                if prefer_t2 { vec![t2, t] } else { vec![t, t2] }
+            }
+            BandSystem::Atsc => vec![TuneRequest::Atsc(AtscTune {
+                frequency_hz,
+                ..Default::default()
+            })],
+            BandSystem::IsdbT => vec![TuneRequest::IsdbT(IsdbTTune {
+                frequency_hz,
+                bandwidth_hz,
+                ..Default::default()
+            })],
+            BandSystem::Cable { annex, modes } => {
+                let mut modes = modes.clone();
+                // annex B fixes the symbol rate by the QAM order
+                if limits.caps.contains(FeCaps::CAN_QAM_AUTO) && *annex != DvbCAnnex::B {
+                    modes = modes
+                        .iter()
+                        .map(|&(_, symbolrate)| (Modulation::QamAuto, symbolrate))
+                        .collect();
+                    modes.dedup();
+                }
+                modes
+                    .into_iter()
+                    .map(|(modulation, symbolrate)| {
+                        TuneRequest::DvbC(DvbCTune {
+                            frequency_hz,
+                            symbolrate,
+                            annex: *annex,
+                            modulation,
+                            ..Default::default()
+                        })
+                    })
+                    .collect()
+            }
+        };
+        requests.retain(|request| limits.delivery_systems.contains(&request.delivery_system()));
+
+        requests
+    }
+}
+
+/// Parameters of [`FeDevice::sweep_band`](super::FeDevice::sweep_band).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BandSweep {
+    /// How long one tune waits for the lock. DVB-T2 and cable frontends
+    /// that search the QAM order take the longest.
+    pub lock_timeout: Duration,
+    /// Tries DVB-T2 before DVB-T, for a region that has switched over.
+    pub prefer_t2: bool,
+}
+
+impl Default for BandSweep {
+    fn default() -> Self {
+        Self {
+            lock_timeout: Duration::from_secs(2),
+            prefer_t2: false,
+        }
+    }
+}
+
+/// Where a sweep is, reported before each channel.
+#[derive(Debug, Clone, Copy)]
+pub struct BandSweepProgress<'a> {
+    /// Channels done so far.
+    pub done: usize,
+    /// Channels in the plan.
+    pub total: usize,
+    /// The next channel.
+    pub channel: &'a BandChannel,
+    /// Channels locked so far.
+    pub found: usize,
+}
+
+/// A channel a sweep locked on.
+#[derive(Debug, Clone)]
+pub struct LockedChannel {
+    /// The channel of the plan.
+    pub channel: BandChannel,
+    /// The tune request that locked.
+    pub request: TuneRequest,
+    /// Frontend statistics at the lock.
+    pub stats: FeStats,
+}
+
+/// Tunes every channel of `plan` until one of its requests locks.
+/// `progress` can stop the sweep before any channel; the channels locked
+/// until then are returned.
+pub(crate) fn sweep<B, F>(
+    bus: &mut B,
+    plan: &BandPlan,
+    sweep: &BandSweep,
+    limits: &FeLimits,
+    mut progress: F,
+) -> Result<Vec<LockedChannel>>
+where
+    B: LockBus,
+    F: FnMut(&BandSweepProgress<'_>) -> ControlFlow<()>,
+{
+    let mut locked = Vec::new();
+    for (done, channel) in plan.channels.iter().enumerate() {
+        let step = BandSweepProgress {
+            done,
+            total: plan.channels.len(),
+            channel,
+            found: locked.len(),
+        };
+        if progress(&step).is_break() {
+            break;
+        }
+
+        for request in plan.requests(channel, limits, sweep.prefer_t2) {
+            if let Some(stats) = bus.lock(&request, sweep.lock_timeout)? {
+                locked.push(LockedChannel {
+                    channel: channel.clone(),
+                    request,
+                    stats,
+                });
+                break;
+            }
+        }
+    }
+
+    Ok(locked)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fe::sys::DeliverySystem;
+
+    fn limits(delivery_systems: Vec<DeliverySystem>, caps: FeCaps) -> FeLimits {
+        FeLimits {
+            delivery_systems,
+            frequency_range: 42_000_000 ..= 1_002_000_000,
+            frequency_khz: false,
+            symbolrate_range: 870_000 ..= 11_700_000,
+            caps,
+        }
+    }
+
+    #[test]
+    fn builtin_rasters() {
+        for name in BandPlan::BUILTIN {
+            let plan = BandPlan::builtin(name).unwrap();
+            assert_eq!(plan.name, name);
+            // in frequency order, and no two channels overlap
+            for pair in plan.channels.windows(2) {
+                let gap = pair[1].frequency_hz - pair[0].frequency_hz;
+                assert!(
+                    gap >= pair[0].bandwidth_hz,
+                    "{}: {} {}",
+                    name,
+                    pair[0],
+                    pair[1]
+                );
+            }
+        }
+        assert!(BandPlan::builtin("mars").is_none());
+
+        let centre = |plan: BandPlan, name: &str| plan.channel(name).unwrap().frequency_hz;
+        assert_eq!(centre(BandPlan::eu_uhf(), "21"), 474_000_000);
+        assert_eq!(centre(BandPlan::eu_uhf(), "69"), 858_000_000);
+        assert_eq!(centre(BandPlan::eu_vhf(), "12"), 226_500_000);
+        assert_eq!(centre(BandPlan::eu_cable(), "S41"), 466_000_000);
+        assert_eq!(centre(BandPlan::us_atsc(), "14"), 473_000_000);
+        assert_eq!(centre(BandPlan::us_cable(), "95"), 93_000_000);
+        assert_eq!(centre(BandPlan::us_cable(), "94"), 645_000_000);
+        assert_eq!(centre(BandPlan::us_cable(), "100"), 651_000_000);
+        assert_eq!(centre(BandPlan::jp_isdbt(), "27"), 557_142_857);
+        assert_eq!(centre(BandPlan::au_dvbt(), "28"), 529_500_000);
+        assert_eq!(
+            BandPlan::eu_uhf().channel("21").unwrap().to_string(),
+            "21 (474.000 MHz)"
+        );
+    }
+
+    #[test]
+    fn requests_follow_the_frontend() {
+        let plan = BandPlan::eu_uhf();
+        let channel = plan.channel("34").unwrap();
+        let systems = |requests: Vec<TuneRequest>| -> Vec<DeliverySystem> {
+            requests.iter().map(TuneRequest::delivery_system).collect()
+        };
+
+        let both = limits(
+            vec![DeliverySystem::Dvbt, DeliverySystem::Dvbt2],
+            FeCaps::empty(),
+        );
+        assert_eq!(
+            systems(plan.requests(channel, &both, false)),
+            [DeliverySystem::Dvbt, DeliverySystem::Dvbt2]
+        );
+        assert_eq!(
+            systems(plan.requests(channel, &both, true)),
+            [DeliverySystem::Dvbt2, DeliverySystem::Dvbt]
+        );
+        let t_only = limits(vec![DeliverySystem::Dvbt], FeCaps::empty());
+        assert_eq!(
+            systems(plan.requests(channel, &t_only, true)),
+            [DeliverySystem::Dvbt]
+        );
+
+        let cable = BandPlan::eu_cable();
+        let channel = cable.channel("S21").unwrap();
+        let dvbc = limits(vec![DeliverySystem::DvbcAnnexA], FeCaps::empty());
+        assert_eq!(cable.requests(channel, &dvbc, false).len(), 4);
+        let auto = limits(vec![DeliverySystem::DvbcAnnexA], FeCaps::CAN_QAM_AUTO);
+        let requests = cable.requests(channel, &auto, false);
+        assert_eq!(requests.len(), 2);
+        assert!(matches!(
+            &requests[1],
+            TuneRequest::DvbC(DvbCTune {
+                modulation: Modulation::QamAuto,
+                symbolrate: 6_875_000,
+                ..
+            })
+        ));
+        assert!(cable.requests(channel, &both, false).is_empty());
+    }
+
+    /// Transmitters on some channels, each with its delivery system.
+    struct Air {
+        on_air: Vec<(u32, DeliverySystem)>,
+        tunes: usize,
+    }
+
+    impl LockBus for Air {
+        fn lock(&mut self, request: &TuneRequest, _timeout: Duration) -> Result<Option<FeStats>> {
+            self.tunes += 1;
+            let frequency_hz = match request {
+                TuneRequest::DvbT(tune) => tune.frequency_hz,
+                TuneRequest::DvbT2(tune) => tune.frequency_hz,
+                _ => unreachable!(),
+            };
+            let lock = self
+                .on_air
+                .contains(&(frequency_hz, request.delivery_system()));
+            Ok(lock.then(FeStats::default))
+        }
+    }
+
+    #[test]
+    fn sweep_falls_back_to_t2() {
+        let mut air = Air {
+            on_air: vec![
+                (490_000_000, DeliverySystem::Dvbt),
+                (522_000_000, DeliverySystem::Dvbt2),
+            ],
+            tunes: 0,
+        };
+        let plan = BandPlan::eu_uhf();
+        let both = limits(
+            vec![DeliverySystem::Dvbt, DeliverySystem::Dvbt2],
+            FeCaps::empty(),
+        );
+
+        let locked = sweep(&mut air, &plan, &BandSweep::default(), &both, |_| {
+            ControlFlow::Continue(())
+        })
+        .unwrap();
+        let found: Vec<(&str, DeliverySystem)> = locked
+            .iter()
+            .map(|lock| (lock.channel.name.as_str(), lock.request.delivery_system()))
+            .collect();
+        assert_eq!(
+            found,
+            [("23", DeliverySystem::Dvbt), ("27", DeliverySystem::Dvbt2)]
+        );
+        // one tune for the DVB-T channel, two for every other
+        assert_eq!(air.tunes, 49 * 2 - 1);
+
+        // stopped once the first channel is found
+        let locked = sweep(&mut air, &plan, &BandSweep::default(), &both, |step| {
+            if step.found == 1 {
+                ControlFlow::Break(())
+            } else {
+                ControlFlow::Continue(())
+            }
+        })
+        .unwrap();
+        assert_eq!(locked.len(), 1);
+    }
+}
@@ -0,0 +1,829 @@
+//! Satellite blind scan.
+//!
+//! A blind scan finds the carriers of one polarization without a
+//! transponder list, in two passes over the transponder range:
+//!
+//! 1. a sweep tunes the frontend in steps with a narrow symbol rate and reads the signal level at
+//!    each step. Runs of steps above the noise floor are carriers, and the width of a run is a
+//!    first guess at the symbol rate;
+//! 2. each carrier is tuned with the delivery systems, symbol rates, code rates and spectral
+//!    inversions worth trying until the frontend locks. What the frontend reads back after the lock
+//!    is the result.
+//!
+//! There is no capability flag for a symbol rate search, so the symbol
+//! rates come from the estimate and a list of the usual ones. The code rate
+//! and the inversion are left to the frontend where `FE_CAN_FEC_AUTO` and
+//! `FE_CAN_INVERSION_AUTO` say it can find them, and tried one by one
+//! otherwise; DVB-S2 is tried where the frontend lists it.
+
+use std::{
+    ops::ControlFlow,
+    time::Duration,
+};
+
+use super::{
+    CalibratedLnb,
+    DvbS2Tune,
+    DvbSTune,
+    FeDevice,
+    FeLimits,
+    FeStats,
+    SecCommand,
+    SecConfig,
+    SecTimings,
+    TuneRequest,
+    sec::sec_sequence,
+    spectrum::{
+        noise_floor,
+        signal_level,
+    },
+    sys::{
+        DTV_DELIVERY_SYSTEM,
+        DTV_FREQUENCY,
+        DTV_INNER_FEC,
+        DTV_INVERSION,
+        DTV_MODULATION,
+        DTV_PILOT,
+        DTV_ROLLOFF,
+        DTV_SYMBOL_RATE,
+        DeliverySystem,
+        DtvPropertyRaw,
+        FeCaps,
+        Fec,
+        Inversion,
+    },
+    wait_lock,
+};
+use crate::error::{
+    Error,
+    Result,
+};
+
+/// Occupied bandwidth of a carrier over its symbol rate, for the 0.35
+/// roll-off of DVB-S.
+const BANDWIDTH_FACTOR: f64 = 1.35;
+
+/// DVB-S code rates, tried in turn on a frontend that cannot find the rate.
+const DVBS_FEC: [(Fec, FeCaps); 5] = [
+    (Fec::Fec1_2, FeCaps::CAN_FEC_1_2),
+    (Fec::Fec2_3, FeCaps::CAN_FEC_2_3),
+    (Fec::Fec3_4, FeCaps::CAN_FEC_3_4),
+    (Fec::Fec5_6, FeCaps::CAN_FEC_5_6),
+    (Fec::Fec7_8, FeCaps::CAN_FEC_7_8),
+];
+
+/// Parameters of [`FeDevice::blind_scan`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlindScan {
+    /// Lower end of the transponder range in MHz.
+    pub from_mhz: u32,
+    /// Upper end of the transponder range in MHz.
+    pub to_mhz: u32,
+    /// Sweep step in MHz. It should not exceed the bandwidth the frontend
+    /// filters at [`BlindScan::probe_symbolrate`], or a narrow carrier
+    /// between two steps is missed.
+    pub step_mhz: u32,
+    /// Symbol rate of the tune requests the sweep measures with.
+    pub probe_symbolrate: u32,
+    /// How far a carrier rises above the noise floor: in dB where the
+    /// frontend reports the signal in dBm, in percentage points where it
+    /// reports it relative.
+    pub threshold: f64,
+    /// Wait after each sweep tune before the signal level is read.
+    pub settle: Duration,
+    /// How long one lock attempt waits for the lock.
+    pub lock_timeout: Duration,
+    /// Symbol rates in baud to try besides the one estimated from the
+    /// width of the carrier, nearest to the estimate first.
+    pub symbolrates: Vec<u32>,
+    /// How many of [`BlindScan::symbolrates`] a carrier is tried with.
+    pub max_symbolrates: usize,
+    /// Waits around the SEC commands.
+    pub timings: SecTimings,
+}
+
+impl Default for BlindScan {
+    fn default() -> Self {
+        Self {
+            from_mhz: 10_700,
+            to_mhz: 12_750,
+            step_mhz: 2,
+            probe_symbolrate: 2_000_000,
+            threshold: 3.0,
+            settle: Duration::from_millis(30),
+            lock_timeout: Duration::from_secs(1),
+            symbolrates: vec![
+                30_000_000, 29_900_000, 27_500_000, 22_000_000, 14_400_000, 7_200_000, 3_333_000,
+            ],
+            max_symbolrates: 2,
+            timings: SecTimings::default(),
+        }
+    }
+}
+
+/// Pass of a blind scan.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlindScanStage {
+    /// Signal level sweep over the transponder range.
+    Sweep,
+    /// Lock attempts on the carriers the sweep found.
+    Lock,
+}
+
+/// Where a blind scan is, reported before each step.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BlindScanProgress {
+    /// Current pass.
+    pub stage: BlindScanStage,
+    /// Steps of the pass done so far.
+    pub done: usize,
+    /// Steps of the pass in all: sweep steps, or carriers to lock.
+    pub total: usize,
+    /// Transponder frequency of the next step in MHz.
+    pub frequency_mhz: u32,
+    /// Carriers locked so far.
+    pub found: usize,
+}
+
+/// A carrier a blind scan locked on.
+#[derive(Debug, Clone)]
+pub struct BlindScanCarrier {
+    /// Transponder frequency in kHz, corrected by where the frontend found
+    /// the carrier.
+    pub transponder_khz: u32,
+    /// The tune that locked, with the parameters the frontend read back.
+    /// Its frequency is the intermediate frequency the frontend reported.
+    pub request: TuneRequest,
+    /// Frontend statistics at the lock.
+    pub stats: FeStats,
+}
+
+/// A run of sweep steps above the noise floor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Candidate {
+    /// Centre of the run in MHz.
+    frequency_mhz: u32,
+    /// Width of the run in MHz.
+    width_mhz: u32,
+}
+
+impl Candidate {
+    /// Symbol rate a carrier of this width would have, to the kbaud.
+    fn symbolrate(&self) -> u32 {
+        (f64::from(self.width_mhz) * 1000.0 / BANDWIDTH_FACTOR).round() as u32 * 1000
+    }
+}
+
+/// Carriers of a sweep: each run of samples at least `threshold` above the
+/// [`noise_floor`], as its centre and its width.
+fn find_carriers(samples: &[(u32, f64)], step_mhz: u32, threshold: f64) -> Vec<Candidate> {
+    let Some(floor) = noise_floor(samples) else {
+        return Vec::new();
+    };
+
+    let mut candidates = Vec::new();
+    let mut run: Option<(u32, u32)> = None;
+    let mut close = |(first, last): (u32, u32)| {
+        candidates.push(Candidate {
+            frequency_mhz: (first + last) / 2,
+            width_mhz: last - first + step_mhz,
+        })
+    };
+    for &(frequency, level) in samples {
+        if level >= floor + threshold {
+            run = Some(match run {
+                Some((first, _)) => (first, frequency),
+                None => (frequency, frequency),
+            });
+        } else if let Some(run) = run.take() {
+            close(run);
+        }
+    }
+    if let Some(run) = run {
+        close(run);
+    }
+
+    candidates
+}
+
+/// Tune requests for a carrier, in the order they are tried.
+fn attempts(
+    frequency_khz: u32,
+    candidate: &Candidate,
+    scan: &BlindScan,
+    limits: &FeLimits,
+) -> Vec<TuneRequest> {
+    let estimate = candidate.symbolrate();
+    let mut symbolrates = scan.symbolrates.clone();
+    symbolrates.sort_by_key(|symbolrate| symbolrate.abs_diff(estimate));
+    symbolrates.truncate(scan.max_symbolrates);
+    symbolrates.insert(0, estimate);
+    symbolrates.dedup();
+    let range = &limits.symbolrate_range;
+    symbolrates.retain(|symbolrate| *range.end() == 0 || range.contains(symbolrate));
+
+    let inversions: &[Inversion] = if limits.caps.contains(FeCaps::CAN_INVERSION_AUTO) {
+        &[Inversion::Auto]
+    } else {
+        &[Inversion::Off, Inversion::On]
+    };
+    let mut fecs: Vec<Fec> = DVBS_FEC
+        .iter()
+        .filter(|(_, cap)| limits.caps.contains(*cap))
+        .map(|&(fec, _)| fec)
+        .collect();
+    if limits.caps.contains(FeCaps::CAN_FEC_AUTO) || fecs.is_empty() {
+        fecs = vec![Fec::Auto];
+    }
+    let dvbs = limits.delivery_systems.contains(&DeliverySystem::Dvbs);
+    let dvbs2 = limits.delivery_systems.contains(&DeliverySystem::Dvbs2);
+
+    let mut requests = Vec::new();
+    for &symbolrate in &symbolrates {
+        for &inversion in inversions {
+            // the PL header of every DVB-S2 frame carries its MODCOD, there
+            // is no code rate to guess
+            if dvbs2 {
+                requests.push(TuneRequest::DvbS2(DvbS2Tune {
+                    frequency_khz,
+                    symbolrate,
+                    inversion,
+                    ..Default::default()
+                }));
+            }
+            if dvbs {
+                for &fec in &fecs {
+                    requests.push(TuneRequest::DvbS(DvbSTune {
+                        frequency_khz,
+                        symbolrate,
+                        fec,
+                        inversion,
+                        ..Default::default()
+                    }));
+                }
+            }
+        }
+    }
+
+    requests
+}
+
+/// Intermediate frequency and symbol rate of a satellite request.
+fn tuned(request: &TuneRequest) -> Option<(u32, u32)> {
+    match request {
+        TuneRequest::DvbS(tune) => Some((tune.frequency_khz, tune.symbolrate)),
+        TuneRequest::DvbS2(tune) => Some((tune.frequency_khz, tune.symbolrate)),
+        _ => None,
+    }
+}
+
+/// What a blind scan needs from the frontend.
+pub(crate) trait BlindScanBus {
+    /// Runs a SEC sequence.
+    fn sec(&mut self, sequence: &[SecCommand]) -> Result<()>;
+    /// Tunes to an intermediate frequency with the probe symbol rate and
+    /// reads the signal level there.
+    fn level(&mut self, frequency_khz: u32) -> Result<f64>;
+    /// Tunes a request and waits for the lock. Returns the request as read
+    /// back from the frontend and its statistics, or `None` without a lock.
+    fn lock(&mut self, request: &TuneRequest) -> Result<Option<(TuneRequest, FeStats)>>;
+}
+
+/// Points the LNB at a transponder and returns the intermediate frequency
+/// to tune. The SEC sequence only runs when it differs from the last one.
+fn point<B: BlindScanBus>(
+    bus: &mut B,
+    last: &mut Option<Vec<SecCommand>>,
+    transponder_mhz: u32,
+    lnb: CalibratedLnb,
+    config: &SecConfig,
+    timings: SecTimings,
+) -> Result<u32> {
+    let setup = sec_sequence(transponder_mhz, lnb, config.clone(), timings)?;
+    if last.as_ref() != Some(&setup.sec_sequence) {
+        bus.sec(&setup.sec_sequence)?;
+        *last = Some(setup.sec_sequence);
+    }
+
+    Ok(setup.frontend_frequency_khz)
+}
+
+/// Runs both passes of a blind scan. `progress` can stop the scan at any
+/// step; the carriers locked until then are returned.
+pub(crate) fn scan<B, F>(
+    bus: &mut B,
+    lnb: CalibratedLnb,
+    config: &SecConfig,
+    scan: &BlindScan,
+    limits: &FeLimits,
+    mut progress: F,
+) -> Result<Vec<BlindScanCarrier>>
+where
+    B: BlindScanBus,
+    F: FnMut(&BlindScanProgress) -> ControlFlow<()>,
+{
+    if !limits.delivery_systems.contains(&DeliverySystem::Dvbs)
+        && !limits.delivery_systems.contains(&DeliverySystem::Dvbs2)
+    {
+        return Err(Error::InvalidData(
+            "blind scan needs a DVB-S or DVB-S2 frontend".to_owned(),
+        ));
+    }
+    if scan.step_mhz == 0 || scan.from_mhz > scan.to_mhz {
+        return Err(Error::InvalidData(format!(
+            "blind scan range {} - {} MHz in steps of {} MHz is empty",
+            scan.from_mhz, scan.to_mhz, scan.step_mhz
+        )));
+    }
+
+    let mut last = None;
+
+    let steps: Vec<u32> = (scan.from_mhz ..= scan.to_mhz)
+        .step_by(scan.step_mhz as usize)
+        .collect();
+    let mut samples = Vec::with_capacity(steps.len());
+    for (done, &frequency_mhz) in steps.iter().enumerate() {
+        let step = BlindScanProgress {
+            stage: BlindScanStage::Sweep,
+            done,
+            total: steps.len(),
+            frequency_mhz,
+            found: 0,
+        };
+        if progress(&step).is_break() {
+            return Ok(Vec::new());
+        }
+
+        let frequency_khz = point(bus, &mut last, frequency_mhz, lnb, config, scan.timings)?;
+        samples.push((frequency_mhz, bus.level(frequency_khz)?));
+    }
+
+    let candidates = find_carriers(&samples, scan.step_mhz, scan.threshold);
+    let mut carriers: Vec<BlindScanCarrier> = Vec::new();
+    for (done, candidate) in candidates.iter().enumerate() {
+        let step = BlindScanProgress {
+            stage: BlindScanStage::Lock,
+            done,
+            total: candidates.len(),
+            frequency_mhz: candidate.frequency_mhz,
+            found: carriers.len(),
+        };
+        if progress(&step).is_break() {
+            return Ok(carriers);
+        }
+
+        // a wide carrier that dips in the middle shows up as two runs
+        let centre_khz = candidate.frequency_mhz * 1000;
+        let covered = carriers.iter().any(|carrier| {
+            let half_khz = tuned(&carrier.request).map_or(0, |(_, symbolrate)| {
+                (f64::from(symbolrate) * BANDWIDTH_FACTOR / 2000.0) as u32
+            });
+            carrier.transponder_khz.abs_diff(centre_khz) <= half_khz
+        });
+        if covered {
+            continue;
+        }
+
+        let frequency_khz = point(
+            bus,
+            &mut last,
+            candidate.frequency_mhz,
+            lnb,
+            config,
+            scan.timings,
+        )?;
+        for request in attempts(frequency_khz, candidate, scan, limits) {
+            let Some((request, stats)) = bus.lock(&request)? else {
+                continue;
+            };
+
+            let found_khz = tuned(&request).map_or(frequency_khz, |(frequency, _)| frequency);
+            let shift = i64::from(found_khz) - i64::from(frequency_khz);
+            let shift = if lnb.lnb.inverts(centre_khz) {
+                -shift
+            } else {
+                shift
+            };
+            carriers.push(BlindScanCarrier {
+                transponder_khz: (i64::from(centre_khz) + shift).max(0) as u32,
+                request,
+                stats,
+            });
+            break;
+        }
+    }
+
+    Ok(carriers)
+}
+
+/// [`BlindScanBus`] over a frontend.
+pub(crate) struct FeBus<'a> {
+    pub(crate) fe: &'a FeDevice,
+    pub(crate) scan: &'a BlindScan,
+}
+
+impl FeBus<'_> {
+    /// `request` with the parameters the frontend reports after the lock,
+    /// keeping the requested ones it does not report.
+    fn read_back(&self, request: &TuneRequest) -> Result<TuneRequest> {
+        let mut cmdseq = [
+            DtvPropertyRaw::new(DTV_DELIVERY_SYSTEM, 0),
+            DtvPropertyRaw::new(DTV_FREQUENCY, 0),
+            DtvPropertyRaw::new(DTV_SYMBOL_RATE, 0),
+            DtvPropertyRaw::new(DTV_MODULATION, 0),
+            DtvPropertyRaw::new(DTV_INNER_FEC, 0),
+            DtvPropertyRaw::new(DTV_INVERSION, 0),
+            DtvPropertyRaw::new(DTV_PILOT, 0),
+            DtvPropertyRaw::new(DTV_ROLLOFF, 0),
+        ];
+        self.fe.get_properties(&mut cmdseq)?;
+        let [
+            system,
+            frequency,
+            symbolrate,
+            modulation,
+            fec,
+            inversion,
+            pilot,
+            rolloff,
+        ] = cmdseq.map(|property| property.data());
+
+        let (requested_khz, requested_symbolrate) = tuned(request).unwrap_or_default();
+        let frequency_khz = if frequency == 0 {
+            requested_khz
+        } else {
+            frequency
+        };
+        let symbolrate = if symbolrate == 0 {
+            requested_symbolrate
+        } else {
+            symbolrate
+        };
+        let system = DeliverySystem::try_from(system).unwrap_or(request.delivery_system());
+
+        Ok(if system == DeliverySystem::Dvbs2 {
+            let default = DvbS2Tune::default();
+            TuneRequest::DvbS2(DvbS2Tune {
+                frequency_khz,
+                symbolrate,
+                modulation: modulation.try_into().unwrap_or(default.modulation),
+                fec: fec.try_into().unwrap_or(default.fec),
+                inversion: inversion.try_into().unwrap_or(default.inversion),
+                pilot: pilot.try_into().unwrap_or(default.pilot),
+                rolloff: rolloff.try_into().unwrap_or(default.rolloff),
+                mis: None,
+            })
+        } else {
+            let default = DvbSTune::default();
+            TuneRequest::DvbS(DvbSTune {
+                frequency_khz,
+                symbolrate,
+                modulation: modulation.try_into().unwrap_or(default.modulation),
+                fec: fec.try_into().unwrap_or(default.fec),
+                inversion: inversion.try_into().unwrap_or(default.inversion),
+            })
+        })
+    }
+}
+
+impl BlindScanBus for FeBus<'_> {
+    fn sec(&mut self, sequence: &[SecCommand]) -> Result<()> {
+        self.fe.run_sec_sequence(sequence)
+    }
+
+    fn level(&mut self, frequency_khz: u32) -> Result<f64> {
+        signal_level(
+            self.fe,
+            frequency_khz,
+            self.scan.probe_symbolrate,
+            self.scan.settle,
+        )
+    }
+
+    fn lock(&mut self, request: &TuneRequest) -> Result<Option<(TuneRequest, FeStats)>> {
+        self.fe.tune(request)?;
+        if !wait_lock(self.fe, self.scan.lock_timeout)? {
+            return Ok(None);
+        }
+
+        let stats = self.fe.get_stats()?;
+        Ok(Some((self.read_back(request)?, stats)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fe::{
+        Lnb,
+        sys::{
+            SecTone,
+            SecVoltage,
+        },
+    };
+
+    const UNIVERSAL: Lnb = Lnb::Universal {
+        lof_low_mhz: 9_750,
+        lof_high_mhz: 10_600,
+        switch_mhz: 11_700,
+    };
+
+    /// A satellite with carriers at fixed transponder frequencies, seen
+    /// through a universal LNB.
+    struct Sky {
+        /// Transponder frequency in kHz, symbol rate, DVB-S2.
+        carriers: Vec<(u32, u32, bool)>,
+        /// Oscillator error of the LNB in kHz.
+        lo_offset_khz: i32,
+        band_high: bool,
+        sequences: usize,
+        attempts: Vec<TuneRequest>,
+    }
+
+    impl Sky {
+        fn new(carriers: Vec<(u32, u32, bool)>) -> Sky {
+            Sky {
+                carriers,
+                lo_offset_khz: 0,
+                band_high: false,
+                sequences: 0,
+                attempts: Vec::new(),
+            }
+        }
+
+        /// Intermediate frequencies of the carriers in the current band.
+        fn visible(&self) -> impl Iterator<Item = (u32, u32, bool)> + '_ {
+            let lo_khz = if self.band_high {
+                10_600_000
+            } else {
+                9_750_000
+            };
+            self.carriers
+                .iter()
+                .filter(move |&&(khz, ..)| (khz >= 11_700_000) == self.band_high)
+                .map(move |&(khz, symbolrate, s2)| {
+                    let frequency = i64::from(khz) - lo_khz - i64::from(self.lo_offset_khz);
+                    (frequency as u32, symbolrate, s2)
+                })
+        }
+    }
+
+    impl BlindScanBus for Sky {
+        fn sec(&mut self, sequence: &[SecCommand]) -> Result<()> {
+            assert!(sequence.contains(&SecCommand::SetVoltage(SecVoltage::V18)));
+            self.band_high = sequence.contains(&SecCommand::SetTone(SecTone::On));
+            self.sequences += 1;
+            Ok(())
+        }
+
+        fn level(&mut self, frequency_khz: u32) -> Result<f64> {
+            let on_carrier = self.visible().any(|(khz, symbolrate, _)| {
+                f64::from(khz.abs_diff(frequency_khz)) * 1000.0
+                    <= f64::from(symbolrate) * BANDWIDTH_FACTOR / 2.0
+            });
+            Ok(if on_carrier { -45.0 } else { -60.0 })
+        }
+
+        fn lock(&mut self, request: &TuneRequest) -> Result<Option<(TuneRequest, FeStats)>> {
+            self.attempts.push(request.clone());
+            let (frequency_khz, symbolrate) = tuned(request).unwrap();
+            let s2_request = matches!(request, TuneRequest::DvbS2(_));
+
+            // within 3 MHz and 5 % of the symbol rate
+            let found = self.visible().find(|&(khz, rate, s2)| {
+                khz.abs_diff(frequency_khz) <= 3_000
+                    && rate.abs_diff(symbolrate) <= rate / 20
+                    && s2 == s2_request
+            });
+            Ok(found.map(|(khz, rate, _)| {
+                let request = match request.clone() {
+                    TuneRequest::DvbS(tune) => TuneRequest::DvbS(DvbSTune {
+                        frequency_khz: khz,
+                        symbolrate: rate,
+                        ..tune
+                    }),
+                    TuneRequest::DvbS2(tune) => TuneRequest::DvbS2(DvbS2Tune {
+                        frequency_khz: khz,
+                        symbolrate: rate,
+                        ..tune
+                    }),
+                    _ => unreachable!(),
+                };
+                (request, FeStats::default())
+            }))
+        }
+    }
+
+    fn limits(delivery_systems: Vec<DeliverySystem>, caps: FeCaps) -> FeLimits {
+        FeLimits {
+            delivery_systems,
+            frequency_range: 950_000 ..= 2_150_000,
+            frequency_khz: true,
+            symbolrate_range: 1_000_000 ..= 45_000_000,
+            caps,
+        }
+    }
+
+    fn horizontal() -> SecConfig {
+        SecConfig::Lnb {
+            voltage: SecVoltage::V18,
+        }
+    }
+
+    #[test]
+    fn carriers_are_found_in_both_bands() {
+        let mut sky = Sky::new(vec![
+            (11_362_000, 22_000_000, true),
+            (11_597_000, 22_000_000, false),
+            (12_051_000, 27_500_000, true),
+        ]);
+        sky.lo_offset_khz = 800;
+        let scan_config = BlindScan {
+            from_mhz: 11_300,
+            to_mhz: 12_100,
+            ..BlindScan::default()
+        };
+
+        // an LNB that knows its oscillator error: the carriers are found
+        // off the sweep grid, at their exact frequencies
+        let carriers = scan(
+            &mut sky,
+            UNIVERSAL.with_lo_offset(800),
+            &horizontal(),
+            &scan_config,
+            &limits(
+                vec![DeliverySystem::Dvbs, DeliverySystem::Dvbs2],
+                FeCaps::CAN_FEC_AUTO | FeCaps::CAN_INVERSION_AUTO | FeCaps::CAN_2G_MODULATION,
+            ),
+            |_| ControlFlow::Continue(()),
+        )
+        .unwrap();
+
+        let found: Vec<(u32, DeliverySystem, u32)> = carriers
+            .iter()
+            .map(|carrier| {
+                let (_, symbolrate) = tuned(&carrier.request).unwrap();
+                (
+                    carrier.transponder_khz,
+                    carrier.request.delivery_system(),
+                    symbolrate,
+                )
+            })
+            .collect();
+        assert_eq!(
+            found,
+            [
+                (11_362_000, DeliverySystem::Dvbs2, 22_000_000),
+                (11_597_000, DeliverySystem::Dvbs, 22_000_000),
+                (12_051_000, DeliverySystem::Dvbs2, 27_500_000),
+            ]
+        );
+        // once per band in the sweep, then the low band and the high band
+        // again for the lock attempts
+        assert_eq!(sky.sequences, 4);
+    }
+
+    #[test]
+    fn carriers_of_a_busy_band() {
+        // carriers over most of the sweep, with narrow gaps between them
+        let samples: Vec<(u32, f64)> = (0 .. 40)
+            .map(|i| {
+                let frequency = 1_000 + i * 2;
+                let level = if i % 10 < 8 { -45.0 } else { -60.0 };
+                (frequency, level)
+            })
+            .collect();
+
+        let found: Vec<u32> = find_carriers(&samples, 2, 6.0)
+            .iter()
+            .map(|candidate| candidate.frequency_mhz)
+            .collect();
+        assert_eq!(found, [1_007, 1_027, 1_047, 1_067]);
+        assert!(find_carriers(&[], 2, 6.0).is_empty());
+    }
+
+    #[test]
+    fn attempts_follow_the_capabilities() {
+        let candidate = Candidate {
+            frequency_mhz: 11_362,
+            width_mhz: 30,
+        };
+        assert_eq!(candidate.symbolrate(), 22_222_000);
+
+        let scan = BlindScan::default();
+        let auto = attempts(
+            1_612_000,
+            &candidate,
+            &scan,
+            &limits(
+                vec![DeliverySystem::Dvbs, DeliverySystem::Dvbs2],
+                FeCaps::CAN_FEC_AUTO | FeCaps::CAN_INVERSION_AUTO,
+            ),
+        );
+        let rates: Vec<_> = auto.iter().map(|r| tuned(r).unwrap().1).collect();
+        assert_eq!(
+            rates,
+            [
+                22_222_000, 22_222_000, 22_000_000, 22_000_000, 27_500_000, 27_500_000
+            ]
+        );
+
+        // DVB-S only, with three code rates and no automatic inversion
+        let manual = attempts(
+            1_612_000,
+            &candidate,
+            &BlindScan {
+                max_symbolrates: 0,
+                ..scan
+            },
+            &limits(
+                vec![DeliverySystem::Dvbs],
+                FeCaps::CAN_FEC_1_2 | FeCaps::CAN_FEC_3_4 | FeCaps::CAN_FEC_7_8,
+            ),
+        );
+        assert_eq!(manual.len(), 6);
+        assert!(manual.iter().all(|request| matches!(
+            request,
+            TuneRequest::DvbS(DvbSTune {
+                inversion: Inversion::Off | Inversion::On,
+                fec: Fec::Fec1_2 | Fec::Fec3_4 | Fec::Fec7_8,
+                ..
+            })
+        )));
+    }
+
+    #[test]
+    fn progress_stops_the_scan() {
+        let mut sky = Sky::new(vec![
+            (11_362_000, 22_000_000, true),
+            (11_597_000, 22_000_000, true),
+        ]);
+        let scan_config = BlindScan {
+            from_mhz: 11_300,
+            to_mhz: 11_650,
+            ..BlindScan::default()
+        };
+        let limits = limits(
+            vec![DeliverySystem::Dvbs2],
+            FeCaps::CAN_FEC_AUTO | FeCaps::CAN_INVERSION_AUTO,
+        );
+
+        // stopped after the first carrier
+        let mut seen = Vec::new();
+        let carriers = scan(
+            &mut sky,
+            UNIVERSAL.into(),
+            &horizontal(),
+            &scan_config,
+            &limits,
+            |step| {
+                seen.push(step.stage);
+                if step.stage == BlindScanStage::Lock && step.found == 1 {
+                    ControlFlow::Break(())
+                } else {
+                    ControlFlow::Continue(())
+                }
+            },
+        )
+        .unwrap();
+        assert_eq!(carriers.len(), 1);
+        assert_eq!(
+            seen.iter().filter(|s| **s == BlindScanStage::Sweep).count(),
+            176
+        );
+
+        // stopped in the sweep
+        let carriers = scan(
+            &mut sky,
+            UNIVERSAL.into(),
+            &horizontal(),
+            &scan_config,
+            &limits,
+            |step| {
+                if step.done == 10 {
+                    ControlFlow::Break(())
+                } else {
+                    ControlFlow::Continue(())
+                }
+            },
+        )
+        .unwrap();
+        assert!(carriers.is_empty());
+
+        let satellite_less = FeLimits {
+            delivery_systems: vec![DeliverySystem::Dvbt],
+            ..limits
+        };
+        assert!(
+            scan(
+                &mut sky,
+                UNIVERSAL.into(),
+                &horizontal(),
+                &scan_config,
+                &satellite_less,
+                |_| { ControlFlow::Continue(()) }
+            )
+            .is_err()
+        );
+    }
+}
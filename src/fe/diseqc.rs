@@ -0,0 +1,280 @@
+//! DiSEqC 2.x bus replies and slave discovery.
+//!
+//! A DiSEqC 2.x master sends a command with a framing byte that asks for a
+//! reply, and the addressed slave answers within 150 ms with a framing byte
+//! of its own and up to three data bytes. Slaves that answer are there; the
+//! read commands tell what they are and how they are set up.
+
+use std::time::Duration;
+
+use super::sys::DiseqcSlaveReply;
+use crate::error::Result;
+
+/// Framing of a master command that expects a reply, first transmission.
+pub const FRAMING_REPLY_REQUIRED: u8 = 0xE2;
+
+/// Reply framing: the command was carried out, data follows if any.
+pub const REPLY_OK: u8 = 0xE4;
+/// Reply framing: the slave does not support the command.
+pub const REPLY_NOT_SUPPORTED: u8 = 0xE5;
+/// Reply framing: parity error, the command should be repeated.
+pub const REPLY_PARITY_ERROR: u8 = 0xE6;
+/// Reply framing: the command was not recognised, it should be repeated.
+pub const REPLY_NOT_RECOGNISED: u8 = 0xE7;
+
+/// Slave addresses [`FeDevice::diseqc_discover`](super::FeDevice::diseqc_discover)
+/// probes when given none: the LNB, switch, SMATV, polariser, positioner,
+/// installer aid and intelligent interface families of the bus
+/// specification.
+pub const DISEQC_ADDRESSES: &[u8] = &[
+    0x10, 0x11, 0x12, 0x14, 0x15, 0x18, 0x20, 0x21, 0x30, 0x31, 0x32, 0x40, 0x41, 0x70, 0x71,
+];
+
+/// One reply received from a DiSEqC slave.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiseqcReply {
+    /// Reply framing byte, one of the `REPLY_*` values for a compliant
+    /// slave.
+    pub framing: u8,
+    /// Data bytes after the framing, 0 to 3.
+    pub data: Vec<u8>,
+}
+
+impl DiseqcReply {
+    /// Converts the kernel reply; `None` when no message was received.
+    pub(crate) fn from_raw(raw: &DiseqcSlaveReply) -> Option<DiseqcReply> {
+        let len = usize::from(raw.len).min(raw.msg.len());
+        if len == 0 {
+            return None;
+        }
+
+        Some(DiseqcReply {
+            framing: raw.msg[0],
+            data: raw.msg[1 .. len].to_vec(),
+        })
+    }
+
+    /// Returns `true` if the slave carried the command out.
+    pub fn is_ok(&self) -> bool {
+        self.framing == REPLY_OK
+    }
+}
+
+/// DiSEqC 2.x read command.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiseqcRead {
+    /// Status register (command `0x10`).
+    Status,
+    /// Configuration register (command `0x11`).
+    Config,
+    /// Committed switch state, port group 0 (command `0x14`).
+    Switch,
+    /// Current local oscillator frequency as a BCD string, "Read LO_string"
+    /// (command `0x50`). `0x51`, "Read LO_now", answers the number of the
+    /// current LO table entry instead.
+    LoFrequency,
+}
+
+impl DiseqcRead {
+    /// Every read command, in the order discovery sends them.
+    pub const ALL: [DiseqcRead; 4] = [
+        DiseqcRead::Status,
+        DiseqcRead::Config,
+        DiseqcRead::Switch,
+        DiseqcRead::LoFrequency,
+    ];
+
+    /// Command byte.
+    pub fn command(self) -> u8 {
+        match self {
+            DiseqcRead::Status => 0x10,
+            DiseqcRead::Config => 0x11,
+            DiseqcRead::Switch => 0x14,
+            DiseqcRead::LoFrequency => 0x50,
+        }
+    }
+
+    /// Master command that sends the read to `address`.
+    pub fn message(self, address: u8) -> [u8; 3] {
+        [FRAMING_REPLY_REQUIRED, address, self.command()]
+    }
+}
+
+/// A slave that answered discovery, with everything it answered.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiseqcSlave {
+    /// Bus address the slave answered at.
+    pub address: u8,
+    /// Replies to the read commands, in the order they were sent. A read
+    /// the slave did not answer is left out.
+    pub replies: Vec<(DiseqcRead, DiseqcReply)>,
+}
+
+impl DiseqcSlave {
+    /// Data of a successful reply to `read`.
+    pub fn data(&self, read: DiseqcRead) -> Option<&[u8]> {
+        self.replies
+            .iter()
+            .find(|(r, reply)| *r == read && reply.is_ok())
+            .map(|(_, reply)| reply.data.as_slice())
+    }
+
+    /// Status register byte.
+    pub fn status(&self) -> Option<u8> {
+        self.data(DiseqcRead::Status)?.first().copied()
+    }
+
+    /// Configuration register byte.
+    pub fn config(&self) -> Option<u8> {
+        self.data(DiseqcRead::Config)?.first().copied()
+    }
+
+    /// Committed switch state byte: the port, polarization and band bits in
+    /// the layout of a DiSEqC 1.0 write.
+    pub fn switch(&self) -> Option<u8> {
+        self.data(DiseqcRead::Switch)?.first().copied()
+    }
+
+    /// Local oscillator frequency in MHz. Slaves report it as BCD digits,
+    /// as many as the value needs.
+    pub fn lo_frequency_mhz(&self) -> Option<u32> {
+        bcd(self.data(DiseqcRead::LoFrequency)?)
+    }
+}
+
+/// Decimal value of packed BCD digits; `None` for an empty or non-BCD
+/// input.
+pub(crate) fn bcd(data: &[u8]) -> Option<u32> {
+    if data.is_empty() {
+        return None;
+    }
+
+    let mut value = 0u32;
+    for &b in data {
+        for digit in [b >> 4, b & 0x0F] {
+            if digit > 9 {
+                return None;
+            }
+            value = value.checked_mul(10)?.checked_add(u32::from(digit))?;
+        }
+    }
+
+    Some(value)
+}
+
+/// Sends every read command to every address through `read` and collects
+/// the slaves that answered at least once.
+///
+/// Any reply counts as an answer, a "not supported" one included: it still
+/// proves a slave at the address. `read` returns `None` when no reply came
+/// within the timeout.
+pub(crate) fn discover<F>(addresses: &[u8], mut read: F) -> Result<Vec<DiseqcSlave>>
+where
+    F: FnMut(u8, DiseqcRead) -> Result<Option<DiseqcReply>>,
+{
+    let mut result = Vec::new();
+
+    for &address in addresses {
+        let mut replies = Vec::new();
+        for command in DiseqcRead::ALL {
+            if let Some(reply) = read(address, command)? {
+                replies.push((command, reply));
+            }
+        }
+
+        if !replies.is_empty() {
+            result.push(DiseqcSlave { address, replies });
+        }
+    }
+
+    Ok(result)
+}
+
+/// Reply timeout in the unit of the kernel struct, saturated.
+pub(crate) fn timeout_ms(timeout: Duration) -> u32 {
+    u32::try_from(timeout.as_millis()).unwrap_or(u32::MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ok(data: &[u8]) -> DiseqcReply {
+        DiseqcReply {
+            framing: REPLY_OK,
+            data: data.to_vec(),
+        }
+    }
+
+    #[test]
+    fn reply_from_raw() {
+        let mut raw = DiseqcSlaveReply::default();
+        assert_eq!(DiseqcReply::from_raw(&raw), None);
+
+        raw.msg = [0xE4, 0x97, 0x50, 0x00];
+        raw.len = 3;
+        assert_eq!(DiseqcReply::from_raw(&raw), Some(ok(&[0x97, 0x50])));
+
+        // a length past the buffer is cut to the buffer
+        raw.len = 9;
+        assert_eq!(DiseqcReply::from_raw(&raw).unwrap().data.len(), 3);
+    }
+
+    #[test]
+    fn read_messages() {
+        assert_eq!(DiseqcRead::Status.message(0x10), [0xE2, 0x10, 0x10]);
+        assert_eq!(DiseqcRead::Config.message(0x11), [0xE2, 0x11, 0x11]);
+        assert_eq!(DiseqcRead::Switch.message(0x14), [0xE2, 0x14, 0x14]);
+        assert_eq!(DiseqcRead::LoFrequency.message(0x11), [0xE2, 0x11, 0x50]);
+    }
+
+    #[test]
+    fn discover_reports_answering_slaves() {
+        let slaves = discover(&[0x10, 0x11, 0x14], |address, read| {
+            Ok(match (address, read) {
+                (0x11, DiseqcRead::Status) => Some(ok(&[0x01])),
+                // "Read LO_string" of the high band, 10600 MHz
+                (0x11, DiseqcRead::LoFrequency) => Some(ok(&[0x01, 0x06, 0x00])),
+                (0x14, DiseqcRead::Switch) => Some(ok(&[0xF3])),
+                (0x14, _) => Some(DiseqcReply {
+                    framing: REPLY_NOT_SUPPORTED,
+                    data: Vec::new(),
+                }),
+                _ => None,
+            })
+        })
+        .unwrap();
+
+        assert_eq!(slaves.len(), 2);
+
+        assert_eq!(slaves[0].address, 0x11);
+        assert_eq!(slaves[0].status(), Some(0x01));
+        assert_eq!(slaves[0].config(), None);
+        assert_eq!(slaves[0].lo_frequency_mhz(), Some(10600));
+
+        assert_eq!(slaves[1].address, 0x14);
+        assert_eq!(slaves[1].replies.len(), 4);
+        assert_eq!(slaves[1].switch(), Some(0xF3));
+        assert_eq!(slaves[1].status(), None);
+    }
+
+    #[test]
+    fn discover_stops_at_the_first_error() {
+        let mut calls = 0;
+        let result = discover(DISEQC_ADDRESSES, |_, _| {
+            calls += 1;
+            Err(crate::error::Error::Nix(nix::errno::Errno::EOPNOTSUPP))
+        });
+
+        assert!(result.is_err());
+        assert_eq!(calls, 1);
+    }
+
+    #[test]
+    fn bcd_digits() {
+        assert_eq!(bcd(&[0x97, 0x50]), Some(9750));
+        assert_eq!(bcd(&[0x01, 0x06, 0x00]), Some(10600));
+        assert_eq!(bcd(&[0x9A]), None);
+        assert_eq!(bcd(&[]), None);
+    }
+}
@@ -0,0 +1,477 @@
+//! VDR `diseqc.conf` files.
+//!
+//! Each line of the file maps a satellite, a frequency range and a
+//! polarization to the local oscillator to convert with and the SEC sequence
+//! that selects them:
+//!
+//! ```text
+//! # satellite slof polarization lof sequence
+//! S19.2E  11700 V  9750  t v W15 [E0 10 38 F0] W15 A W15 t
+//! S19.2E  99999 V 10600  t v W15 [E0 10 38 F1] W15 A W15 T
+//! ```
+//!
+//! The first line whose satellite and polarization match and whose `slof`
+//! lies above the transponder frequency applies. A line of device numbers
+//! ending in a colon, such as `1 2:`, limits the lines after it to those
+//! devices, up to the next such line. Sequences are in the DSL of
+//! [`SecConfig::Dsl`](super::SecConfig::Dsl), placeholders of
+//! [`SecTemplate`] included; the VDR positioner and SCR bank commands are
+//! not supported.
+
+use super::{
+    Lnb,
+    Polarization,
+    SecSetup,
+    SecTemplate,
+    UserBand,
+    sec::{
+        parse_sec_sequence,
+        template_sequence,
+    },
+    sys::SecTone,
+};
+use crate::error::{
+    Error,
+    Result,
+};
+
+/// One line of a `diseqc.conf` file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiseqcConfEntry {
+    /// Devices the line applies to, `None` for all of them.
+    pub devices: Option<Vec<u32>>,
+    /// Satellite source code, such as `S19.2E`.
+    pub source: String,
+    /// Upper end of the frequency range in MHz, exclusive.
+    pub slof_mhz: u32,
+    /// Polarization.
+    pub polarization: Polarization,
+    /// Local oscillator frequency in MHz.
+    pub lof_mhz: u32,
+    /// Whether the line covers the upper band of its satellite and
+    /// polarization: a line with a lower `slof` comes before it.
+    pub high_band: bool,
+    /// SEC sequence, empty when the line has none.
+    pub sequence: String,
+}
+
+impl DiseqcConfEntry {
+    /// Returns `true` if the line applies to a device, satellite, frequency
+    /// and polarization. `device` `None` matches only lines for all devices.
+    pub fn matches(
+        &self,
+        device: Option<u32>,
+        source: &str,
+        transponder_mhz: u32,
+        polarization: Polarization,
+    ) -> bool {
+        let device_matches = match (&self.devices, device) {
+            (None, _) => true,
+            (Some(devices), Some(device)) => devices.contains(&device),
+            (Some(_), None) => false,
+        };
+
+        device_matches
+            && self.source.eq_ignore_ascii_case(source)
+            && transponder_mhz < self.slof_mhz
+            && self.polarization == polarization
+    }
+
+    /// Builds the SEC setup of a transponder this line applies to.
+    ///
+    /// The transponder is converted through the local oscillator - an
+    /// oscillator above the transponder inverts, as in the C band. `port`
+    /// and `user_band` fill the placeholders of the sequence.
+    pub fn sec_setup(
+        &self,
+        transponder_mhz: u32,
+        port: u8,
+        user_band: Option<UserBand>,
+    ) -> Result<SecSetup> {
+        let lnb = if self.lof_mhz <= transponder_mhz {
+            Lnb::Single {
+                lof_mhz: self.lof_mhz,
+            }
+        } else {
+            Lnb::CBand {
+                lof_mhz: self.lof_mhz,
+            }
+        };
+        let (frequency_mhz, _) = lnb.intermediate(transponder_mhz)?;
+        let band = if self.high_band {
+            SecTone::On
+        } else {
+            SecTone::Off
+        };
+
+        template_sequence(
+            frequency_mhz,
+            &SecTemplate {
+                sequence: self.sequence.clone(),
+                voltage: self.polarization.voltage(),
+                port,
+                user_band,
+            },
+            band,
+        )
+    }
+}
+
+/// A parsed `diseqc.conf` file: the lookup table from satellite, frequency
+/// and polarization to the SEC setup.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DiseqcConf {
+    entries: Vec<DiseqcConfEntry>,
+}
+
+impl DiseqcConf {
+    /// Parses the text of a `diseqc.conf` file. An error names the line it
+    /// was found on.
+    pub fn parse(input: &str) -> Result<DiseqcConf> {
+        let mut entries: Vec<DiseqcConfEntry> = Vec::new();
+        let mut devices = None;
+
+        for (i, line) in input.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let error =
+                |message: String| Error::InvalidData(format!("line {}: {}", i + 1, message));
+
+            if let Some(list) = line.strip_suffix(':') {
+                let list = list
+                    .split_whitespace()
+                    .map(|device| {
+                        device
+                            .parse()
+                            .map_err(|_| error(format!("invalid device number {:?}", device)))
+                    })
+                    .collect::<Result<Vec<u32>>>()?;
+                devices = Some(list);
+                continue;
+            }
+
+            let mut rest = line;
+            let mut field = |name: &str| {
+                let (field, tail) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+                rest = tail.trim_start();
+                if field.is_empty() {
+                    return Err(error(format!("missing {}", name)));
+                }
+                Ok(field)
+            };
+
+            let source = field("satellite")?.to_owned();
+            if !source.starts_with(['S', 's']) {
+                return Err(error(format!("{:?} is not a satellite source", source)));
+            }
+
+            let slof = field("slof")?;
+            let slof_mhz = slof
+                .parse()
+                .map_err(|_| error(format!("invalid slof {:?}", slof)))?;
+
+            let polarization = field("polarization")?;
+            let polarization = match polarization.chars().collect::<Vec<_>>()[..] {
+                [code] => Polarization::from_code(code),
+                _ => None,
+            }
+            .ok_or_else(|| error(format!("invalid polarization {:?}", polarization)))?;
+
+            let lof = field("lof")?;
+            let lof_mhz = lof
+                .parse()
+                .map_err(|_| error(format!("invalid lof {:?}", lof)))?;
+
+            let sequence = rest.to_owned();
+            check_sequence(&sequence).map_err(|e| error(e.to_string()))?;
+
+            let high_band = entries.iter().any(|entry| {
+                entry.devices == devices
+                    && entry.source.eq_ignore_ascii_case(&source)
+                    && entry.polarization == polarization
+                    && entry.slof_mhz < slof_mhz
+            });
+
+            entries.push(DiseqcConfEntry {
+                devices: devices.clone(),
+                source,
+                slof_mhz,
+                polarization,
+                lof_mhz,
+                high_band,
+                sequence,
+            });
+        }
+
+        Ok(DiseqcConf { entries })
+    }
+
+    /// Lines of the file, in order.
+    pub fn entries(&self) -> &[DiseqcConfEntry] {
+        &self.entries
+    }
+
+    /// The line that applies to a transponder, as VDR picks it: the first
+    /// match in file order.
+    pub fn lookup(
+        &self,
+        device: Option<u32>,
+        source: &str,
+        transponder_mhz: u32,
+        polarization: Polarization,
+    ) -> Option<&DiseqcConfEntry> {
+        self.entries
+            .iter()
+            .find(|entry| entry.matches(device, source, transponder_mhz, polarization))
+    }
+
+    /// Builds the SEC setup of a transponder from the line that applies to
+    /// it; see [`DiseqcConfEntry::sec_setup`]. `port` fills the `{port}`
+    /// placeholder, and goes unused by a line without one.
+    pub fn sec_setup(
+        &self,
+        device: Option<u32>,
+        source: &str,
+        transponder_mhz: u32,
+        polarization: Polarization,
+        port: u8,
+        user_band: Option<UserBand>,
+    ) -> Result<SecSetup> {
+        let entry = self
+            .lookup(device, source, transponder_mhz, polarization)
+            .ok_or_else(|| {
+                Error::InvalidData(format!(
+                    "diseqc.conf has no line for {} {} MHz {}",
+                    source,
+                    transponder_mhz,
+                    polarization.code()
+                ))
+            })?;
+
+        entry.sec_setup(transponder_mhz, port, user_band)
+    }
+}
+
+/// Checks the syntax of a sequence, placeholders replaced with a value of
+/// the right shape.
+fn check_sequence(sequence: &str) -> Result<()> {
+    let sample = sequence
+        .replace("{band}", "t")
+        .replace("{pol}", "v")
+        .replace("{port}", "F0")
+        .replace("{ub}", "00 00")
+        .replace("XX", "00");
+    if let Some(start) = sample.find('{') {
+        let end = sample[start ..]
+            .find('}')
+            .map_or(sample.len(), |end| start + end + 1);
+        return Err(Error::InvalidData(format!(
+            "unknown placeholder {}",
+            &sample[start .. end]
+        )));
+    }
+
+    parse_sec_sequence(&sample).map(|_| ())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fe::{
+        SecCommand,
+        sys::{
+            SecMiniCmd,
+            SecVoltage,
+        },
+    };
+
+    const CONF: &str = "\
+# DiSEqC 1.0 + toneburst, VDR style
+S19.2E  11700 V  9750  t v W15 [E0 10 38 F0] W15 A W15 t
+S19.2E  99999 V 10600  t v W15 [E0 10 38 F1] W15 A W15 T
+S19.2E  11700 H  9750  t V W15 [E0 10 38 F2] W15 A W15 t
+S19.2E  99999 H 10600  t V W15 [E0 10 38 F3] W15 A W15 T
+
+1 2:
+S13.0E  11700 V  9750  t {pol} W15 [E0 10 38 {port}] W15 {band}
+S13.0E  99999 V 10600  t {pol} W15 [E0 10 38 {port}] W15 {band}
+
+3:
+S13.0E  11700 V  9750  t v W10 [E0 10 5A XX XX] W10 v
+S13.0E  99999 V 10600  t v W10 [E0 10 5A {ub}] W10 v
+S4.0W   99999 R  5150
+";
+
+    #[test]
+    fn parse_reads_every_field() {
+        let conf = DiseqcConf::parse(CONF).unwrap();
+        assert_eq!(conf.entries().len(), 9);
+
+        let entry = &conf.entries()[1];
+        assert_eq!(entry.devices, None);
+        assert_eq!(entry.source, "S19.2E");
+        assert_eq!(entry.slof_mhz, 99999);
+        assert_eq!(entry.polarization, Polarization::Vertical);
+        assert_eq!(entry.lof_mhz, 10600);
+        assert!(entry.high_band);
+        assert_eq!(entry.sequence, "t v W15 [E0 10 38 F1] W15 A W15 T");
+
+        assert!(!conf.entries()[0].high_band);
+        assert!(!conf.entries()[2].high_band);
+        assert_eq!(conf.entries()[4].devices, Some(vec![1, 2]));
+        assert_eq!(conf.entries()[6].devices, Some(vec![3]));
+        assert_eq!(conf.entries()[8].sequence, "");
+    }
+
+    #[test]
+    fn lookup_takes_the_first_matching_line() {
+        let conf = DiseqcConf::parse(CONF).unwrap();
+
+        let setup = conf
+            .sec_setup(None, "S19.2E", 11_836, Polarization::Horizontal, 1, None)
+            .unwrap();
+        assert_eq!(setup.frontend_frequency_khz, 1_236_000);
+        assert_eq!(
+            setup.sec_sequence,
+            vec![
+                SecCommand::SetTone(SecTone::Off),
+                SecCommand::SetVoltage(SecVoltage::V18),
+                SecCommand::Wait(std::time::Duration::from_millis(15)),
+                SecCommand::SendMasterCommand(vec![0xE0, 0x10, 0x38, 0xF3]),
+                SecCommand::Wait(std::time::Duration::from_millis(15)),
+                SecCommand::SendBurst(SecMiniCmd::A),
+                SecCommand::Wait(std::time::Duration::from_millis(15)),
+                SecCommand::SetTone(SecTone::On),
+            ]
+        );
+
+        // lines limited to other devices do not apply
+        assert!(
+            conf.lookup(None, "S13.0E", 11_000, Polarization::Vertical)
+                .is_none()
+        );
+        assert!(
+            conf.lookup(Some(4), "S13.0E", 11_000, Polarization::Vertical)
+                .is_none()
+        );
+        assert!(
+            conf.lookup(None, "S19.2E", 11_000, Polarization::Left)
+                .is_none()
+        );
+        assert!(
+            conf.sec_setup(None, "S28.2E", 11_000, Polarization::Vertical, 1, None)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn placeholders_expand_per_transponder() {
+        let conf = DiseqcConf::parse(CONF).unwrap();
+
+        let low = conf
+            .sec_setup(Some(2), "S13.0E", 11_034, Polarization::Vertical, 1, None)
+            .unwrap();
+        assert_eq!(low.frontend_frequency_khz, 1_284_000);
+        assert_eq!(low.sec_sequence[1], SecCommand::SetVoltage(SecVoltage::V13));
+        assert_eq!(
+            low.sec_sequence[3],
+            SecCommand::SendMasterCommand(vec![0xE0, 0x10, 0x38, 0xF0])
+        );
+        assert_eq!(low.sec_sequence[5], SecCommand::SetTone(SecTone::Off));
+
+        let high = conf
+            .sec_setup(Some(1), "S13.0E", 12_380, Polarization::Vertical, 1, None)
+            .unwrap();
+        assert_eq!(
+            high.sec_sequence[3],
+            SecCommand::SendMasterCommand(vec![0xE0, 0x10, 0x38, 0xF1])
+        );
+        assert_eq!(high.sec_sequence[5], SecCommand::SetTone(SecTone::On));
+
+        // the port selects the committed switch input
+        let port = conf
+            .sec_setup(Some(2), "S13.0E", 12_380, Polarization::Vertical, 2, None)
+            .unwrap();
+        assert_eq!(
+            port.sec_sequence[3],
+            SecCommand::SendMasterCommand(vec![0xE0, 0x10, 0x38, 0xF5])
+        );
+    }
+
+    #[test]
+    fn user_band_placeholders_encode_the_channel_change() {
+        let conf = DiseqcConf::parse(CONF).unwrap();
+        let user_band = Some(UserBand {
+            slot: 2,
+            frequency_mhz: 1420,
+        });
+
+        // IF 1284 MHz: (1284 + 1420 + 2) / 4 - 350 = 326
+        let setup = conf
+            .sec_setup(
+                Some(3),
+                "S13.0E",
+                11_034,
+                Polarization::Vertical,
+                1,
+                user_band,
+            )
+            .unwrap();
+        assert_eq!(setup.frontend_frequency_khz, 1_420_000);
+        assert_eq!(
+            setup.sec_sequence[3],
+            SecCommand::SendMasterCommand(vec![0xE0, 0x10, 0x5A, 0x21, 0x46])
+        );
+
+        // {ub} in the high band sets the band bit
+        let setup = conf
+            .sec_setup(
+                Some(3),
+                "S13.0E",
+                11_884,
+                Polarization::Vertical,
+                1,
+                user_band,
+            )
+            .unwrap();
+        assert_eq!(
+            setup.sec_sequence[3],
+            SecCommand::SendMasterCommand(vec![0xE0, 0x10, 0x5A, 0x25, 0x46])
+        );
+
+        // without a user band there is nothing to encode
+        assert!(
+            conf.sec_setup(Some(3), "S13.0E", 11_034, Polarization::Vertical, 1, None)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn an_inverting_oscillator_converts_the_c_band() {
+        let conf = DiseqcConf::parse(CONF).unwrap();
+        let setup = conf
+            .sec_setup(Some(3), "S4.0W", 3_900, Polarization::Right, 1, None)
+            .unwrap();
+        assert_eq!(setup.frontend_frequency_khz, 1_250_000);
+        assert!(setup.sec_sequence.is_empty());
+    }
+
+    #[test]
+    fn parse_errors_name_the_line() {
+        for (input, line) in [
+            ("S19.2E 11700 V 9750 t\nS19.2E 99999 X 10600 T\n", "line 2"),
+            ("\n\nS19.2E 11700 V\n", "line 3"),
+            ("C 11700 V 9750\n", "line 1"),
+            ("S19.2E abc V 9750\n", "line 1"),
+            ("# comment\nS19.2E 11700 V 9750 t [E0 10]\n", "line 2"),
+            ("S19.2E 11700 V 9750 {lnb}\n", "line 1"),
+            ("1 x:\n", "line 1"),
+        ] {
+            let error = DiseqcConf::parse(input).unwrap_err().to_string();
+            assert!(error.contains(line), "{input:?}: {error}");
+        }
+    }
+}
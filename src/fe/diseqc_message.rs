@@ -0,0 +1,708 @@
+//! Typed DiSEqC messages.
+//!
+//! A DiSEqC message is a framing byte, an address byte, a command byte and
+//! up to three data bytes. [`DiseqcMessage`] builds one from the framing,
+//! address and command sets of the DiSEqC bus specification instead of from
+//! hand-encoded bytes, and decodes any 3 to 6 byte message back into them -
+//! bytes outside the known sets are kept as they are.
+
+use std::fmt;
+
+use super::{
+    PositionerCommand,
+    SecCommand,
+    diseqc::bcd,
+};
+use crate::error::{
+    Error,
+    Result,
+};
+
+/// Framing byte: who sends, whether a reply is expected, and whether the
+/// message is a repeat.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiseqcFraming {
+    /// Master command, no reply expected, first transmission (`E0`).
+    Command,
+    /// Master command, no reply expected, repeated transmission (`E1`).
+    CommandRepeat,
+    /// Master command, reply expected, first transmission (`E2`).
+    Request,
+    /// Master command, reply expected, repeated transmission (`E3`).
+    RequestRepeat,
+    /// Slave reply, no errors (`E4`).
+    ReplyOk,
+    /// Slave reply, command not supported (`E5`).
+    ReplyNotSupported,
+    /// Slave reply, parity error, repeat the command (`E6`).
+    ReplyParityError,
+    /// Slave reply, command not recognised, repeat it (`E7`).
+    ReplyNotRecognised,
+    /// Any other framing byte.
+    Other(u8),
+}
+
+impl DiseqcFraming {
+    /// Framing byte.
+    pub fn byte(self) -> u8 {
+        match self {
+            DiseqcFraming::Command => 0xE0,
+            DiseqcFraming::CommandRepeat => 0xE1,
+            DiseqcFraming::Request => 0xE2,
+            DiseqcFraming::RequestRepeat => 0xE3,
+            DiseqcFraming::ReplyOk => 0xE4,
+            DiseqcFraming::ReplyNotSupported => 0xE5,
+            DiseqcFraming::ReplyParityError => 0xE6,
+            DiseqcFraming::ReplyNotRecognised => 0xE7,
+            DiseqcFraming::Other(byte) => byte,
+        }
+    }
+
+    /// Framing of a byte.
+    pub fn from_byte(byte: u8) -> DiseqcFraming {
+        match byte {
+            0xE0 => DiseqcFraming::Command,
+            0xE1 => DiseqcFraming::CommandRepeat,
+            0xE2 => DiseqcFraming::Request,
+            0xE3 => DiseqcFraming::RequestRepeat,
+            0xE4 => DiseqcFraming::ReplyOk,
+            0xE5 => DiseqcFraming::ReplyNotSupported,
+            0xE6 => DiseqcFraming::ReplyParityError,
+            0xE7 => DiseqcFraming::ReplyNotRecognised,
+            byte => DiseqcFraming::Other(byte),
+        }
+    }
+
+    /// The same framing, marked as a repeated transmission.
+    pub fn repeat(self) -> DiseqcFraming {
+        match self {
+            DiseqcFraming::Command => DiseqcFraming::CommandRepeat,
+            DiseqcFraming::Request => DiseqcFraming::RequestRepeat,
+            framing => framing,
+        }
+    }
+}
+
+impl fmt::Display for DiseqcFraming {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DiseqcFraming::Command => write!(f, "command"),
+            DiseqcFraming::CommandRepeat => write!(f, "command (repeat)"),
+            DiseqcFraming::Request => write!(f, "request"),
+            DiseqcFraming::RequestRepeat => write!(f, "request (repeat)"),
+            DiseqcFraming::ReplyOk => write!(f, "reply"),
+            DiseqcFraming::ReplyNotSupported => write!(f, "reply: not supported"),
+            DiseqcFraming::ReplyParityError => write!(f, "reply: parity error"),
+            DiseqcFraming::ReplyNotRecognised => write!(f, "reply: not recognised"),
+            DiseqcFraming::Other(byte) => write!(f, "framing {:02X}", byte),
+        }
+    }
+}
+
+/// Address byte: the family of slaves a message is for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiseqcAddress {
+    /// Every slave (`00`).
+    Any,
+    /// Any LNB, switcher or SMATV (`10`).
+    AnyLnb,
+    /// LNB (`11`).
+    Lnb,
+    /// LNB with loop-through switching (`12`).
+    LnbLoopThrough,
+    /// Switcher, DC blocking (`14`).
+    Switcher,
+    /// Switcher with DC loop-through (`15`).
+    SwitcherLoopThrough,
+    /// SMATV (`18`).
+    Smatv,
+    /// Any polariser (`20`).
+    AnyPolariser,
+    /// Linear polarisation (skew) controller (`21`).
+    LinearPolariser,
+    /// Any positioner (`30`).
+    AnyPositioner,
+    /// Polar / azimuth positioner (`31`).
+    PolarPositioner,
+    /// Elevation positioner (`32`).
+    ElevationPositioner,
+    /// Any installer aid (`40`).
+    AnyInstallerAid,
+    /// Signal strength analogue value (`41`).
+    SignalStrength,
+    /// Any intelligent slave interface (`70`).
+    AnyInterface,
+    /// Interface for subscriber controlled headends (`71`).
+    SubscriberHeadend,
+    /// Any other address.
+    Other(u8),
+}
+
+impl DiseqcAddress {
+    const KNOWN: [(DiseqcAddress, u8, &'static str); 16] = [
+        (DiseqcAddress::Any, 0x00, "any"),
+        (DiseqcAddress::AnyLnb, 0x10, "any LNB/switcher/SMATV"),
+        (DiseqcAddress::Lnb, 0x11, "LNB"),
+        (DiseqcAddress::LnbLoopThrough, 0x12, "LNB with loop-through"),
+        (DiseqcAddress::Switcher, 0x14, "switcher"),
+        (
+            DiseqcAddress::SwitcherLoopThrough,
+            0x15,
+            "switcher with loop-through",
+        ),
+        (DiseqcAddress::Smatv, 0x18, "SMATV"),
+        (DiseqcAddress::AnyPolariser, 0x20, "any polariser"),
+        (DiseqcAddress::LinearPolariser, 0x21, "linear polariser"),
+        (DiseqcAddress::AnyPositioner, 0x30, "any positioner"),
+        (
+            DiseqcAddress::PolarPositioner,
+            0x31,
+            "polar/azimuth positioner",
+        ),
+        (
+            DiseqcAddress::ElevationPositioner,
+            0x32,
+            "elevation positioner",
+        ),
+        (DiseqcAddress::AnyInstallerAid, 0x40, "any installer aid"),
+        (DiseqcAddress::SignalStrength, 0x41, "signal strength"),
+        (DiseqcAddress::AnyInterface, 0x70, "any interface"),
+        (
+            DiseqcAddress::SubscriberHeadend,
+            0x71,
+            "subscriber headend interface",
+        ),
+    ];
+
+    /// Address byte.
+    pub fn byte(self) -> u8 {
+        match self {
+            DiseqcAddress::Other(byte) => byte,
+            address => Self::KNOWN
+                .iter()
+                .find(|(known, ..)| *known == address)
+                .map_or(0, |&(_, byte, _)| byte),
+        }
+    }
+
+    /// Address of a byte.
+    pub fn from_byte(byte: u8) -> DiseqcAddress {
+        Self::KNOWN
+            .iter()
+            .find(|&&(_, known, _)| known == byte)
+            .map_or(DiseqcAddress::Other(byte), |&(address, ..)| address)
+    }
+}
+
+impl fmt::Display for DiseqcAddress {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DiseqcAddress::Other(byte) => write!(f, "address {:02X}", byte),
+            address => {
+                let name = Self::KNOWN
+                    .iter()
+                    .find(|(known, ..)| known == address)
+                    .map_or("", |&(.., name)| name);
+                write!(f, "{}", name)
+            }
+        }
+    }
+}
+
+/// Command byte and its data.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DiseqcCommand {
+    /// Resets the slave (`00`).
+    Reset,
+    /// Clears the reset flag (`01`).
+    ClearReset,
+    /// Switches the peripheral power supply off (`02`).
+    Standby,
+    /// Switches the peripheral power supply on (`03`).
+    PowerOn,
+    /// Reads the status register (`10`).
+    ReadStatus,
+    /// Reads the configuration register (`11`).
+    ReadConfig,
+    /// Reads the committed switch state (`14`).
+    ReadSwitch0,
+    /// Reads the uncommitted switch state (`15`).
+    ReadSwitch1,
+    /// Selects the low local oscillator (`20`).
+    SetLo,
+    /// Selects vertical / right circular polarisation (`21`).
+    SetVr,
+    /// Selects satellite position A (`22`).
+    SetPositionA,
+    /// Selects switch option A (`23`).
+    SetOptionA,
+    /// Selects the high local oscillator (`24`).
+    SetHi,
+    /// Selects horizontal / left circular polarisation (`25`).
+    SetHl,
+    /// Selects satellite position B (`26`).
+    SetPositionB,
+    /// Selects switch option B (`27`).
+    SetOptionB,
+    /// Sets one uncommitted switch, `1..=4`, to A or B (`28` to `2F`).
+    SetUncommitted { switch: u8, b: bool },
+    /// Writes port group 0, the committed switches (`38`).
+    WriteN0(u8),
+    /// Writes port group 1, the uncommitted switches (`39`).
+    WriteN1(u8),
+    /// Reads analogue value 0 (`40`).
+    ReadA0,
+    /// Reads analogue value 1 (`41`).
+    ReadA1,
+    /// Writes analogue value 0, a polariser skew (`48`).
+    WriteA0(u8),
+    /// Writes analogue value 1 (`49`).
+    WriteA1(u8),
+    /// Reads the current local oscillator frequency, a BCD string in MHz
+    /// (`50`).
+    ReadLoString,
+    /// Reads the number of the current entry of the local oscillator table
+    /// (`51`).
+    ReadLoNow,
+    /// Reads the low local oscillator frequency (`52`).
+    ReadLoLo,
+    /// Reads the high local oscillator frequency (`53`).
+    ReadLoHi,
+    /// Unicable I / EN 50494 channel change, its two data bytes (`5A`).
+    UnicableChannelChange([u8; 2]),
+    /// Tunes an SMATV or LNB synthesizer to a frequency, in kHz, sent as
+    /// six BCD digits of 100 kHz (`58`).
+    WriteFrequency { frequency_khz: u32 },
+    /// Selects a channel by number (`59`).
+    ChannelNumber(u16),
+    /// Reads the positioner status (`64`).
+    PositionerStatus,
+    /// A positioner command: halt, limits, drive, store, goto, the USALS
+    /// angle and recalculate (`60` to `6F`).
+    Positioner(PositionerCommand),
+    /// Any other command, or a known one with data it does not take.
+    Other { command: u8, data: Vec<u8> },
+}
+
+impl DiseqcCommand {
+    /// Command byte and data bytes.
+    fn encode(&self) -> Result<(u8, Vec<u8>)> {
+        let simple = |command: u8| Ok((command, Vec::new()));
+        match self {
+            DiseqcCommand::Reset => simple(0x00),
+            DiseqcCommand::ClearReset => simple(0x01),
+            DiseqcCommand::Standby => simple(0x02),
+            DiseqcCommand::PowerOn => simple(0x03),
+            DiseqcCommand::ReadStatus => simple(0x10),
+            DiseqcCommand::ReadConfig => simple(0x11),
+            DiseqcCommand::ReadSwitch0 => simple(0x14),
+            DiseqcCommand::ReadSwitch1 => simple(0x15),
+            DiseqcCommand::SetLo => simple(0x20),
+            DiseqcCommand::SetVr => simple(0x21),
+            DiseqcCommand::SetPositionA => simple(0x22),
+            DiseqcCommand::SetOptionA => simple(0x23),
+            DiseqcCommand::SetHi => simple(0x24),
+            DiseqcCommand::SetHl => simple(0x25),
+            DiseqcCommand::SetPositionB => simple(0x26),
+            DiseqcCommand::SetOptionB => simple(0x27),
+            DiseqcCommand::SetUncommitted { switch, b } => {
+                if !(1 ..= 4).contains(switch) {
+                    return Err(Error::InvalidData(format!(
+                        "DiSEqC uncommitted switch must be in range 1..=4, got {}",
+                        switch
+                    )));
+                }
+                simple(0x28 + (switch - 1) + if *b { 4 } else { 0 })
+            }
+            DiseqcCommand::WriteN0(data) => Ok((0x38, vec![*data])),
+            DiseqcCommand::WriteN1(data) => Ok((0x39, vec![*data])),
+            DiseqcCommand::ReadA0 => simple(0x40),
+            DiseqcCommand::ReadA1 => simple(0x41),
+            DiseqcCommand::WriteA0(data) => Ok((0x48, vec![*data])),
+            DiseqcCommand::WriteA1(data) => Ok((0x49, vec![*data])),
+            DiseqcCommand::ReadLoString => simple(0x50),
+            DiseqcCommand::ReadLoNow => simple(0x51),
+            DiseqcCommand::ReadLoLo => simple(0x52),
+            DiseqcCommand::ReadLoHi => simple(0x53),
+            DiseqcCommand::UnicableChannelChange(data) => Ok((0x5A, data.to_vec())),
+            DiseqcCommand::WriteFrequency { frequency_khz } => {
+                let value = frequency_khz / 100;
+                if value > 999_999 || frequency_khz % 100 != 0 {
+                    return Err(Error::InvalidData(format!(
+                        "DiSEqC frequency must be a multiple of 100 kHz below 100 GHz, got {} kHz",
+                        frequency_khz
+                    )));
+                }
+                let digits = [
+                    value / 100_000,
+                    value / 10_000 % 10,
+                    value / 1_000 % 10,
+                    value / 100 % 10,
+                    value / 10 % 10,
+                    value % 10,
+                ];
+                let data = digits
+                    .chunks(2)
+                    .map(|pair| ((pair[0] << 4) | pair[1]) as u8)
+                    .collect();
+                Ok((0x58, data))
+            }
+            DiseqcCommand::ChannelNumber(channel) => Ok((0x59, channel.to_be_bytes().to_vec())),
+            DiseqcCommand::PositionerStatus => simple(0x64),
+            DiseqcCommand::Positioner(command) => command.command(),
+            DiseqcCommand::Other { command, data } => Ok((*command, data.clone())),
+        }
+    }
+
+    /// Command of a command byte and its data.
+    fn decode(command: u8, data: &[u8]) -> DiseqcCommand {
+        let decoded = match (command, data) {
+            (0x00, []) => Some(DiseqcCommand::Reset),
+            (0x01, []) => Some(DiseqcCommand::ClearReset),
+            (0x02, []) => Some(DiseqcCommand::Standby),
+            (0x03, []) => Some(DiseqcCommand::PowerOn),
+            (0x10, []) => Some(DiseqcCommand::ReadStatus),
+            (0x11, []) => Some(DiseqcCommand::ReadConfig),
+            (0x14, []) => Some(DiseqcCommand::ReadSwitch0),
+            (0x15, []) => Some(DiseqcCommand::ReadSwitch1),
+            (0x20, []) => Some(DiseqcCommand::SetLo),
+            (0x21, []) => Some(DiseqcCommand::SetVr),
+            (0x22, []) => Some(DiseqcCommand::SetPositionA),
+            (0x23, []) => Some(DiseqcCommand::SetOptionA),
+            (0x24, []) => Some(DiseqcCommand::SetHi),
+            (0x25, []) => Some(DiseqcCommand::SetHl),
+            (0x26, []) => Some(DiseqcCommand::SetPositionB),
+            (0x27, []) => Some(DiseqcCommand::SetOptionB),
+            (0x28 ..= 0x2F, []) => Some(DiseqcCommand::SetUncommitted {
+                switch: (command - 0x28) % 4 + 1,
+                b: command >= 0x2C,
+            }),
+            (0x38, [data]) => Some(DiseqcCommand::WriteN0(*data)),
+            (0x39, [data]) => Some(DiseqcCommand::WriteN1(*data)),
+            (0x40, []) => Some(DiseqcCommand::ReadA0),
+            (0x41, []) => Some(DiseqcCommand::ReadA1),
+            (0x48, [data]) => Some(DiseqcCommand::WriteA0(*data)),
+            (0x49, [data]) => Some(DiseqcCommand::WriteA1(*data)),
+            (0x50, []) => Some(DiseqcCommand::ReadLoString),
+            (0x51, []) => Some(DiseqcCommand::ReadLoNow),
+            (0x52, []) => Some(DiseqcCommand::ReadLoLo),
+            (0x53, []) => Some(DiseqcCommand::ReadLoHi),
+            (0x5A, [b1, b2]) => Some(DiseqcCommand::UnicableChannelChange([*b1, *b2])),
+            (0x58, [_, _, _]) => bcd(data).map(|value| DiseqcCommand::WriteFrequency {
+                frequency_khz: value * 100,
+            }),
+            (0x59, [high, low]) => Some(DiseqcCommand::ChannelNumber(u16::from_be_bytes([
+                *high, *low,
+            ]))),
+            (0x64, []) => Some(DiseqcCommand::PositionerStatus),
+            _ => PositionerCommand::decode(command, data).map(DiseqcCommand::Positioner),
+        };
+
+        decoded.unwrap_or_else(|| DiseqcCommand::Other {
+            command,
+            data: data.to_vec(),
+        })
+    }
+}
+
+impl fmt::Display for DiseqcCommand {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DiseqcCommand::Reset => write!(f, "reset"),
+            DiseqcCommand::ClearReset => write!(f, "clear reset"),
+            DiseqcCommand::Standby => write!(f, "standby"),
+            DiseqcCommand::PowerOn => write!(f, "power on"),
+            DiseqcCommand::ReadStatus => write!(f, "read status"),
+            DiseqcCommand::ReadConfig => write!(f, "read config"),
+            DiseqcCommand::ReadSwitch0 => write!(f, "read switch 0"),
+            DiseqcCommand::ReadSwitch1 => write!(f, "read switch 1"),
+            DiseqcCommand::SetLo => write!(f, "set low band"),
+            DiseqcCommand::SetVr => write!(f, "set vertical/right"),
+            DiseqcCommand::SetPositionA => write!(f, "set position A"),
+            DiseqcCommand::SetOptionA => write!(f, "set option A"),
+            DiseqcCommand::SetHi => write!(f, "set high band"),
+            DiseqcCommand::SetHl => write!(f, "set horizontal/left"),
+            DiseqcCommand::SetPositionB => write!(f, "set position B"),
+            DiseqcCommand::SetOptionB => write!(f, "set option B"),
+            DiseqcCommand::SetUncommitted { switch, b } => {
+                write!(f, "set S{}{}", switch, if *b { 'B' } else { 'A' })
+            }
+            DiseqcCommand::WriteN0(data) => {
+                write!(f, "write N0 {:02X}", data)?;
+                if data & 0xF0 == 0xF0 {
+                    write!(
+                        f,
+                        " (port {}, {}, {} band)",
+                        ((data >> 2) & 0x03) + 1,
+                        if data & 0x02 != 0 { "18V" } else { "13V" },
+                        if data & 0x01 != 0 { "high" } else { "low" }
+                    )?;
+                }
+                Ok(())
+            }
+            DiseqcCommand::WriteN1(data) => {
+                write!(f, "write N1 {:02X}", data)?;
+                if data & 0xF0 == 0xF0 {
+                    write!(f, " (port {})", (data & 0x0F) + 1)?;
+                }
+                Ok(())
+            }
+            DiseqcCommand::ReadA0 => write!(f, "read A0"),
+            DiseqcCommand::ReadA1 => write!(f, "read A1"),
+            DiseqcCommand::WriteA0(data) => write!(f, "write A0 {:02X}", data),
+            DiseqcCommand::WriteA1(data) => write!(f, "write A1 {:02X}", data),
+            DiseqcCommand::ReadLoString => write!(f, "read LO string"),
+            DiseqcCommand::ReadLoNow => write!(f, "read LO now"),
+            DiseqcCommand::ReadLoLo => write!(f, "read low LO"),
+            DiseqcCommand::ReadLoHi => write!(f, "read high LO"),
+            DiseqcCommand::UnicableChannelChange([b1, b2]) => write!(
+                f,
+                "Unicable channel change (user band {}, position {}, {}, {} band, tuning word {})",
+                (b1 >> 5) + 1,
+                if b1 & 0x10 != 0 { 'B' } else { 'A' },
+                if b1 & 0x08 != 0 { "18V" } else { "13V" },
+                if b1 & 0x04 != 0 { "high" } else { "low" },
+                (u16::from(b1 & 0x03) << 8) | u16::from(*b2)
+            ),
+            DiseqcCommand::WriteFrequency { frequency_khz } => write!(
+                f,
+                "write frequency {}.{} MHz",
+                frequency_khz / 1000,
+                frequency_khz % 1000 / 100
+            ),
+            DiseqcCommand::ChannelNumber(channel) => write!(f, "channel {}", channel),
+            DiseqcCommand::PositionerStatus => write!(f, "positioner status"),
+            DiseqcCommand::Positioner(command) => write!(f, "{}", command),
+            DiseqcCommand::Other { command, data } => {
+                write!(f, "command {:02X}", command)?;
+                for byte in data {
+                    write!(f, " {:02X}", byte)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// One DiSEqC message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiseqcMessage {
+    pub framing: DiseqcFraming,
+    pub address: DiseqcAddress,
+    pub command: DiseqcCommand,
+}
+
+impl DiseqcMessage {
+    /// Master command without a reply, first transmission.
+    pub fn new(address: DiseqcAddress, command: DiseqcCommand) -> DiseqcMessage {
+        DiseqcMessage {
+            framing: DiseqcFraming::Command,
+            address,
+            command,
+        }
+    }
+
+    /// Master command that asks for a reply.
+    pub fn request(address: DiseqcAddress, command: DiseqcCommand) -> DiseqcMessage {
+        DiseqcMessage {
+            framing: DiseqcFraming::Request,
+            address,
+            command,
+        }
+    }
+
+    /// The same message marked as a repeated transmission.
+    pub fn repeat(&self) -> DiseqcMessage {
+        DiseqcMessage {
+            framing: self.framing.repeat(),
+            ..self.clone()
+        }
+    }
+
+    /// Encodes the message, 3 to 6 bytes.
+    pub fn encode(&self) -> Result<Vec<u8>> {
+        let (command, data) = self.command.encode()?;
+        if data.len() > 3 {
+            return Err(Error::InvalidData(format!(
+                "DiSEqC message carries up to 3 data bytes, got {}",
+                data.len()
+            )));
+        }
+
+        let mut msg = vec![self.framing.byte(), self.address.byte(), command];
+        msg.extend(data);
+        Ok(msg)
+    }
+
+    /// The message as a step of a SEC sequence.
+    pub fn to_sec_command(&self) -> Result<SecCommand> {
+        Ok(SecCommand::SendMasterCommand(self.encode()?))
+    }
+
+    /// Decodes a 3 to 6 byte message. Bytes outside the known framing,
+    /// address and command sets decode to their `Other` variants, so any
+    /// message of a valid length decodes, and encodes back to the same
+    /// bytes.
+    pub fn decode(msg: &[u8]) -> Result<DiseqcMessage> {
+        if !(3 ..= 6).contains(&msg.len()) {
+            return Err(Error::InvalidData(format!(
+                "DiSEqC message length must be 3..=6 bytes, got {}",
+                msg.len()
+            )));
+        }
+
+        Ok(DiseqcMessage {
+            framing: DiseqcFraming::from_byte(msg[0]),
+            address: DiseqcAddress::from_byte(msg[1]),
+            command: DiseqcCommand::decode(msg[2], &msg[3 ..]),
+        })
+    }
+}
+
+impl fmt::Display for DiseqcMessage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}, {}: {}", self.framing, self.address, self.command)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fe::diseqc::DiseqcRead;
+
+    #[test]
+    fn messages_encode_to_spec_bytes() {
+        for (message, bytes) in [
+            (
+                DiseqcMessage::new(DiseqcAddress::AnyLnb, DiseqcCommand::Reset),
+                vec![0xE0, 0x10, 0x00],
+            ),
+            (
+                DiseqcMessage::new(DiseqcAddress::AnyLnb, DiseqcCommand::WriteN0(0xF3)),
+                vec![0xE0, 0x10, 0x38, 0xF3],
+            ),
+            (
+                DiseqcMessage::new(DiseqcAddress::Switcher, DiseqcCommand::WriteN1(0xFA)).repeat(),
+                vec![0xE1, 0x14, 0x39, 0xFA],
+            ),
+            (
+                DiseqcMessage::new(
+                    DiseqcAddress::AnyLnb,
+                    DiseqcCommand::SetUncommitted { switch: 3, b: true },
+                ),
+                vec![0xE0, 0x10, 0x2E],
+            ),
+            (
+                DiseqcMessage::new(
+                    DiseqcAddress::Smatv,
+                    DiseqcCommand::WriteFrequency {
+                        frequency_khz: 11_836_500,
+                    },
+                ),
+                vec![0xE0, 0x18, 0x58, 0x11, 0x83, 0x65],
+            ),
+            (
+                DiseqcMessage::new(DiseqcAddress::Smatv, DiseqcCommand::ChannelNumber(0x0123)),
+                vec![0xE0, 0x18, 0x59, 0x01, 0x23],
+            ),
+            (
+                DiseqcMessage::new(
+                    DiseqcAddress::PolarPositioner,
+                    DiseqcCommand::Positioner(PositionerCommand::GotoAngle { sixteenths: -613 }),
+                ),
+                vec![0xE0, 0x31, 0x6E, 0xD2, 0x65],
+            ),
+            (
+                DiseqcMessage::request(DiseqcAddress::Lnb, DiseqcCommand::ReadLoNow),
+                vec![0xE2, 0x11, 0x51],
+            ),
+            (
+                DiseqcMessage::request(DiseqcAddress::Lnb, DiseqcCommand::ReadLoString),
+                DiseqcRead::LoFrequency.message(0x11).to_vec(),
+            ),
+        ] {
+            assert_eq!(message.encode().unwrap(), bytes, "{}", message);
+            assert_eq!(DiseqcMessage::decode(&bytes).unwrap(), message);
+        }
+    }
+
+    #[test]
+    fn any_message_round_trips() {
+        for msg in [
+            &[0xE0, 0x10, 0x38, 0xF3][..],
+            &[0xE0, 0x10, 0x5A, 0x21, 0x46],
+            &[0xE0, 0x31, 0x6F, 0x00, 0x00, 0x00],
+            &[0xE0, 0x31, 0x68, 0xFC],
+            // position 0 cannot be stored, and recalculate takes no data
+            &[0xE0, 0x31, 0x6A, 0x00],
+            &[0xE0, 0x31, 0x6F, 0x01, 0x02, 0x03],
+            &[0xD5, 0x99, 0xAB, 0x01, 0x02, 0x03],
+            // a known command with data it does not take
+            &[0xE0, 0x10, 0x00, 0x01],
+            // not BCD
+            &[0xE0, 0x18, 0x58, 0x1A, 0x00, 0x00],
+        ] {
+            let message = DiseqcMessage::decode(msg).unwrap();
+            assert_eq!(message.encode().unwrap(), msg, "{}", message);
+        }
+
+        assert!(DiseqcMessage::decode(&[0xE0, 0x10]).is_err());
+        assert!(DiseqcMessage::decode(&[0xE0; 7]).is_err());
+    }
+
+    #[test]
+    fn messages_read_as_text() {
+        let text = |msg: &[u8]| DiseqcMessage::decode(msg).unwrap().to_string();
+
+        assert_eq!(
+            text(&[0xE0, 0x10, 0x38, 0xF3]),
+            "command, any LNB/switcher/SMATV: write N0 F3 (port 1, 18V, high band)"
+        );
+        assert_eq!(
+            text(&[0xE1, 0x10, 0x39, 0xFA]),
+            "command (repeat), any LNB/switcher/SMATV: write N1 FA (port 11)"
+        );
+        assert_eq!(
+            text(&[0xE0, 0x10, 0x5A, 0x21, 0x46]),
+            "command, any LNB/switcher/SMATV: Unicable channel change (user band 2, position A, \
+             13V, low band, tuning word 326)"
+        );
+        assert_eq!(
+            text(&[0xE0, 0x31, 0x69, 0xFC]),
+            "command, polar/azimuth positioner: drive west 4 steps"
+        );
+        assert_eq!(
+            text(&[0xE0, 0x31, 0x6E, 0xE0, 0xFA]),
+            "command, polar/azimuth positioner: goto angle 15.62E"
+        );
+        assert_eq!(
+            text(&[0xD5, 0x99, 0xAB, 0x01]),
+            "framing D5, address 99: command AB 01"
+        );
+    }
+
+    #[test]
+    fn invalid_values_are_rejected() {
+        for command in [
+            DiseqcCommand::SetUncommitted {
+                switch: 5,
+                b: false,
+            },
+            DiseqcCommand::WriteFrequency {
+                frequency_khz: 11_836_550,
+            },
+            DiseqcCommand::Positioner(PositionerCommand::GotoAngle { sixteenths: 0x1000 }),
+            DiseqcCommand::Positioner(PositionerCommand::Store(0)),
+            DiseqcCommand::Other {
+                command: 0x38,
+                data: vec![0; 4],
+            },
+        ] {
+            assert!(
+                DiseqcMessage::new(DiseqcAddress::Any, command)
+                    .encode()
+                    .is_err()
+            );
+        }
+    }
+}
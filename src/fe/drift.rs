@@ -0,0 +1,212 @@
+//! LNB oscillator drift learning.
+//!
+//! The oscillator of an LNB is off by up to a few MHz and moves with the
+//! temperature. Once the demodulator has locked, the frequency it reports
+//! is where the carrier really is; the difference to the intermediate
+//! frequency it was asked to tune is the part of the oscillator error the
+//! [`Lnb`] did not account for. [`LnbDriftTracker`] keeps a smoothed
+//! estimate of that error per LNB and puts it into a [`CalibratedLnb`] for
+//! the next tune, so a narrow SCPC carrier lands inside the capture range
+//! the first time.
+
+use std::collections::HashMap;
+
+use super::{
+    CalibratedLnb,
+    Lnb,
+};
+
+/// Inputs for an [`LnbDriftTracker`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LnbDriftConfig {
+    /// Weight of a new measurement in the estimate, `0.0 ..= 1.0`. Low
+    /// values ride out the jitter of the readback, high values follow the
+    /// temperature faster.
+    pub smoothing: f64,
+    /// Largest oscillator error in kHz taken as a measurement. A lock
+    /// further off is on a neighbouring carrier, or a driver that reports
+    /// something else, and is ignored.
+    pub max_offset_khz: i32,
+}
+
+impl Default for LnbDriftConfig {
+    fn default() -> Self {
+        LnbDriftConfig {
+            smoothing: 0.25,
+            max_offset_khz: 5_000,
+        }
+    }
+}
+
+/// Learns the oscillator error of each LNB from the frequencies its
+/// frontends lock on.
+///
+/// LNBs are named by the caller, with any key that tells them apart - an
+/// adapter and a satellite, a dish input. The tracker keeps nothing about
+/// the frontend; the usual round is:
+///
+/// 1. `lnb = tracker.apply(key, lnb)`, then the SEC setup and the tune with it;
+/// 2. after the lock, `tracker.observe(key, lnb, transponder_mhz, fe.read_frequency()?)` with that
+///    same `lnb`.
+///
+/// Only drivers that report the frequency they found, rather than the one
+/// they were given, teach it anything; with the others the measured error
+/// stays at whatever [`CalibratedLnb::lo_offset_khz`] started with.
+#[derive(Debug, Clone, Default)]
+pub struct LnbDriftTracker {
+    config: LnbDriftConfig,
+    offsets: HashMap<String, f64>,
+}
+
+impl LnbDriftTracker {
+    /// Tracker with the default [`LnbDriftConfig`].
+    pub fn new() -> LnbDriftTracker {
+        LnbDriftTracker::default()
+    }
+
+    /// Tracker with its own [`LnbDriftConfig`].
+    pub fn with_config(config: LnbDriftConfig) -> LnbDriftTracker {
+        LnbDriftTracker {
+            config,
+            offsets: HashMap::new(),
+        }
+    }
+
+    /// Takes in a lock: `lnb` is the LNB the tune used, offset included,
+    /// and `frequency_khz` the intermediate frequency read back after the
+    /// lock. Returns the updated estimate in kHz, or `None` if the
+    /// measurement was left out: a [`Lnb::Passthrough`], a transponder the
+    /// LNB does not convert, or an error past
+    /// [`LnbDriftConfig::max_offset_khz`].
+    pub fn observe(
+        &mut self,
+        key: &str,
+        lnb: impl Into<CalibratedLnb>,
+        transponder_mhz: u32,
+        frequency_khz: u32,
+    ) -> Option<i32> {
+        let lnb = lnb.into();
+        if lnb.lnb == Lnb::Passthrough {
+            return None;
+        }
+
+        let transponder_khz = transponder_mhz.checked_mul(1000)?;
+        let (requested_khz, _) = lnb.intermediate_khz(transponder_khz).ok()?;
+
+        // an oscillator that runs high moves the carrier down, unless the
+        // conversion is inverted
+        let residual = i64::from(requested_khz) - i64::from(frequency_khz);
+        let residual = if lnb.lnb.inverts(transponder_khz) {
+            -residual
+        } else {
+            residual
+        };
+        let measured = i64::from(lnb.lo_offset_khz) + residual;
+        if measured.abs() > i64::from(self.config.max_offset_khz) {
+            return None;
+        }
+
+        let measured = measured as f64;
+        let smoothing = self.config.smoothing.clamp(0.0, 1.0);
+        let estimate = self
+            .offsets
+            .entry(key.to_owned())
+            .and_modify(|estimate| *estimate += smoothing * (measured - *estimate))
+            .or_insert(measured);
+
+        Some(estimate.round() as i32)
+    }
+
+    /// Current estimate for an LNB in kHz, `None` before its first lock.
+    pub fn offset_khz(&self, key: &str) -> Option<i32> {
+        self.offsets
+            .get(key)
+            .map(|estimate| estimate.round() as i32)
+    }
+
+    /// `lnb` with the learned offset, or as it is before the first lock.
+    pub fn apply(&self, key: &str, lnb: impl Into<CalibratedLnb>) -> CalibratedLnb {
+        let lnb = lnb.into();
+        match self.offset_khz(key) {
+            Some(offset_khz) => lnb.lnb.with_lo_offset(offset_khz),
+            None => lnb,
+        }
+    }
+
+    /// Drops what was learned about an LNB, after it was replaced.
+    pub fn forget(&mut self, key: &str) {
+        self.offsets.remove(key);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const UNIVERSAL: Lnb = Lnb::Universal {
+        lof_low_mhz: 9_750,
+        lof_high_mhz: 10_600,
+        switch_mhz: 11_700,
+    };
+
+    #[test]
+    fn first_lock_sets_the_offset() {
+        let mut tracker = LnbDriftTracker::new();
+        assert_eq!(tracker.offset_khz("19.2E"), None);
+        assert_eq!(tracker.apply("19.2E", UNIVERSAL), UNIVERSAL.into());
+
+        // asked for 1232 MHz, found the carrier 1.2 MHz lower: the
+        // oscillator runs 1.2 MHz high
+        assert_eq!(
+            tracker.observe("19.2E", UNIVERSAL, 11_832, 1_230_800),
+            Some(1_200)
+        );
+        assert_eq!(
+            tracker.apply("19.2E", UNIVERSAL),
+            UNIVERSAL.with_lo_offset(1_200)
+        );
+
+        // the corrected tune lands on the carrier, nothing more to learn
+        let lnb = tracker.apply("19.2E", UNIVERSAL);
+        let (requested, _) = lnb.intermediate_khz(11_832_000).unwrap();
+        assert_eq!(requested, 1_230_800);
+        assert_eq!(
+            tracker.observe("19.2E", lnb, 11_832, requested),
+            Some(1_200)
+        );
+
+        assert_eq!(tracker.offset_khz("13.0E"), None);
+    }
+
+    #[test]
+    fn later_locks_are_smoothed() {
+        let mut tracker = LnbDriftTracker::with_config(LnbDriftConfig {
+            smoothing: 0.5,
+            ..LnbDriftConfig::default()
+        });
+
+        tracker.observe("a", UNIVERSAL, 11_832, 1_231_000);
+        // the oscillator warmed up by another 400 kHz
+        let lnb = tracker.apply("a", UNIVERSAL);
+        assert_eq!(tracker.observe("a", lnb, 11_832, 1_230_600), Some(1_200));
+
+        tracker.forget("a");
+        assert_eq!(tracker.offset_khz("a"), None);
+    }
+
+    #[test]
+    fn inverted_conversion_and_outliers() {
+        let mut tracker = LnbDriftTracker::new();
+        let cband = Lnb::CBand { lof_mhz: 5_150 };
+
+        // an oscillator 300 kHz high moves a C band carrier up
+        assert_eq!(tracker.observe("c", cband, 3_800, 1_350_300), Some(300));
+
+        assert_eq!(tracker.observe("x", UNIVERSAL, 11_832, 1_220_000), None);
+        assert_eq!(tracker.offset_khz("x"), None);
+        assert_eq!(
+            tracker.observe("x", Lnb::Passthrough, 1_232, 1_231_000),
+            None
+        );
+    }
+}
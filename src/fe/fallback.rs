@@ -0,0 +1,541 @@
+//! Tuning with fallback parameters.
+//!
+//! A channel list is not always right about a transponder: one listed as
+//! DVB-S may have moved to DVB-S2, the pilot or the rolloff may differ, the
+//! symbol rate may be a little off. And a frontend without
+//! [`FeCaps::CAN_INVERSION_AUTO`] cannot lock with the spectral inversion
+//! left to it. [`TuneFallback`] tunes the request as given and, when it does
+//! not lock, works down a ladder of alternatives until one does.
+
+use std::{
+    fmt,
+    time::Duration,
+};
+
+use super::{
+    DvbS2Tune,
+    DvbSTune,
+    DvbT2Tune,
+    DvbTTune,
+    FeLimits,
+    FeStats,
+    LockBus,
+    TuneRequest,
+    TuneViolation,
+    sys::{
+        DeliverySystem,
+        FeCaps,
+        Fec,
+        Inversion,
+        Modulation,
+        Pilot,
+        Rolloff,
+    },
+};
+use crate::error::Result;
+
+/// A rung of the [`TuneFallback`] ladder: which parameter to try other
+/// values of.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FallbackStep {
+    /// DVB-S as DVB-S2 and back, DVB-T as DVB-T2 and back, as far as the
+    /// frontend lists the other system. A DVB-S2 request goes back to DVB-S
+    /// only with QPSK and no input stream, a DVB-T2 request to DVB-T only
+    /// without a PLP other than 0.
+    DeliverySystem,
+    /// Spectral inversion off and on, unless left to a frontend that can
+    /// detect it.
+    Inversion,
+    /// DVB-S2 pilot on and off.
+    Pilot,
+    /// DVB-S2 rolloff 0.35, 0.25 and 0.20.
+    Rolloff,
+    /// Symbol rate off by the given thousandths, for the satellite and
+    /// cable requests.
+    SymbolRatePermille(Vec<i32>),
+    /// Frequency off by the given kHz.
+    FrequencyKhz(Vec<i32>),
+}
+
+/// How an attempt of [`TuneFallback`] differs from the request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FallbackChange {
+    /// Tuned with another delivery system.
+    DeliverySystem(DeliverySystem),
+    /// Tuned with another spectral inversion.
+    Inversion(Inversion),
+    /// Tuned with another DVB-S2 pilot.
+    Pilot(Pilot),
+    /// Tuned with another DVB-S2 rolloff.
+    Rolloff(Rolloff),
+    /// Tuned with another symbol rate, in baud.
+    SymbolRate(u32),
+    /// Tuned with the frequency off by kHz.
+    FrequencyOffsetKhz(i32),
+}
+
+impl fmt::Display for FallbackChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FallbackChange::DeliverySystem(system) => write!(f, "{}", system),
+            FallbackChange::Inversion(inversion) => write!(f, "inversion {:?}", inversion),
+            FallbackChange::Pilot(pilot) => write!(f, "pilot {:?}", pilot),
+            FallbackChange::Rolloff(rolloff) => write!(f, "rolloff {:?}", rolloff),
+            FallbackChange::SymbolRate(symbolrate) => write!(f, "symbol rate {}", symbolrate),
+            FallbackChange::FrequencyOffsetKhz(offset) => write!(f, "frequency {:+} kHz", offset),
+        }
+    }
+}
+
+/// Parameters of
+/// [`FeDevice::tune_with_fallback`](super::FeDevice::tune_with_fallback).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TuneFallback {
+    /// How long one attempt waits for the lock.
+    pub lock_timeout: Duration,
+    /// The rungs, tried in order. Each one is tried on every attempt of the
+    /// rungs before it, so a DVB-S request that turns out to be DVB-S2 is
+    /// still tried with the other pilots.
+    pub ladder: Vec<FallbackStep>,
+    /// Most attempts, the request included.
+    pub max_attempts: usize,
+}
+
+impl Default for TuneFallback {
+    fn default() -> Self {
+        Self {
+            lock_timeout: Duration::from_secs(1),
+            ladder: vec![
+                FallbackStep::DeliverySystem,
+                FallbackStep::Inversion,
+                FallbackStep::Pilot,
+                FallbackStep::Rolloff,
+            ],
+            max_attempts: 24,
+        }
+    }
+}
+
+/// The attempt of
+/// [`FeDevice::tune_with_fallback`](super::FeDevice::tune_with_fallback)
+/// that locked.
+#[derive(Debug, Clone)]
+pub struct FallbackLock {
+    /// The tune request that locked.
+    pub request: TuneRequest,
+    /// How it differs from the request, empty when the request locked as
+    /// given.
+    pub changes: Vec<FallbackChange>,
+    /// Number of the attempt, 0 for the first.
+    pub attempt: usize,
+    /// Frontend statistics at the lock.
+    pub stats: FeStats,
+}
+
+fn inversion_mut(request: &mut TuneRequest) -> &mut Inversion {
+    match request {
+        TuneRequest::DvbS(tune) => &mut tune.inversion,
+        TuneRequest::DvbS2(tune) => &mut tune.inversion,
+        TuneRequest::DvbC(tune) => &mut tune.inversion,
+        TuneRequest::DvbT(tune) => &mut tune.inversion,
+        TuneRequest::DvbT2(tune) => &mut tune.inversion,
+        TuneRequest::Atsc(tune) => &mut tune.inversion,
+        TuneRequest::IsdbT(tune) => &mut tune.inversion,
+    }
+}
+
+fn can_inversion_auto(limits: &FeLimits) -> bool {
+    limits.caps == FeCaps::IS_STUPID || limits.caps.contains(FeCaps::CAN_INVERSION_AUTO)
+}
+
+/// The request with another delivery system, if it has a counterpart.
+fn other_system(request: &TuneRequest) -> Option<TuneRequest> {
+    match request {
+        TuneRequest::DvbS(tune) => Some(TuneRequest::DvbS2(DvbS2Tune {
+            frequency_khz: tune.frequency_khz,
+            symbolrate: tune.symbolrate,
+            // the demodulator reads the modulation from the frame headers
+            modulation: Modulation::Qpsk,
+            inversion: tune.inversion,
+            rolloff: Rolloff::Auto,
+            ..Default::default()
+        })),
+        TuneRequest::DvbS2(tune) if tune.modulation == Modulation::Qpsk && tune.mis.is_none() => {
+            let fec = match tune.fec {
+                Fec::Fec1_2 | Fec::Fec2_3 | Fec::Fec3_4 | Fec::Fec5_6 | Fec::Fec7_8 => tune.fec,
+                _ => Fec::Auto,
+            };
+            Some(TuneRequest::DvbS(DvbSTune {
+                frequency_khz: tune.frequency_khz,
+                symbolrate: tune.symbolrate,
+                fec,
+                inversion: tune.inversion,
+                ..Default::default()
+            }))
+        }
+        TuneRequest::DvbT(tune) => Some(TuneRequest::DvbT2(DvbT2Tune {
+            frequency_hz: tune.frequency_hz,
+            bandwidth_hz: tune.bandwidth_hz,
+            inversion: tune.inversion,
+            ..Default::default()
+        })),
+        TuneRequest::DvbT2(tune) if tune.stream_id.unwrap_or(0) == 0 => {
+            Some(TuneRequest::DvbT(DvbTTune {
+                frequency_hz: tune.frequency_hz,
+                bandwidth_hz: tune.bandwidth_hz,
+                inversion: tune.inversion,
+                ..Default::default()
+            }))
+        }
+        _ => None,
+    }
+}
+
+/// The alternatives of one rung to a request.
+fn variants(
+    step: &FallbackStep,
+    request: &TuneRequest,
+    limits: &FeLimits,
+) -> Vec<(TuneRequest, FallbackChange)> {
+    let mut result = Vec::new();
+
+    match step {
+        FallbackStep::DeliverySystem => {
+            if let Some(other) = other_system(request) {
+                let system = other.delivery_system();
+                result.push((other, FallbackChange::DeliverySystem(system)));
+            }
+        }
+        FallbackStep::Inversion => {
+            let mut other = request.clone();
+            let inversion = inversion_mut(&mut other);
+            let value = match *inversion {
+                Inversion::Off => Inversion::On,
+                Inversion::On => Inversion::Off,
+                Inversion::Auto if can_inversion_auto(limits) => return result,
+                Inversion::Auto => Inversion::Off,
+            };
+            *inversion = value;
+            result.push((other, FallbackChange::Inversion(value)));
+        }
+        FallbackStep::Pilot => {
+            if let TuneRequest::DvbS2(tune) = request {
+                for pilot in [Pilot::On, Pilot::Off] {
+                    if tune.pilot != pilot {
+                        let other = DvbS2Tune {
+                            pilot,
+                            ..tune.clone()
+                        };
+                        result.push((TuneRequest::DvbS2(other), FallbackChange::Pilot(pilot)));
+                    }
+                }
+            }
+        }
+        FallbackStep::Rolloff => {
+            if let TuneRequest::DvbS2(tune) = request {
+                for rolloff in [Rolloff::R35, Rolloff::R25, Rolloff::R20] {
+                    if tune.rolloff != rolloff {
+                        let other = DvbS2Tune {
+                            rolloff,
+                            ..tune.clone()
+                        };
+                        result.push((TuneRequest::DvbS2(other), FallbackChange::Rolloff(rolloff)));
+                    }
+                }
+            }
+        }
+        FallbackStep::SymbolRatePermille(offsets) => {
+            for &permille in offsets {
+                let mut other = request.clone();
+                let symbolrate = match &mut other {
+                    TuneRequest::DvbS(tune) => &mut tune.symbolrate,
+                    TuneRequest::DvbS2(tune) => &mut tune.symbolrate,
+                    TuneRequest::DvbC(tune) => &mut tune.symbolrate,
+                    _ => break,
+                };
+                let value = i64::from(*symbolrate) * i64::from(1000 + permille) / 1000;
+                let Ok(value) = u32::try_from(value) else {
+                    continue;
+                };
+                *symbolrate = value;
+                result.push((other, FallbackChange::SymbolRate(value)));
+            }
+        }
+        FallbackStep::FrequencyKhz(offsets) => {
+            for &offset in offsets {
+                let mut other = request.clone();
+                let (frequency, scale) = match &mut other {
+                    TuneRequest::DvbS(tune) => (&mut tune.frequency_khz, 1),
+                    TuneRequest::DvbS2(tune) => (&mut tune.frequency_khz, 1),
+                    TuneRequest::DvbC(tune) => (&mut tune.frequency_hz, 1000),
+                    TuneRequest::DvbT(tune) => (&mut tune.frequency_hz, 1000),
+                    TuneRequest::DvbT2(tune) => (&mut tune.frequency_hz, 1000),
+                    TuneRequest::Atsc(tune) => (&mut tune.frequency_hz, 1000),
+                    TuneRequest::IsdbT(tune) => (&mut tune.frequency_hz, 1000),
+                };
+                let value = i64::from(*frequency) + i64::from(offset) * scale;
+                let Ok(value) = u32::try_from(value) else {
+                    continue;
+                };
+                *frequency = value;
+                result.push((other, FallbackChange::FrequencyOffsetKhz(offset)));
+            }
+        }
+    }
+
+    // the frontend would reject these outright
+    result.retain(|(other, _)| {
+        limits.validate(other).iter().all(|violation| {
+            !matches!(
+                violation,
+                TuneViolation::DeliverySystem { .. }
+                    | TuneViolation::Frequency { .. }
+                    | TuneViolation::SymbolRate { .. }
+            )
+        })
+    });
+
+    result
+}
+
+impl TuneFallback {
+    /// The attempts in the order they are tuned, each with how it differs
+    /// from `request`.
+    ///
+    /// The first is the request itself, with the spectral inversion turned
+    /// off when it is left to a frontend that cannot detect it. Each rung
+    /// then adds its alternatives to every attempt before it. An attempt
+    /// that equals an earlier one, or that `limits` rule out by delivery
+    /// system, frequency or symbol rate, is left out.
+    pub fn attempts(
+        &self,
+        request: &TuneRequest,
+        limits: &FeLimits,
+    ) -> Vec<(TuneRequest, Vec<FallbackChange>)> {
+        let mut first = request.clone();
+        let mut changes = Vec::new();
+        let inversion = inversion_mut(&mut first);
+        if *inversion == Inversion::Auto && !can_inversion_auto(limits) {
+            *inversion = Inversion::Off;
+            changes.push(FallbackChange::Inversion(Inversion::Off));
+        }
+
+        let mut attempts = vec![(first, changes)];
+        for step in &self.ladder {
+            for i in 0 .. attempts.len() {
+                for (other, change) in variants(step, &attempts[i].0, limits) {
+                    if attempts.len() >= self.max_attempts {
+                        return attempts;
+                    }
+                    if attempts.iter().any(|(request, _)| *request == other) {
+                        continue;
+                    }
+                    let mut changes = attempts[i].1.clone();
+                    changes.push(change);
+                    attempts.push((other, changes));
+                }
+            }
+        }
+        attempts.truncate(self.max_attempts);
+
+        attempts
+    }
+}
+
+/// Tunes the attempts of `fallback` until one locks.
+pub(crate) fn tune<B: LockBus>(
+    bus: &mut B,
+    request: &TuneRequest,
+    fallback: &TuneFallback,
+    limits: &FeLimits,
+) -> Result<Option<FallbackLock>> {
+    for (attempt, (request, changes)) in fallback.attempts(request, limits).into_iter().enumerate()
+    {
+        if let Some(stats) = bus.lock(&request, fallback.lock_timeout)? {
+            return Ok(Some(FallbackLock {
+                request,
+                changes,
+                attempt,
+                stats,
+            }));
+        }
+    }
+
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limits(delivery_systems: Vec<DeliverySystem>, caps: FeCaps) -> FeLimits {
+        FeLimits {
+            delivery_systems,
+            frequency_range: 950_000 ..= 2_150_000,
+            frequency_khz: true,
+            symbolrate_range: 1_000_000 ..= 45_000_000,
+            caps,
+        }
+    }
+
+    fn dvbs() -> TuneRequest {
+        TuneRequest::DvbS(DvbSTune {
+            frequency_khz: 1_201_500,
+            symbolrate: 27_500_000,
+            fec: Fec::Fec3_4,
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn ladder_order() {
+        let satellite = limits(
+            vec![DeliverySystem::Dvbs, DeliverySystem::Dvbs2],
+            FeCaps::CAN_FEC_AUTO,
+        );
+        let attempts = TuneFallback::default().attempts(&dvbs(), &satellite);
+        let changes: Vec<String> = attempts
+            .iter()
+            .map(|(_, changes)| {
+                let changes: Vec<String> = changes.iter().map(ToString::to_string).collect();
+                changes.join(", ")
+            })
+            .collect();
+        assert_eq!(
+            changes[.. 6],
+            [
+                "inversion Off",
+                "inversion Off, dvb-s2",
+                "inversion Off, inversion On",
+                "inversion Off, dvb-s2, inversion On",
+                "inversion Off, dvb-s2, pilot On",
+                "inversion Off, dvb-s2, pilot Off",
+            ]
+        );
+        assert_eq!(attempts.len(), 24);
+        assert!(matches!(
+            &attempts[1].0,
+            TuneRequest::DvbS2(DvbS2Tune {
+                frequency_khz: 1_201_500,
+                symbolrate: 27_500_000,
+                modulation: Modulation::Qpsk,
+                inversion: Inversion::Off,
+                ..
+            })
+        ));
+
+        // S2 that is not listed, and inversion left to the frontend
+        let s_only = limits(
+            vec![DeliverySystem::Dvbs],
+            FeCaps::CAN_FEC_AUTO | FeCaps::CAN_INVERSION_AUTO,
+        );
+        let attempts = TuneFallback::default().attempts(&dvbs(), &s_only);
+        assert_eq!(attempts, [(dvbs(), Vec::new())]);
+
+        let short = TuneFallback {
+            max_attempts: 4,
+            ..Default::default()
+        };
+        assert_eq!(short.attempts(&dvbs(), &satellite).len(), 4);
+    }
+
+    #[test]
+    fn offsets_stay_in_range() {
+        let satellite = limits(vec![DeliverySystem::Dvbs], FeCaps::IS_STUPID);
+        let fallback = TuneFallback {
+            ladder: vec![
+                FallbackStep::SymbolRatePermille(vec![-5, 5]),
+                FallbackStep::FrequencyKhz(vec![-2000, 2000]),
+            ],
+            ..Default::default()
+        };
+        let request = TuneRequest::DvbS(DvbSTune {
+            frequency_khz: 2_149_000,
+            symbolrate: 45_000_000,
+            ..Default::default()
+        });
+        let attempts = fallback.attempts(&request, &satellite);
+        let changes: Vec<&[FallbackChange]> =
+            attempts.iter().map(|(_, changes)| &changes[..]).collect();
+        assert_eq!(
+            changes,
+            [
+                &[][..],
+                &[FallbackChange::SymbolRate(44_775_000)],
+                &[FallbackChange::FrequencyOffsetKhz(-2000)],
+                &[
+                    FallbackChange::SymbolRate(44_775_000),
+                    FallbackChange::FrequencyOffsetKhz(-2000),
+                ],
+            ]
+        );
+    }
+
+    /// A transponder that locks on one request only.
+    struct Transponder {
+        locks_on: TuneRequest,
+        tunes: usize,
+    }
+
+    impl LockBus for Transponder {
+        fn lock(&mut self, request: &TuneRequest, _timeout: Duration) -> Result<Option<FeStats>> {
+            self.tunes += 1;
+            Ok((*request == self.locks_on).then(FeStats::default))
+        }
+    }
+
+    #[test]
+    fn reports_the_variant_that_locked() {
+        let terrestrial = FeLimits {
+            delivery_systems: vec![DeliverySystem::Dvbt, DeliverySystem::Dvbt2],
+            frequency_range: 174_000_000 ..= 862_000_000,
+            frequency_khz: false,
+            symbolrate_range: 0 ..= 0,
+            caps: FeCaps::IS_STUPID,
+        };
+        let request = TuneRequest::DvbT(DvbTTune {
+            frequency_hz: 506_000_000,
+            ..Default::default()
+        });
+        let mut transponder = Transponder {
+            locks_on: TuneRequest::DvbT2(DvbT2Tune {
+                frequency_hz: 506_000_000,
+                inversion: Inversion::On,
+                ..Default::default()
+            }),
+            tunes: 0,
+        };
+        let fallback = TuneFallback {
+            ladder: vec![FallbackStep::DeliverySystem, FallbackStep::Inversion],
+            ..Default::default()
+        };
+
+        // inversion is left to the frontend, so it is never tried
+        assert!(
+            tune(&mut transponder, &request, &fallback, &terrestrial)
+                .unwrap()
+                .is_none()
+        );
+        assert_eq!(transponder.tunes, 2);
+
+        let terrestrial = FeLimits {
+            caps: FeCaps::CAN_QAM_AUTO,
+            ..terrestrial
+        };
+        transponder.tunes = 0;
+        let lock = tune(&mut transponder, &request, &fallback, &terrestrial)
+            .unwrap()
+            .unwrap();
+        assert_eq!(lock.request, transponder.locks_on);
+        assert_eq!(lock.attempt, 3);
+        assert_eq!(transponder.tunes, 4);
+        assert_eq!(
+            lock.changes,
+            [
+                FallbackChange::Inversion(Inversion::Off),
+                FallbackChange::DeliverySystem(DeliverySystem::Dvbt2),
+                FallbackChange::Inversion(Inversion::On),
+            ]
+        );
+    }
+}
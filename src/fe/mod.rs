@@ -1,7 +1,22 @@
+pub mod arbiter;
+pub mod bandplan;
+pub mod blindscan;
+pub mod diseqc;
+pub mod diseqc_conf;
+pub mod diseqc_message;
+pub mod drift;
+pub mod fallback;
+pub mod multistream;
+pub mod pool;
+pub mod positioner;
 pub mod sec;
+mod sec_state;
+mod spectrum;
 mod stats;
 pub mod sys;
 mod tune;
+pub mod unicable;
+mod validate;
 
 use std::{
     fmt,
@@ -9,7 +24,10 @@ use std::{
         File,
         OpenOptions,
     },
-    ops::Range,
+    ops::{
+        ControlFlow,
+        Range,
+    },
     os::{
         fd::{
             AsFd,
@@ -20,19 +38,111 @@ use std::{
             RawFd,
         },
     },
+    sync::{
+        Mutex,
+        MutexGuard,
+        PoisonError,
+    },
+    time::{
+        Duration,
+        Instant,
+    },
 };
 
+pub use arbiter::{
+    CableLock,
+    UnicableArbiter,
+    UnicableArbiterConfig,
+    UserBandAllocator,
+    UserBandLease,
+};
+pub use bandplan::{
+    BandChannel,
+    BandPlan,
+    BandSweep,
+    BandSweepProgress,
+    BandSystem,
+    LockedChannel,
+};
+pub use blindscan::{
+    BlindScan,
+    BlindScanCarrier,
+    BlindScanProgress,
+    BlindScanStage,
+};
+pub use diseqc::{
+    DISEQC_ADDRESSES,
+    DiseqcRead,
+    DiseqcReply,
+    DiseqcSlave,
+};
+pub use diseqc_conf::{
+    DiseqcConf,
+    DiseqcConfEntry,
+};
+pub use diseqc_message::{
+    DiseqcAddress,
+    DiseqcCommand,
+    DiseqcFraming,
+    DiseqcMessage,
+};
+pub use drift::{
+    LnbDriftConfig,
+    LnbDriftTracker,
+};
+pub use fallback::{
+    FallbackChange,
+    FallbackLock,
+    FallbackStep,
+    TuneFallback,
+};
+pub use multistream::{
+    COMMON_PLS_CODES,
+    InputStream,
+    MultistreamScan,
+    PlsSearch,
+};
+pub use pool::{
+    FeLease,
+    FePool,
+    PoolFrontend,
+    SatelliteInput,
+    SatelliteTarget,
+};
+pub use positioner::{
+    PositionerCommand,
+    PositionerDirection,
+    PositionerDrive,
+    usals_angle,
+};
 pub use sec::{
+    CalibratedLnb,
+    CascadeConfig,
+    CascadeLevel,
     DiseqcSwitchConfig,
+    DualBandSelect,
+    KaBand,
     Lnb,
+    Polarization,
+    PositionerConfig,
     SecCommand,
     SecConfig,
     SecSetup,
+    SecTemplate,
+    SecTimeline,
+    SecTimelineEntry,
     SecTimings,
     ToneburstConfig,
     UnicableConfig,
+    UsalsConfig,
+    format_sec_sequence,
     sec_sequence,
 };
+use sec_state::SecTracker;
+pub use sec_state::{
+    LnbIdlePolicy,
+    SecState,
+};
 pub use stats::{
     FeLevel,
     FeStats,
@@ -50,9 +160,19 @@ pub use tune::{
     PlsMode,
     TuneRequest,
 };
+pub use unicable::{
+    UnicableScan,
+    UnicableStandard,
+    UserBand,
+};
+pub use validate::{
+    FeLimits,
+    TuneViolation,
+};
 
 use self::sys::*;
 use crate::{
+    dvr::DvrDevice,
     error::{
         Error,
         Result,
@@ -145,8 +265,13 @@ pub struct FeDevice {
     delivery_system_list: Vec<DeliverySystem>,
     frequency_range: Range<u32>,
     symbolrate_range: Range<u32>,
+    frequency_khz: bool,
     caps: FeCaps,
 
+    validate_tune: bool,
+
+    sec: Mutex<SecTracker>,
+
     vendor_id: Option<u32>,
     device_id: Option<u32>,
 }
@@ -207,6 +332,8 @@ impl FeDevice {
 
         self.frequency_range = feinfo.frequency_min .. feinfo.frequency_max;
         self.symbolrate_range = feinfo.symbol_rate_min .. feinfo.symbol_rate_max;
+        // the kernel reports the range in kHz for a satellite delivery system
+        self.frequency_khz = feinfo.fe_type == FE_QPSK;
 
         self.caps = FeCaps::from_bits_retain(feinfo.caps);
 
@@ -254,8 +381,13 @@ impl FeDevice {
             delivery_system_list: Vec::default(),
             frequency_range: 0 .. 0,
             symbolrate_range: 0 .. 0,
+            frequency_khz: false,
             caps: FeCaps::empty(),
 
+            validate_tune: false,
+
+            sec: Mutex::default(),
+
             vendor_id,
             device_id,
         };
@@ -334,10 +466,50 @@ impl FeDevice {
     /// For satellite systems the SEC step runs first, with
     /// [`FeDevice::setup_sec`]: it drives the LNB and any DiSEqC equipment,
     /// and reports the frequency the request has to carry.
+    ///
+    /// With [`FeDevice::set_tune_validation`] enabled the request is checked
+    /// with [`FeDevice::validate`] first, and rejected with
+    /// [`Error::InvalidTune`] instead of reaching the kernel, which would
+    /// only answer `EINVAL`.
     pub fn tune(&self, request: &TuneRequest) -> Result<()> {
+        if self.validate_tune {
+            let violations = self.validate(request);
+            if !violations.is_empty() {
+                return Err(Error::InvalidTune(violations));
+            }
+        }
+
+        self.sec().active();
         self.set_properties(&request.properties())
     }
 
+    /// Checks a tune request against what the frontend reports it can tune:
+    /// its delivery systems, frequency and symbol rate ranges and capability
+    /// flags. Returns every violation found; an empty list means the request
+    /// passes.
+    ///
+    /// The device is not touched. See [`FeLimits::validate`] for the checks.
+    pub fn validate(&self, request: &TuneRequest) -> Vec<TuneViolation> {
+        self.limits().validate(request)
+    }
+
+    /// Turns the [`FeDevice::validate`] step of [`FeDevice::tune`] on or off.
+    /// It is off by default.
+    pub fn set_tune_validation(&mut self, enabled: bool) {
+        self.validate_tune = enabled;
+    }
+
+    /// Frontend limits [`FeDevice::validate`] checks a request against.
+    pub fn limits(&self) -> FeLimits {
+        FeLimits {
+            delivery_systems: self.delivery_system_list.clone(),
+            frequency_range: self.frequency_range.start ..= self.frequency_range.end,
+            frequency_khz: self.frequency_khz,
+            symbolrate_range: self.symbolrate_range.start ..= self.symbolrate_range.end,
+            caps: self.caps,
+        }
+    }
+
     /// Gets properties from frontend device (raw read path)
     pub(crate) fn get_properties(&self, cmdseq: &mut [DtvPropertyRaw]) -> Result<()> {
         let mut cmd = DtvProperties {
@@ -382,6 +554,19 @@ impl FeDevice {
         FeStats::read(self)
     }
 
+    /// Reads back the frequency the frontend is tuned to (`DTV_FREQUENCY`),
+    /// in the units of the tune request: kHz for satellite, Hz otherwise.
+    ///
+    /// After a lock, drivers that track the carrier report where they found
+    /// it rather than the frequency they were given; the difference is what
+    /// [`LnbDriftTracker`] learns from.
+    pub fn read_frequency(&self) -> Result<u32> {
+        let mut cmdseq = [DtvPropertyRaw::new(DTV_FREQUENCY, 0)];
+        self.get_properties(&mut cmdseq)?;
+
+        Ok(cmdseq[0].data())
+    }
+
     /// Returns frontend status flags
     /// - [`FeStatusFlags::NONE`]
     /// - [`FeStatusFlags::HAS_SIGNAL`]
@@ -489,6 +674,7 @@ impl FeDevice {
             nix::request_code_none!(b'o', 66)
         );
         unsafe { ioctl_call(self.as_raw_fd(), (value as u32) as _) }?;
+        self.sec().tone(value);
 
         Ok(())
     }
@@ -511,6 +697,11 @@ impl FeDevice {
     ///     - Left in circular LNB
     /// - OFF is needed with external power supply, for example to use same LNB with several
     ///   receivers.
+    ///
+    /// LNB supply controllers shut down on a short circuit or overcurrent, and their drivers
+    /// report it as `EIO`; that is returned as [`Error::LnbOverload`]. The same `EIO` comes
+    /// from a failed i2c transfer to the controller, so the error is a possible overload, not
+    /// a certain one.
     pub fn set_voltage(&self, value: SecVoltage) -> Result<()> {
         // FE_SET_VOLTAGE
         nix::ioctl_write_int_bad!(
@@ -518,7 +709,27 @@ impl FeDevice {
             ioctl_call,
             nix::request_code_none!(b'o', 67)
         );
-        unsafe { ioctl_call(self.as_raw_fd(), (value as u32) as _) }?;
+        unsafe { ioctl_call(self.as_raw_fd(), (value as u32) as _) }.map_err(lnb_error)?;
+        self.sec().voltage(value);
+
+        Ok(())
+    }
+
+    /// Raises the LNB voltage by about 1V, to make up for the voltage drop
+    /// along a long cable (`FE_ENABLE_HIGH_LNB_VOLTAGE`).
+    ///
+    /// The setting stays until it is changed or the frontend is closed.
+    /// Not every frontend has it; those that do not fail with `EOPNOTSUPP`
+    /// or `EINVAL`. A possible overload fails with [`Error::LnbOverload`],
+    /// as [`FeDevice::set_voltage`] does.
+    pub fn set_high_lnb_voltage(&self, enabled: bool) -> Result<()> {
+        // FE_ENABLE_HIGH_LNB_VOLTAGE
+        nix::ioctl_write_int_bad!(
+            #[inline]
+            ioctl_call,
+            nix::request_code_none!(b'o', 68)
+        );
+        unsafe { ioctl_call(self.as_raw_fd(), enabled as _) }.map_err(lnb_error)?;
 
         Ok(())
     }
@@ -532,6 +743,7 @@ impl FeDevice {
             nix::request_code_none!(b'o', 65)
         );
         unsafe { ioctl_call(self.as_raw_fd(), (cmd as u32) as _) }?;
+        self.sec().switched();
 
         Ok(())
     }
@@ -569,10 +781,219 @@ impl FeDevice {
         // FE_DISEQC_SEND_MASTER_CMD
         nix::ioctl_write_ptr!(ioctl_call, b'o', 63, DiseqcMasterCmd);
         unsafe { ioctl_call(self.as_raw_fd(), &cmd as *const _) }?;
+        self.sec().switched();
 
         Ok(())
     }
 
+    /// Sends a DiSEqC 1.2 positioner command.
+    ///
+    /// Returns once the command is on the wire; the positioner reports
+    /// neither arrival nor the current position, so a caller that waits for
+    /// a move to finish has to estimate its duration.
+    pub fn positioner(&self, command: PositionerCommand) -> Result<()> {
+        self.diseqc_master_cmd(&command.encode()?)
+    }
+
+    /// Receives a DiSEqC 2.x slave reply to the last master command.
+    ///
+    /// Waits up to `timeout` for the reply; returns `None` when none came.
+    /// A frontend that cannot receive replies fails with the driver error,
+    /// `EOPNOTSUPP` or `ENOTTY` as a rule.
+    pub fn diseqc_recv_reply(&self, timeout: Duration) -> Result<Option<DiseqcReply>> {
+        let mut reply = DiseqcSlaveReply {
+            timeout: diseqc::timeout_ms(timeout),
+            ..Default::default()
+        };
+
+        // FE_DISEQC_RECV_SLAVE_REPLY
+        nix::ioctl_read!(ioctl_call, b'o', 64, DiseqcSlaveReply);
+        match unsafe { ioctl_call(self.as_raw_fd(), &mut reply as *mut _) } {
+            Ok(_) => Ok(DiseqcReply::from_raw(&reply)),
+            Err(nix::errno::Errno::ETIMEDOUT) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Sends a DiSEqC 2.x read command to `address` and receives the reply.
+    pub fn diseqc_read(
+        &self,
+        address: u8,
+        read: DiseqcRead,
+        timeout: Duration,
+    ) -> Result<Option<DiseqcReply>> {
+        self.diseqc_master_cmd(&read.message(address))?;
+        self.diseqc_recv_reply(timeout)
+    }
+
+    /// Discovers the DiSEqC 2.x slaves on the bus: sends every
+    /// [`DiseqcRead`] to each of `addresses` and returns the slaves that
+    /// answered, with their replies. [`DISEQC_ADDRESSES`] covers the device
+    /// families of the bus specification.
+    ///
+    /// The bus must be powered with the tone off, so set a voltage and
+    /// [`SecTone::Off`] first. Each read waits up to `timeout` for its reply
+    /// (the specification allows a slave 150 ms), which makes a full scan of
+    /// an empty bus take a while. A frontend that cannot receive replies
+    /// fails on the first read instead of reporting an empty bus.
+    pub fn diseqc_discover(&self, addresses: &[u8], timeout: Duration) -> Result<Vec<DiseqcSlave>> {
+        diseqc::discover(addresses, |address, read| {
+            self.diseqc_read(address, read, timeout)
+        })
+    }
+
+    /// Lists the user bands of a Unicable ODU on this frontend's cable, with
+    /// their centre frequencies - the values [`UnicableConfig`] needs.
+    ///
+    /// An EN 50607 ODU is asked with the `ODU_UB_avail` and `ODU_UB_freq`
+    /// queries. When the frontend cannot receive the replies, and for an
+    /// EN 50494 ODU, the user bands are found from the test carrier
+    /// `ODU_UBxSignal_ON` puts on each of them: a sweep of the IF band
+    /// measures the signal level, and switching off one slot at a time tells
+    /// the carriers apart. The sweep tunes the frontend several hundred
+    /// times and takes a while; [`UnicableScan`] sets its range, step and
+    /// thresholds.
+    ///
+    /// The test carriers disturb every other receiver on the cable, so this
+    /// belongs to installation, not to normal operation.
+    pub fn unicable_user_bands(
+        &self,
+        standard: UnicableStandard,
+        scan: &UnicableScan,
+    ) -> Result<Vec<UserBand>> {
+        unicable::discover(&mut unicable::FeBus { fe: self, scan }, standard, scan)
+    }
+
+    /// Searches one polarization of a satellite for carriers, without a
+    /// transponder list, and returns those the frontend locked on.
+    ///
+    /// A sweep over the transponder range of [`BlindScan`] tunes the
+    /// frontend in steps through `lnb` and `config` and reads the signal
+    /// level; the carriers found there are then tried with DVB-S2 and
+    /// DVB-S, as far as the frontend lists them, with the symbol rate
+    /// estimated from the width of the carrier and the nearest usual ones.
+    /// The code rate and the spectral inversion are left to the frontend
+    /// where its `FE_CAN_*` flags allow, and tried in turn otherwise. Each
+    /// [`BlindScanCarrier`] holds the parameters the frontend read back.
+    ///
+    /// `progress` is called before every step and stops the scan with
+    /// [`ControlFlow::Break`]; the carriers locked until then are returned.
+    /// A full scan of a universal LNB band tunes the frontend a thousand
+    /// times and takes minutes, so it belongs on a thread of its own.
+    pub fn blind_scan<F>(
+        &self,
+        lnb: impl Into<CalibratedLnb>,
+        config: SecConfig,
+        scan: &BlindScan,
+        progress: F,
+    ) -> Result<Vec<BlindScanCarrier>>
+    where
+        F: FnMut(&BlindScanProgress) -> ControlFlow<()>,
+    {
+        blindscan::scan(
+            &mut blindscan::FeBus { fe: self, scan },
+            lnb.into(),
+            &config,
+            scan,
+            &self.limits(),
+            progress,
+        )
+    }
+
+    /// Tunes every channel of a terrestrial or cable [`BandPlan`] and
+    /// returns those the frontend locked on, with their statistics.
+    ///
+    /// Each channel is tried with the requests of [`BandPlan::requests`]:
+    /// DVB-T and DVB-T2 in the order [`BandSweep::prefer_t2`] sets, the
+    /// cable modes of the plan, and only the delivery systems the frontend
+    /// lists. Every request waits up to [`BandSweep::lock_timeout`] for the
+    /// lock, so an empty band costs the timeout per request.
+    ///
+    /// `progress` is called before every channel and stops the sweep with
+    /// [`ControlFlow::Break`]; the channels locked until then are returned.
+    pub fn sweep_band<F>(
+        &self,
+        plan: &BandPlan,
+        sweep: &BandSweep,
+        progress: F,
+    ) -> Result<Vec<LockedChannel>>
+    where
+        F: FnMut(&BandSweepProgress<'_>) -> ControlFlow<()>,
+    {
+        bandplan::sweep(&mut &*self, plan, sweep, &self.limits(), progress)
+    }
+
+    /// Tunes a request and, when it does not lock, the alternatives of a
+    /// [`TuneFallback`] ladder in turn; returns the attempt that locked,
+    /// with how it differs from the request, or `None` when none did.
+    ///
+    /// Each attempt waits up to [`TuneFallback::lock_timeout`] for the lock.
+    /// See [`TuneFallback::attempts`] for the attempts and their order; a
+    /// satellite request is tuned as it is, so the SEC must be set up
+    /// before.
+    pub fn tune_with_fallback(
+        &self,
+        request: &TuneRequest,
+        fallback: &TuneFallback,
+    ) -> Result<Option<FallbackLock>> {
+        fallback::tune(&mut &*self, request, fallback, &self.limits())
+    }
+
+    /// Tunes a DVB-S2 request with the PLS code of its [`Mis`] and, when it
+    /// does not lock, the codes of [`PlsSearch`]; returns the [`Mis`] that
+    /// locked, with the stream id of the request, or `None` when no code
+    /// did. A request without one tunes the streams with
+    /// [`NO_STREAM_ID_FILTER`].
+    ///
+    /// The frontend does not lock on a transponder with the wrong code, so
+    /// every code tried costs [`PlsSearch::lock_timeout`]. The SEC must be
+    /// set up before.
+    pub fn search_pls(&self, request: &DvbS2Tune, search: &PlsSearch) -> Result<Option<Mis>> {
+        let stream_id = request
+            .mis
+            .map_or(sys::NO_STREAM_ID_FILTER, |mis| mis.stream_id);
+        multistream::search(
+            &mut multistream::FeBus {
+                fe: self,
+                dvr: None,
+            },
+            request,
+            search,
+            stream_id,
+        )
+    }
+
+    /// Lists the input streams of a DVB-S2 multistream transponder, with the
+    /// [`Mis`] to tune each with.
+    ///
+    /// The frontend is switched to BBFrame delivery with
+    /// [`BBFRAME_STREAM_ID`](crate::bbframe::BBFRAME_STREAM_ID) and tuned
+    /// with the PLS codes of [`MultistreamScan::pls`] until it locks; the
+    /// baseband frames read from `dvr` for [`MultistreamScan::listen`] then
+    /// give the streams by the ISI of their headers. A single input stream
+    /// is listed once, with
+    /// [`NO_STREAM_ID_FILTER`]. The pseudo
+    /// transport stream must be routed to `dvr` with
+    /// [`DmxDevice::set_ts_tap`](crate::dmx::DmxDevice::set_ts_tap) before.
+    ///
+    /// The list is empty when no code locks, or the driver does not know
+    /// BBFrame delivery.
+    pub fn find_input_streams(
+        &self,
+        request: &DvbS2Tune,
+        dvr: &mut DvrDevice,
+        scan: &MultistreamScan,
+    ) -> Result<Vec<InputStream>> {
+        multistream::scan(
+            &mut multistream::FeBus {
+                fe: self,
+                dvr: Some(dvr),
+            },
+            request,
+            scan,
+        )
+    }
+
     /// Points the frontend at a transponder: converts the frequency through
     /// the LNB, sets the polarization voltage and the band tone, drives any
     /// DiSEqC equipment, and blocks for the waits the sequence asks for.
@@ -591,9 +1012,22 @@ impl FeDevice {
     /// The waits are the [`SecTimings`] defaults. An installation that needs
     /// its own calls [`sec_sequence`] with them and hands the result to
     /// [`FeDevice::run_sec_sequence`], which is what this method does.
-    pub fn setup_sec(&self, transponder_mhz: u32, lnb: Lnb, config: SecConfig) -> Result<u32> {
+    ///
+    /// With [`FeDevice::set_sec_cache`] enabled, a sequence whose switch
+    /// commands are already in place is cut down to the voltage and tone
+    /// that changed.
+    pub fn setup_sec(
+        &self,
+        transponder_mhz: u32,
+        lnb: impl Into<CalibratedLnb>,
+        config: SecConfig,
+    ) -> Result<u32> {
         let setup = sec_sequence(transponder_mhz, lnb, config, SecTimings::default())?;
-        self.run_sec_sequence(&setup.sec_sequence)?;
+        let sequence = {
+            let sec = self.sec();
+            sec.power_up(sec.reduce(&setup.sec_sequence, Instant::now()))
+        };
+        self.run_sec_sequence(&sequence)?;
 
         Ok(setup.frontend_frequency_khz)
     }
@@ -609,6 +1043,7 @@ impl FeDevice {
     /// The sequence usually comes from [`sec_sequence`], which also reports
     /// the frequency to tune to once it has run.
     pub fn run_sec_sequence(&self, sequence: &[SecCommand]) -> Result<()> {
+        self.sec().active();
         for command in sequence {
             match command {
                 SecCommand::SetTone(value) => self.set_tone(*value)?,
@@ -618,10 +1053,119 @@ impl FeDevice {
                 SecCommand::Wait(duration) => std::thread::sleep(*duration),
             }
         }
+        self.sec().sequence_done(sequence, Instant::now());
 
         Ok(())
     }
 
+    /// Turns the SEC state cache of [`FeDevice::setup_sec`] on or off. It is
+    /// off by default.
+    ///
+    /// The frontend always tracks the voltage, tone and switch commands it
+    /// last sent, see [`FeDevice::sec_state`]. With the cache on,
+    /// `setup_sec` compares the bursts and master commands of the new
+    /// sequence with the last ones sent; if they are the same, the switches
+    /// are already in place and only a changed voltage or tone is sent,
+    /// without the switch settle waits. Zapping between transponders of one
+    /// satellite and polarization then costs no SEC time at all.
+    ///
+    /// The cache only knows what went through this frontend. Call
+    /// [`FeDevice::invalidate_sec_state`] when the equipment was power
+    /// cycled or another program shares the cable.
+    pub fn set_sec_cache(&mut self, enabled: bool) {
+        self.sec
+            .get_mut()
+            .unwrap_or_else(PoisonError::into_inner)
+            .enabled = enabled;
+    }
+
+    /// Sets how long the SEC state cache trusts the switches: once this much
+    /// time has passed since the switch commands were last sent, the next
+    /// [`FeDevice::setup_sec`] runs in full. 5 minutes by default; `None`
+    /// trusts them until invalidated.
+    pub fn set_sec_refresh_interval(&mut self, interval: Option<Duration>) {
+        self.sec
+            .get_mut()
+            .unwrap_or_else(PoisonError::into_inner)
+            .refresh_interval = interval;
+    }
+
+    /// Forgets the tracked SEC state, so the next [`FeDevice::setup_sec`]
+    /// runs in full.
+    pub fn invalidate_sec_state(&self) {
+        self.sec().invalidate();
+    }
+
+    /// Sets when the frontend switches the LNB supply off while idle, or
+    /// `None`, the default, to keep it powered.
+    ///
+    /// The idle time starts at [`FeDevice::release_lnb`] and ends at the
+    /// next SEC sequence or tune. The frontend runs no timer of its own, so
+    /// the supply only goes off when the application asks: it calls
+    /// [`FeDevice::power_off_if_idle`] from its housekeeping, at
+    /// [`FeDevice::lnb_idle_deadline`], or leaves a thread in
+    /// [`FeDevice::power_off_when_idle`]. The next [`FeDevice::setup_sec`] after a power-off
+    /// switches the supply back on and waits the power-on settle time of the
+    /// policy before the sequence.
+    pub fn set_lnb_idle_policy(&mut self, policy: Option<LnbIdlePolicy>) {
+        self.sec
+            .get_mut()
+            .unwrap_or_else(PoisonError::into_inner)
+            .idle_policy = policy;
+    }
+
+    /// Marks the frontend idle: nothing it is tuned to is needed any more.
+    /// The idle time of [`FeDevice::set_lnb_idle_policy`] counts from the
+    /// first release after the last use.
+    pub fn release_lnb(&self) {
+        self.sec().release(Instant::now());
+    }
+
+    /// Switches the LNB supply off if the frontend has been idle for the
+    /// time of its idle policy. Returns `true` if it did.
+    pub fn power_off_if_idle(&self) -> Result<bool> {
+        if !self.sec().idle_expired(Instant::now()) {
+            return Ok(false);
+        }
+
+        self.set_voltage(SecVoltage::Off)?;
+        self.sec().power_down();
+
+        Ok(true)
+    }
+
+    /// Time the idle policy wants the LNB supply off; `None` while the
+    /// frontend is in use, without a policy, or with the supply already off.
+    pub fn lnb_idle_deadline(&self) -> Option<Instant> {
+        self.sec().idle_deadline()
+    }
+
+    /// Sleeps until the idle time of the frontend is over and switches the
+    /// LNB supply off. Returns `true` if it did, `false` once no idle time
+    /// runs any more - the frontend was used again or has no policy.
+    ///
+    /// The frontend is shared with the thread that sits here, which only
+    /// looks at it again at the deadline it slept for.
+    pub fn power_off_when_idle(&self) -> Result<bool> {
+        while let Some(deadline) = self.lnb_idle_deadline() {
+            std::thread::sleep(deadline.saturating_duration_since(Instant::now()));
+            if self.power_off_if_idle()? {
+                return Ok(true);
+            }
+        }
+
+        Ok(false)
+    }
+
+    /// Voltage, tone and switch commands this frontend last sent.
+    pub fn sec_state(&self) -> SecState {
+        self.sec().state.clone()
+    }
+
+    fn sec(&self) -> MutexGuard<'_, SecTracker> {
+        self.sec.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
     /// Returns the current API version
     pub fn api_version(&self) -> ApiVersion {
         self.api_version
@@ -662,3 +1206,44 @@ impl FeDevice {
         self.device_id
     }
 }
+
+/// What the scans that tune request after request need from the frontend.
+pub(crate) trait LockBus {
+    /// Tunes a request and waits up to `timeout` for the lock; the
+    /// statistics on a lock.
+    fn lock(&mut self, request: &TuneRequest, timeout: Duration) -> Result<Option<FeStats>>;
+}
+
+impl LockBus for &FeDevice {
+    fn lock(&mut self, request: &TuneRequest, timeout: Duration) -> Result<Option<FeStats>> {
+        self.tune(request)?;
+        if !wait_lock(self, timeout)? {
+            return Ok(None);
+        }
+
+        self.get_stats().map(Some)
+    }
+}
+
+/// Polls the frontend status until it locks or the deadline passes.
+pub(crate) fn wait_lock(fe: &FeDevice, deadline: Duration) -> Result<bool> {
+    let start = Instant::now();
+    loop {
+        if fe.get_stats()?.has_lock() {
+            return Ok(true);
+        }
+        if start.elapsed() >= deadline {
+            return Ok(false);
+        }
+        std::thread::sleep(Duration::from_millis(20));
+    }
+}
+
+/// Error of an LNB supply ioctl: `EIO` is the supply shutting down, or the
+/// driver failing to reach it.
+fn lnb_error(errno: ::nix::errno::Errno) -> Error {
+    match errno {
+        ::nix::errno::Errno::EIO => Error::LnbOverload,
+        errno => Error::Nix(errno),
+    }
+}
@@ -0,0 +1,401 @@
+//! Input streams and PLS codes of DVB-S2 multistream transponders.
+//!
+//! Tuning one stream of a multistream transponder takes its input stream
+//! identifier and the PLS code its physical layer is scrambled with, and
+//! channel lists do not always have them. The frontend does not lock on a
+//! transponder with the wrong PLS code, so [`PlsSearch`] tunes the codes in
+//! use until one locks. With the right code, a frontend in BBFrame delivery
+//! hands over the baseband frames of every stream, and the ISI in their
+//! headers tells which streams there are.
+
+use std::{
+    collections::BTreeMap,
+    time::{
+        Duration,
+        Instant,
+    },
+};
+
+use super::{
+    DvbS2Tune,
+    FeDevice,
+    FeStats,
+    LockBus,
+    Mis,
+    PlsMode,
+    TuneRequest,
+    sys::NO_STREAM_ID_FILTER,
+};
+use crate::{
+    bbframe::{
+        BBFRAME_STREAM_ID,
+        BbFrameReader,
+        StreamFormat,
+    },
+    dvr::DvrDevice,
+    error::Result,
+};
+
+/// PLS codes seen on satellite multistream feeds. Channel lists give a code
+/// either as the Gold index or as the root it is generated from; the table
+/// holds one entry per scrambling sequence, with the root each Gold index
+/// converts to by EN 302 307-1, 5.5.4:
+///
+/// | Entry       | Gold   | Root  |
+/// |-------------|--------|-------|
+/// | Root 1      | 0      | 1     |
+/// | Gold 8      | 8      | 1024  |
+/// | Gold 131070 | 131070 | 16416 |
+/// | Gold 65536  | 65536  | 49184 |
+/// | Gold 262141 | 262141 | 4     |
+///
+/// Root code 1 is the default scrambling of a transponder without PLS.
+pub const COMMON_PLS_CODES: [(PlsMode, u32); 5] = [
+    (PlsMode::Root, 1),
+    (PlsMode::Gold, 8),
+    (PlsMode::Gold, 131070),
+    (PlsMode::Gold, 65536),
+    (PlsMode::Gold, 262141),
+];
+
+/// Parameters of [`FeDevice::search_pls`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlsSearch {
+    /// How long one code waits for the lock.
+    pub lock_timeout: Duration,
+    /// Codes to try in order, after the one of the request.
+    pub codes: Vec<(PlsMode, u32)>,
+}
+
+impl Default for PlsSearch {
+    fn default() -> Self {
+        Self {
+            lock_timeout: Duration::from_secs(1),
+            codes: COMMON_PLS_CODES.to_vec(),
+        }
+    }
+}
+
+/// Parameters of [`FeDevice::find_input_streams`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MultistreamScan {
+    /// The PLS codes to find the lock with.
+    pub pls: PlsSearch,
+    /// How long to read baseband frames once locked. A stream missing from
+    /// the frames read in that time is not found.
+    pub listen: Duration,
+    /// PID of the pseudo transport stream, `0x2000` for any.
+    pub pid: u16,
+}
+
+impl Default for MultistreamScan {
+    fn default() -> Self {
+        Self {
+            pls: PlsSearch::default(),
+            listen: Duration::from_secs(2),
+            pid: 0x2000,
+        }
+    }
+}
+
+/// An input stream a multistream scan found.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InputStream {
+    /// The PLS code and input stream identifier to tune the stream with.
+    /// The stream id is [`NO_STREAM_ID_FILTER`] on a single input stream.
+    pub mis: Mis,
+    /// What the stream carries.
+    pub format: StreamFormat,
+    /// `true` with constant coding and modulation.
+    pub ccm: bool,
+    /// Baseband frames of the stream read.
+    pub frames: usize,
+}
+
+/// What a multistream scan needs from the frontend, besides the lock.
+pub(crate) trait MultistreamBus: LockBus {
+    /// Reads the pseudo transport stream, waiting up to `timeout`. Returns
+    /// 0 when nothing arrived.
+    fn read(&mut self, buf: &mut [u8], timeout: Duration) -> Result<usize>;
+}
+
+/// The PLS codes to try for `request`: its own first, then those of
+/// `search`, each scrambling sequence once.
+fn candidates(request: &DvbS2Tune, search: &PlsSearch) -> Vec<(PlsMode, u32)> {
+    let mut codes: Vec<(PlsMode, u32)> = Vec::new();
+    let mut indices = Vec::new();
+    let own = request.mis.map(|mis| (mis.mode, mis.code));
+    for (mode, code) in own.into_iter().chain(search.codes.iter().copied()) {
+        let mis = Mis {
+            mode,
+            code,
+            stream_id: 0,
+        };
+        let index = mis.pls_code().unwrap_or(0);
+        if !indices.contains(&index) {
+            indices.push(index);
+            codes.push((mode, code));
+        }
+    }
+
+    codes
+}
+
+/// Tunes `request` with the PLS codes of `search` and `stream_id` until
+/// one locks.
+pub(crate) fn search<B: MultistreamBus>(
+    bus: &mut B,
+    request: &DvbS2Tune,
+    search: &PlsSearch,
+    stream_id: u32,
+) -> Result<Option<Mis>> {
+    for (mode, code) in candidates(request, search) {
+        let mis = Mis {
+            mode,
+            code,
+            stream_id,
+        };
+        let tune = DvbS2Tune {
+            mis: Some(mis),
+            ..request.clone()
+        };
+        if bus
+            .lock(&TuneRequest::DvbS2(tune), search.lock_timeout)?
+            .is_some()
+        {
+            return Ok(Some(mis));
+        }
+    }
+
+    Ok(None)
+}
+
+/// Finds the PLS code in BBFrame delivery, then reads the baseband frames
+/// for the ISIs in them.
+pub(crate) fn scan<B: MultistreamBus>(
+    bus: &mut B,
+    request: &DvbS2Tune,
+    scan: &MultistreamScan,
+) -> Result<Vec<InputStream>> {
+    let Some(pls) = search(bus, request, &scan.pls, BBFRAME_STREAM_ID)? else {
+        return Ok(Vec::new());
+    };
+
+    let mut reader = BbFrameReader::new(scan.pid);
+    let mut streams: BTreeMap<u32, InputStream> = BTreeMap::new();
+    let mut buf = vec![0; 188 * 64];
+    let deadline = Instant::now() + scan.listen;
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            break;
+        }
+        let size = bus.read(&mut buf, remaining)?;
+        for frame in reader.push(&buf[.. size]) {
+            let header = frame.header;
+            let stream_id = if header.single_stream {
+                NO_STREAM_ID_FILTER
+            } else {
+                u32::from(header.isi)
+            };
+            let stream = streams.entry(stream_id).or_insert(InputStream {
+                mis: Mis { stream_id, ..pls },
+                format: header.format,
+                ccm: header.ccm,
+                frames: 0,
+            });
+            stream.frames += 1;
+        }
+    }
+
+    Ok(streams.into_values().collect())
+}
+
+/// [`MultistreamBus`] over a frontend and the DVR device of its adapter.
+pub(crate) struct FeBus<'a> {
+    pub(crate) fe: &'a FeDevice,
+    pub(crate) dvr: Option<&'a mut DvrDevice>,
+}
+
+impl LockBus for FeBus<'_> {
+    fn lock(&mut self, request: &TuneRequest, timeout: Duration) -> Result<Option<FeStats>> {
+        self.fe.lock(request, timeout)
+    }
+}
+
+impl MultistreamBus for FeBus<'_> {
+    fn read(&mut self, buf: &mut [u8], timeout: Duration) -> Result<usize> {
+        match self.dvr.as_deref_mut() {
+            Some(dvr) => dvr.read_timeout(buf, timeout),
+            None => Ok(0),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bbframe::BBHEADER_SIZE;
+
+    /// A transponder scrambled with one gold code, carrying baseband frames
+    /// of some streams.
+    struct Transponder {
+        gold: u32,
+        isis: Vec<u8>,
+        single: bool,
+        tuned: Vec<Mis>,
+        sent: bool,
+    }
+
+    impl Transponder {
+        fn pseudo_ts(&self) -> Vec<u8> {
+            let mut ts = Vec::new();
+            for (i, &isi) in self.isis.iter().cycle().take(7).enumerate() {
+                let matype = if self.single { 0xF0 } else { 0xD0 };
+                let mut frame = vec![matype, isi, 0x05, 0xE0, 0x00, 0x08 * 8, 0x47, 0, 0];
+                frame.push(crate::bbframe::crc8(&frame));
+                frame.resize(BBHEADER_SIZE + 8, 0);
+                ts.extend_from_slice(&[0x47, 0x41, 0x18, 0x10 | (i as u8 & 0x0F)]);
+                ts.extend_from_slice(&frame);
+                ts.resize((i + 1) * 188, 0xFF);
+            }
+            ts
+        }
+    }
+
+    impl LockBus for Transponder {
+        fn lock(&mut self, request: &TuneRequest, _timeout: Duration) -> Result<Option<FeStats>> {
+            let TuneRequest::DvbS2(tune) = request else {
+                unreachable!()
+            };
+            let mis = tune.mis.unwrap();
+            self.tuned.push(mis);
+            Ok((mis.pls_code() == Some(self.gold)).then(FeStats::default))
+        }
+    }
+
+    impl MultistreamBus for Transponder {
+        fn read(&mut self, buf: &mut [u8], _timeout: Duration) -> Result<usize> {
+            if self.sent {
+                return Ok(0);
+            }
+            self.sent = true;
+            let ts = self.pseudo_ts();
+            buf[.. ts.len()].copy_from_slice(&ts);
+            Ok(ts.len())
+        }
+    }
+
+    fn transponder(gold: u32, isis: Vec<u8>, single: bool) -> Transponder {
+        Transponder {
+            gold,
+            isis,
+            single,
+            tuned: Vec::new(),
+            sent: false,
+        }
+    }
+
+    #[test]
+    fn pls_search_skips_equal_codes() {
+        let request = DvbS2Tune {
+            frequency_khz: 1_420_000,
+            symbolrate: 30_000_000,
+            mis: Some(Mis {
+                mode: PlsMode::Gold,
+                code: 0,
+                stream_id: 5,
+            }),
+            ..Default::default()
+        };
+        let mut bus = transponder(131070, vec![5], false);
+        let mis = search(&mut bus, &request, &PlsSearch::default(), 5)
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            mis,
+            Mis {
+                mode: PlsMode::Gold,
+                code: 131070,
+                stream_id: 5,
+            }
+        );
+        // gold 0 is root 1, so the default code is tuned once
+        let tuned: Vec<(PlsMode, u32)> = bus.tuned.iter().map(|mis| (mis.mode, mis.code)).collect();
+        assert_eq!(
+            tuned,
+            [
+                (PlsMode::Gold, 0),
+                (PlsMode::Gold, 8),
+                (PlsMode::Gold, 131070),
+            ]
+        );
+
+        let mut bus = transponder(4711, vec![5], false);
+        assert!(
+            search(&mut bus, &request, &PlsSearch::default(), 5)
+                .unwrap()
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn common_codes_match_their_roots() {
+        let roots: [u32; 5] = [1, 1024, 16416, 49184, 4];
+        for ((mode, code), root) in COMMON_PLS_CODES.into_iter().zip(roots) {
+            let gold = Mis {
+                mode,
+                code,
+                stream_id: 0,
+            }
+            .pls_code()
+            .unwrap_or(0);
+            let from_root = Mis {
+                mode: PlsMode::Root,
+                code: root,
+                stream_id: 0,
+            }
+            .pls_code();
+            assert_eq!(from_root, Some(gold), "root {}", root);
+        }
+    }
+
+    #[test]
+    fn scan_lists_the_input_streams() {
+        let request = DvbS2Tune {
+            frequency_khz: 1_420_000,
+            symbolrate: 30_000_000,
+            ..Default::default()
+        };
+        let scan_with = |bus: &mut Transponder| {
+            let scan_params = MultistreamScan {
+                listen: Duration::from_millis(50),
+                ..Default::default()
+            };
+            scan(bus, &request, &scan_params).unwrap()
+        };
+
+        let mut bus = transponder(65536, vec![2, 1, 7], false);
+        let streams = scan_with(&mut bus);
+        let found: Vec<(u32, usize)> = streams
+            .iter()
+            .map(|stream| (stream.mis.stream_id, stream.frames))
+            .collect();
+        assert_eq!(found, [(1, 2), (2, 3), (7, 2)]);
+        assert_eq!(streams[0].mis.code, 65536);
+        assert_eq!(streams[0].format, StreamFormat::Transport);
+        assert!(streams[0].ccm);
+        assert!(
+            bus.tuned
+                .iter()
+                .all(|mis| mis.stream_id == BBFRAME_STREAM_ID)
+        );
+
+        let mut bus = transponder(0, vec![0], true);
+        let streams = scan_with(&mut bus);
+        assert_eq!(streams.len(), 1);
+        assert_eq!(streams[0].mis.stream_id, NO_STREAM_ID_FILTER);
+        assert_eq!(streams[0].mis.mode, PlsMode::Root);
+        assert_eq!(streams[0].frames, 7);
+    }
+}
@@ -0,0 +1,629 @@
+//! A pool of the frontends of a host, handed out for tune requests.
+//!
+//! A box with several tuners has them wired in different ways: terrestrial
+//! and satellite frontends side by side, satellite inputs behind switches
+//! that reach some satellites and not others, and inputs that hang on the
+//! same LNB - loop-through, a passive splitter, the two inputs of a twin
+//! tuner fed from one cable. [`FePool`] records what each frontend can tune
+//! and how it is wired, and [`FePool::acquire`] picks a free frontend for a
+//! request and leases it until the [`FeLease`] is dropped.
+//!
+//! Frontends on one cable share the voltage and the tone of the LNB, and
+//! the switch in front of it: while one of them is leased for a
+//! transponder, the others on the cable can only be leased for the same
+//! satellite, polarization and band. A Unicable input leaves the LNB to the
+//! ODU and has no such constraint; its user bands are for
+//! [`UserBandAllocator`](super::UserBandAllocator) to hand out.
+
+use std::sync::{
+    Arc,
+    Mutex,
+};
+
+use super::{
+    CalibratedLnb,
+    FeDevice,
+    FeLimits,
+    Polarization,
+    SecConfig,
+    TuneRequest,
+    TuneViolation,
+    sys::{
+        SecTone,
+        SecVoltage,
+    },
+};
+use crate::{
+    error::Result,
+    scan,
+};
+
+/// A satellite a frontend input reaches, and how.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SatelliteInput {
+    /// Source of the satellite as the channel lists name it, such as
+    /// `S19.2E`.
+    pub source: String,
+    /// The LNB, with the oscillator offset measured for it.
+    pub lnb: CalibratedLnb,
+    /// The switch or Unicable setup in front of the LNB.
+    pub config: SecConfig,
+}
+
+/// The satellite transponder a request of [`FePool::acquire`] is for.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SatelliteTarget {
+    /// Source of the satellite as the channel lists name it, such as
+    /// `S19.2E`; `None` for any input.
+    pub source: Option<String>,
+    /// Transponder frequency in kHz.
+    pub transponder_khz: u32,
+    /// Polarization of the transponder.
+    pub polarization: Polarization,
+}
+
+/// A frontend of a [`FePool`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PoolFrontend {
+    /// Adapter number.
+    pub adapter: u32,
+    /// Frontend number on the adapter.
+    pub device: u32,
+    /// Frontend name.
+    pub name: String,
+    /// What the frontend can tune.
+    pub limits: FeLimits,
+    /// Cable of the satellite input. Frontends that name the same cable
+    /// share its LNB; `None` for an input with a cable of its own.
+    pub cable: Option<String>,
+    /// Satellites the input reaches, empty when it is not wired.
+    pub satellites: Vec<SatelliteInput>,
+}
+
+impl PoolFrontend {
+    /// Frontend with its limits and no wiring.
+    pub fn new(adapter: u32, device: u32, name: impl Into<String>, limits: FeLimits) -> Self {
+        PoolFrontend {
+            adapter,
+            device,
+            name: name.into(),
+            limits,
+            cable: None,
+            satellites: Vec::new(),
+        }
+    }
+}
+
+/// What a lease holds the LNB of its cable to: the satellite, the
+/// polarization voltage and the band tone.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct LnbSetting {
+    cable: String,
+    source: String,
+    voltage: Option<SecVoltage>,
+    tone: SecTone,
+}
+
+#[derive(Debug)]
+struct Slot {
+    frontend: PoolFrontend,
+    leased: bool,
+    setting: Option<LnbSetting>,
+}
+
+/// The frontend [`FePool::acquire`] leases so far.
+struct Candidate {
+    index: usize,
+    input: Option<SatelliteInput>,
+    intermediate_khz: Option<u32>,
+    setting: Option<LnbSetting>,
+    /// Lower goes first: not joining a held setting, then the number of
+    /// delivery systems.
+    rank: (bool, usize),
+}
+
+/// The frontends of a host, leased out one request at a time. Clones share
+/// the pool.
+#[derive(Debug, Clone, Default)]
+pub struct FePool {
+    slots: Arc<Mutex<Vec<Slot>>>,
+}
+
+/// A frontend taken from a [`FePool`]; it goes back to the pool on drop.
+#[derive(Debug)]
+pub struct FeLease {
+    index: usize,
+    adapter: u32,
+    device: u32,
+    input: Option<SatelliteInput>,
+    intermediate_khz: Option<u32>,
+    slots: Arc<Mutex<Vec<Slot>>>,
+}
+
+/// `true` unless the frontend would reject the request outright. The
+/// frequency is only checked once it is known: a satellite request is a
+/// template until the intermediate frequency is set.
+fn can_tune(limits: &FeLimits, request: &TuneRequest, frequency_known: bool) -> bool {
+    limits
+        .validate(request)
+        .iter()
+        .all(|violation| match violation {
+            TuneViolation::DeliverySystem { .. } | TuneViolation::SymbolRate { .. } => false,
+            TuneViolation::Frequency { .. } => !frequency_known,
+            TuneViolation::Capability { .. } => true,
+        })
+}
+
+fn is_unicable(config: &SecConfig) -> bool {
+    matches!(config, SecConfig::Unicable1(_) | SecConfig::Unicable2(_))
+}
+
+/// The LNB setting `input` holds its cable to for `target` in `tone`,
+/// `None` when it drives no LNB of a shared cable.
+fn lnb_setting(
+    cable: Option<&String>,
+    input: &SatelliteInput,
+    target: &SatelliteTarget,
+    tone: SecTone,
+) -> Option<LnbSetting> {
+    let cable = cable?;
+    if is_unicable(&input.config) {
+        return None;
+    }
+
+    let voltage = input
+        .lnb
+        .band_voltage(target.transponder_khz / 1000)
+        .or(Some(target.polarization.voltage()));
+    Some(LnbSetting {
+        cable: cable.clone(),
+        source: input.source.clone(),
+        voltage,
+        tone,
+    })
+}
+
+impl FePool {
+    /// Pool of the given frontends.
+    pub fn new(frontends: impl IntoIterator<Item = PoolFrontend>) -> Self {
+        FePool {
+            slots: Arc::new(Mutex::new(
+                frontends
+                    .into_iter()
+                    .map(|frontend| Slot {
+                        frontend,
+                        leased: false,
+                        setting: None,
+                    })
+                    .collect(),
+            )),
+        }
+    }
+
+    /// Pool of the frontends under /dev/dvb, as [`scan::scan`] finds them.
+    /// A frontend another process holds, or that fails its probe, is left
+    /// out. None is wired yet, see [`FePool::wire`].
+    pub fn scan() -> Self {
+        let frontends = scan::scan()
+            .into_iter()
+            .filter(|probe| probe.error.is_none() && probe.busy == Some(false))
+            .filter_map(|probe| {
+                let fe = FeDevice::open_ro(probe.adapter, probe.device).ok()?;
+                Some(PoolFrontend::new(
+                    probe.adapter,
+                    probe.device,
+                    fe.name(),
+                    fe.limits(),
+                ))
+            });
+
+        FePool::new(frontends)
+    }
+
+    /// Records how the satellite input of a frontend is wired: the cable it
+    /// hangs on, and the satellites it reaches. Returns `false` when the
+    /// frontend is not in the pool.
+    pub fn wire(
+        &self,
+        adapter: u32,
+        device: u32,
+        cable: Option<String>,
+        satellites: Vec<SatelliteInput>,
+    ) -> bool {
+        let mut slots = self.slots.lock().unwrap_or_else(|e| e.into_inner());
+        let Some(slot) = slots
+            .iter_mut()
+            .find(|slot| slot.frontend.adapter == adapter && slot.frontend.device == device)
+        else {
+            return false;
+        };
+
+        slot.frontend.cable = cable;
+        slot.frontend.satellites = satellites;
+        true
+    }
+
+    /// The frontends of the pool, in the order they were added.
+    pub fn frontends(&self) -> Vec<PoolFrontend> {
+        let slots = self.slots.lock().unwrap_or_else(|e| e.into_inner());
+        slots.iter().map(|slot| slot.frontend.clone()).collect()
+    }
+
+    /// Number of frontends not leased.
+    pub fn available(&self) -> usize {
+        let slots = self.slots.lock().unwrap_or_else(|e| e.into_inner());
+        slots.iter().filter(|slot| !slot.leased).count()
+    }
+
+    /// Leases a free frontend that can tune `request`, `None` when there is
+    /// none.
+    ///
+    /// The frontend must list the delivery system and, where it reports
+    /// one, the symbol rate range of the request; a terrestrial or cable
+    /// request must be in its frequency range. A satellite request is a
+    /// template for the transponder of `satellite`, which it needs: an input
+    /// that reaches its source - any input, or a frontend that is not wired,
+    /// without one - through an LNB that receives the transponder at an
+    /// intermediate frequency in the frontend range, on a cable whose other
+    /// leases hold the LNB to the same satellite, band and polarization.
+    ///
+    /// Of the frontends that qualify, one on a cable already held to that
+    /// setting goes first, which leaves the other cables free; then the one
+    /// that lists the fewest delivery systems, which keeps the versatile
+    /// frontends for the requests only they can tune.
+    pub fn acquire(
+        &self,
+        request: &TuneRequest,
+        satellite: Option<&SatelliteTarget>,
+    ) -> Option<FeLease> {
+        let is_satellite = matches!(request, TuneRequest::DvbS(_) | TuneRequest::DvbS2(_));
+        let target = match satellite {
+            Some(target) if is_satellite => Some(target),
+            None if is_satellite => return None,
+            _ => None,
+        };
+
+        let mut slots = self.slots.lock().unwrap_or_else(|e| e.into_inner());
+
+        let mut best: Option<Candidate> = None;
+        for (index, slot) in slots.iter().enumerate() {
+            let frontend = &slot.frontend;
+            if slot.leased {
+                continue;
+            }
+
+            let (input, intermediate_khz, setting) = match target {
+                Some(target) => {
+                    let input = frontend.satellites.iter().find(|input| {
+                        target
+                            .source
+                            .as_ref()
+                            .is_none_or(|source| input.source == *source)
+                    });
+                    match input {
+                        Some(input) => {
+                            let Ok((frequency_khz, tone)) =
+                                input.lnb.intermediate_khz(target.transponder_khz)
+                            else {
+                                continue;
+                            };
+                            // a Unicable input tunes its user band instead
+                            let intermediate_khz =
+                                (!is_unicable(&input.config)).then_some(frequency_khz);
+                            let setting = lnb_setting(frontend.cable.as_ref(), input, target, tone);
+                            (Some(input.clone()), intermediate_khz, setting)
+                        }
+                        None if frontend.satellites.is_empty() && target.source.is_none() => {
+                            (None, None, None)
+                        }
+                        None => continue,
+                    }
+                }
+                None => (None, None, None),
+            };
+
+            let tunable = match intermediate_khz {
+                Some(frequency_khz) => request
+                    .with_intermediate_khz(frequency_khz)
+                    .is_ok_and(|request| can_tune(&frontend.limits, &request, true)),
+                None => can_tune(&frontend.limits, request, !is_satellite),
+            };
+            if !tunable {
+                continue;
+            }
+
+            // the other leases on the cable
+            let mut shared = false;
+            if let Some(setting) = &setting {
+                let held: Vec<&LnbSetting> = slots
+                    .iter()
+                    .filter_map(|slot| slot.setting.as_ref())
+                    .filter(|held| held.cable == setting.cable)
+                    .collect();
+                if held.iter().any(|held| *held != setting) {
+                    continue;
+                }
+                shared = !held.is_empty();
+            }
+
+            let rank = (!shared, frontend.limits.delivery_systems.len());
+            if best.as_ref().is_none_or(|best| rank < best.rank) {
+                best = Some(Candidate {
+                    index,
+                    input,
+                    intermediate_khz,
+                    setting,
+                    rank,
+                });
+            }
+        }
+
+        let Candidate {
+            index,
+            input,
+            intermediate_khz,
+            setting,
+            ..
+        } = best?;
+        let slot = &mut slots[index];
+        slot.leased = true;
+        slot.setting = setting;
+
+        Some(FeLease {
+            index,
+            adapter: slot.frontend.adapter,
+            device: slot.frontend.device,
+            input,
+            intermediate_khz,
+            slots: self.slots.clone(),
+        })
+    }
+}
+
+impl FeLease {
+    /// Adapter number of the leased frontend.
+    pub fn adapter(&self) -> u32 {
+        self.adapter
+    }
+
+    /// Frontend number of the leased frontend.
+    pub fn device(&self) -> u32 {
+        self.device
+    }
+
+    /// The satellite input to tune through, with the LNB and the SEC
+    /// configuration for [`FeDevice::setup_sec`]; `None` for a terrestrial
+    /// or cable request, and on a frontend that is not wired.
+    pub fn input(&self) -> Option<&SatelliteInput> {
+        self.input.as_ref()
+    }
+
+    /// Intermediate frequency in kHz the LNB of the input converts the
+    /// transponder to, for [`TuneRequest::with_intermediate_khz`]. `None`
+    /// for a terrestrial or cable request, on a frontend that is not wired,
+    /// and on a Unicable input, which tunes the user band
+    /// [`FeDevice::setup_sec`] returns.
+    pub fn intermediate_khz(&self) -> Option<u32> {
+        self.intermediate_khz
+    }
+
+    /// Opens the leased frontend read-write.
+    pub fn open(&self) -> Result<FeDevice> {
+        FeDevice::open_rw(self.adapter, self.device)
+    }
+}
+
+impl Drop for FeLease {
+    fn drop(&mut self) {
+        let mut slots = self.slots.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(slot) = slots.get_mut(self.index) {
+            slot.leased = false;
+            slot.setting = None;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fe::{
+        DvbS2Tune,
+        DvbTTune,
+        Lnb,
+        UnicableConfig,
+        sys::{
+            DeliverySystem,
+            FeCaps,
+        },
+    };
+
+    fn frontend(adapter: u32, delivery_systems: Vec<DeliverySystem>) -> PoolFrontend {
+        let satellite = delivery_systems.contains(&DeliverySystem::Dvbs);
+        PoolFrontend::new(
+            adapter,
+            0,
+            format!("fe{}", adapter),
+            FeLimits {
+                delivery_systems,
+                frequency_range: if satellite {
+                    950_000 ..= 2_150_000
+                } else {
+                    174_000_000 ..= 862_000_000
+                },
+                frequency_khz: satellite,
+                symbolrate_range: 1_000_000 ..= 45_000_000,
+                caps: FeCaps::IS_STUPID,
+            },
+        )
+    }
+
+    fn astra(config: SecConfig) -> SatelliteInput {
+        SatelliteInput {
+            source: "S19.2E".to_owned(),
+            lnb: Lnb::Universal {
+                lof_low_mhz: 9750,
+                lof_high_mhz: 10600,
+                switch_mhz: 11700,
+            }
+            .into(),
+            config,
+        }
+    }
+
+    fn dvbs2() -> TuneRequest {
+        TuneRequest::DvbS2(DvbS2Tune {
+            symbolrate: 27_500_000,
+            ..Default::default()
+        })
+    }
+
+    fn target(
+        source: Option<&str>,
+        transponder_khz: u32,
+        polarization: Polarization,
+    ) -> SatelliteTarget {
+        SatelliteTarget {
+            source: source.map(str::to_owned),
+            transponder_khz,
+            polarization,
+        }
+    }
+
+    #[test]
+    fn leases_the_narrowest_frontend() {
+        let pool = FePool::new([
+            frontend(0, vec![DeliverySystem::Dvbt, DeliverySystem::Dvbt2]),
+            frontend(
+                1,
+                vec![
+                    DeliverySystem::DvbcAnnexA,
+                    DeliverySystem::Dvbt,
+                    DeliverySystem::Dvbt2,
+                ],
+            ),
+            frontend(2, vec![DeliverySystem::Dvbs, DeliverySystem::Dvbs2]),
+        ]);
+        let dvbt = TuneRequest::DvbT(DvbTTune {
+            frequency_hz: 506_000_000,
+            ..Default::default()
+        });
+
+        let first = pool.acquire(&dvbt, None).unwrap();
+        assert_eq!(first.adapter(), 0);
+        assert!(first.input().is_none());
+        let second = pool.acquire(&dvbt, None).unwrap();
+        assert_eq!(second.adapter(), 1);
+        assert!(pool.acquire(&dvbt, None).is_none());
+        assert_eq!(pool.available(), 1);
+
+        drop(first);
+        assert_eq!(pool.acquire(&dvbt, None).unwrap().adapter(), 0);
+
+        // out of the terrestrial range
+        let vhf = TuneRequest::DvbT(DvbTTune {
+            frequency_hz: 100_000_000,
+            ..Default::default()
+        });
+        assert!(pool.acquire(&vhf, None).is_none());
+
+        // an unwired satellite frontend, for a request without a source
+        let horizontal = target(None, 11_362_000, Polarization::Horizontal);
+        let satellite = pool.acquire(&dvbs2(), Some(&horizontal)).unwrap();
+        assert_eq!(satellite.adapter(), 2);
+        assert_eq!(satellite.intermediate_khz(), None);
+        drop(satellite);
+        let astra = target(Some("S19.2E"), 11_362_000, Polarization::Horizontal);
+        assert!(pool.acquire(&dvbs2(), Some(&astra)).is_none());
+        // a satellite request names its transponder
+        assert!(pool.acquire(&dvbs2(), None).is_none());
+    }
+
+    #[test]
+    fn frontends_on_one_cable_share_the_lnb() {
+        let satellite = vec![DeliverySystem::Dvbs, DeliverySystem::Dvbs2];
+        let pool = FePool::new((0 .. 3).map(|adapter| frontend(adapter, satellite.clone())));
+        let lnb = astra(SecConfig::Lnb {
+            voltage: SecVoltage::V13,
+        });
+        assert!(pool.wire(0, 0, Some("twin".to_owned()), vec![lnb.clone()]));
+        assert!(pool.wire(1, 0, Some("twin".to_owned()), vec![lnb.clone()]));
+        assert!(pool.wire(
+            2,
+            0,
+            Some("twin".to_owned()),
+            vec![astra(SecConfig::Unicable1(UnicableConfig {
+                slot: 1,
+                user_band_frequency_mhz: 1210,
+                position: 0,
+                voltage: SecVoltage::V13,
+                pin: None,
+            }))]
+        ));
+        assert!(!pool.wire(3, 0, None, Vec::new()));
+
+        // high band horizontal, then the same on the other input
+        let astra =
+            |transponder_khz, polarization| target(Some("S19.2E"), transponder_khz, polarization);
+        let horizontal = Polarization::Horizontal;
+        let first = pool
+            .acquire(&dvbs2(), Some(&astra(11_836_000, horizontal)))
+            .unwrap();
+        assert_eq!(first.input(), Some(&lnb));
+        assert_eq!(first.intermediate_khz(), Some(1_236_000));
+
+        // vertical is only on the Unicable input, which tunes its user band
+        let vertical = astra(11_953_000, Polarization::Vertical);
+        let unicable = pool.acquire(&dvbs2(), Some(&vertical)).unwrap();
+        assert_eq!(unicable.adapter(), 2);
+        assert_eq!(unicable.intermediate_khz(), None);
+        assert!(pool.acquire(&dvbs2(), Some(&vertical)).is_none());
+        // and so is the low band
+        assert!(
+            pool.acquire(&dvbs2(), Some(&astra(11_303_000, horizontal)))
+                .is_none()
+        );
+
+        let second = pool
+            .acquire(&dvbs2(), Some(&astra(12_188_000, horizontal)))
+            .unwrap();
+        assert_eq!(second.adapter(), 1);
+        assert_eq!(second.intermediate_khz(), Some(1_588_000));
+
+        // the cable is free again once both leases are gone
+        drop(first);
+        drop(second);
+        assert!(
+            pool.acquire(&dvbs2(), Some(&astra(11_303_000, horizontal)))
+                .is_some()
+        );
+        let hotbird = target(Some("S13.0E"), 11_362_000, horizontal);
+        assert!(pool.acquire(&dvbs2(), Some(&hotbird)).is_none());
+    }
+
+    #[test]
+    fn intermediate_frequency_in_the_frontend_range() {
+        let pool = FePool::new([frontend(
+            0,
+            vec![DeliverySystem::Dvbs, DeliverySystem::Dvbs2],
+        )]);
+        let single = SatelliteInput {
+            source: "S19.2E".to_owned(),
+            lnb: Lnb::Single { lof_mhz: 9750 }.with_lo_offset(-2_000),
+            config: SecConfig::Lnb {
+                voltage: SecVoltage::V13,
+            },
+        };
+        assert!(pool.wire(0, 0, None, vec![single]));
+
+        let vertical = |transponder_khz| target(None, transponder_khz, Polarization::Vertical);
+        let lease = pool.acquire(&dvbs2(), Some(&vertical(11_000_000))).unwrap();
+        // the oscillator runs 2 MHz low
+        assert_eq!(lease.intermediate_khz(), Some(1_252_000));
+        drop(lease);
+        // 2.748 GHz is beyond the frontend
+        assert!(
+            pool.acquire(&dvbs2(), Some(&vertical(12_500_000)))
+                .is_none()
+        );
+    }
+}
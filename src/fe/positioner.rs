@@ -0,0 +1,315 @@
+//! DiSEqC 1.2 positioner commands and USALS (DiSEqC 1.3) angles.
+//!
+//! A motorized dish is driven by a positioner on the DiSEqC bus, at address
+//! `0x31` (polar/azimuth positioner). The commands move the dish, store and
+//! recall positions, and fence its travel in with soft limits. A USALS
+//! positioner also takes the motor angle itself, computed from the site
+//! coordinates and the orbital position of the satellite.
+
+use std::fmt;
+
+use super::SecCommand;
+use crate::error::{
+    Error,
+    Result,
+};
+
+/// Master command, no reply expected, first transmission.
+const FRAMING: u8 = 0xE0;
+/// Polar/azimuth positioner.
+const ADDRESS: u8 = 0x31;
+
+/// Direction of dish travel, as seen from behind the dish looking south.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PositionerDirection {
+    East,
+    West,
+}
+
+/// How far a drive command moves the dish.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PositionerDrive {
+    /// Keeps moving until [`PositionerCommand::Halt`] or a limit stops it.
+    Continuous,
+    /// Moves for a number of seconds, `1..=127`.
+    Timed { seconds: u8 },
+    /// Moves a number of motor steps, `1..=128`.
+    Steps { count: u8 },
+}
+
+/// One DiSEqC 1.2 positioner command, or the USALS goto-angle extension.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PositionerCommand {
+    /// Stops any movement.
+    Halt,
+    /// Lifts both soft limits.
+    DisableLimits,
+    /// Stores the current position as the soft limit in one direction, and
+    /// turns the limits on.
+    SetLimit(PositionerDirection),
+    /// Drives the dish.
+    Drive(PositionerDirection, PositionerDrive),
+    /// Stores the current position under a number, `1..=255`.
+    Store(u8),
+    /// Moves to a stored position. Position 0 is the reference position the
+    /// positioner was calibrated at.
+    Goto(u8),
+    /// Recalculates the stored positions from the current one, after the
+    /// dish has been re-aligned.
+    Recalculate,
+    /// Moves to a motor angle, in 1/16 degree east of the reference
+    /// position; negative is west. USALS (DiSEqC 1.3) only. The magnitude
+    /// is limited to 12 bits, 255 15/16 degrees.
+    GotoAngle { sixteenths: i16 },
+}
+
+impl PositionerCommand {
+    /// Encodes the command as a DiSEqC master command.
+    pub fn encode(&self) -> Result<Vec<u8>> {
+        let (command, data) = self.command()?;
+        let mut msg = vec![FRAMING, ADDRESS, command];
+        msg.extend(data);
+
+        Ok(msg)
+    }
+
+    /// Command byte and data bytes.
+    pub(crate) fn command(&self) -> Result<(u8, Vec<u8>)> {
+        let command = match *self {
+            PositionerCommand::Halt => (0x60, Vec::new()),
+            PositionerCommand::DisableLimits => (0x63, Vec::new()),
+            PositionerCommand::SetLimit(PositionerDirection::East) => (0x66, Vec::new()),
+            PositionerCommand::SetLimit(PositionerDirection::West) => (0x67, Vec::new()),
+            PositionerCommand::Drive(direction, drive) => {
+                let command = match direction {
+                    PositionerDirection::East => 0x68,
+                    PositionerDirection::West => 0x69,
+                };
+                (command, vec![drive_data(drive)?])
+            }
+            PositionerCommand::Store(0) => {
+                return Err(Error::InvalidData(
+                    "positioner position 0 is the reference and cannot be stored".to_owned(),
+                ));
+            }
+            PositionerCommand::Store(position) => (0x6A, vec![position]),
+            PositionerCommand::Goto(position) => (0x6B, vec![position]),
+            PositionerCommand::Recalculate => (0x6F, vec![0x00, 0x00, 0x00]),
+            PositionerCommand::GotoAngle { sixteenths } => {
+                let magnitude = sixteenths.unsigned_abs();
+                if magnitude > 0x0FFF {
+                    return Err(Error::InvalidData(format!(
+                        "USALS angle must fit 12 bits, got {} sixteenths",
+                        magnitude
+                    )));
+                }
+                let direction = if sixteenths < 0 { 0xD0 } else { 0xE0 };
+                (
+                    0x6E,
+                    vec![direction | (magnitude >> 8) as u8, magnitude as u8],
+                )
+            }
+        };
+
+        Ok(command)
+    }
+
+    /// Command of a command byte and its data, `None` when they are not a
+    /// positioner command that encodes back to the same bytes.
+    pub(crate) fn decode(command: u8, data: &[u8]) -> Option<PositionerCommand> {
+        let decoded = match (command, data) {
+            (0x60, []) => PositionerCommand::Halt,
+            (0x63, []) => PositionerCommand::DisableLimits,
+            (0x66, []) => PositionerCommand::SetLimit(PositionerDirection::East),
+            (0x67, []) => PositionerCommand::SetLimit(PositionerDirection::West),
+            (0x68, [data]) => PositionerCommand::Drive(PositionerDirection::East, drive(*data)),
+            (0x69, [data]) => PositionerCommand::Drive(PositionerDirection::West, drive(*data)),
+            (0x6A, [position]) if *position != 0 => PositionerCommand::Store(*position),
+            (0x6B, [position]) => PositionerCommand::Goto(*position),
+            (0x6E, [high, low]) if matches!(high & 0xF0, 0xD0 | 0xE0) => {
+                let magnitude = (i16::from(high & 0x0F) << 8) | i16::from(*low);
+                PositionerCommand::GotoAngle {
+                    sixteenths: if high & 0xF0 == 0xD0 {
+                        -magnitude
+                    } else {
+                        magnitude
+                    },
+                }
+            }
+            (0x6F, [0x00, 0x00, 0x00]) => PositionerCommand::Recalculate,
+            _ => return None,
+        };
+
+        Some(decoded)
+    }
+
+    /// The command as a step of a SEC sequence.
+    pub fn to_sec_command(&self) -> Result<SecCommand> {
+        Ok(SecCommand::SendMasterCommand(self.encode()?))
+    }
+
+    /// USALS goto command for a motor angle in degrees, east positive,
+    /// rounded to the nearest 1/16 degree.
+    pub fn goto_angle(degrees: f64) -> PositionerCommand {
+        let sixteenths = (degrees.abs() * 16.0).round().min(f64::from(i16::MAX)) as i16;
+        PositionerCommand::GotoAngle {
+            sixteenths: if degrees < 0.0 {
+                -sixteenths
+            } else {
+                sixteenths
+            },
+        }
+    }
+}
+
+/// Equatorial radius of the Earth, km.
+const EARTH_RADIUS_KM: f64 = 6378.14;
+/// Radius of the geostationary orbit, km.
+const ORBIT_RADIUS_KM: f64 = 42164.2;
+
+/// USALS motor angle in degrees to point a dish at a satellite, east of the
+/// reference position positive.
+///
+/// Coordinates are in degrees, north and east positive; the satellite is
+/// given by the longitude of its orbital position. The angle is the one
+/// between the meridian plane of the site and the plane through the polar
+/// mount axis and the satellite - the hour angle a polar mount turns by.
+pub fn usals_angle(site_latitude: f64, site_longitude: f64, satellite_longitude: f64) -> f64 {
+    let delta = (site_longitude - satellite_longitude).to_radians();
+    let x = ORBIT_RADIUS_KM * delta.cos() - EARTH_RADIUS_KM * site_latitude.to_radians().cos();
+    let y = ORBIT_RADIUS_KM * delta.sin();
+
+    -(y / x).atan().to_degrees()
+}
+
+/// Data byte of a drive command: 0 runs continuously, a positive value is a
+/// timeout in seconds, a negative one (two's complement) a number of steps.
+fn drive_data(drive: PositionerDrive) -> Result<u8> {
+    match drive {
+        PositionerDrive::Continuous => Ok(0x00),
+        PositionerDrive::Timed { seconds } => {
+            if !(1 ..= 127).contains(&seconds) {
+                return Err(Error::InvalidData(format!(
+                    "positioner drive time must be in range 1..=127 seconds, got {}",
+                    seconds
+                )));
+            }
+            Ok(seconds)
+        }
+        PositionerDrive::Steps { count } => {
+            if !(1 ..= 128).contains(&count) {
+                return Err(Error::InvalidData(format!(
+                    "positioner drive steps must be in range 1..=128, got {}",
+                    count
+                )));
+            }
+            Ok(count.wrapping_neg())
+        }
+    }
+}
+
+/// Drive of a drive command data byte.
+fn drive(data: u8) -> PositionerDrive {
+    match data {
+        0 => PositionerDrive::Continuous,
+        1 ..= 0x7F => PositionerDrive::Timed { seconds: data },
+        steps => PositionerDrive::Steps {
+            count: steps.wrapping_neg(),
+        },
+    }
+}
+
+impl fmt::Display for PositionerCommand {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PositionerCommand::Halt => write!(f, "halt"),
+            PositionerCommand::DisableLimits => write!(f, "limits off"),
+            PositionerCommand::SetLimit(PositionerDirection::East) => write!(f, "set east limit"),
+            PositionerCommand::SetLimit(PositionerDirection::West) => write!(f, "set west limit"),
+            PositionerCommand::Drive(direction, drive) => {
+                match direction {
+                    PositionerDirection::East => write!(f, "drive east ")?,
+                    PositionerDirection::West => write!(f, "drive west ")?,
+                }
+                match drive {
+                    PositionerDrive::Continuous => write!(f, "continuously"),
+                    PositionerDrive::Timed { seconds } => write!(f, "{} s", seconds),
+                    PositionerDrive::Steps { count } => write!(f, "{} steps", count),
+                }
+            }
+            PositionerCommand::Store(position) => write!(f, "store position {}", position),
+            PositionerCommand::Goto(position) => write!(f, "goto position {}", position),
+            PositionerCommand::Recalculate => write!(f, "recalculate positions"),
+            PositionerCommand::GotoAngle { sixteenths } => write!(
+                f,
+                "goto angle {:.2}{}",
+                f64::from(sixteenths.unsigned_abs()) / 16.0,
+                if *sixteenths < 0 { 'W' } else { 'E' }
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Reference motor angles, to 1/10 degree, for sites across Europe.
+    #[test]
+    fn usals_angle_reference_values() {
+        for (latitude, longitude, satellite, expected) in [
+            // the Netherlands
+            (52.0, 5.0, 19.2, 15.6),
+            (52.0, 5.0, 28.2, 25.5),
+            (52.0, 5.0, -30.0, -38.3),
+            // Madrid
+            (40.4, -3.7, 13.0, 18.8),
+            // Moscow
+            (55.75, 37.62, 36.0, -1.8),
+        ] {
+            let angle = usals_angle(latitude, longitude, satellite);
+            assert!(
+                (angle - expected).abs() < 0.05,
+                "{latitude}N {longitude}E to {satellite}E: {angle}"
+            );
+        }
+    }
+
+    #[test]
+    fn usals_angle_is_zero_on_the_site_meridian() {
+        for latitude in [-60.0, 0.0, 45.0, 70.0] {
+            assert!(usals_angle(latitude, 13.0, 13.0).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn usals_angle_is_symmetric() {
+        let east = usals_angle(48.0, 10.0, 30.0);
+        let west = usals_angle(48.0, 10.0, -10.0);
+        assert!(east > 0.0);
+        assert!((east + west).abs() < 1e-9);
+    }
+
+    #[test]
+    fn goto_angle_encodes_direction_and_sixteenths() {
+        // 15.64 degrees east: 250 sixteenths
+        let command = PositionerCommand::goto_angle(15.6386);
+        assert_eq!(command, PositionerCommand::GotoAngle { sixteenths: 250 });
+        assert_eq!(command.encode().unwrap(), [0xE0, 0x31, 0x6E, 0xE0, 0xFA]);
+
+        // 38.31 degrees west: 613 sixteenths
+        let command = PositionerCommand::goto_angle(-38.3097);
+        assert_eq!(command, PositionerCommand::GotoAngle { sixteenths: -613 });
+        assert_eq!(command.encode().unwrap(), [0xE0, 0x31, 0x6E, 0xD2, 0x65]);
+
+        assert_eq!(
+            PositionerCommand::goto_angle(0.0).encode().unwrap(),
+            [0xE0, 0x31, 0x6E, 0xE0, 0x00]
+        );
+        assert!(
+            PositionerCommand::GotoAngle { sixteenths: 0x1000 }
+                .encode()
+                .is_err()
+        );
+    }
+}
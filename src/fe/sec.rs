@@ -1,9 +1,20 @@
-use std::time::Duration;
+use std::{
+    fmt,
+    time::Duration,
+};
 
-use super::sys::{
-    SecMiniCmd,
-    SecTone,
-    SecVoltage,
+use super::{
+    DiseqcMessage,
+    UserBand,
+    positioner::{
+        PositionerCommand,
+        usals_angle,
+    },
+    sys::{
+        SecMiniCmd,
+        SecTone,
+        SecVoltage,
+    },
 };
 use crate::error::{
     Error,
@@ -30,6 +41,10 @@ pub enum SecCommand {
 /// Converts a transponder frequency into the intermediate frequency the
 /// frontend tunes to, together with the 22 kHz tone that selects that
 /// band - one decision, so the two cannot contradict each other.
+///
+/// The conversion assumes the nominal oscillator frequencies; a measured
+/// oscillator error goes into a [`CalibratedLnb`], see
+/// [`Lnb::with_lo_offset`].
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Lnb {
     /// The frequency is already an intermediate frequency; nothing to
@@ -56,17 +71,114 @@ pub enum Lnb {
         /// Local oscillator frequency in MHz.
         lof_mhz: u32,
     },
+    /// Wideband LNB: one oscillator, usually 10.4 GHz, converts the whole
+    /// Ku band to a 290 to 2340 MHz intermediate frequency. Each
+    /// polarization has an output of its own, so the voltage selects
+    /// nothing and the tone stays off; the output is picked by the switch
+    /// port or the Unicable position in front of it.
+    Wideband {
+        /// Local oscillator frequency in MHz.
+        lof_mhz: u32,
+    },
+    /// Ka band LNB with one oscillator per band, selected by a combination
+    /// of voltage and tone instead of polarization. [`Lnb::KA_BANDS`] is
+    /// the common four-band layout.
+    Ka {
+        /// Bands, by transponder range.
+        bands: [KaBand; 4],
+    },
+    /// Dual-band C/Ku LNB: a C band feed with the inverted conversion and a
+    /// Ku band feed with one oscillator. A transponder below `c_lof_mhz` is
+    /// in the C band; `select` says how the LNB is switched between the two.
+    DualBand {
+        /// C band local oscillator frequency in MHz.
+        c_lof_mhz: u32,
+        /// Ku band local oscillator frequency in MHz.
+        ku_lof_mhz: u32,
+        /// What selects the band.
+        select: DualBandSelect,
+    },
+}
+
+/// How a [`Lnb::DualBand`] is switched between its C and Ku band feeds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DualBandSelect {
+    /// The 22 kHz tone: on for the Ku band, off for the C band. The voltage
+    /// still selects the polarization.
+    Tone,
+    /// The voltage: 18V for the Ku band, 13V for the C band, in place of the
+    /// polarization voltage. The tone stays off.
+    Voltage,
+}
+
+/// One band of an [`Lnb::Ka`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KaBand {
+    /// Lowest transponder frequency of the band in MHz.
+    pub from_mhz: u32,
+    /// First transponder frequency past the band in MHz.
+    pub to_mhz: u32,
+    /// Local oscillator frequency in MHz.
+    pub lof_mhz: u32,
+    /// Voltage that selects the band.
+    pub voltage: SecVoltage,
+    /// Tone that selects the band.
+    pub tone: SecTone,
 }
 
 impl Lnb {
+    /// Bands of the common four-oscillator Ka LNB, covering 18.2 to
+    /// 22.2 GHz with a 950 to 1950 MHz intermediate frequency.
+    pub const KA_BANDS: [KaBand; 4] = [
+        KaBand {
+            from_mhz: 18_200,
+            to_mhz: 19_200,
+            lof_mhz: 17_250,
+            voltage: SecVoltage::V18,
+            tone: SecTone::On,
+        },
+        KaBand {
+            from_mhz: 19_200,
+            to_mhz: 20_200,
+            lof_mhz: 18_250,
+            voltage: SecVoltage::V13,
+            tone: SecTone::Off,
+        },
+        KaBand {
+            from_mhz: 20_200,
+            to_mhz: 21_200,
+            lof_mhz: 19_250,
+            voltage: SecVoltage::V18,
+            tone: SecTone::Off,
+        },
+        KaBand {
+            from_mhz: 21_200,
+            to_mhz: 22_200,
+            lof_mhz: 20_250,
+            voltage: SecVoltage::V13,
+            tone: SecTone::On,
+        },
+    ];
+
+    /// The common C/Ku dual-band LNB: 5150 MHz for the C band, 10750 MHz
+    /// for the Ku band, switched by the tone.
+    pub const C_KU: Lnb = Lnb::DualBand {
+        c_lof_mhz: 5_150,
+        ku_lof_mhz: 10_750,
+        select: DualBandSelect::Tone,
+    };
+
     /// Picks the LNB a transponder frequency in MHz implies.
     ///
     /// Each broadcast band is served by one kind of LNB, so the frequency
     /// alone identifies the conversion: an L-band frequency is already an
     /// intermediate frequency, the C and S bands invert around a single
-    /// oscillator, and the Ku band uses the universal two-oscillator LNB.
-    /// Returns `None` for a frequency outside every known band, where no
-    /// oscillator can be assumed.
+    /// oscillator, the Ku band uses the universal two-oscillator LNB and
+    /// the Ka band the four-oscillator one. Returns `None` for a frequency
+    /// outside every known band, where no oscillator can be assumed.
+    ///
+    /// A dual-band C/Ku LNB serves two bands, so no frequency implies one;
+    /// see [`Lnb::auto_dual_band`].
     pub fn auto(transponder_mhz: u32) -> Option<Lnb> {
         match transponder_mhz {
             // L band: an intermediate frequency already
@@ -83,6 +195,19 @@ impl Lnb {
                 lof_high_mhz: 10_600,
                 switch_mhz: 11_700,
             }),
+            // Ka band
+            18_200 .. 22_200 => Some(Lnb::Ka {
+                bands: Lnb::KA_BANDS,
+            }),
+            _ => None,
+        }
+    }
+
+    /// [`Lnb::C_KU`] for a transponder it converts into the intermediate
+    /// frequency range, `None` otherwise: the C band and the upper Ku band.
+    pub fn auto_dual_band(transponder_mhz: u32) -> Option<Lnb> {
+        match transponder_mhz {
+            3_400 ..= 4_200 | 11_700 ..= 12_750 => Some(Lnb::C_KU),
             _ => None,
         }
     }
@@ -90,30 +215,174 @@ impl Lnb {
     /// Intermediate frequency in MHz for `transponder_mhz`, and the band
     /// tone that selects it.
     pub fn intermediate(&self, transponder_mhz: u32) -> Result<(u32, SecTone)> {
+        CalibratedLnb::from(*self).intermediate(transponder_mhz)
+    }
+
+    /// Intermediate frequency in kHz for `transponder_khz`, and the band
+    /// tone that selects it.
+    pub fn intermediate_khz(&self, transponder_khz: u32) -> Result<(u32, SecTone)> {
+        self.convert(transponder_khz, 0)
+    }
+
+    /// Voltage the LNB needs to select the band of `transponder_mhz`, in
+    /// place of the polarization voltage of the SEC configuration. Only a
+    /// [`Lnb::Ka`] and a [`Lnb::DualBand`] switched by
+    /// [`DualBandSelect::Voltage`] select bands by voltage.
+    pub fn band_voltage(&self, transponder_mhz: u32) -> Option<SecVoltage> {
+        match *self {
+            Lnb::Ka { bands } => ka_band(&bands, transponder_mhz.saturating_mul(1000))
+                .ok()
+                .map(|band| band.voltage),
+            Lnb::DualBand {
+                c_lof_mhz,
+                select: DualBandSelect::Voltage,
+                ..
+            } => Some(if transponder_mhz < c_lof_mhz {
+                SecVoltage::V13
+            } else {
+                SecVoltage::V18
+            }),
+            _ => None,
+        }
+    }
+
+    /// The same LNB with a measured oscillator error of `offset_khz`.
+    pub fn with_lo_offset(self, offset_khz: i32) -> CalibratedLnb {
+        CalibratedLnb {
+            lnb: self,
+            lo_offset_khz: offset_khz,
+        }
+    }
+
+    /// Whether the conversion of `transponder_khz` mirrors the spectrum, a
+    /// higher transponder frequency coming out lower, as in the C band.
+    pub(crate) fn inverts(&self, transponder_khz: u32) -> bool {
         match *self {
-            Lnb::Passthrough => Ok((transponder_mhz, SecTone::Off)),
-            Lnb::Single { lof_mhz } => Ok((lnb_offset(transponder_mhz, lof_mhz)?, SecTone::Off)),
+            Lnb::CBand { .. } => true,
+            Lnb::DualBand { c_lof_mhz, .. } => transponder_khz < c_lof_mhz.saturating_mul(1000),
+            _ => false,
+        }
+    }
+
+    /// Intermediate frequency in kHz for `transponder_khz` with the
+    /// oscillators `lo_offset_khz` off their nominal frequencies, and the
+    /// band tone.
+    fn convert(&self, transponder_khz: u32, lo_offset_khz: i32) -> Result<(u32, SecTone)> {
+        let lo = |lof_mhz: u32| -> Result<u32> {
+            (lof_mhz as i64 * 1000 + lo_offset_khz as i64)
+                .try_into()
+                .map_err(|_| {
+                    Error::InvalidData(format!(
+                        "LNB oscillator {} MHz with {} kHz offset is out of range",
+                        lof_mhz, lo_offset_khz
+                    ))
+                })
+        };
+
+        match *self {
+            Lnb::Passthrough => Ok((transponder_khz, SecTone::Off)),
+            Lnb::Single { lof_mhz } | Lnb::Wideband { lof_mhz } => {
+                Ok((lnb_offset(transponder_khz, lo(lof_mhz)?)?, SecTone::Off))
+            }
             Lnb::Universal {
                 lof_low_mhz,
                 lof_high_mhz,
                 switch_mhz,
             } => {
-                if transponder_mhz >= switch_mhz {
-                    Ok((lnb_offset(transponder_mhz, lof_high_mhz)?, SecTone::On))
+                if transponder_khz >= switch_mhz.saturating_mul(1000) {
+                    Ok((lnb_offset(transponder_khz, lo(lof_high_mhz)?)?, SecTone::On))
                 } else {
-                    Ok((lnb_offset(transponder_mhz, lof_low_mhz)?, SecTone::Off))
+                    Ok((lnb_offset(transponder_khz, lo(lof_low_mhz)?)?, SecTone::Off))
+                }
+            }
+            Lnb::CBand { lof_mhz } => {
+                Ok((lnb_offset(lo(lof_mhz)?, transponder_khz)?, SecTone::Off))
+            }
+            Lnb::Ka { bands } => {
+                let band = ka_band(&bands, transponder_khz)?;
+                Ok((lnb_offset(transponder_khz, lo(band.lof_mhz)?)?, band.tone))
+            }
+            Lnb::DualBand {
+                c_lof_mhz,
+                ku_lof_mhz,
+                select,
+            } => {
+                let tone = |ku: bool| match select {
+                    DualBandSelect::Tone if ku => SecTone::On,
+                    _ => SecTone::Off,
+                };
+                if self.inverts(transponder_khz) {
+                    Ok((lnb_offset(lo(c_lof_mhz)?, transponder_khz)?, tone(false)))
+                } else {
+                    Ok((lnb_offset(transponder_khz, lo(ku_lof_mhz)?)?, tone(true)))
                 }
             }
-            Lnb::CBand { lof_mhz } => Ok((lnb_offset(lof_mhz, transponder_mhz)?, SecTone::Off)),
         }
     }
 }
 
-fn lnb_offset(from_mhz: u32, subtract_mhz: u32) -> Result<u32> {
-    from_mhz.checked_sub(subtract_mhz).ok_or_else(|| {
+/// An [`Lnb`] with the measured error of its oscillators: the real
+/// oscillators run at the nominal frequencies plus `lo_offset_khz`. Cheap
+/// LNBs are often off by a few hundred kHz, enough to miss a narrow
+/// carrier.
+///
+/// Everything that takes an LNB takes either; a plain [`Lnb`] is a
+/// calibrated one with no error.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CalibratedLnb {
+    /// The LNB, with its nominal oscillators.
+    pub lnb: Lnb,
+    /// Oscillator error in kHz, the same for every oscillator of the LNB.
+    pub lo_offset_khz: i32,
+}
+
+impl From<Lnb> for CalibratedLnb {
+    fn from(lnb: Lnb) -> Self {
+        lnb.with_lo_offset(0)
+    }
+}
+
+impl CalibratedLnb {
+    /// Intermediate frequency in MHz for `transponder_mhz`, and the band
+    /// tone that selects it. The oscillator offset is rounded to the
+    /// nearest MHz; [`CalibratedLnb::intermediate_khz`] keeps it exact.
+    pub fn intermediate(&self, transponder_mhz: u32) -> Result<(u32, SecTone)> {
+        let transponder_khz = transponder_mhz.checked_mul(1000).ok_or_else(|| {
+            Error::InvalidData(format!(
+                "transponder frequency {} MHz is out of range",
+                transponder_mhz
+            ))
+        })?;
+        let (frequency_khz, band) = self.intermediate_khz(transponder_khz)?;
+
+        Ok(((frequency_khz + 500) / 1000, band))
+    }
+
+    /// Intermediate frequency in kHz for `transponder_khz`, with the
+    /// oscillator offset applied, and the band tone that selects it.
+    pub fn intermediate_khz(&self, transponder_khz: u32) -> Result<(u32, SecTone)> {
+        self.lnb.convert(transponder_khz, self.lo_offset_khz)
+    }
+
+    /// See [`Lnb::band_voltage`].
+    pub fn band_voltage(&self, transponder_mhz: u32) -> Option<SecVoltage> {
+        self.lnb.band_voltage(transponder_mhz)
+    }
+}
+
+fn ka_band(bands: &[KaBand], transponder_khz: u32) -> Result<KaBand> {
+    bands
+        .iter()
+        .find(|band| (band.from_mhz * 1000 .. band.to_mhz * 1000).contains(&transponder_khz))
+        .copied()
+        .ok_or_else(|| Error::InvalidData(format!("no Ka LNB band covers {} kHz", transponder_khz)))
+}
+
+fn lnb_offset(from_khz: u32, subtract_khz: u32) -> Result<u32> {
+    from_khz.checked_sub(subtract_khz).ok_or_else(|| {
         Error::InvalidData(format!(
-            "LNB conversion needs {} MHz to be at least {} MHz",
-            from_mhz, subtract_mhz
+            "LNB conversion needs {} kHz to be at least {} kHz",
+            from_khz, subtract_khz
         ))
     })
 }
@@ -136,6 +405,35 @@ pub struct ToneburstConfig {
     pub voltage: SecVoltage,
 }
 
+/// One switch in a [`CascadeConfig`] chain.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CascadeLevel {
+    /// DiSEqC 1.0 committed switch, ports `1..=4`. Its command also carries
+    /// the polarization and the band.
+    Committed { port: u8 },
+    /// DiSEqC 1.1 uncommitted switch, ports `1..=16`.
+    Uncommitted { port: u8 },
+    /// Mini-DiSEqC toneburst switch. The burst follows every DiSEqC message,
+    /// so it has to be the last level.
+    Toneburst(SecMiniCmd),
+}
+
+/// Inputs for a chain of switches between the LNB and the frontend.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CascadeConfig {
+    /// Switches in the order the commands are sent, the one nearest the
+    /// frontend first: a switch only passes DiSEqC on to the port it has
+    /// selected, so the ones behind it are reached after it has switched.
+    pub levels: Vec<CascadeLevel>,
+    /// How many times the DiSEqC messages are sent again, with the repeat
+    /// framing byte. A switch behind a freshly selected port has only just
+    /// been powered and can miss the first round; one repeat is usual for a
+    /// DiSEqC 1.1 cascade.
+    pub repeat: u8,
+    /// Polarization selection.
+    pub voltage: SecVoltage,
+}
+
 /// Common inputs for Unicable channel-change commands.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct UnicableConfig {
@@ -151,6 +449,156 @@ pub struct UnicableConfig {
     pub pin: Option<u8>,
 }
 
+/// Inputs for a DiSEqC 1.2 positioner in front of the rest of the SEC
+/// equipment.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PositionerConfig {
+    /// Stored position to move to. 0 is the reference position.
+    pub position: u8,
+    /// How long the move is expected to take. The sequence waits this long
+    /// after the goto command, so it should cover the longest move the
+    /// installation makes - DiSEqC 1.2 reports no arrival.
+    pub move_time: Duration,
+    /// What sits behind the positioner; its sequence runs once the dish has
+    /// arrived. Usually [`SecConfig::Lnb`].
+    pub next: Box<SecConfig>,
+}
+
+/// Inputs for a USALS (DiSEqC 1.3) positioner in front of the rest of the
+/// SEC equipment.
+///
+/// Angles are in 1/10 degree, north and east positive, as VDR keeps them:
+/// 192 is 19.2E, -300 is 30.0W.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UsalsConfig {
+    /// Latitude of the dish site.
+    pub site_latitude: i16,
+    /// Longitude of the dish site.
+    pub site_longitude: i16,
+    /// Orbital position of the satellite to point at.
+    pub satellite: i16,
+    /// Orbital position the dish points at before the move, `None` when it
+    /// is not known. The move time is estimated from the angular distance
+    /// between the two; from an unknown position it is estimated as a move
+    /// from the far end of the usable arc.
+    pub from_satellite: Option<i16>,
+    /// Motor speed in 1/10 degree per second. Common motors turn at 1.5 to
+    /// 2.5 degrees per second at 13V, faster at 18V.
+    pub speed: u16,
+    /// What sits behind the positioner; its sequence runs once the dish has
+    /// arrived. Usually [`SecConfig::Lnb`].
+    pub next: Box<SecConfig>,
+}
+
+/// Motor angle at the ends of the arc a USALS motor covers, in degrees.
+const USALS_MAX_ANGLE: f64 = 75.0;
+
+impl UsalsConfig {
+    /// Motor angle of [`UsalsConfig::satellite`] in degrees, east positive.
+    pub fn angle(&self) -> f64 {
+        self.angle_of(self.satellite)
+    }
+
+    /// Estimated time the move takes.
+    pub fn move_time(&self) -> Result<Duration> {
+        if self.speed == 0 {
+            return Err(Error::InvalidData(
+                "USALS motor speed must not be zero".to_owned(),
+            ));
+        }
+
+        let to = self.angle();
+        let distance = match self.from_satellite {
+            Some(from) => (to - self.angle_of(from)).abs(),
+            None => (to + USALS_MAX_ANGLE)
+                .abs()
+                .max((to - USALS_MAX_ANGLE).abs()),
+        };
+
+        Ok(Duration::from_secs_f64(
+            distance * 10.0 / f64::from(self.speed),
+        ))
+    }
+
+    fn angle_of(&self, satellite: i16) -> f64 {
+        usals_angle(
+            f64::from(self.site_latitude) / 10.0,
+            f64::from(self.site_longitude) / 10.0,
+            f64::from(satellite) / 10.0,
+        )
+    }
+}
+
+/// Polarization of a satellite transponder.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Polarization {
+    Horizontal,
+    Vertical,
+    /// Left circular.
+    Left,
+    /// Right circular.
+    Right,
+}
+
+impl Polarization {
+    /// Parses the one-letter code VDR and most channel lists use: `H`, `V`,
+    /// `L` or `R`, in either case.
+    pub fn from_code(code: char) -> Option<Polarization> {
+        match code.to_ascii_uppercase() {
+            'H' => Some(Polarization::Horizontal),
+            'V' => Some(Polarization::Vertical),
+            'L' => Some(Polarization::Left),
+            'R' => Some(Polarization::Right),
+            _ => None,
+        }
+    }
+
+    /// One-letter code, upper case.
+    pub fn code(self) -> char {
+        match self {
+            Polarization::Horizontal => 'H',
+            Polarization::Vertical => 'V',
+            Polarization::Left => 'L',
+            Polarization::Right => 'R',
+        }
+    }
+
+    /// LNB voltage that selects the polarization: 18V for horizontal and
+    /// left circular, 13V for vertical and right circular.
+    pub fn voltage(self) -> SecVoltage {
+        match self {
+            Polarization::Horizontal | Polarization::Left => SecVoltage::V18,
+            Polarization::Vertical | Polarization::Right => SecVoltage::V13,
+        }
+    }
+}
+
+/// A DSL sequence with placeholders, expanded for each transponder.
+///
+/// Placeholders:
+///
+/// - `{band}` - the band tone command, `T` in the high band, `t` otherwise
+/// - `{pol}` - the polarization voltage command, `V` for 18V, `v` for 13V
+/// - `{port}` - inside a master command, the DiSEqC 1.0 committed data byte for
+///   [`SecTemplate::port`], the polarization and the band
+/// - `{ub}` - inside a master command, the two Unicable I channel change data bytes for
+///   [`SecTemplate::user_band`]; port 1 is satellite position A, port 2 position B. `XX`, as VDR
+///   writes it, stands for one of the two bytes, in order, inside a master command only.
+///
+/// A sequence with a user band placeholder tunes the frontend to the user
+/// band frequency instead of the intermediate frequency.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SecTemplate {
+    /// DSL sequence.
+    pub sequence: String,
+    /// Polarization selection.
+    pub voltage: SecVoltage,
+    /// Switch port, `1..=4`.
+    pub port: u8,
+    /// User band the Unicable placeholders encode.
+    pub user_band: Option<UserBand>,
+}
+
 /// Wait times used while building a SEC sequence.
 ///
 /// The defaults reproduce the sequences this module generates when nothing
@@ -204,10 +652,22 @@ pub enum SecConfig {
     Switch1_1(DiseqcSwitchConfig),
     /// Mini-DiSEqC toneburst.
     Toneburst(ToneburstConfig),
+    /// A chain of committed, uncommitted and toneburst switches.
+    Cascade(CascadeConfig),
     /// Unicable I / EN 50494.
     Unicable1(UnicableConfig),
     /// Unicable II / EN 50607.
     Unicable2(UnicableConfig),
+    /// DiSEqC 1.2 positioner: moves the dish to a stored position, then
+    /// runs the configuration behind it.
+    Positioner(PositionerConfig),
+    /// USALS (DiSEqC 1.3) positioner: turns the dish to the angle computed
+    /// from the site and the satellite, then runs the configuration behind
+    /// it.
+    Usals(UsalsConfig),
+    /// DSL sequence with placeholders for the band, the polarization, the
+    /// switch port and the Unicable user band code.
+    Template(SecTemplate),
     /// Custom SEC/DiSEqC sequence in the documented DSL format. It carries
     /// its own tone commands, so the band the [`Lnb`] derives is not applied.
     Dsl(String),
@@ -222,6 +682,166 @@ pub struct SecSetup {
     pub sec_sequence: Vec<SecCommand>,
 }
 
+impl SecSetup {
+    /// The sequence written in the SEC DSL, see [`format_sec_sequence`].
+    pub fn to_dsl(&self) -> Result<String> {
+        format_sec_sequence(&self.sec_sequence)
+    }
+
+    /// Dry run of the sequence, see [`SecTimeline`].
+    pub fn timeline(&self) -> SecTimeline {
+        SecTimeline::new(&self.sec_sequence)
+    }
+}
+
+/// Writes a sequence in the SEC DSL, the inverse of the parser behind
+/// [`SecConfig::Dsl`]: the string parses back to the same commands.
+///
+/// Commands are separated by one space and master commands are written as
+/// upper-case hex bytes, e.g. `t V W15 [E0 10 38 F3] W15 T`. The DSL has no
+/// token for [`SecVoltage::Off`] and counts waits in whole milliseconds, so
+/// a sequence with either is rejected rather than written differently.
+pub fn format_sec_sequence(sequence: &[SecCommand]) -> Result<String> {
+    let mut tokens = Vec::with_capacity(sequence.len());
+
+    for command in sequence {
+        let token = match command {
+            SecCommand::SetTone(SecTone::Off) => "t".to_owned(),
+            SecCommand::SetTone(SecTone::On) => "T".to_owned(),
+            SecCommand::SetVoltage(SecVoltage::V13) => "v".to_owned(),
+            SecCommand::SetVoltage(SecVoltage::V18) => "V".to_owned(),
+            SecCommand::SetVoltage(SecVoltage::Off) => {
+                return Err(Error::InvalidData(
+                    "SEC DSL has no token for voltage off".to_owned(),
+                ));
+            }
+            SecCommand::SendBurst(SecMiniCmd::A) => "A".to_owned(),
+            SecCommand::SendBurst(SecMiniCmd::B) => "B".to_owned(),
+            SecCommand::Wait(duration) => {
+                if duration.subsec_nanos() % 1_000_000 != 0 {
+                    return Err(Error::InvalidData(format!(
+                        "SEC DSL waits are whole milliseconds, got {:?}",
+                        duration
+                    )));
+                }
+                format!("W{}", duration.as_millis())
+            }
+            SecCommand::SendMasterCommand(msg) => {
+                if !(3 ..= 6).contains(&msg.len()) {
+                    return Err(Error::InvalidData(format!(
+                        "DiSEqC master command length must be 3..=6 bytes, got {}",
+                        msg.len()
+                    )));
+                }
+                format!("[{}]", hex(msg))
+            }
+        };
+        tokens.push(token);
+    }
+
+    Ok(tokens.join(" "))
+}
+
+/// Bytes as upper-case hex pairs separated by spaces.
+fn hex(msg: &[u8]) -> String {
+    msg.iter()
+        .map(|b| format!("{:02X}", b))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// One step of a [`SecTimeline`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SecTimelineEntry {
+    /// Time from the start of the sequence, the sum of the waits before
+    /// the step.
+    pub offset: Duration,
+    /// Voltage, tone, burst or master command; [`SecTimeline::new`] folds
+    /// the waits into the offsets.
+    pub command: SecCommand,
+}
+
+/// Dry run of a SEC sequence: what goes out on the cable and when.
+///
+/// Only the waits of the sequence advance the clock. The time the frontend
+/// spends sending a burst or a master command on the bus - about 13.5 ms
+/// per byte - is not added, since it depends on the driver; an analyzer
+/// capture therefore shows each later step somewhat after its offset.
+///
+/// `Display` renders one line per step, with the offset in milliseconds
+/// and the master commands decoded:
+///
+/// ```text
+///      0.0 ms  tone off
+///      0.0 ms  18V
+///     15.0 ms  DiSEqC [E0 10 38 F3] command, any LNB/switcher/SMATV: write N0 F3 (port 1, 18V, high band)
+///     30.0 ms  tone on
+///     30.0 ms  end
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SecTimeline {
+    /// Steps in the order they are sent.
+    pub entries: Vec<SecTimelineEntry>,
+    /// Sum of every wait, trailing ones included.
+    pub duration: Duration,
+}
+
+impl SecTimeline {
+    /// Timeline of `sequence`.
+    pub fn new(sequence: &[SecCommand]) -> SecTimeline {
+        let mut entries = Vec::new();
+        let mut offset = Duration::ZERO;
+
+        for command in sequence {
+            match command {
+                SecCommand::Wait(duration) => offset += *duration,
+                command => entries.push(SecTimelineEntry {
+                    offset,
+                    command: command.clone(),
+                }),
+            }
+        }
+
+        SecTimeline {
+            entries,
+            duration: offset,
+        }
+    }
+}
+
+impl fmt::Display for SecTimeline {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let ms = |offset: Duration| offset.as_secs_f64() * 1000.0;
+
+        for entry in &self.entries {
+            write!(f, "{:>8.1} ms  ", ms(entry.offset))?;
+            match &entry.command {
+                SecCommand::SetTone(SecTone::On) => writeln!(f, "tone on")?,
+                SecCommand::SetTone(SecTone::Off) => writeln!(f, "tone off")?,
+                SecCommand::SetVoltage(SecVoltage::V13) => writeln!(f, "13V")?,
+                SecCommand::SetVoltage(SecVoltage::V18) => writeln!(f, "18V")?,
+                SecCommand::SetVoltage(SecVoltage::Off) => writeln!(f, "voltage off")?,
+                SecCommand::SendBurst(SecMiniCmd::A) => writeln!(f, "burst A")?,
+                SecCommand::SendBurst(SecMiniCmd::B) => writeln!(f, "burst B")?,
+                SecCommand::SendMasterCommand(msg) => {
+                    write!(f, "DiSEqC [{}]", hex(msg))?;
+                    // EN 50607 messages carry no framing byte to decode
+                    if let Some(framing) = msg.first()
+                        && (0xE0 ..= 0xE7).contains(framing)
+                        && let Ok(message) = DiseqcMessage::decode(msg)
+                    {
+                        write!(f, " {}", message)?;
+                    }
+                    writeln!(f)?;
+                }
+                SecCommand::Wait(duration) => writeln!(f, "wait {:.1} ms", ms(*duration))?,
+            }
+        }
+
+        writeln!(f, "{:>8.1} ms  end", ms(self.duration))
+    }
+}
+
 /// Parses an Astra-compatible SEC/DiSEqC DSL sequence.
 ///
 /// Supported commands:
@@ -234,7 +854,7 @@ pub struct SecSetup {
 /// - `B` - mini burst B
 /// - `W<number>` - wait in milliseconds
 /// - `[hex bytes]` - DiSEqC master command, 3 to 6 bytes
-fn parse_sec_sequence(input: &str) -> Result<Vec<SecCommand>> {
+pub(crate) fn parse_sec_sequence(input: &str) -> Result<Vec<SecCommand>> {
     let mut parser = Parser::new(input);
     parser.parse()
 }
@@ -242,36 +862,153 @@ fn parse_sec_sequence(input: &str) -> Result<Vec<SecCommand>> {
 /// Builds the SEC sequence that puts the requested transponder in front of
 /// the frontend, and reports the frequency to tune to afterwards.
 ///
-/// `transponder_mhz` is the broadcast frequency; `lnb` converts it to the
-/// intermediate frequency and derives the band tone. `timings` are the wait
+/// `transponder_mhz` is the broadcast frequency; `lnb`, an [`Lnb`] or a
+/// [`CalibratedLnb`], converts it to the intermediate frequency and derives
+/// the band tone. `timings` are the wait
 /// times to place between the commands, and are ignored by
 /// [`SecConfig::Dsl`], which carries its own.
 pub fn sec_sequence(
     transponder_mhz: u32,
-    lnb: Lnb,
+    lnb: impl Into<CalibratedLnb>,
     config: SecConfig,
     timings: SecTimings,
 ) -> Result<SecSetup> {
+    let lnb = lnb.into();
     let (frequency_mhz, band) = lnb.intermediate(transponder_mhz)?;
-
-    match config {
-        SecConfig::Lnb { voltage } => Ok(lnb_sequence(frequency_mhz, voltage, band, timings)),
-        SecConfig::Shared => Ok(sec_setup(
+    let config = match lnb.band_voltage(transponder_mhz) {
+        Some(voltage) => with_voltage(config, voltage),
+        None => config,
+    };
+    // the frontend tunes the intermediate frequency itself, not a Unicable
+    // user band, so it gets the oscillator offset to the kHz
+    let exact = match &config {
+        SecConfig::Unicable1(_)
+        | SecConfig::Unicable2(_)
+        | SecConfig::Positioner(_)
+        | SecConfig::Usals(_) => false,
+        SecConfig::Template(template) => template.user_band.is_none(),
+        _ => true,
+    };
+
+    let mut setup = match config {
+        SecConfig::Lnb { voltage } => lnb_sequence(frequency_mhz, voltage, band, timings),
+        SecConfig::Shared => sec_setup(
             frequency_mhz,
             vec![
                 SecCommand::SetTone(SecTone::Off),
                 SecCommand::SetVoltage(SecVoltage::Off),
             ],
-        )),
-        SecConfig::Switch1_0(config) => diseqc_1_0_sequence(frequency_mhz, config, band, timings),
-        SecConfig::Switch1_1(config) => diseqc_1_1_sequence(frequency_mhz, config, band, timings),
-        SecConfig::Toneburst(config) => {
-            Ok(toneburst_sequence(frequency_mhz, config, band, timings))
+        ),
+        SecConfig::Switch1_0(config) => diseqc_1_0_sequence(frequency_mhz, config, band, timings)?,
+        SecConfig::Switch1_1(config) => diseqc_1_1_sequence(frequency_mhz, config, band, timings)?,
+        SecConfig::Toneburst(config) => toneburst_sequence(frequency_mhz, config, band, timings),
+        SecConfig::Cascade(config) => cascade_sequence(frequency_mhz, config, band, timings)?,
+        SecConfig::Unicable1(config) => {
+            return unicable_1_sequence(frequency_mhz, config, band, timings);
+        }
+        SecConfig::Unicable2(config) => {
+            return unicable_2_sequence(frequency_mhz, config, band, timings);
+        }
+        SecConfig::Positioner(config) => {
+            let goto = PositionerCommand::Goto(config.position).to_sec_command()?;
+            return positioner_sequence(
+                transponder_mhz,
+                lnb,
+                *config.next,
+                goto,
+                config.move_time,
+                timings,
+            );
+        }
+        SecConfig::Usals(config) => {
+            let goto = PositionerCommand::goto_angle(config.angle()).to_sec_command()?;
+            let move_time = config.move_time()?;
+            return positioner_sequence(
+                transponder_mhz,
+                lnb,
+                *config.next,
+                goto,
+                move_time,
+                timings,
+            );
+        }
+        SecConfig::Template(template) => template_sequence(frequency_mhz, &template, band)?,
+        SecConfig::Dsl(input) => sec_setup(frequency_mhz, parse_sec_sequence(&input)?),
+    };
+
+    if exact {
+        setup.frontend_frequency_khz = lnb.intermediate_khz(transponder_mhz * 1000)?.0;
+    }
+
+    Ok(setup)
+}
+
+/// The configuration with its polarization voltage replaced, for an LNB
+/// that selects bands by voltage. The configurations behind a positioner
+/// are left alone: they get the same treatment when their turn comes.
+fn with_voltage(config: SecConfig, voltage: SecVoltage) -> SecConfig {
+    match config {
+        SecConfig::Lnb { .. } => SecConfig::Lnb { voltage },
+        SecConfig::Switch1_0(config) => {
+            SecConfig::Switch1_0(DiseqcSwitchConfig { voltage, ..config })
         }
-        SecConfig::Unicable1(config) => unicable_1_sequence(frequency_mhz, config, band, timings),
-        SecConfig::Unicable2(config) => unicable_2_sequence(frequency_mhz, config, band, timings),
-        SecConfig::Dsl(input) => Ok(sec_setup(frequency_mhz, parse_sec_sequence(&input)?)),
+        SecConfig::Switch1_1(config) => {
+            SecConfig::Switch1_1(DiseqcSwitchConfig { voltage, ..config })
+        }
+        SecConfig::Toneburst(config) => SecConfig::Toneburst(ToneburstConfig { voltage, ..config }),
+        SecConfig::Cascade(config) => SecConfig::Cascade(CascadeConfig { voltage, ..config }),
+        SecConfig::Unicable1(config) => SecConfig::Unicable1(UnicableConfig { voltage, ..config }),
+        SecConfig::Unicable2(config) => SecConfig::Unicable2(UnicableConfig { voltage, ..config }),
+        SecConfig::Template(template) => SecConfig::Template(SecTemplate {
+            voltage,
+            ..template
+        }),
+        config => config,
+    }
+}
+
+/// Prepends a positioner move to the sequence of the configuration behind
+/// the positioner.
+///
+/// The motor runs on the LNB supply, so the move is sent with the voltage
+/// the following sequence sets first; a configuration that releases the
+/// voltage leaves nothing to power the motor and is rejected.
+fn positioner_sequence(
+    transponder_mhz: u32,
+    lnb: CalibratedLnb,
+    next: SecConfig,
+    command: SecCommand,
+    move_time: Duration,
+    timings: SecTimings,
+) -> Result<SecSetup> {
+    let mut setup = sec_sequence(transponder_mhz, lnb, next, timings)?;
+
+    let voltage = setup
+        .sec_sequence
+        .iter()
+        .find_map(|command| match command {
+            SecCommand::SetVoltage(voltage) => Some(*voltage),
+            _ => None,
+        })
+        .unwrap_or(SecVoltage::V13);
+    if voltage == SecVoltage::Off {
+        return Err(Error::InvalidData(
+            "positioner needs the LNB supply, but the configuration behind it releases it"
+                .to_owned(),
+        ));
     }
+
+    let mut sequence = vec![
+        SecCommand::SetTone(SecTone::Off),
+        SecCommand::SetVoltage(voltage),
+        SecCommand::Wait(timings.switch_settle),
+        command,
+        SecCommand::Wait(move_time),
+    ];
+    sequence.append(&mut setup.sec_sequence);
+    setup.sec_sequence = sequence;
+
+    Ok(setup)
 }
 
 /// Builds the voltage/tone sequence of a plain LNB.
@@ -300,22 +1037,7 @@ fn diseqc_1_0_sequence(
     band: SecTone,
     timings: SecTimings,
 ) -> Result<SecSetup> {
-    if !(1 ..= 4).contains(&config.port) {
-        return Err(Error::InvalidData(format!(
-            "DiSEqC 1.0 port must be in range 1..=4, got {}",
-            config.port
-        )));
-    }
-
-    let port = config.port - 1;
-    let data = 0xF0
-        | (port << 2)
-        | (if config.voltage == SecVoltage::V18 {
-            0x02
-        } else {
-            0x00
-        })
-        | (if band == SecTone::On { 0x01 } else { 0x00 });
+    let data = committed_data(config.port, config.voltage, band)?;
 
     Ok(sec_setup(
         frequency_mhz,
@@ -330,20 +1052,106 @@ fn diseqc_1_1_sequence(
     band: SecTone,
     timings: SecTimings,
 ) -> Result<SecSetup> {
-    if !(1 ..= 16).contains(&config.port) {
+    let data = uncommitted_data(config.port)?;
+
+    Ok(sec_setup(
+        frequency_mhz,
+        controlled_master_sequence(config.voltage, band, [0xE0, 0x10, 0x39, data], timings),
+    ))
+}
+
+/// Data byte of a DiSEqC 1.0 committed switch command.
+fn committed_data(port: u8, voltage: SecVoltage, band: SecTone) -> Result<u8> {
+    if !(1 ..= 4).contains(&port) {
+        return Err(Error::InvalidData(format!(
+            "DiSEqC 1.0 port must be in range 1..=4, got {}",
+            port
+        )));
+    }
+
+    Ok(0xF0 | ((port - 1) << 2) | sec_voltage_bit(voltage, 0x02) | sec_tone_bit(band, 0x01))
+}
+
+/// Data byte of a DiSEqC 1.1 uncommitted switch command.
+fn uncommitted_data(port: u8) -> Result<u8> {
+    if !(1 ..= 16).contains(&port) {
         return Err(Error::InvalidData(format!(
             "DiSEqC 1.1 port must be in range 1..=16, got {}",
-            config.port
+            port
         )));
     }
 
-    let port = config.port - 1;
-    let data = 0xF0 | port;
+    Ok(0xF0 | (port - 1))
+}
 
-    Ok(sec_setup(
-        frequency_mhz,
-        controlled_master_sequence(config.voltage, band, [0xE0, 0x10, 0x39, data], timings),
-    ))
+/// Builds the sequence of a switch chain.
+///
+/// The DiSEqC messages go out in level order, `repeat` more times with the
+/// repeat framing byte, each followed by the message gap; the switch behind
+/// a freshly selected port gets the settle time to power up before a repeat.
+/// The toneburst and then the band tone come last.
+fn cascade_sequence(
+    frequency_mhz: u32,
+    config: CascadeConfig,
+    band: SecTone,
+    timings: SecTimings,
+) -> Result<SecSetup> {
+    if config.levels.is_empty() {
+        return Err(Error::InvalidData(
+            "switch cascade needs at least one level".to_owned(),
+        ));
+    }
+
+    let mut messages = Vec::new();
+    let mut burst = None;
+    for level in &config.levels {
+        if burst.is_some() {
+            return Err(Error::InvalidData(
+                "toneburst must be the last level of a switch cascade".to_owned(),
+            ));
+        }
+
+        match *level {
+            CascadeLevel::Committed { port } => {
+                messages.push([0x10, 0x38, committed_data(port, config.voltage, band)?]);
+            }
+            CascadeLevel::Uncommitted { port } => {
+                messages.push([0x10, 0x39, uncommitted_data(port)?]);
+            }
+            CascadeLevel::Toneburst(cmd) => burst = Some(cmd),
+        }
+    }
+
+    let mut sequence = vec![
+        SecCommand::SetTone(SecTone::Off),
+        SecCommand::SetVoltage(config.voltage),
+        SecCommand::Wait(timings.switch_settle),
+    ];
+
+    if !messages.is_empty() {
+        for round in 0 ..= config.repeat {
+            if round > 0 {
+                sequence.push(SecCommand::Wait(timings.switch_settle));
+            }
+
+            let framing = if round == 0 { 0xE0 } else { 0xE1 };
+            for &[address, command, data] in &messages {
+                sequence.push(SecCommand::SendMasterCommand(vec![
+                    framing, address, command, data,
+                ]));
+                sequence.push(SecCommand::Wait(timings.message_gap));
+            }
+        }
+    }
+
+    if let Some(cmd) = burst {
+        sequence.push(SecCommand::SendBurst(cmd));
+        sequence.push(SecCommand::Wait(timings.message_gap));
+    }
+
+    sequence.push(SecCommand::SetTone(band));
+
+    Ok(sec_setup(frequency_mhz, sequence))
 }
 
 /// Builds a toneburst A/B sequence.
@@ -373,6 +1181,17 @@ fn unicable_1_sequence(
     band: SecTone,
     timings: SecTimings,
 ) -> Result<SecSetup> {
+    let [b1, b2] = unicable_1_code(frequency_mhz, &config, band)?;
+
+    Ok(unicable_tune(
+        config.user_band_frequency_mhz,
+        vec![0xE0, 0x10, 0x5A, b1, b2],
+        timings,
+    ))
+}
+
+/// Data bytes of a Unicable I / EN 50494 channel change.
+fn unicable_1_code(frequency_mhz: u32, config: &UnicableConfig, band: SecTone) -> Result<[u8; 2]> {
     if !(1 ..= 8).contains(&config.slot) {
         return Err(Error::InvalidData(format!(
             "Unicable I slot must be in range 1..=8, got {}",
@@ -407,11 +1226,7 @@ fn unicable_1_sequence(
         | ((x >> 8) as u8 & 0x03);
     let b2 = x as u8;
 
-    Ok(unicable_tune(
-        config.user_band_frequency_mhz,
-        vec![0xE0, 0x10, 0x5A, b1, b2],
-        timings,
-    ))
+    Ok([b1, b2])
 }
 
 /// Builds a Unicable II / EN 50607 channel-change sequence.
@@ -460,6 +1275,107 @@ fn unicable_2_sequence(
     Ok(unicable_tune(config.user_band_frequency_mhz, msg, timings))
 }
 
+/// Expands a [`SecTemplate`] and parses the result.
+pub(crate) fn template_sequence(
+    frequency_mhz: u32,
+    template: &SecTemplate,
+    band: SecTone,
+) -> Result<SecSetup> {
+    let (sequence, frontend_mhz) = expand_template(frequency_mhz, template, band)?;
+    Ok(sec_setup(frontend_mhz, parse_sec_sequence(&sequence)?))
+}
+
+/// Replaces the placeholders of a [`SecTemplate`] with their values; also
+/// returns the frequency to tune to, in MHz.
+fn expand_template(
+    frequency_mhz: u32,
+    template: &SecTemplate,
+    band: SecTone,
+) -> Result<(String, u32)> {
+    let input = template.sequence.as_str();
+    let error = |message: String| Error::InvalidData(format!("{} in {:?}", message, input));
+
+    let mut user_band_code = None;
+    let mut code = || -> Result<[u8; 2]> {
+        if let Some(code) = user_band_code {
+            return Ok(code);
+        }
+
+        let user_band = template
+            .user_band
+            .ok_or_else(|| error("user band placeholder without a user band".to_owned()))?;
+        let position = template.port.checked_sub(1).unwrap_or(u8::MAX);
+        let config = UnicableConfig {
+            slot: user_band.slot,
+            user_band_frequency_mhz: user_band.frequency_mhz,
+            position,
+            voltage: template.voltage,
+            pin: None,
+        };
+        let code = unicable_1_code(frequency_mhz, &config, band)?;
+        user_band_code = Some(code);
+        Ok(code)
+    };
+
+    let mut result = String::with_capacity(input.len());
+    let mut code_bytes = 0;
+    let mut in_command = false;
+    let mut rest = input;
+    while let Some(c) = rest.chars().next() {
+        if c == '{' {
+            let end = rest
+                .find('}')
+                .ok_or_else(|| error("unterminated placeholder".to_owned()))?;
+            match &rest[1 .. end] {
+                "band" => result.push(if band == SecTone::On { 'T' } else { 't' }),
+                "pol" => match template.voltage {
+                    SecVoltage::V13 => result.push('v'),
+                    SecVoltage::V18 => result.push('V'),
+                    SecVoltage::Off => {
+                        return Err(error("{pol} needs a polarization voltage".to_owned()));
+                    }
+                },
+                "port" => {
+                    let data = committed_data(template.port, template.voltage, band)?;
+                    result.push_str(&format!("{:02X}", data));
+                }
+                "ub" => {
+                    let [b1, b2] = code()?;
+                    result.push_str(&format!("{:02X} {:02X}", b1, b2));
+                    code_bytes = 2;
+                }
+                name => return Err(error(format!("unknown placeholder {{{}}}", name))),
+            }
+            rest = &rest[end + 1 ..];
+        } else if in_command && rest.starts_with("XX") {
+            let code = code()?;
+            let byte = code
+                .get(code_bytes)
+                .ok_or_else(|| error("more than two XX user band bytes".to_owned()))?;
+            result.push_str(&format!("{:02X}", byte));
+            code_bytes += 1;
+            rest = &rest[2 ..];
+        } else {
+            match c {
+                '[' => in_command = true,
+                ']' => in_command = false,
+                _ => {}
+            }
+            result.push(c);
+            rest = &rest[c.len_utf8() ..];
+        }
+    }
+
+    let frontend_mhz = match user_band_code {
+        Some(_) => template
+            .user_band
+            .map_or(frequency_mhz, |band| band.frequency_mhz),
+        None => frequency_mhz,
+    };
+
+    Ok((result, frontend_mhz))
+}
+
 fn controlled_master_sequence<const N: usize>(
     voltage: SecVoltage,
     tone: SecTone,
@@ -486,19 +1402,32 @@ fn sec_setup(frequency_mhz: u32, sec_sequence: Vec<SecCommand>) -> SecSetup {
 fn unicable_tune(frequency_mhz: u32, msg: Vec<u8>, timings: SecTimings) -> SecSetup {
     SecSetup {
         frontend_frequency_khz: frequency_mhz * 1000,
-        sec_sequence: vec![
-            SecCommand::SetVoltage(SecVoltage::V13),
-            SecCommand::SetTone(SecTone::Off),
-            SecCommand::Wait(timings.unicable_settle),
-            SecCommand::SetVoltage(SecVoltage::V18),
-            SecCommand::Wait(timings.message_gap),
-            SecCommand::SendMasterCommand(msg),
-            SecCommand::Wait(timings.unicable_hold),
-            SecCommand::SetVoltage(SecVoltage::V13),
-        ],
+        sec_sequence: unicable_command(msg, timings),
     }
 }
 
+/// Carries one Unicable message: the bus idles at 13V and is raised to 18V
+/// for the message, then dropped back once the hold time is over.
+pub(crate) fn unicable_command(msg: Vec<u8>, timings: SecTimings) -> Vec<SecCommand> {
+    let mut sequence = unicable_raise(msg, timings);
+    sequence.push(SecCommand::Wait(timings.unicable_hold));
+    sequence.push(SecCommand::SetVoltage(SecVoltage::V13));
+    sequence
+}
+
+/// First half of [`unicable_command`], up to and including the message,
+/// for a caller that waits for a reply before dropping the voltage.
+pub(crate) fn unicable_raise(msg: Vec<u8>, timings: SecTimings) -> Vec<SecCommand> {
+    vec![
+        SecCommand::SetVoltage(SecVoltage::V13),
+        SecCommand::SetTone(SecTone::Off),
+        SecCommand::Wait(timings.unicable_settle),
+        SecCommand::SetVoltage(SecVoltage::V18),
+        SecCommand::Wait(timings.message_gap),
+        SecCommand::SendMasterCommand(msg),
+    ]
+}
+
 fn sec_voltage_bit(voltage: SecVoltage, bit: u8) -> u8 {
     if voltage == SecVoltage::V18 { bit } else { 0 }
 }
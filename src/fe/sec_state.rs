@@ -0,0 +1,423 @@
+//! SEC state tracking for fast zapping.
+//!
+//! Every SEC sequence ends with the cable in a known state: an LNB voltage,
+//! a band tone, and switches that keep the last port they were sent for as
+//! long as they stay powered. When the next transponder needs the same
+//! switch commands, only the voltage and tone that differ have to be sent,
+//! which saves the switch settle and message gap waits - and the move time
+//! of a positioner.
+//!
+//! The same tracking lets an idle frontend switch the LNB supply off and
+//! bring it back, with time to settle, when it is needed again.
+
+use std::time::{
+    Duration,
+    Instant,
+};
+
+use super::{
+    SecCommand,
+    sys::{
+        SecTone,
+        SecVoltage,
+    },
+};
+
+/// What the frontend last put on the cable. `None` is not known.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SecState {
+    /// LNB voltage.
+    pub voltage: Option<SecVoltage>,
+    /// Continuous 22 kHz tone.
+    pub tone: Option<SecTone>,
+    /// Bursts and master commands of the last sequence that sent any, in
+    /// order. Switching the voltage off forgets them: an unpowered switch
+    /// falls back to its default port.
+    pub switch: Option<Vec<SecCommand>>,
+}
+
+/// When an idle frontend switches the LNB supply off, see
+/// [`FeDevice::set_lnb_idle_policy`](super::FeDevice::set_lnb_idle_policy).
+///
+/// The policy runs no timer: the supply goes off when the application calls
+/// [`FeDevice::power_off_if_idle`](super::FeDevice::power_off_if_idle)
+/// after the idle time, or leaves a thread in
+/// [`FeDevice::power_off_when_idle`](super::FeDevice::power_off_when_idle).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LnbIdlePolicy {
+    /// Time the frontend has to stay idle before the supply goes off.
+    pub idle_time: Duration,
+    /// Wait after the supply comes back on, before the SEC sequence that
+    /// needs it. An LNB and the switches behind it take a few hundred
+    /// milliseconds to start up.
+    pub power_on_settle: Duration,
+}
+
+impl Default for LnbIdlePolicy {
+    fn default() -> Self {
+        LnbIdlePolicy {
+            idle_time: Duration::from_secs(600),
+            power_on_settle: Duration::from_millis(500),
+        }
+    }
+}
+
+/// Per-frontend [`SecState`] and the cache built on it.
+#[derive(Debug)]
+pub(crate) struct SecTracker {
+    pub state: SecState,
+    /// Skip the switch commands that are already in place.
+    pub enabled: bool,
+    /// Sequences run in full once this long has passed since the last
+    /// full run, in case a switch lost its state unseen.
+    pub refresh_interval: Option<Duration>,
+    /// Time of the last sequence that sent its switch commands.
+    refreshed: Option<Instant>,
+    pub idle_policy: Option<LnbIdlePolicy>,
+    /// Time the frontend was released, while nothing uses it.
+    idle_since: Option<Instant>,
+    /// The supply was switched off by the idle policy.
+    powered_down: bool,
+}
+
+impl Default for SecTracker {
+    fn default() -> Self {
+        SecTracker {
+            state: SecState::default(),
+            enabled: false,
+            refresh_interval: Some(Duration::from_secs(300)),
+            refreshed: None,
+            idle_policy: None,
+            idle_since: None,
+            powered_down: false,
+        }
+    }
+}
+
+impl SecTracker {
+    /// Part of `sequence` that still has to run to reach its end state.
+    ///
+    /// With the cache off, the state unknown, the switch commands different
+    /// from the cached ones or the refresh interval over, that is the whole
+    /// sequence. Otherwise it is the final voltage and tone of the sequence,
+    /// each only if it differs from the current one and each followed by
+    /// the wait that follows it in the sequence.
+    pub fn reduce(&self, sequence: &[SecCommand], now: Instant) -> Vec<SecCommand> {
+        let switch = switch_commands(sequence);
+
+        let expired = match (self.refresh_interval, self.refreshed) {
+            (Some(interval), Some(refreshed)) => now.duration_since(refreshed) >= interval,
+            _ => false,
+        };
+        let cached = switch.is_empty() || self.state.switch.as_ref() == Some(&switch);
+
+        if !self.enabled
+            || expired
+            || !cached
+            || self.state.voltage.is_none()
+            || self.state.tone.is_none()
+        {
+            return sequence.to_vec();
+        }
+
+        let mut result = Vec::new();
+
+        let last_voltage = sequence
+            .iter()
+            .rposition(|c| matches!(c, SecCommand::SetVoltage(_)));
+        let last_tone = sequence
+            .iter()
+            .rposition(|c| matches!(c, SecCommand::SetTone(_)));
+
+        for position in [last_voltage, last_tone].into_iter().flatten() {
+            let changed = match &sequence[position] {
+                SecCommand::SetVoltage(v) => self.state.voltage != Some(*v),
+                SecCommand::SetTone(t) => self.state.tone != Some(*t),
+                _ => false,
+            };
+            if changed {
+                result.push(sequence[position].clone());
+                if let Some(wait @ SecCommand::Wait(_)) = sequence.get(position + 1) {
+                    result.push(wait.clone());
+                }
+            }
+        }
+
+        result
+    }
+
+    /// Records a voltage put on the cable.
+    pub fn voltage(&mut self, voltage: SecVoltage) {
+        self.state.voltage = Some(voltage);
+        if voltage == SecVoltage::Off {
+            self.state.switch = None;
+        } else {
+            self.powered_down = false;
+        }
+    }
+
+    /// Records a tone put on the cable.
+    pub fn tone(&mut self, tone: SecTone) {
+        self.state.tone = Some(tone);
+    }
+
+    /// Records a burst or master command sent outside a sequence: the
+    /// switches are no longer known to match the cached commands.
+    pub fn switched(&mut self) {
+        self.state.switch = None;
+    }
+
+    /// Records the switch commands of a sequence that ran to the end.
+    pub fn sequence_done(&mut self, sequence: &[SecCommand], now: Instant) {
+        let switch = switch_commands(sequence);
+        if !switch.is_empty() {
+            // a sequence that powers the switches off leaves nothing cached
+            if self.state.voltage != Some(SecVoltage::Off) {
+                self.state.switch = Some(switch);
+            }
+            self.refreshed = Some(now);
+        }
+    }
+
+    /// Forgets everything, for a cable that was power cycled or touched by
+    /// another program.
+    pub fn invalidate(&mut self) {
+        self.state = SecState::default();
+        self.refreshed = None;
+        self.powered_down = false;
+    }
+
+    /// Records that nothing uses the frontend from `now` on. A release
+    /// while already idle keeps the earlier time.
+    pub fn release(&mut self, now: Instant) {
+        self.idle_since.get_or_insert(now);
+    }
+
+    /// Records that the frontend is in use.
+    pub fn active(&mut self) {
+        self.idle_since = None;
+    }
+
+    /// Time the idle policy wants the supply off: the end of the idle time,
+    /// while the frontend is released and still powers the LNB.
+    pub fn idle_deadline(&self) -> Option<Instant> {
+        let (Some(policy), Some(idle_since)) = (self.idle_policy, self.idle_since) else {
+            return None;
+        };
+
+        matches!(self.state.voltage, Some(SecVoltage::V13 | SecVoltage::V18))
+            .then(|| idle_since + policy.idle_time)
+    }
+
+    /// Returns `true` if the idle policy wants the supply off at `now`.
+    pub fn idle_expired(&self, now: Instant) -> bool {
+        self.idle_deadline().is_some_and(|deadline| now >= deadline)
+    }
+
+    /// Records that the idle policy switched the supply off.
+    pub fn power_down(&mut self) {
+        self.voltage(SecVoltage::Off);
+        self.powered_down = true;
+    }
+
+    /// Prepends the power-up the idle policy owes to `sequence`: its first
+    /// voltage, then the power-on settle time.
+    pub fn power_up(&self, mut sequence: Vec<SecCommand>) -> Vec<SecCommand> {
+        let Some(policy) = self.idle_policy.filter(|_| self.powered_down) else {
+            return sequence;
+        };
+
+        let voltage = sequence.iter().find_map(|command| match command {
+            SecCommand::SetVoltage(voltage) if *voltage != SecVoltage::Off => Some(*voltage),
+            _ => None,
+        });
+        if let Some(voltage) = voltage {
+            sequence.splice(
+                0 .. 0,
+                [
+                    SecCommand::SetVoltage(voltage),
+                    SecCommand::Wait(policy.power_on_settle),
+                ],
+            );
+        }
+
+        sequence
+    }
+}
+
+fn switch_commands(sequence: &[SecCommand]) -> Vec<SecCommand> {
+    sequence
+        .iter()
+        .filter(|c| {
+            matches!(
+                c,
+                SecCommand::SendBurst(_) | SecCommand::SendMasterCommand(_)
+            )
+        })
+        .cloned()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn switch_sequence(data: u8, tone: SecTone) -> Vec<SecCommand> {
+        vec![
+            SecCommand::SetTone(SecTone::Off),
+            SecCommand::SetVoltage(SecVoltage::V18),
+            SecCommand::Wait(Duration::from_millis(200)),
+            SecCommand::SendMasterCommand(vec![0xE0, 0x10, 0x38, data]),
+            SecCommand::Wait(Duration::from_millis(15)),
+            SecCommand::SetTone(tone),
+        ]
+    }
+
+    /// Runs `sequence` through the tracker the way the frontend does.
+    fn run(tracker: &mut SecTracker, sequence: &[SecCommand], now: Instant) -> Vec<SecCommand> {
+        let reduced = tracker.reduce(sequence, now);
+        for command in &reduced {
+            match command {
+                SecCommand::SetVoltage(v) => tracker.voltage(*v),
+                SecCommand::SetTone(t) => tracker.tone(*t),
+                _ => {}
+            }
+        }
+        tracker.sequence_done(&reduced, now);
+        reduced
+    }
+
+    fn enabled() -> SecTracker {
+        SecTracker {
+            enabled: true,
+            ..SecTracker::default()
+        }
+    }
+
+    #[test]
+    fn disabled_tracker_runs_everything() {
+        let mut tracker = SecTracker::default();
+        let now = Instant::now();
+        let sequence = switch_sequence(0xF2, SecTone::Off);
+
+        assert_eq!(run(&mut tracker, &sequence, now), sequence);
+        assert_eq!(run(&mut tracker, &sequence, now), sequence);
+        assert_eq!(tracker.state.voltage, Some(SecVoltage::V18));
+    }
+
+    #[test]
+    fn same_switch_sends_only_the_difference() {
+        let mut tracker = enabled();
+        let now = Instant::now();
+
+        let first = switch_sequence(0xF2, SecTone::Off);
+        assert_eq!(run(&mut tracker, &first, now), first);
+
+        // same switch, same tone: nothing to do
+        assert!(run(&mut tracker, &first, now).is_empty());
+
+        // the switch bytes carry the band, so a new band runs in full
+        let high = switch_sequence(0xF3, SecTone::On);
+        assert_eq!(run(&mut tracker, &high, now), high);
+
+        let lnb = vec![
+            SecCommand::SetTone(SecTone::Off),
+            SecCommand::SetVoltage(SecVoltage::V13),
+            SecCommand::Wait(Duration::from_millis(20)),
+            SecCommand::SetTone(SecTone::On),
+            SecCommand::Wait(Duration::from_millis(20)),
+        ];
+        assert_eq!(
+            run(&mut tracker, &lnb, now),
+            [
+                SecCommand::SetVoltage(SecVoltage::V13),
+                SecCommand::Wait(Duration::from_millis(20)),
+            ]
+        );
+    }
+
+    #[test]
+    fn power_off_and_invalidate_forget_the_switch() {
+        let mut tracker = enabled();
+        let now = Instant::now();
+        let sequence = switch_sequence(0xF2, SecTone::Off);
+
+        run(&mut tracker, &sequence, now);
+        tracker.voltage(SecVoltage::Off);
+        assert_eq!(run(&mut tracker, &sequence, now), sequence);
+
+        tracker.invalidate();
+        assert_eq!(tracker.state, SecState::default());
+        assert_eq!(run(&mut tracker, &sequence, now), sequence);
+
+        tracker.switched();
+        assert_eq!(run(&mut tracker, &sequence, now), sequence);
+    }
+
+    #[test]
+    fn refresh_interval_forces_a_full_run() {
+        let mut tracker = SecTracker {
+            refresh_interval: Some(Duration::from_secs(60)),
+            ..enabled()
+        };
+        let start = Instant::now();
+        let sequence = switch_sequence(0xF2, SecTone::Off);
+
+        run(&mut tracker, &sequence, start);
+        assert!(run(&mut tracker, &sequence, start + Duration::from_secs(59)).is_empty());
+        assert_eq!(
+            run(&mut tracker, &sequence, start + Duration::from_secs(60)),
+            sequence
+        );
+        assert!(run(&mut tracker, &sequence, start + Duration::from_secs(61)).is_empty());
+    }
+
+    #[test]
+    fn idle_policy_powers_down_and_up() {
+        let mut tracker = SecTracker {
+            idle_policy: Some(LnbIdlePolicy {
+                idle_time: Duration::from_secs(60),
+                power_on_settle: Duration::from_millis(500),
+            }),
+            ..enabled()
+        };
+        let start = Instant::now();
+        let sequence = switch_sequence(0xF2, SecTone::Off);
+
+        // not powered yet, nothing to switch off
+        tracker.release(start);
+        assert_eq!(tracker.idle_deadline(), None);
+        assert!(!tracker.idle_expired(start + Duration::from_secs(60)));
+
+        tracker.active();
+        run(&mut tracker, &sequence, start);
+        tracker.release(start);
+        tracker.release(start + Duration::from_secs(30));
+        assert_eq!(
+            tracker.idle_deadline(),
+            Some(start + Duration::from_secs(60))
+        );
+        assert!(!tracker.idle_expired(start + Duration::from_secs(59)));
+        assert!(tracker.idle_expired(start + Duration::from_secs(60)));
+
+        tracker.power_down();
+        assert_eq!(tracker.idle_deadline(), None);
+        assert!(!tracker.idle_expired(start + Duration::from_secs(61)));
+
+        let reduced = tracker.reduce(&sequence, start);
+        assert_eq!(reduced, sequence);
+        let powered = tracker.power_up(reduced);
+        assert_eq!(
+            powered[.. 2],
+            [
+                SecCommand::SetVoltage(SecVoltage::V18),
+                SecCommand::Wait(Duration::from_millis(500)),
+            ]
+        );
+        assert_eq!(powered[2 ..], sequence);
+
+        // the supply is back on, nothing more is owed
+        tracker.voltage(SecVoltage::V18);
+        assert_eq!(tracker.power_up(sequence.clone()), sequence);
+    }
+}
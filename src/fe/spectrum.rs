@@ -0,0 +1,49 @@
+//! Signal levels across a frequency range, for the scans that look for
+//! carriers in them: the blind scan over the transponder range and the
+//! user-band discovery of a Unicable ODU.
+
+use std::time::Duration;
+
+use super::{
+    DvbSTune,
+    FeDevice,
+    TuneRequest,
+};
+use crate::error::Result;
+
+/// Noise floor of a sweep: the level below which a tenth of its samples
+/// lie, `None` for an empty sweep.
+///
+/// On a busy band carriers cover most of the sweep, and the median sits at
+/// carrier level. The gaps between the carriers and the band edges still
+/// give the lowest samples, so a low percentile follows the noise whether
+/// the band is busy or empty, without the single deep notch that the
+/// minimum would take.
+pub(crate) fn noise_floor(samples: &[(u32, f64)]) -> Option<f64> {
+    let mut levels: Vec<f64> = samples.iter().map(|&(_, level)| level).collect();
+    levels.sort_by(f64::total_cmp);
+    levels.get(levels.len() / 10).copied()
+}
+
+/// Tunes to an intermediate frequency with a DVB-S request of `symbolrate`,
+/// waits `settle` for the AGC, and reads the signal level there: in dB where
+/// the frontend reports it, else the relative level.
+pub(crate) fn signal_level(
+    fe: &FeDevice,
+    frequency_khz: u32,
+    symbolrate: u32,
+    settle: Duration,
+) -> Result<f64> {
+    fe.tune(&TuneRequest::DvbS(DvbSTune {
+        frequency_khz,
+        symbolrate,
+        ..Default::default()
+    }))?;
+    std::thread::sleep(settle);
+
+    let signal = fe.get_stats()?.signal();
+    Ok(signal
+        .decibel()
+        .or_else(|| signal.relative().map(f64::from))
+        .unwrap_or(0.0))
+}
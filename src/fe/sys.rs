@@ -827,6 +827,10 @@ impl fmt::Debug for DtvStats {
     }
 }
 
+/// `DTV_STREAM_ID` of no input stream: the frontend passes every stream
+/// of the transponder.
+pub const NO_STREAM_ID_FILTER: u32 = !0;
+
 pub const MAX_DTV_STATS: usize = 4;
 
 /// Store Digital TV frontend statistics
@@ -12,6 +12,10 @@ use super::{
         TransmitMode,
     },
 };
+use crate::error::{
+    Error,
+    Result,
+};
 
 /// DVB-S tune parameters.
 ///
@@ -436,6 +440,25 @@ impl TuneRequest {
 
         cmdseq
     }
+
+    /// This satellite request with its frequency set to `frequency_khz`,
+    /// the intermediate frequency the SEC setup returned for the
+    /// transponder. Fails for a request of another delivery system.
+    pub fn with_intermediate_khz(&self, frequency_khz: u32) -> Result<TuneRequest> {
+        let mut request = self.clone();
+        match &mut request {
+            TuneRequest::DvbS(tune) => tune.frequency_khz = frequency_khz,
+            TuneRequest::DvbS2(tune) => tune.frequency_khz = frequency_khz,
+            _ => {
+                return Err(Error::InvalidData(format!(
+                    "an intermediate frequency needs a satellite tune request, got {}",
+                    self.delivery_system()
+                )));
+            }
+        }
+
+        Ok(request)
+    }
 }
 
 impl From<&TuneRequest> for Vec<DtvProperty> {
@@ -810,4 +833,22 @@ mod tests {
         let cmdseq: Vec<DtvProperty> = (&request).into();
         assert_eq!(cmdseq, request.properties());
     }
+
+    #[test]
+    fn intermediate_frequency_of_a_satellite_request() {
+        let request = TuneRequest::DvbS2(DvbS2Tune {
+            symbolrate: 27_500_000,
+            ..Default::default()
+        });
+        match request.with_intermediate_khz(1_210_000).unwrap() {
+            TuneRequest::DvbS2(tune) => {
+                assert_eq!(tune.frequency_khz, 1_210_000);
+                assert_eq!(tune.symbolrate, 27_500_000);
+            }
+            _ => unreachable!(),
+        }
+
+        let request = TuneRequest::DvbC(DvbCTune::default());
+        assert!(request.with_intermediate_khz(1_210_000).is_err());
+    }
 }
@@ -0,0 +1,489 @@
+//! Unicable user-band discovery.
+//!
+//! A Unicable ODU serves each receiver on one user band: a fixed slot of the
+//! IF band the ODU converts the requested transponder to. Which slots exist
+//! and where they sit is a property of the ODU; discovery finds both.
+//!
+//! An EN 50607 ODU answers the `ODU_UB_avail` and `ODU_UB_freq` queries over
+//! DiSEqC 2.x, when the frontend can receive slave replies. Otherwise every
+//! ODU has the EN 50494 `ODU_UBxSignal_ON` command, which puts a test carrier
+//! on the centre of each user band: a sweep of the IF band finds the carriers
+//! from the signal level, and `ODU_PowerOFF` for one slot at a time tells
+//! which carrier belongs to which slot.
+
+use std::time::Duration;
+
+use super::{
+    DiseqcReply,
+    FeDevice,
+    SecTimings,
+    sec::{
+        unicable_command,
+        unicable_raise,
+    },
+    spectrum::{
+        noise_floor,
+        signal_level,
+    },
+    sys::SecVoltage,
+};
+use crate::error::{
+    Error,
+    Result,
+};
+
+/// Unicable standard of the ODU.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnicableStandard {
+    /// Unicable I / EN 50494, user bands `1..=8`.
+    Unicable1,
+    /// Unicable II / EN 50607, user bands `1..=32`.
+    Unicable2,
+}
+
+impl UnicableStandard {
+    /// Highest user band number.
+    pub fn max_slot(self) -> u8 {
+        match self {
+            UnicableStandard::Unicable1 => 8,
+            UnicableStandard::Unicable2 => 32,
+        }
+    }
+
+    /// `ODU_PowerOFF` for one user band: a channel change to tuning word 0.
+    fn power_off(self, slot: u8) -> Vec<u8> {
+        match self {
+            UnicableStandard::Unicable1 => vec![0xE0, 0x10, 0x5A, (slot - 1) << 5, 0x00],
+            UnicableStandard::Unicable2 => vec![0x70, (slot - 1) << 3, 0x00, 0x00],
+        }
+    }
+}
+
+/// A user band the ODU provides.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UserBand {
+    /// User band / SCR slot number, as [`UnicableConfig::slot`](super::UnicableConfig::slot).
+    pub slot: u8,
+    /// Centre frequency in MHz, as
+    /// [`UnicableConfig::user_band_frequency_mhz`](super::UnicableConfig::user_band_frequency_mhz).
+    pub frequency_mhz: u32,
+}
+
+/// Parameters of [`FeDevice::unicable_user_bands`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct UnicableScan {
+    /// Lower end of the IF sweep in MHz.
+    pub from_mhz: u32,
+    /// Upper end of the IF sweep in MHz.
+    pub to_mhz: u32,
+    /// Sweep step in MHz. It should not exceed the bandwidth the frontend
+    /// filters at [`UnicableScan::symbolrate`], or a carrier between two
+    /// steps is missed.
+    pub step_mhz: u32,
+    /// Symbol rate of the tune requests the sweep measures with.
+    pub symbolrate: u32,
+    /// How far a test carrier rises above the noise floor, and falls when
+    /// its user band is switched off: in dB where the frontend reports the
+    /// signal in dBm, in percentage points where it reports it relative.
+    pub threshold: f64,
+    /// Wait after each tune before the signal level is read.
+    pub settle: Duration,
+    /// How long a query waits for the reply of an EN 50607 ODU.
+    pub reply_timeout: Duration,
+    /// Waits around the Unicable commands.
+    pub timings: SecTimings,
+}
+
+impl Default for UnicableScan {
+    fn default() -> Self {
+        Self {
+            from_mhz: 950,
+            to_mhz: 2150,
+            step_mhz: 2,
+            symbolrate: 2_000_000,
+            threshold: 6.0,
+            settle: Duration::from_millis(30),
+            reply_timeout: Duration::from_millis(150),
+            timings: SecTimings::default(),
+        }
+    }
+}
+
+/// EN 50494 `ODU_UBxSignal_ON`: a test carrier on every user band.
+const UB_SIGNAL_ON: [u8; 5] = [0xE0, 0x10, 0x5B, 0x00, 0x00];
+/// EN 50607 `ODU_UB_avail`.
+const UB_AVAIL: [u8; 4] = [0x7A, 0x00, 0x00, 0x00];
+
+/// EN 50607 `ODU_UB_freq` for one user band.
+fn ub_freq(slot: u8) -> [u8; 4] {
+    [0x7D, (slot - 1) << 3, 0x00, 0x00]
+}
+
+/// User bands set in an `ODU_UB_avail` reply: a bitmap, user band 1 in the
+/// most significant bit of the first byte.
+fn decode_ub_avail(data: &[u8], max_slot: u8) -> Vec<u8> {
+    let mut slots = Vec::new();
+    for (i, byte) in data.iter().enumerate() {
+        for bit in 0 .. 8 {
+            let slot = i * 8 + bit + 1;
+            if slot > usize::from(max_slot) {
+                return slots;
+            }
+            if byte & (0x80 >> bit) != 0 {
+                slots.push(slot as u8);
+            }
+        }
+    }
+
+    slots
+}
+
+/// Centre frequency in an `ODU_UB_freq` reply, in MHz: 11 bits of MHz above
+/// 100, laid out as in the channel change command.
+fn decode_ub_freq(data: &[u8]) -> Option<u32> {
+    match data {
+        [high, low, ..] => Some(((u32::from(high & 0x07) << 8) | u32::from(*low)) + 100),
+        _ => None,
+    }
+}
+
+/// Carrier peaks of a sweep: each run of samples at least `threshold` above
+/// the [`noise_floor`], represented by its strongest sample.
+fn find_peaks(samples: &[(u32, f64)], threshold: f64) -> Vec<(u32, f64)> {
+    let Some(floor) = noise_floor(samples) else {
+        return Vec::new();
+    };
+
+    let mut peaks = Vec::new();
+    let mut current: Option<(u32, f64)> = None;
+    for &(frequency, level) in samples {
+        if level >= floor + threshold {
+            if current.is_none_or(|(_, peak)| level > peak) {
+                current = Some((frequency, level));
+            }
+        } else if let Some(peak) = current.take() {
+            peaks.push(peak);
+        }
+    }
+    peaks.extend(current);
+
+    peaks
+}
+
+/// What discovery needs from the frontend.
+pub(crate) trait UnicableBus {
+    /// Sends a Unicable command.
+    fn send(&mut self, msg: &[u8]) -> Result<()>;
+    /// Sends a Unicable command and receives the reply.
+    fn query(&mut self, msg: &[u8]) -> Result<Option<DiseqcReply>>;
+    /// Tunes to an IF frequency and reads the signal level there.
+    fn level(&mut self, frequency_mhz: u32) -> Result<f64>;
+}
+
+/// Lists the user bands of the ODU, ordered by slot.
+pub(crate) fn discover<B: UnicableBus>(
+    bus: &mut B,
+    standard: UnicableStandard,
+    scan: &UnicableScan,
+) -> Result<Vec<UserBand>> {
+    if standard == UnicableStandard::Unicable2
+        && let Some(bands) = query_user_bands(bus, standard)?
+    {
+        return Ok(bands);
+    }
+
+    probe_user_bands(bus, standard, scan)
+}
+
+/// Asks an EN 50607 ODU for its user bands. `None` when the frontend cannot
+/// receive replies or the ODU does not answer.
+fn query_user_bands<B: UnicableBus>(
+    bus: &mut B,
+    standard: UnicableStandard,
+) -> Result<Option<Vec<UserBand>>> {
+    let Some(reply) = optional_reply(bus.query(&UB_AVAIL))? else {
+        return Ok(None);
+    };
+
+    let mut bands = Vec::new();
+    for slot in decode_ub_avail(&reply.data, standard.max_slot()) {
+        let Some(reply) = optional_reply(bus.query(&ub_freq(slot)))? else {
+            return Ok(None);
+        };
+        let Some(frequency_mhz) = decode_ub_freq(&reply.data) else {
+            return Ok(None);
+        };
+        bands.push(UserBand {
+            slot,
+            frequency_mhz,
+        });
+    }
+
+    if bands.is_empty() {
+        return Ok(None);
+    }
+
+    Ok(Some(bands))
+}
+
+/// A successful reply, or `None` for no reply, a refusal, or a frontend
+/// without reply support.
+fn optional_reply(reply: Result<Option<DiseqcReply>>) -> Result<Option<DiseqcReply>> {
+    match reply {
+        Ok(reply) => Ok(reply.filter(DiseqcReply::is_ok)),
+        Err(Error::Nix(nix::errno::Errno::EOPNOTSUPP | nix::errno::Errno::ENOTTY)) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// Finds the user bands from their test carriers.
+///
+/// A carrier that does not fall when any slot is switched off is not a
+/// user band of this ODU and is left out.
+fn probe_user_bands<B: UnicableBus>(
+    bus: &mut B,
+    standard: UnicableStandard,
+    scan: &UnicableScan,
+) -> Result<Vec<UserBand>> {
+    if scan.step_mhz == 0 || scan.from_mhz > scan.to_mhz {
+        return Err(Error::InvalidData(format!(
+            "Unicable sweep needs a step and an ordered range, got {}..={} MHz in {} MHz steps",
+            scan.from_mhz, scan.to_mhz, scan.step_mhz
+        )));
+    }
+
+    bus.send(&UB_SIGNAL_ON)?;
+
+    let mut samples = Vec::new();
+    for frequency in (scan.from_mhz ..= scan.to_mhz).step_by(scan.step_mhz as usize) {
+        samples.push((frequency, bus.level(frequency)?));
+    }
+    let mut candidates = find_peaks(&samples, scan.threshold);
+
+    let mut bands = Vec::new();
+    for slot in 1 ..= standard.max_slot() {
+        if candidates.is_empty() {
+            break;
+        }
+
+        bus.send(&standard.power_off(slot))?;
+
+        let mut best: Option<(usize, f64)> = None;
+        for (i, &(frequency, level)) in candidates.iter().enumerate() {
+            let fall = level - bus.level(frequency)?;
+            if fall >= scan.threshold && best.is_none_or(|(_, most)| fall > most) {
+                best = Some((i, fall));
+            }
+        }
+
+        bus.send(&UB_SIGNAL_ON)?;
+
+        if let Some((i, _)) = best {
+            let (frequency_mhz, _) = candidates.remove(i);
+            bands.push(UserBand {
+                slot,
+                frequency_mhz,
+            });
+        }
+    }
+
+    // the test carriers stay on until each user band is switched off
+    for band in &bands {
+        bus.send(&standard.power_off(band.slot))?;
+    }
+
+    Ok(bands)
+}
+
+/// [`UnicableBus`] over a frontend.
+pub(crate) struct FeBus<'a> {
+    pub(crate) fe: &'a FeDevice,
+    pub(crate) scan: &'a UnicableScan,
+}
+
+impl UnicableBus for FeBus<'_> {
+    fn send(&mut self, msg: &[u8]) -> Result<()> {
+        self.fe
+            .run_sec_sequence(&unicable_command(msg.to_vec(), self.scan.timings))
+    }
+
+    fn query(&mut self, msg: &[u8]) -> Result<Option<DiseqcReply>> {
+        self.fe
+            .run_sec_sequence(&unicable_raise(msg.to_vec(), self.scan.timings))?;
+        let reply = self.fe.diseqc_recv_reply(self.scan.reply_timeout);
+        self.fe.set_voltage(SecVoltage::V13)?;
+        reply
+    }
+
+    fn level(&mut self, frequency_mhz: u32) -> Result<f64> {
+        signal_level(
+            self.fe,
+            frequency_mhz * 1000,
+            self.scan.symbolrate,
+            self.scan.settle,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An ODU with test carriers at fixed frequencies, one per slot.
+    struct Odu {
+        standard: UnicableStandard,
+        bands: Vec<UserBand>,
+        replies: bool,
+        signal_on: bool,
+        off: Vec<u8>,
+    }
+
+    impl Odu {
+        fn new(standard: UnicableStandard, replies: bool) -> Odu {
+            Odu {
+                standard,
+                bands: vec![
+                    UserBand {
+                        slot: 1,
+                        frequency_mhz: 1210,
+                    },
+                    UserBand {
+                        slot: 2,
+                        frequency_mhz: 1420,
+                    },
+                    UserBand {
+                        slot: 3,
+                        frequency_mhz: 1680,
+                    },
+                    UserBand {
+                        slot: 4,
+                        frequency_mhz: 2040,
+                    },
+                ],
+                replies,
+                signal_on: false,
+                off: Vec::new(),
+            }
+        }
+    }
+
+    impl UnicableBus for Odu {
+        fn send(&mut self, msg: &[u8]) -> Result<()> {
+            if msg == UB_SIGNAL_ON {
+                self.signal_on = true;
+                self.off.clear();
+            } else {
+                let slot = (1 ..= self.standard.max_slot())
+                    .find(|&slot| self.standard.power_off(slot) == msg)
+                    .expect("unexpected command");
+                self.off.push(slot);
+            }
+            Ok(())
+        }
+
+        fn query(&mut self, msg: &[u8]) -> Result<Option<DiseqcReply>> {
+            if !self.replies {
+                return Err(Error::Nix(nix::errno::Errno::EOPNOTSUPP));
+            }
+
+            let data = if msg == UB_AVAIL {
+                let mut bitmap = 0u32;
+                for band in &self.bands {
+                    bitmap |= 0x8000_0000 >> (band.slot - 1);
+                }
+                bitmap.to_be_bytes()[.. 3].to_vec()
+            } else {
+                let band = self
+                    .bands
+                    .iter()
+                    .find(|band| ub_freq(band.slot) == msg)
+                    .unwrap();
+                let x = band.frequency_mhz - 100;
+                vec![(x >> 8) as u8, x as u8]
+            };
+
+            Ok(Some(DiseqcReply {
+                framing: 0xE4,
+                data,
+            }))
+        }
+
+        fn level(&mut self, frequency_mhz: u32) -> Result<f64> {
+            let carrier = self.signal_on
+                && self.bands.iter().any(|band| {
+                    band.frequency_mhz.abs_diff(frequency_mhz) <= 1
+                        && !self.off.contains(&band.slot)
+                });
+            Ok(if carrier { -40.0 } else { -70.0 })
+        }
+    }
+
+    #[test]
+    fn query_lists_the_bands_an_en50607_odu_reports() {
+        let mut odu = Odu::new(UnicableStandard::Unicable2, true);
+        let bands = discover(
+            &mut odu,
+            UnicableStandard::Unicable2,
+            &UnicableScan::default(),
+        );
+
+        assert_eq!(bands.unwrap(), odu.bands);
+        // answered from the replies, no carriers were switched on
+        assert!(!odu.signal_on);
+    }
+
+    #[test]
+    fn probe_finds_the_carriers_and_their_slots() {
+        for standard in [UnicableStandard::Unicable1, UnicableStandard::Unicable2] {
+            let mut odu = Odu::new(standard, false);
+            // the slot order need not follow the frequency order
+            odu.bands[0].slot = 3;
+            odu.bands[2].slot = 1;
+
+            let mut bands = discover(&mut odu, standard, &UnicableScan::default()).unwrap();
+            bands.sort_by_key(|band| band.frequency_mhz);
+            assert_eq!(bands, odu.bands);
+
+            // every carrier is switched off again
+            assert_eq!(odu.off.len(), 4);
+        }
+    }
+
+    #[test]
+    fn probe_skips_a_carrier_no_slot_switches_off() {
+        let mut odu = Odu::new(UnicableStandard::Unicable1, false);
+        odu.bands[3].slot = 9;
+
+        let bands = discover(
+            &mut odu,
+            UnicableStandard::Unicable1,
+            &UnicableScan::default(),
+        );
+        assert_eq!(bands.unwrap(), odu.bands[.. 3]);
+    }
+
+    #[test]
+    fn reply_decoding() {
+        assert_eq!(decode_ub_avail(&[0xA0, 0x01], 32), [1, 3, 16]);
+        assert_eq!(decode_ub_avail(&[0xFF, 0xFF], 8), [1, 2, 3, 4, 5, 6, 7, 8]);
+        assert_eq!(decode_ub_freq(&[0x04, 0x56]), Some(1210));
+        assert_eq!(decode_ub_freq(&[0x04]), None);
+    }
+
+    #[test]
+    fn peaks_stand_out_of_the_noise_floor() {
+        let samples = [
+            (1000, -70.0),
+            (1002, -45.0),
+            (1004, -40.0),
+            (1006, -44.0),
+            (1008, -71.0),
+            (1010, -69.0),
+            (1012, -70.0),
+            (1014, -68.0),
+            (1016, -50.0),
+        ];
+        assert_eq!(find_peaks(&samples, 6.0), [(1004, -40.0), (1016, -50.0)]);
+        assert!(find_peaks(&[], 6.0).is_empty());
+    }
+}
@@ -0,0 +1,442 @@
+use std::{
+    fmt,
+    ops::RangeInclusive,
+};
+
+use super::{
+    TuneRequest,
+    sys::{
+        DeliverySystem,
+        FeCaps,
+        Fec,
+        GuardInterval,
+        Hierarchy,
+        Inversion,
+        Modulation,
+        TransmitMode,
+    },
+};
+
+/// What a frontend reports it can tune, as read by `FE_GET_INFO` and
+/// `DTV_ENUM_DELSYS`.
+///
+/// [`FeDevice::limits`](super::FeDevice::limits) fills it from an open
+/// frontend; building one by hand checks requests against a frontend that is
+/// not open, or that is described by a configuration file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FeLimits {
+    /// Delivery systems the frontend lists.
+    pub delivery_systems: Vec<DeliverySystem>,
+    /// Tunable frequency range, in the unit `frequency_khz` tells.
+    ///
+    /// `FE_GET_INFO` reports the range of the current delivery system only.
+    /// On a frontend that tunes both satellite and terrestrial or cable
+    /// systems, the range of the other kind is not known without switching
+    /// the frontend over; requests of that kind are not checked against it.
+    pub frequency_range: RangeInclusive<u32>,
+    /// `true` when `frequency_range` is in kHz, as the kernel reports it for a
+    /// frontend whose current delivery system is a satellite one; in Hz
+    /// otherwise.
+    pub frequency_khz: bool,
+    /// Symbol rate range in baud, for the delivery systems that have one.
+    pub symbolrate_range: RangeInclusive<u32>,
+    /// Capability flags.
+    pub caps: FeCaps,
+}
+
+/// One reason a frontend would reject a [`TuneRequest`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TuneViolation {
+    /// The frontend does not list the delivery system of the request.
+    DeliverySystem {
+        requested: DeliverySystem,
+        supported: Vec<DeliverySystem>,
+    },
+    /// The frequency is outside the tunable range. Both are in Hz.
+    Frequency {
+        requested_hz: u64,
+        range_hz: RangeInclusive<u64>,
+    },
+    /// The symbol rate is outside the supported range. Both are in baud.
+    SymbolRate {
+        requested: u32,
+        range: RangeInclusive<u32>,
+    },
+    /// A parameter is left to the frontend to detect, or needs a feature,
+    /// without the capability flag that allows it.
+    Capability {
+        /// Name of the request field.
+        parameter: &'static str,
+        /// The flag the frontend lacks.
+        missing: FeCaps,
+    },
+}
+
+impl fmt::Display for TuneViolation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TuneViolation::DeliverySystem {
+                requested,
+                supported,
+            } => {
+                write!(
+                    f,
+                    "delivery system {} is not supported (frontend has",
+                    requested
+                )?;
+                for (i, ds) in supported.iter().enumerate() {
+                    write!(f, "{}{}", if i == 0 { " " } else { ", " }, ds)?;
+                }
+                if supported.is_empty() {
+                    write!(f, " none")?;
+                }
+                write!(f, ")")
+            }
+            TuneViolation::Frequency {
+                requested_hz,
+                range_hz,
+            } => write!(
+                f,
+                "frequency {} Hz is out of range {}..={} Hz",
+                requested_hz,
+                range_hz.start(),
+                range_hz.end()
+            ),
+            TuneViolation::SymbolRate { requested, range } => write!(
+                f,
+                "symbol rate {} is out of range {}..={}",
+                requested,
+                range.start(),
+                range.end()
+            ),
+            TuneViolation::Capability { parameter, missing } => {
+                write!(f, "{} needs frontend capability {:?}", parameter, missing)
+            }
+        }
+    }
+}
+
+impl FeLimits {
+    /// Checks `request` against the limits and returns every violation found,
+    /// in request field order. An empty list means the frontend should accept
+    /// the request.
+    ///
+    /// A limit the frontend does not report is not checked: a zero frequency
+    /// or symbol rate range, and the capability flags of a frontend that
+    /// reports none ([`FeCaps::IS_STUPID`]).
+    pub fn validate(&self, request: &TuneRequest) -> Vec<TuneViolation> {
+        let mut result = Vec::new();
+
+        let delivery_system = request.delivery_system();
+        if !self.delivery_systems.contains(&delivery_system) {
+            result.push(TuneViolation::DeliverySystem {
+                requested: delivery_system,
+                supported: self.delivery_systems.clone(),
+            });
+        }
+
+        self.check_frequency(request, &mut result);
+
+        match request {
+            TuneRequest::DvbS(tune) => {
+                self.check_symbolrate(tune.symbolrate, &mut result);
+                self.check_inversion(tune.inversion, &mut result);
+                self.check_fec("fec", tune.fec, &mut result);
+            }
+            TuneRequest::DvbS2(tune) => {
+                self.check_symbolrate(tune.symbolrate, &mut result);
+                self.check_inversion(tune.inversion, &mut result);
+                self.check_fec("fec", tune.fec, &mut result);
+                if tune.mis.is_some() {
+                    self.check_cap("mis", FeCaps::CAN_MULTISTREAM, &mut result);
+                }
+            }
+            TuneRequest::DvbC(tune) => {
+                self.check_symbolrate(tune.symbolrate, &mut result);
+                self.check_modulation(tune.modulation, &mut result);
+                self.check_inversion(tune.inversion, &mut result);
+                self.check_fec("fec", tune.fec, &mut result);
+            }
+            TuneRequest::DvbT(tune) => {
+                self.check_modulation(tune.modulation, &mut result);
+                self.check_inversion(tune.inversion, &mut result);
+                self.check_fec("code_rate_hp", tune.code_rate_hp, &mut result);
+                self.check_fec("code_rate_lp", tune.code_rate_lp, &mut result);
+                self.check_guard_interval(tune.guard_interval, &mut result);
+                self.check_transmission_mode(tune.transmission_mode, &mut result);
+                if tune.hierarchy == Hierarchy::Auto {
+                    self.check_cap("hierarchy", FeCaps::CAN_HIERARCHY_AUTO, &mut result);
+                }
+            }
+            TuneRequest::DvbT2(tune) => {
+                self.check_modulation(tune.modulation, &mut result);
+                self.check_inversion(tune.inversion, &mut result);
+                self.check_fec("code_rate", tune.code_rate, &mut result);
+                self.check_guard_interval(tune.guard_interval, &mut result);
+                self.check_transmission_mode(tune.transmission_mode, &mut result);
+                if tune.stream_id.is_some() {
+                    self.check_cap("stream_id", FeCaps::CAN_MULTISTREAM, &mut result);
+                }
+            }
+            TuneRequest::Atsc(tune) => {
+                self.check_inversion(tune.inversion, &mut result);
+            }
+            TuneRequest::IsdbT(tune) => {
+                self.check_inversion(tune.inversion, &mut result);
+            }
+        }
+
+        result
+    }
+
+    fn check_frequency(&self, request: &TuneRequest, result: &mut Vec<TuneViolation>) {
+        if *self.frequency_range.end() == 0 {
+            return;
+        }
+
+        let requested_hz = match request {
+            // a range of the other kind of delivery system is no limit
+            TuneRequest::DvbS(_) | TuneRequest::DvbS2(_) if !self.frequency_khz => return,
+            TuneRequest::DvbS(tune) => u64::from(tune.frequency_khz) * 1000,
+            TuneRequest::DvbS2(tune) => u64::from(tune.frequency_khz) * 1000,
+            _ if self.frequency_khz => return,
+            TuneRequest::DvbC(tune) => u64::from(tune.frequency_hz),
+            TuneRequest::DvbT(tune) => u64::from(tune.frequency_hz),
+            TuneRequest::DvbT2(tune) => u64::from(tune.frequency_hz),
+            TuneRequest::Atsc(tune) => u64::from(tune.frequency_hz),
+            TuneRequest::IsdbT(tune) => u64::from(tune.frequency_hz),
+        };
+
+        let scale = if self.frequency_khz { 1000 } else { 1 };
+        let range_hz = u64::from(*self.frequency_range.start()) * scale
+            ..= u64::from(*self.frequency_range.end()) * scale;
+
+        if !range_hz.contains(&requested_hz) {
+            result.push(TuneViolation::Frequency {
+                requested_hz,
+                range_hz,
+            });
+        }
+    }
+
+    fn check_symbolrate(&self, symbolrate: u32, result: &mut Vec<TuneViolation>) {
+        if *self.symbolrate_range.end() == 0 || self.symbolrate_range.contains(&symbolrate) {
+            return;
+        }
+
+        result.push(TuneViolation::SymbolRate {
+            requested: symbolrate,
+            range: self.symbolrate_range.clone(),
+        });
+    }
+
+    fn check_inversion(&self, inversion: Inversion, result: &mut Vec<TuneViolation>) {
+        if inversion == Inversion::Auto {
+            self.check_cap("inversion", FeCaps::CAN_INVERSION_AUTO, result);
+        }
+    }
+
+    fn check_fec(&self, parameter: &'static str, fec: Fec, result: &mut Vec<TuneViolation>) {
+        if fec == Fec::Auto {
+            self.check_cap(parameter, FeCaps::CAN_FEC_AUTO, result);
+        }
+    }
+
+    fn check_modulation(&self, modulation: Modulation, result: &mut Vec<TuneViolation>) {
+        if modulation == Modulation::QamAuto {
+            self.check_cap("modulation", FeCaps::CAN_QAM_AUTO, result);
+        }
+    }
+
+    fn check_guard_interval(&self, guard_interval: GuardInterval, result: &mut Vec<TuneViolation>) {
+        if guard_interval == GuardInterval::Auto {
+            self.check_cap("guard_interval", FeCaps::CAN_GUARD_INTERVAL_AUTO, result);
+        }
+    }
+
+    fn check_transmission_mode(&self, mode: TransmitMode, result: &mut Vec<TuneViolation>) {
+        if mode == TransmitMode::Auto {
+            self.check_cap(
+                "transmission_mode",
+                FeCaps::CAN_TRANSMISSION_MODE_AUTO,
+                result,
+            );
+        }
+    }
+
+    fn check_cap(&self, parameter: &'static str, cap: FeCaps, result: &mut Vec<TuneViolation>) {
+        if self.caps == FeCaps::IS_STUPID || self.caps.contains(cap) {
+            return;
+        }
+
+        result.push(TuneViolation::Capability {
+            parameter,
+            missing: cap,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fe::{
+        DvbCTune,
+        DvbS2Tune,
+        DvbSTune,
+        DvbTTune,
+        Mis,
+        PlsMode,
+    };
+
+    fn satellite() -> FeLimits {
+        FeLimits {
+            delivery_systems: vec![DeliverySystem::Dvbs, DeliverySystem::Dvbs2],
+            frequency_range: 950_000 ..= 2_150_000,
+            frequency_khz: true,
+            symbolrate_range: 1_000_000 ..= 45_000_000,
+            caps: FeCaps::CAN_INVERSION_AUTO | FeCaps::CAN_FEC_AUTO | FeCaps::CAN_QPSK,
+        }
+    }
+
+    #[test]
+    fn valid_request_has_no_violations() {
+        let request = TuneRequest::DvbS2(DvbS2Tune {
+            frequency_khz: 1_232_000,
+            symbolrate: 27_500_000,
+            ..Default::default()
+        });
+
+        assert_eq!(satellite().validate(&request), Vec::new());
+    }
+
+    #[test]
+    fn every_violation_is_reported() {
+        let limits = FeLimits {
+            caps: FeCaps::CAN_QPSK,
+            ..satellite()
+        };
+        let request = TuneRequest::DvbS2(DvbS2Tune {
+            frequency_khz: 2_200_000,
+            symbolrate: 500_000,
+            mis: Some(Mis {
+                mode: PlsMode::Root,
+                code: 0,
+                stream_id: 1,
+            }),
+            ..Default::default()
+        });
+
+        assert_eq!(
+            limits.validate(&request),
+            vec![
+                TuneViolation::Frequency {
+                    requested_hz: 2_200_000_000,
+                    range_hz: 950_000_000 ..= 2_150_000_000,
+                },
+                TuneViolation::SymbolRate {
+                    requested: 500_000,
+                    range: 1_000_000 ..= 45_000_000,
+                },
+                TuneViolation::Capability {
+                    parameter: "inversion",
+                    missing: FeCaps::CAN_INVERSION_AUTO,
+                },
+                TuneViolation::Capability {
+                    parameter: "fec",
+                    missing: FeCaps::CAN_FEC_AUTO,
+                },
+                TuneViolation::Capability {
+                    parameter: "mis",
+                    missing: FeCaps::CAN_MULTISTREAM,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn delivery_system_must_be_listed() {
+        let request = TuneRequest::DvbC(DvbCTune {
+            frequency_hz: 346_000_000,
+            symbolrate: 6_900_000,
+            ..Default::default()
+        });
+
+        let violations = satellite().validate(&request);
+        assert_eq!(
+            violations.first(),
+            Some(&TuneViolation::DeliverySystem {
+                requested: DeliverySystem::DvbcAnnexA,
+                supported: vec![DeliverySystem::Dvbs, DeliverySystem::Dvbs2],
+            })
+        );
+        assert_eq!(
+            violations[0].to_string(),
+            "delivery system dvb-c is not supported (frontend has dvb-s, dvb-s2)"
+        );
+    }
+
+    #[test]
+    fn range_edges_are_inclusive() {
+        for frequency_khz in [950_000, 2_150_000] {
+            let request = TuneRequest::DvbS(DvbSTune {
+                frequency_khz,
+                symbolrate: 45_000_000,
+                ..Default::default()
+            });
+            assert!(satellite().validate(&request).is_empty());
+        }
+    }
+
+    #[test]
+    fn frequency_range_in_hz() {
+        let limits = FeLimits {
+            delivery_systems: vec![DeliverySystem::Dvbt],
+            frequency_range: 174_000_000 ..= 862_000_000,
+            frequency_khz: false,
+            symbolrate_range: 0 ..= 0,
+            caps: FeCaps::IS_STUPID,
+        };
+
+        let request = TuneRequest::DvbT(DvbTTune {
+            frequency_hz: 474_000_000,
+            ..Default::default()
+        });
+        assert!(limits.validate(&request).is_empty());
+
+        let request = TuneRequest::DvbT(DvbTTune {
+            frequency_hz: 100_000_000,
+            ..Default::default()
+        });
+        assert_eq!(limits.validate(&request).len(), 1);
+
+        // the range of DVB-T says nothing about DVB-S on the same frontend
+        let limits = FeLimits {
+            delivery_systems: vec![DeliverySystem::Dvbt, DeliverySystem::Dvbs],
+            ..limits
+        };
+        let request = TuneRequest::DvbS(DvbSTune {
+            frequency_khz: 1_232_000,
+            symbolrate: 27_500_000,
+            ..Default::default()
+        });
+        assert!(limits.validate(&request).is_empty());
+    }
+
+    #[test]
+    fn unreported_limits_are_not_checked() {
+        let limits = FeLimits {
+            delivery_systems: vec![DeliverySystem::Dvbs],
+            frequency_range: 0 ..= 0,
+            frequency_khz: true,
+            symbolrate_range: 0 ..= 0,
+            caps: FeCaps::IS_STUPID,
+        };
+        let request = TuneRequest::DvbS(DvbSTune {
+            frequency_khz: 1_232_000,
+            symbolrate: 27_500_000,
+            ..Default::default()
+        });
+
+        assert!(limits.validate(&request).is_empty());
+    }
+}
@@ -1,4 +1,6 @@
+pub mod bbframe;
 pub mod ca;
+pub mod channels;
 pub mod dmx;
 pub mod dvr;
 pub mod error;
@@ -6,10 +8,20 @@ pub mod fe;
 pub mod modulator;
 pub mod net;
 pub mod scan;
+pub mod si;
 pub mod sysfs;
+pub mod t2mi;
 
 mod fd;
 
+pub use bbframe::{
+    BbFrame,
+    BbFrameReader,
+    BbHeader,
+    GsePdu,
+    GseReassembler,
+    TsReassembler,
+};
 pub use ca::{
     CaDevice,
     CaEvent,
@@ -28,32 +40,98 @@ pub use ca::{
     CiDriverEvent,
     CiDriverHandle,
 };
+pub use channels::{
+    Channel,
+    ComponentPid,
+    ScanTableEntry,
+};
 pub use dvr::DvrDevice;
 pub use fe::{
     ApiVersion,
     AtscTune,
+    BandChannel,
+    BandPlan,
+    BandSweep,
+    BandSweepProgress,
+    BandSystem,
+    BlindScan,
+    BlindScanCarrier,
+    BlindScanProgress,
+    BlindScanStage,
+    CableLock,
+    CalibratedLnb,
+    CascadeConfig,
+    CascadeLevel,
+    DISEQC_ADDRESSES,
+    DiseqcAddress,
+    DiseqcCommand,
+    DiseqcConf,
+    DiseqcConfEntry,
+    DiseqcFraming,
+    DiseqcMessage,
+    DiseqcRead,
+    DiseqcReply,
+    DiseqcSlave,
     DiseqcSwitchConfig,
     DtvProperty,
+    DualBandSelect,
     DvbCAnnex,
     DvbCTune,
     DvbS2Tune,
     DvbSTune,
     DvbT2Tune,
     DvbTTune,
+    FallbackChange,
+    FallbackLock,
+    FallbackStep,
     FeDevice,
+    FeLease,
     FeLevel,
+    FeLimits,
+    FePool,
     FeStats,
+    InputStream,
     IsdbTTune,
+    KaBand,
     Lnb,
+    LnbDriftConfig,
+    LnbDriftTracker,
+    LnbIdlePolicy,
+    LockedChannel,
     Mis,
+    MultistreamScan,
     PlsMode,
+    PlsSearch,
+    Polarization,
+    PoolFrontend,
+    PositionerCommand,
+    PositionerConfig,
+    PositionerDirection,
+    PositionerDrive,
+    SatelliteInput,
+    SatelliteTarget,
     SecCommand,
     SecConfig,
     SecSetup,
+    SecState,
+    SecTemplate,
+    SecTimeline,
+    SecTimelineEntry,
     SecTimings,
     ToneburstConfig,
+    TuneFallback,
     TuneRequest,
+    TuneViolation,
+    UnicableArbiter,
+    UnicableArbiterConfig,
     UnicableConfig,
+    UnicableScan,
+    UnicableStandard,
+    UsalsConfig,
+    UserBand,
+    UserBandAllocator,
+    UserBandLease,
+    format_sec_sequence,
     sec_sequence,
 };
 pub use net::NetDevice;
@@ -61,3 +139,14 @@ pub use scan::{
     FeProbe,
     scan,
 };
+pub use si::{
+    NetworkScanner,
+    NetworkTransport,
+    NitScan,
+    SatelliteOrbit,
+    Service,
+    ServiceList,
+    ServiceScan,
+    ServiceStream,
+};
+pub use t2mi::T2miDecoder;
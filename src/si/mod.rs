@@ -0,0 +1,19 @@
+//! Service information: the PSI/SI tables a transport stream carries, read
+//! through a demux section filter.
+
+pub mod network;
+pub mod service;
+
+pub use network::{
+    NetworkScanner,
+    NetworkTransport,
+    NitScan,
+    SatelliteOrbit,
+    decode_nit,
+};
+pub use service::{
+    Service,
+    ServiceList,
+    ServiceScan,
+    ServiceStream,
+};
@@ -0,0 +1,976 @@
+//! Network scan from the NIT.
+//!
+//! The network information table of a transport stream lists the
+//! multiplexes of its network - and, in the NIT other, of other networks -
+//! each with a delivery system descriptor that says how to tune it. A
+//! network scan tunes one known transponder, reads the NIT there, and
+//! works through the transponders it lists, reading the NIT on each in
+//! turn, until no new ones turn up.
+
+use std::{
+    fmt,
+    time::Duration,
+};
+
+use libmpegts::psi::{
+    DescriptorRef,
+    NIT_PID,
+    NitSectionRef,
+};
+
+use crate::{
+    dmx::{
+        DmxDevice,
+        SectionCollector,
+    },
+    error::{
+        Error,
+        Result,
+    },
+    fe::{
+        DvbCTune,
+        DvbS2Tune,
+        DvbSTune,
+        DvbT2Tune,
+        DvbTTune,
+        Mis,
+        PlsMode,
+        Polarization,
+        TuneRequest,
+        sys::{
+            Fec,
+            GuardInterval,
+            Hierarchy,
+            Modulation,
+            Rolloff,
+            TransmitMode,
+        },
+    },
+};
+
+/// Table id of the NIT of the actual network.
+const NIT_ACTUAL: u8 = 0x40;
+/// Table id of the NIT of other networks.
+const NIT_OTHER: u8 = 0x41;
+
+/// Where a satellite transport stream comes from, as its satellite delivery
+/// system descriptor tells.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SatelliteOrbit {
+    /// Transponder frequency in kHz, for the SEC setup.
+    pub transponder_khz: u32,
+    /// Orbital position in tenths of a degree.
+    pub orbital_position: u16,
+    /// `true` east of the Greenwich meridian.
+    pub east: bool,
+    /// Polarization of the transponder, for the SEC setup.
+    pub polarization: Polarization,
+}
+
+impl SatelliteOrbit {
+    /// Orbital position in degrees, east positive.
+    pub fn degrees(&self) -> f64 {
+        let degrees = f64::from(self.orbital_position) / 10.0;
+        if self.east { degrees } else { -degrees }
+    }
+}
+
+impl fmt::Display for SatelliteOrbit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}.{}{} {}",
+            self.orbital_position / 10,
+            self.orbital_position % 10,
+            if self.east { 'E' } else { 'W' },
+            self.polarization.code()
+        )
+    }
+}
+
+/// A transport stream a NIT lists, with what it takes to tune it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NetworkTransport {
+    /// Network whose NIT listed it.
+    pub network_id: u16,
+    /// Original network id.
+    pub original_network_id: u16,
+    /// Transport stream id.
+    pub transport_stream_id: u16,
+    /// Tune request from the delivery system descriptor. That of a
+    /// satellite transport stream is a template with frequency 0: its
+    /// [`TuneRequest::with_intermediate_khz`] takes the frequency the SEC
+    /// setup for [`SatelliteOrbit::transponder_khz`] returns.
+    pub request: TuneRequest,
+    /// Transponder, orbital position and polarization of a satellite
+    /// transport stream.
+    pub satellite: Option<SatelliteOrbit>,
+}
+
+impl NetworkTransport {
+    /// A transport stream known only by how it is tuned, such as the home
+    /// transponder a scan starts from. The ids are 0.
+    pub fn new(request: TuneRequest, satellite: Option<SatelliteOrbit>) -> NetworkTransport {
+        NetworkTransport {
+            network_id: 0,
+            original_network_id: 0,
+            transport_stream_id: 0,
+            request,
+            satellite,
+        }
+    }
+
+    /// `true` if both are the same multiplex on the air: the same kind of
+    /// delivery system, stream and polarization, with frequencies closer
+    /// than the accuracy NITs give them with.
+    pub fn same_multiplex(&self, other: &NetworkTransport) -> bool {
+        let (Some(a), Some(b)) = (self.carrier(), other.carrier()) else {
+            return false;
+        };
+
+        a.kind == b.kind
+            && a.stream_id == b.stream_id
+            && self.satellite.map(|orbit| orbit.polarization)
+                == other.satellite.map(|orbit| orbit.polarization)
+            && a.frequency.abs_diff(b.frequency) <= a.tolerance
+    }
+
+    fn carrier(&self) -> Option<Carrier> {
+        let carrier = |kind, frequency, tolerance, stream_id| Carrier {
+            kind,
+            frequency,
+            tolerance,
+            stream_id,
+        };
+        let transponder_khz = self.satellite.map_or(0, |orbit| orbit.transponder_khz);
+
+        Some(match &self.request {
+            TuneRequest::DvbS(_) => carrier(0, transponder_khz, 2_000, None),
+            TuneRequest::DvbS2(tune) => {
+                carrier(0, transponder_khz, 2_000, tune.mis.map(|mis| mis.stream_id))
+            }
+            TuneRequest::DvbC(tune) => carrier(1, tune.frequency_hz, 1_000_000, None),
+            TuneRequest::DvbT(tune) => carrier(2, tune.frequency_hz, 1_000_000, None),
+            TuneRequest::DvbT2(tune) => carrier(2, tune.frequency_hz, 1_000_000, tune.stream_id),
+            _ => return None,
+        })
+    }
+}
+
+/// What tells two multiplexes apart.
+struct Carrier {
+    /// 0 satellite, 1 cable, 2 terrestrial.
+    kind: u8,
+    /// Frequency in the unit of the request.
+    frequency: u32,
+    /// How far apart two listings of one multiplex can be.
+    tolerance: u32,
+    stream_id: Option<u32>,
+}
+
+/// Value of `digits` BCD digits from the start of `data`.
+fn bcd(data: &[u8], digits: usize) -> u32 {
+    (0 .. digits).fold(0, |value, i| {
+        let byte = data[i / 2];
+        let digit = if i % 2 == 0 { byte >> 4 } else { byte & 0x0F };
+        value * 10 + u32::from(digit)
+    })
+}
+
+/// FEC inner of the satellite and cable delivery system descriptors.
+fn fec_inner(value: u8) -> Fec {
+    match value {
+        1 => Fec::Fec1_2,
+        2 => Fec::Fec2_3,
+        3 => Fec::Fec3_4,
+        4 => Fec::Fec5_6,
+        5 => Fec::Fec7_8,
+        6 => Fec::Fec8_9,
+        7 => Fec::Fec3_5,
+        8 => Fec::Fec4_5,
+        9 => Fec::Fec9_10,
+        15 => Fec::None,
+        _ => Fec::Auto,
+    }
+}
+
+fn polarization(value: u8) -> Polarization {
+    match value & 0x03 {
+        0 => Polarization::Horizontal,
+        1 => Polarization::Vertical,
+        2 => Polarization::Left,
+        _ => Polarization::Right,
+    }
+}
+
+/// Satellite delivery system descriptor, EN 300 468 6.2.13.2.
+fn satellite(data: &[u8]) -> Option<(TuneRequest, SatelliteOrbit)> {
+    if data.len() < 11 {
+        return None;
+    }
+
+    let orbit = SatelliteOrbit {
+        transponder_khz: bcd(data, 8) * 10,
+        orbital_position: bcd(&data[4 ..], 4) as u16,
+        east: data[6] & 0x80 != 0,
+        polarization: polarization(data[6] >> 5),
+    };
+    let symbolrate = bcd(&data[7 ..], 7) * 100;
+    let fec = fec_inner(data[10] & 0x0F);
+
+    let request = if data[6] & 0x04 != 0 {
+        TuneRequest::DvbS2(DvbS2Tune {
+            symbolrate,
+            modulation: match data[6] & 0x03 {
+                0 => Modulation::QamAuto,
+                1 => Modulation::Qpsk,
+                2 => Modulation::Psk8,
+                _ => Modulation::Apsk16,
+            },
+            fec,
+            rolloff: match (data[6] >> 3) & 0x03 {
+                0 => Rolloff::R35,
+                1 => Rolloff::R25,
+                2 => Rolloff::R20,
+                _ => Rolloff::Auto,
+            },
+            ..Default::default()
+        })
+    } else {
+        TuneRequest::DvbS(DvbSTune {
+            symbolrate,
+            fec,
+            ..Default::default()
+        })
+    };
+
+    Some((request, orbit))
+}
+
+/// Scrambling sequence and input stream of the S2 satellite delivery
+/// system descriptor, EN 300 468 6.2.13.3, or of the S2X one.
+fn mis(scrambling: Option<&[u8]>, input_stream: Option<u8>) -> Option<Mis> {
+    if scrambling.is_none() && input_stream.is_none() {
+        return None;
+    }
+
+    Some(Mis {
+        mode: PlsMode::Gold,
+        code: scrambling.map_or(0, |index| {
+            (u32::from(index[0] & 0x03) << 16) | (u32::from(index[1]) << 8) | u32::from(index[2])
+        }),
+        stream_id: input_stream.map_or(0, u32::from),
+    })
+}
+
+/// S2 satellite delivery system descriptor, EN 300 468 6.2.13.3.
+fn s2_satellite(data: &[u8]) -> Option<Mis> {
+    let flags = *data.first()?;
+    let mut rest = &data[1 ..];
+
+    let scrambling = if flags & 0x80 != 0 {
+        let (index, after) = rest.split_at_checked(3)?;
+        rest = after;
+        Some(index)
+    } else {
+        None
+    };
+    let input_stream = if flags & 0x40 != 0 {
+        Some(*rest.first()?)
+    } else {
+        None
+    };
+
+    mis(scrambling, input_stream)
+}
+
+/// S2X satellite delivery system descriptor, EN 300 468 6.4.6.5, from the
+/// tag extension on. Channel bonding is not supported; the primary channel
+/// is taken.
+fn s2x_satellite(data: &[u8]) -> Option<(TuneRequest, SatelliteOrbit)> {
+    // data[1] is receiver_profiles
+    let flags = *data.get(2)?;
+    let mut rest = &data[3 ..];
+
+    let scrambling = if flags & 0x20 != 0 {
+        let (index, after) = rest.split_at_checked(3)?;
+        rest = after;
+        Some(index)
+    } else {
+        None
+    };
+    // timeslice_number of the time-sliced mode
+    if flags >> 6 == 2 {
+        rest = rest.get(1 ..)?;
+    }
+    if rest.len() < 11 {
+        return None;
+    }
+
+    let orbit = SatelliteOrbit {
+        transponder_khz: bcd(rest, 8) * 10,
+        orbital_position: bcd(&rest[4 ..], 4) as u16,
+        east: rest[6] & 0x80 != 0,
+        polarization: polarization(rest[6] >> 5),
+    };
+    let rolloff = match rest[6] & 0x07 {
+        0 => Rolloff::R35,
+        1 => Rolloff::R25,
+        2 => Rolloff::R20,
+        4 => Rolloff::R15,
+        5 => Rolloff::R10,
+        6 => Rolloff::R5,
+        _ => Rolloff::Auto,
+    };
+    // the symbol rate starts in the low nibble
+    let symbolrate =
+        (u32::from_be_bytes([rest[7], rest[8], rest[9], rest[10]]) & 0x0FFF_FFFF).to_be_bytes();
+    let symbolrate = bcd(&symbolrate, 8) * 100;
+    let input_stream = if rest[6] & 0x10 != 0 {
+        Some(*rest.get(11)?)
+    } else {
+        None
+    };
+
+    let request = TuneRequest::DvbS2(DvbS2Tune {
+        symbolrate,
+        rolloff,
+        mis: mis(scrambling, input_stream),
+        ..Default::default()
+    });
+
+    Some((request, orbit))
+}
+
+/// Cable delivery system descriptor, EN 300 468 6.2.13.1.
+fn cable(data: &[u8]) -> Option<TuneRequest> {
+    if data.len() < 11 {
+        return None;
+    }
+
+    Some(TuneRequest::DvbC(DvbCTune {
+        frequency_hz: bcd(data, 8) * 100,
+        symbolrate: bcd(&data[7 ..], 7) * 100,
+        modulation: match data[6] {
+            1 => Modulation::Qam16,
+            2 => Modulation::Qam32,
+            3 => Modulation::Qam64,
+            4 => Modulation::Qam128,
+            5 => Modulation::Qam256,
+            _ => Modulation::QamAuto,
+        },
+        fec: fec_inner(data[10] & 0x0F),
+        ..Default::default()
+    }))
+}
+
+/// Terrestrial delivery system descriptor, EN 300 468 6.2.13.4.
+fn terrestrial(data: &[u8]) -> Option<DvbTTune> {
+    if data.len() < 7 {
+        return None;
+    }
+
+    let code_rate = |value: u8| match value & 0x07 {
+        0 => Fec::Fec1_2,
+        1 => Fec::Fec2_3,
+        2 => Fec::Fec3_4,
+        3 => Fec::Fec5_6,
+        4 => Fec::Fec7_8,
+        _ => Fec::Auto,
+    };
+
+    Some(DvbTTune {
+        frequency_hz: u32::from_be_bytes([data[0], data[1], data[2], data[3]]).checked_mul(10)?,
+        bandwidth_hz: match data[4] >> 5 {
+            0 => 8_000_000,
+            1 => 7_000_000,
+            2 => 6_000_000,
+            3 => 5_000_000,
+            _ => return None,
+        },
+        modulation: match data[5] >> 6 {
+            0 => Modulation::Qpsk,
+            1 => Modulation::Qam16,
+            2 => Modulation::Qam64,
+            _ => Modulation::QamAuto,
+        },
+        hierarchy: match (data[5] >> 3) & 0x03 {
+            0 => Hierarchy::None,
+            1 => Hierarchy::H1,
+            2 => Hierarchy::H2,
+            _ => Hierarchy::H4,
+        },
+        code_rate_hp: code_rate(data[5]),
+        code_rate_lp: code_rate(data[6] >> 5),
+        guard_interval: match (data[6] >> 3) & 0x03 {
+            0 => GuardInterval::Gi1_32,
+            1 => GuardInterval::Gi1_16,
+            2 => GuardInterval::Gi1_8,
+            _ => GuardInterval::Gi1_4,
+        },
+        transmission_mode: match (data[6] >> 1) & 0x03 {
+            0 => TransmitMode::Tm2K,
+            1 => TransmitMode::Tm8K,
+            2 => TransmitMode::Tm4K,
+            _ => TransmitMode::Auto,
+        },
+        ..Default::default()
+    })
+}
+
+/// T2 delivery system descriptor, EN 300 468 6.4.6.3, after the tag
+/// extension: the PLP, the parameters when given, and the centre frequency
+/// of each cell.
+fn t2(data: &[u8]) -> Option<(DvbT2Tune, Vec<u32>)> {
+    let plp_id = *data.get(1)?;
+    let mut tune = DvbT2Tune {
+        stream_id: Some(u32::from(plp_id)),
+        ..Default::default()
+    };
+    let mut frequencies = Vec::new();
+    if data.len() < 6 {
+        return Some((tune, frequencies));
+    }
+
+    tune.bandwidth_hz = match (data[4] >> 2) & 0x0F {
+        0 => 8_000_000,
+        1 => 7_000_000,
+        2 => 6_000_000,
+        3 => 5_000_000,
+        4 => 10_000_000,
+        5 => 1_712_000,
+        _ => tune.bandwidth_hz,
+    };
+    tune.guard_interval = match data[5] >> 5 {
+        0 => GuardInterval::Gi1_32,
+        1 => GuardInterval::Gi1_16,
+        2 => GuardInterval::Gi1_8,
+        3 => GuardInterval::Gi1_4,
+        4 => GuardInterval::Gi1_128,
+        5 => GuardInterval::Gi19_128,
+        6 => GuardInterval::Gi19_256,
+        _ => GuardInterval::Auto,
+    };
+    tune.transmission_mode = match (data[5] >> 2) & 0x07 {
+        0 => TransmitMode::Tm2K,
+        1 => TransmitMode::Tm8K,
+        2 => TransmitMode::Tm4K,
+        3 => TransmitMode::Tm1K,
+        4 => TransmitMode::Tm16K,
+        5 => TransmitMode::Tm32K,
+        _ => TransmitMode::Auto,
+    };
+    let tfs = data[5] & 0x01 != 0;
+
+    let frequency =
+        |bytes: &[u8]| u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]).checked_mul(10);
+    let mut cells = &data[6 ..];
+    while cells.len() >= 2 {
+        cells = &cells[2 ..];
+        let length = if tfs {
+            let length = usize::from(*cells.first()?);
+            cells = &cells[1 ..];
+            length
+        } else {
+            4
+        };
+        let (centres, after) = cells.split_at_checked(length)?;
+        frequencies.extend(centres.chunks_exact(4).filter_map(frequency));
+        let subcells = usize::from(*after.first()?);
+        cells = after.get(1 + subcells ..)?;
+    }
+    frequencies.dedup();
+
+    Some((tune, frequencies))
+}
+
+/// Delivery system descriptors of one transport stream.
+#[derive(Default)]
+struct Delivery {
+    satellite: Option<(TuneRequest, SatelliteOrbit)>,
+    s2: Option<Mis>,
+    s2x: Option<(TuneRequest, SatelliteOrbit)>,
+    cable: Option<TuneRequest>,
+    terrestrial: Option<DvbTTune>,
+    t2: Option<(DvbT2Tune, Vec<u32>)>,
+}
+
+impl Delivery {
+    fn add(&mut self, descriptor: DescriptorRef<'_>) {
+        let data = descriptor.data();
+        match (descriptor.tag(), data.first()) {
+            (0x43, _) => self.satellite = satellite(data),
+            (0x44, _) => self.cable = cable(data),
+            (0x5A, _) => self.terrestrial = terrestrial(data),
+            (0x79, _) => self.s2 = s2_satellite(data),
+            (0x7F, Some(0x04)) => self.t2 = t2(data),
+            (0x7F, Some(0x17)) => self.s2x = s2x_satellite(data),
+            _ => {}
+        }
+    }
+
+    /// Tune requests, with the orbit for satellite ones. A terrestrial
+    /// stream with a T2 descriptor is DVB-T2, at the frequencies of the
+    /// cells or else at the one of the terrestrial descriptor.
+    fn requests(self) -> Vec<(TuneRequest, Option<SatelliteOrbit>)> {
+        if let Some((request, orbit)) = self.satellite {
+            let request = match (request, self.s2) {
+                (TuneRequest::DvbS2(tune), Some(mis)) => TuneRequest::DvbS2(DvbS2Tune {
+                    mis: Some(mis),
+                    ..tune
+                }),
+                (request, _) => request,
+            };
+            return vec![(request, Some(orbit))];
+        }
+        if let Some((request, orbit)) = self.s2x {
+            return vec![(request, Some(orbit))];
+        }
+        if let Some(request) = self.cable {
+            return vec![(request, None)];
+        }
+
+        match (self.t2, self.terrestrial) {
+            (Some((tune, frequencies)), terrestrial) if !frequencies.is_empty() => {
+                let bandwidth_hz = terrestrial.map_or(tune.bandwidth_hz, |t| t.bandwidth_hz);
+                frequencies
+                    .into_iter()
+                    .map(|frequency_hz| {
+                        let request = TuneRequest::DvbT2(DvbT2Tune {
+                            frequency_hz,
+                            bandwidth_hz,
+                            ..tune.clone()
+                        });
+                        (request, None)
+                    })
+                    .collect()
+            }
+            (Some((tune, _)), Some(terrestrial)) => {
+                let request = TuneRequest::DvbT2(DvbT2Tune {
+                    frequency_hz: terrestrial.frequency_hz,
+                    bandwidth_hz: terrestrial.bandwidth_hz,
+                    ..tune
+                });
+                vec![(request, None)]
+            }
+            (_, Some(terrestrial)) => vec![(TuneRequest::DvbT(terrestrial), None)],
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// Decodes the transport streams of a NIT section, actual or other, with
+/// a tune request for each that has a delivery system descriptor this
+/// crate tunes: satellite with S2 and S2X, cable, terrestrial and T2.
+/// A T2 stream broadcast from several cells is listed once per frequency.
+pub fn decode_nit(section: &[u8]) -> Result<Vec<NetworkTransport>> {
+    let nit = NitSectionRef::try_from(section)
+        .map_err(|e| Error::InvalidData(format!("NIT section: {}", e)))?;
+
+    let mut transports = Vec::new();
+    for stream in nit.transport_streams() {
+        let stream = stream.map_err(|e| Error::InvalidData(format!("NIT section: {}", e)))?;
+
+        let mut delivery = Delivery::default();
+        for descriptor in stream.transport_stream_descriptors().into_iter().flatten() {
+            let descriptor =
+                descriptor.map_err(|e| Error::InvalidData(format!("NIT section: {}", e)))?;
+            delivery.add(descriptor);
+        }
+
+        for (request, satellite) in delivery.requests() {
+            transports.push(NetworkTransport {
+                network_id: nit.network_id(),
+                original_network_id: stream.original_network_id(),
+                transport_stream_id: stream.transport_stream_id(),
+                request,
+                satellite,
+            });
+        }
+    }
+
+    Ok(transports)
+}
+
+/// Parameters of [`NetworkScanner::read`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NitScan {
+    /// How long the NIT may take. EN 300 468 repeats it at least every
+    /// 10 s.
+    pub timeout: Duration,
+    /// Reads the NIT other as well, with the transport streams of the
+    /// other networks the stream knows of. The read then lasts until a
+    /// section comes round a second time, rather than until the NIT
+    /// actual is complete.
+    pub other: bool,
+}
+
+impl Default for NitScan {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(12),
+            other: true,
+        }
+    }
+}
+
+/// Works through the transport streams of a network, as NITs list them.
+///
+/// Each multiplex is handed out once by [`NetworkScanner::next_transport`],
+/// however many NITs list it; see [`NetworkTransport::same_multiplex`]. The
+/// usual round is:
+///
+/// 1. [`NetworkScanner::add`] the home transponder;
+/// 2. for each [`NetworkScanner::next_transport`]: the SEC setup with its
+///    transponder and polarization, the tune of the intermediate frequency,
+///    and on a lock [`NetworkScanner::read`].
+///
+/// The NIT other of a satellite network may list transport streams of
+/// other orbital positions; the caller skips those it cannot reach.
+#[derive(Debug, Clone, Default)]
+pub struct NetworkScanner {
+    transports: Vec<NetworkTransport>,
+    next: usize,
+}
+
+impl NetworkScanner {
+    /// Empty scanner.
+    pub fn new() -> NetworkScanner {
+        NetworkScanner::default()
+    }
+
+    /// Adds a transport stream unless its multiplex is already there.
+    /// Returns `true` if it was new.
+    pub fn add(&mut self, transport: NetworkTransport) -> bool {
+        if self
+            .transports
+            .iter()
+            .any(|known| known.same_multiplex(&transport))
+        {
+            return false;
+        }
+
+        self.transports.push(transport);
+        true
+    }
+
+    /// Adds the transport streams of a NIT section. Returns how many were
+    /// new.
+    pub fn add_section(&mut self, section: &[u8]) -> Result<usize> {
+        Ok(decode_nit(section)?
+            .into_iter()
+            .filter(|transport| self.add(transport.clone()))
+            .count())
+    }
+
+    /// Reads the NIT on the transponder the frontend is tuned to, through
+    /// a demux of the same adapter, and adds its transport streams. Returns
+    /// how many were new. Fails with `ETIMEDOUT` when no NIT actual arrives.
+    pub fn read(&mut self, dmx: &mut DmxDevice, scan: &NitScan) -> Result<usize> {
+        let sections = if scan.other {
+            // one filter for both: the read ends once the carousel comes
+            // round, and a transponder without a NIT other does not wait
+            // out the timeout for it
+            let sections =
+                dmx.read_tables(NIT_PID, NIT_ACTUAL, !(NIT_ACTUAL ^ NIT_OTHER), scan.timeout)?;
+            let mut actual = SectionCollector::new();
+            for section in sections.iter().filter(|section| section[0] == NIT_ACTUAL) {
+                actual.push(section.clone())?;
+            }
+            if !actual.is_complete() {
+                return Err(Error::Nix(nix::errno::Errno::ETIMEDOUT));
+            }
+            sections
+        } else {
+            dmx.read_table(NIT_PID, NIT_ACTUAL, None, scan.timeout)?
+        };
+
+        let mut added = 0;
+        for section in &sections {
+            added += self.add_section(section)?;
+        }
+
+        Ok(added)
+    }
+
+    /// The next transport stream to tune, in the order they were found;
+    /// `None` once every one was handed out.
+    pub fn next_transport(&mut self) -> Option<NetworkTransport> {
+        let transport = self.transports.get(self.next)?.clone();
+        self.next += 1;
+        Some(transport)
+    }
+
+    /// Every transport stream found so far.
+    pub fn transports(&self) -> &[NetworkTransport] {
+        &self.transports
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use libmpegts::utils::crc32b;
+
+    use super::*;
+
+    /// NIT section of network 0x0085 with the given transport streams:
+    /// (transport stream id, descriptors).
+    fn nit(table_id: u8, streams: &[(u16, Vec<u8>)]) -> Vec<u8> {
+        let mut loop_bytes = Vec::new();
+        for (tsid, descriptors) in streams {
+            loop_bytes.extend_from_slice(&tsid.to_be_bytes());
+            loop_bytes.extend_from_slice(&0x0001u16.to_be_bytes());
+            loop_bytes.extend_from_slice(&(0xF000 | descriptors.len() as u16).to_be_bytes());
+            loop_bytes.extend_from_slice(descriptors);
+        }
+
+        let mut section = vec![table_id, 0, 0, 0x00, 0x85, 0xC1, 0x00, 0x00, 0xF0, 0x00];
+        section.extend_from_slice(&(0xF000 | loop_bytes.len() as u16).to_be_bytes());
+        section.extend_from_slice(&loop_bytes);
+        let length = (section.len() - 3 + 4) as u16;
+        section[1 .. 3].copy_from_slice(&(0xF000 | length).to_be_bytes());
+        let crc = crc32b(&section);
+        section.extend_from_slice(&crc.to_be_bytes());
+        section
+    }
+
+    #[test]
+    fn satellite_descriptors() {
+        let section = nit(
+            NIT_ACTUAL,
+            &[
+                // 11.362 GHz, 19.2E, horizontal, DVB-S2 8PSK 0.35, 22000, 2/3
+                (
+                    1019,
+                    vec![
+                        0x43, 0x0B, 0x01, 0x13, 0x62, 0x00, 0x01, 0x92, 0x86, 0x02, 0x20, 0x00,
+                        0x02,
+                    ],
+                ),
+                // 12.603 GHz, 19.2E, horizontal, DVB-S QPSK, 22000, 5/6
+                (
+                    1111,
+                    vec![
+                        0x43, 0x0B, 0x01, 0x26, 0x03, 0x00, 0x01, 0x92, 0x81, 0x02, 0x20, 0x00,
+                        0x04,
+                    ],
+                ),
+                // 11.013 GHz, 5.0W, vertical, S2 with a scrambling index
+                // and input stream 4
+                (
+                    7,
+                    vec![
+                        0x43, 0x0B, 0x01, 0x10, 0x13, 0x00, 0x00, 0x50, 0x26, 0x03, 0x00, 0x00,
+                        0x00, 0x79, 0x05, 0xC0, 0x00, 0x01, 0x02, 0x04,
+                    ],
+                ),
+            ],
+        );
+
+        let transports = decode_nit(&section).unwrap();
+        assert_eq!(transports.len(), 3);
+        assert_eq!(transports[0].network_id, 0x0085);
+        assert_eq!(transports[0].transport_stream_id, 1019);
+        assert_eq!(
+            transports[0].request,
+            TuneRequest::DvbS2(DvbS2Tune {
+                symbolrate: 22_000_000,
+                modulation: Modulation::Psk8,
+                fec: Fec::Fec2_3,
+                rolloff: Rolloff::R35,
+                ..Default::default()
+            })
+        );
+        let orbit = transports[0].satellite.unwrap();
+        assert_eq!(orbit.transponder_khz, 11_362_000);
+        assert_eq!(orbit.to_string(), "19.2E H");
+        assert_eq!(orbit.degrees(), 19.2);
+
+        assert_eq!(
+            transports[1].request,
+            TuneRequest::DvbS(DvbSTune {
+                symbolrate: 22_000_000,
+                fec: Fec::Fec5_6,
+                ..Default::default()
+            })
+        );
+        assert_eq!(transports[1].satellite.unwrap().transponder_khz, 12_603_000);
+
+        let TuneRequest::DvbS2(tune) = &transports[2].request else {
+            panic!("not DVB-S2");
+        };
+        assert_eq!(
+            tune.mis,
+            Some(Mis {
+                mode: PlsMode::Gold,
+                code: 0x102,
+                stream_id: 4,
+            })
+        );
+        assert_eq!(transports[2].satellite.unwrap().to_string(), "5.0W V");
+
+        // modulation_type 0 leaves the modulation to the frontend
+        let (request, _) = satellite(&[
+            0x01, 0x13, 0x62, 0x00, 0x01, 0x92, 0x84, 0x02, 0x20, 0x00, 0x02,
+        ])
+        .unwrap();
+        let TuneRequest::DvbS2(tune) = request else {
+            panic!("not DVB-S2");
+        };
+        assert_eq!(tune.modulation, Modulation::QamAuto);
+    }
+
+    #[test]
+    fn s2x_descriptor() {
+        // 11.727 GHz, 9.0E, vertical, roll-off 0.15, 27500, scrambling
+        // index 5 and input stream 3
+        let section = nit(
+            NIT_ACTUAL,
+            &[(
+                42,
+                vec![
+                    0x7F, 0x12, 0x17, 0x08, 0x63, 0xFC, 0x00, 0x05, 0x01, 0x17, 0x27, 0x00, 0x00,
+                    0x90, 0xB4, 0xF0, 0x27, 0x50, 0x00, 0x03,
+                ],
+            )],
+        );
+
+        let transports = decode_nit(&section).unwrap();
+        assert_eq!(transports.len(), 1);
+        assert_eq!(
+            transports[0].request,
+            TuneRequest::DvbS2(DvbS2Tune {
+                symbolrate: 27_500_000,
+                rolloff: Rolloff::R15,
+                mis: Some(Mis {
+                    mode: PlsMode::Gold,
+                    code: 5,
+                    stream_id: 3,
+                }),
+                ..Default::default()
+            })
+        );
+        let orbit = transports[0].satellite.unwrap();
+        assert_eq!(orbit.transponder_khz, 11_727_000);
+        assert_eq!(orbit.to_string(), "9.0E V");
+    }
+
+    #[test]
+    fn cable_and_terrestrial_descriptors() {
+        let section = nit(
+            NIT_OTHER,
+            &[
+                // 346 MHz, 256-QAM, 6900
+                (
+                    1,
+                    vec![
+                        0x44, 0x0B, 0x03, 0x46, 0x00, 0x00, 0xFF, 0xF2, 0x05, 0x00, 0x69, 0x00,
+                        0x0F,
+                    ],
+                ),
+                // 594 MHz, 8 MHz, 64-QAM, 2/3, 1/4, 8k
+                (
+                    2,
+                    vec![
+                        0x5A, 0x0B, 0x03, 0x8A, 0x5F, 0x40, 0x1F, 0x81, 0x1A, 0xFF, 0xFF, 0xFF,
+                        0xFF,
+                    ],
+                ),
+                // T2 PLP 1 on two cells, 490 and 506 MHz
+                (
+                    3,
+                    vec![
+                        0x5A, 0x0B, 0x02, 0xEB, 0xAE, 0x40, 0x1F, 0x81, 0x1A, 0xFF, 0xFF, 0xFF,
+                        0xFF, 0x7F, 0x14, 0x04, 0x01, 0x00, 0x05, 0x00, 0x64, 0x00, 0x01, 0x02,
+                        0xEB, 0xAE, 0x40, 0x00, 0x00, 0x02, 0x03, 0x04, 0x18, 0x40, 0x00,
+                    ],
+                ),
+            ],
+        );
+
+        let transports = decode_nit(&section).unwrap();
+        let requests: Vec<&TuneRequest> = transports.iter().map(|t| &t.request).collect();
+        assert_eq!(
+            requests[0],
+            &TuneRequest::DvbC(DvbCTune {
+                frequency_hz: 346_000_000,
+                symbolrate: 6_900_000,
+                modulation: Modulation::Qam256,
+                fec: Fec::None,
+                ..Default::default()
+            })
+        );
+        assert_eq!(
+            requests[1],
+            &TuneRequest::DvbT(DvbTTune {
+                frequency_hz: 594_000_000,
+                bandwidth_hz: 8_000_000,
+                modulation: Modulation::Qam64,
+                code_rate_hp: Fec::Fec2_3,
+                code_rate_lp: Fec::Fec1_2,
+                guard_interval: GuardInterval::Gi1_4,
+                transmission_mode: TransmitMode::Tm8K,
+                ..Default::default()
+            })
+        );
+        let t2: Vec<(u32, Option<u32>)> = requests[2 ..]
+            .iter()
+            .map(|request| match request {
+                TuneRequest::DvbT2(tune) => (tune.frequency_hz, tune.stream_id),
+                _ => panic!("not DVB-T2"),
+            })
+            .collect();
+        assert_eq!(t2, [(490_000_000, Some(1)), (506_000_000, Some(1))]);
+        assert!(transports.iter().all(|t| t.satellite.is_none()));
+    }
+
+    #[test]
+    fn scanner_hands_out_each_multiplex_once() {
+        let home = TuneRequest::DvbS2(DvbS2Tune {
+            symbolrate: 22_000_000,
+            ..Default::default()
+        });
+        let horizontal = SatelliteOrbit {
+            transponder_khz: 11_361_000,
+            orbital_position: 192,
+            east: true,
+            polarization: Polarization::Horizontal,
+        };
+
+        let mut scanner = NetworkScanner::new();
+        assert!(scanner.add(NetworkTransport::new(home, Some(horizontal))));
+        let home = scanner.next_transport().unwrap();
+        assert_eq!(home.transport_stream_id, 0);
+        assert!(scanner.next_transport().is_none());
+
+        // the home transponder 1 MHz off, and a new one
+        let section = nit(
+            NIT_ACTUAL,
+            &[
+                (
+                    1019,
+                    vec![
+                        0x43, 0x0B, 0x01, 0x13, 0x62, 0x00, 0x01, 0x92, 0x86, 0x02, 0x20, 0x00,
+                        0x02,
+                    ],
+                ),
+                (
+                    1111,
+                    vec![
+                        0x43, 0x0B, 0x01, 0x26, 0x03, 0x00, 0x01, 0x92, 0x81, 0x02, 0x20, 0x00,
+                        0x04,
+                    ],
+                ),
+            ],
+        );
+        assert_eq!(scanner.add_section(&section).unwrap(), 1);
+        // the NIT of the next transponder lists the same ones again
+        assert_eq!(scanner.add_section(&section).unwrap(), 0);
+
+        assert_eq!(scanner.next_transport().unwrap().transport_stream_id, 1111);
+        assert!(scanner.next_transport().is_none());
+        assert_eq!(scanner.transports().len(), 2);
+
+        assert!(scanner.add_section(&section[.. 20]).is_err());
+    }
+}
@@ -0,0 +1,457 @@
+//! Service scan from the PAT, SDT and PMTs.
+//!
+//! The PAT of a transport stream lists its services with the PID of each
+//! PMT, the SDT actual names them, and each PMT lists the components of
+//! its service and the CA systems that scramble it.
+
+use std::time::Duration;
+
+use libmpegts::psi::{
+    CaDescriptorRef,
+    DescriptorsRef,
+    PAT_PID,
+    PatSectionRef,
+    PmtSectionRef,
+    SDT_PID,
+    SdtSectionRef,
+    ServiceDescriptorRef,
+};
+
+use crate::{
+    dmx::DmxDevice,
+    error::{
+        Error,
+        Result,
+    },
+    fe::TuneRequest,
+};
+
+/// Table id of the PAT.
+const PAT_TABLE: u8 = 0x00;
+/// Table id of the PMT.
+const PMT_TABLE: u8 = 0x02;
+/// Table id of the SDT of the actual transport stream.
+const SDT_ACTUAL: u8 = 0x42;
+
+/// One component of a service.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServiceStream {
+    /// Elementary PID.
+    pub pid: u16,
+    /// ISO/IEC 13818-1 stream type, such as 0x1B for H.264 video.
+    pub stream_type: u8,
+}
+
+/// A service of a transport stream.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Service {
+    /// Service id, the program number of the PAT and PMT.
+    pub service_id: u16,
+    /// Service name from the SDT, empty without one.
+    pub name: String,
+    /// Service provider name from the SDT, empty without one.
+    pub provider: String,
+    /// EN 300 468 service type, such as 0x01 for digital television or
+    /// 0x02 for digital radio. 0 without an SDT entry.
+    pub service_type: u8,
+    /// PID of the PMT. 0 for a service the SDT lists but the PAT does not.
+    pub pmt_pid: u16,
+    /// PID of the PCR, from the PMT.
+    pub pcr_pid: u16,
+    /// Components, from the PMT.
+    pub streams: Vec<ServiceStream>,
+    /// CA system ids of the CA descriptors of the PMT, program and
+    /// component level, each once.
+    pub ca_system_ids: Vec<u16>,
+    /// `true` if the service is scrambled: the free CA mode of the SDT, or
+    /// without an SDT entry whether the PMT has CA descriptors.
+    pub scrambled: bool,
+}
+
+/// Timeouts of [`ServiceList::read`], one per table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ServiceScan {
+    /// How long the PAT may take. Without one the read fails.
+    pub pat_timeout: Duration,
+    /// How long the SDT actual may take. EN 300 468 repeats it at least
+    /// every 2 s; ATSC streams carry none, and the services stay unnamed.
+    pub sdt_timeout: Duration,
+    /// How long each PMT may take. A service whose PMT does not arrive
+    /// keeps no components.
+    pub pmt_timeout: Duration,
+}
+
+impl Default for ServiceScan {
+    fn default() -> Self {
+        Self {
+            pat_timeout: Duration::from_secs(2),
+            sdt_timeout: Duration::from_secs(3),
+            pmt_timeout: Duration::from_secs(2),
+        }
+    }
+}
+
+/// The services of one transport stream.
+///
+/// [`ServiceList::read`] reads the tables through a demux of the adapter
+/// whose frontend is tuned to `request`; the `add_*` methods take in
+/// sections from anywhere else, in any order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServiceList {
+    /// Request the frontend was tuned with.
+    pub request: TuneRequest,
+    /// Transport stream id, from the PAT.
+    pub transport_stream_id: u16,
+    /// Original network id, from the SDT; 0 without one.
+    pub original_network_id: u16,
+    /// Services, by service id.
+    pub services: Vec<Service>,
+    /// Services with an SDT entry.
+    described: Vec<u16>,
+}
+
+fn invalid(table: &str, error: impl std::fmt::Display) -> Error {
+    Error::InvalidData(format!("{} section: {}", table, error))
+}
+
+impl ServiceList {
+    /// Empty list for the transport stream `request` tunes.
+    pub fn new(request: TuneRequest) -> ServiceList {
+        ServiceList {
+            request,
+            transport_stream_id: 0,
+            original_network_id: 0,
+            services: Vec::new(),
+            described: Vec::new(),
+        }
+    }
+
+    /// Reads the PAT, the SDT actual and the PMT of each service, on the
+    /// transport stream the frontend is tuned to with `request`. Fails with
+    /// `ETIMEDOUT` when no PAT arrives; a missing SDT or PMT leaves the
+    /// services it would describe as they are.
+    pub fn read(
+        dmx: &mut DmxDevice,
+        request: TuneRequest,
+        scan: &ServiceScan,
+    ) -> Result<ServiceList> {
+        let mut list = ServiceList::new(request);
+
+        for section in dmx.read_table(PAT_PID, PAT_TABLE, None, scan.pat_timeout)? {
+            list.add_pat(&section)?;
+        }
+
+        match dmx.read_table(SDT_PID, SDT_ACTUAL, None, scan.sdt_timeout) {
+            Ok(sections) => {
+                for section in sections {
+                    list.add_sdt(&section)?;
+                }
+            }
+            Err(Error::Nix(nix::errno::Errno::ETIMEDOUT)) => {}
+            Err(e) => return Err(e),
+        }
+
+        let programs: Vec<(u16, u16)> = list
+            .services
+            .iter()
+            .filter(|service| service.pmt_pid != 0)
+            .map(|service| (service.pmt_pid, service.service_id))
+            .collect();
+        for (pid, service_id) in programs {
+            match dmx.read_table(pid, PMT_TABLE, Some(service_id), scan.pmt_timeout) {
+                Ok(sections) => {
+                    for section in sections {
+                        list.add_pmt(&section)?;
+                    }
+                }
+                Err(Error::Nix(nix::errno::Errno::ETIMEDOUT)) => {}
+                Err(e) => return Err(e),
+            }
+        }
+
+        Ok(list)
+    }
+
+    /// The service `service_id`, added if it is not there yet.
+    fn service_mut(&mut self, service_id: u16) -> &mut Service {
+        let index = match self
+            .services
+            .binary_search_by_key(&service_id, |service| service.service_id)
+        {
+            Ok(index) => index,
+            Err(index) => {
+                self.services.insert(
+                    index,
+                    Service {
+                        service_id,
+                        ..Default::default()
+                    },
+                );
+                index
+            }
+        };
+
+        &mut self.services[index]
+    }
+
+    /// The service `service_id`.
+    pub fn service(&self, service_id: u16) -> Option<&Service> {
+        self.services
+            .binary_search_by_key(&service_id, |service| service.service_id)
+            .ok()
+            .map(|index| &self.services[index])
+    }
+
+    /// Takes in a PAT section: the transport stream id, and the services
+    /// with their PMT PIDs. The NIT entry, program number 0, is skipped.
+    pub fn add_pat(&mut self, section: &[u8]) -> Result<()> {
+        let pat = PatSectionRef::try_from(section).map_err(|e| invalid("PAT", e))?;
+        self.transport_stream_id = pat.transport_stream_id();
+
+        for program in pat.programs() {
+            let program = program.map_err(|e| invalid("PAT", e))?;
+            if program.program_number() != 0 {
+                self.service_mut(program.program_number()).pmt_pid = program.pid();
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Takes in an SDT section: the original network id, and the name,
+    /// provider, type and free CA mode of the services.
+    pub fn add_sdt(&mut self, section: &[u8]) -> Result<()> {
+        let sdt = SdtSectionRef::try_from(section).map_err(|e| invalid("SDT", e))?;
+        self.original_network_id = sdt.original_network_id();
+
+        for entry in sdt.services() {
+            let entry = entry.map_err(|e| invalid("SDT", e))?;
+            if !self.described.contains(&entry.service_id()) {
+                self.described.push(entry.service_id());
+            }
+            let service = self.service_mut(entry.service_id());
+            service.scrambled = entry.free_ca_mode();
+
+            for descriptor in entry.service_descriptors().into_iter().flatten() {
+                let descriptor = descriptor.map_err(|e| invalid("SDT", e))?;
+                if descriptor.tag() != ServiceDescriptorRef::TAG {
+                    continue;
+                }
+
+                let descriptor =
+                    ServiceDescriptorRef::try_from(descriptor).map_err(|e| invalid("SDT", e))?;
+                service.service_type = descriptor.service_type();
+                service.provider = match descriptor.provider_name_text() {
+                    Ok(text) => text.to_string(),
+                    Err(_) => String::from_utf8_lossy(descriptor.provider_name()).into_owned(),
+                };
+                service.name = match descriptor.service_name_text() {
+                    Ok(text) => text.to_string(),
+                    Err(_) => String::from_utf8_lossy(descriptor.service_name()).into_owned(),
+                };
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Takes in a PMT section: the PCR PID, the components and the CA
+    /// systems of its service.
+    pub fn add_pmt(&mut self, section: &[u8]) -> Result<()> {
+        let pmt = PmtSectionRef::try_from(section).map_err(|e| invalid("PMT", e))?;
+
+        let mut streams = Vec::new();
+        let mut ca_system_ids = Vec::new();
+        add_ca_system_ids(&mut ca_system_ids, pmt.program_descriptors())?;
+        for stream in pmt.streams() {
+            let stream = stream.map_err(|e| invalid("PMT", e))?;
+            streams.push(ServiceStream {
+                pid: stream.elementary_pid(),
+                stream_type: stream.stream_type(),
+            });
+            add_ca_system_ids(&mut ca_system_ids, stream.stream_descriptors())?;
+        }
+
+        let described = self.described.contains(&pmt.program_number());
+        let service = self.service_mut(pmt.program_number());
+        service.pcr_pid = pmt.pcr_pid();
+        service.streams = streams;
+        if !described {
+            service.scrambled = !ca_system_ids.is_empty();
+        }
+        service.ca_system_ids = ca_system_ids;
+
+        Ok(())
+    }
+}
+
+/// Adds the CA system ids of the CA descriptors in `descriptors` that are
+/// not in `ids` yet.
+fn add_ca_system_ids(ids: &mut Vec<u16>, descriptors: Option<DescriptorsRef<'_>>) -> Result<()> {
+    for descriptor in descriptors.into_iter().flatten() {
+        let descriptor = descriptor.map_err(|e| invalid("PMT", e))?;
+        if descriptor.tag() != CaDescriptorRef::TAG {
+            continue;
+        }
+
+        let ca = CaDescriptorRef::try_from(descriptor).map_err(|e| invalid("PMT", e))?;
+        if !ids.contains(&ca.ca_system_id()) {
+            ids.push(ca.ca_system_id());
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use libmpegts::{
+        psi::{
+            PatBuilder,
+            PatConfig,
+            PatProgram,
+            PmtBuilder,
+            PmtConfig,
+            PmtStream,
+        },
+        utils::crc32b,
+    };
+
+    use super::*;
+    use crate::fe::DvbCTune;
+
+    fn pat() -> Vec<u8> {
+        let programs = [(0, 0x0010), (0x2B66, 0x0064), (0x2B67, 0x0065)]
+            .into_iter()
+            .map(|(program_number, pid)| PatProgram {
+                program_number,
+                pid,
+            })
+            .collect();
+        PatBuilder::build(PatConfig {
+            transport_stream_id: 1019,
+            version: 0,
+            programs,
+        })[0]
+            .to_vec()
+    }
+
+    fn pmt(program_number: u16, ca: bool) -> Vec<u8> {
+        let program_descriptors = if ca {
+            vec![0x09, 0x04, 0x09, 0x8D, 0xE0, 0x40]
+        } else {
+            Vec::new()
+        };
+        let stream = |stream_type, elementary_pid| PmtStream {
+            stream_type,
+            elementary_pid,
+            stream_descriptors: Vec::new(),
+        };
+        PmtBuilder::build(PmtConfig {
+            program_number,
+            pcr_pid: 0x00FF,
+            version: 0,
+            program_descriptors,
+            streams: vec![stream(0x1B, 0x00FF), stream(0x04, 0x0100)],
+        })[0]
+            .to_vec()
+    }
+
+    /// SDT actual of transport stream 1019 with the given services:
+    /// (service id, free CA mode, provider, name).
+    fn sdt(services: &[(u16, bool, &[u8], &[u8])]) -> Vec<u8> {
+        let mut loop_bytes = Vec::new();
+        for (service_id, scrambled, provider, name) in services {
+            let mut descriptor = vec![0x48, 0, 0x01, provider.len() as u8];
+            descriptor.extend_from_slice(provider);
+            descriptor.push(name.len() as u8);
+            descriptor.extend_from_slice(name);
+            descriptor[1] = (descriptor.len() - 2) as u8;
+
+            loop_bytes.extend_from_slice(&service_id.to_be_bytes());
+            loop_bytes.push(0xFC);
+            let status = 0x8000 | if *scrambled { 0x1000 } else { 0 };
+            loop_bytes.extend_from_slice(&(status | descriptor.len() as u16).to_be_bytes());
+            loop_bytes.extend_from_slice(&descriptor);
+        }
+
+        let mut section = vec![
+            SDT_ACTUAL, 0, 0, 0x03, 0xFB, 0xC1, 0x00, 0x00, 0x00, 0x01, 0xFF,
+        ];
+        section.extend_from_slice(&loop_bytes);
+        let length = (section.len() - 3 + 4) as u16;
+        section[1 .. 3].copy_from_slice(&(0xF000 | length).to_be_bytes());
+        let crc = crc32b(&section);
+        section.extend_from_slice(&crc.to_be_bytes());
+        section
+    }
+
+    #[test]
+    fn services_from_pat_sdt_and_pmt() {
+        let mut list = ServiceList::new(TuneRequest::DvbC(DvbCTune::default()));
+        list.add_pat(&pat()).unwrap();
+        list.add_sdt(&sdt(&[
+            (0x2B66, false, b"ARD", b"Das Erste HD"),
+            // ISO/IEC 8859-5 text
+            (
+                0x2B67,
+                true,
+                b"\x01\xBD\xC2\xB2",
+                b"\x01\xBF\xD5\xE0\xD2\xEB\xD9",
+            ),
+        ]))
+        .unwrap();
+        list.add_pmt(&pmt(0x2B66, false)).unwrap();
+        list.add_pmt(&pmt(0x2B67, true)).unwrap();
+
+        assert_eq!(list.transport_stream_id, 1019);
+        assert_eq!(list.original_network_id, 1);
+        assert_eq!(list.services.len(), 2);
+
+        let first = list.service(0x2B66).unwrap();
+        assert_eq!(first.name, "Das Erste HD");
+        assert_eq!(first.provider, "ARD");
+        assert_eq!(first.service_type, 0x01);
+        assert_eq!(first.pmt_pid, 0x0064);
+        assert_eq!(first.pcr_pid, 0x00FF);
+        assert_eq!(
+            first.streams,
+            [
+                ServiceStream {
+                    pid: 0x00FF,
+                    stream_type: 0x1B,
+                },
+                ServiceStream {
+                    pid: 0x0100,
+                    stream_type: 0x04,
+                },
+            ]
+        );
+        assert!(!first.scrambled);
+
+        let second = list.service(0x2B67).unwrap();
+        assert_eq!(second.name, "Первый");
+        assert_eq!(second.provider, "НТВ");
+        assert_eq!(second.ca_system_ids, [0x098D]);
+        assert!(second.scrambled);
+    }
+
+    #[test]
+    fn tables_in_any_order_and_without_sdt() {
+        let mut list = ServiceList::new(TuneRequest::DvbC(DvbCTune::default()));
+        list.add_pmt(&pmt(0x2B67, true)).unwrap();
+        list.add_pat(&pat()).unwrap();
+
+        let second = list.service(0x2B67).unwrap();
+        assert_eq!(second.pmt_pid, 0x0065);
+        assert!(second.name.is_empty());
+        // no SDT entry: the CA descriptors tell
+        assert!(second.scrambled);
+        assert_eq!(list.service(0x2B66).unwrap().streams, []);
+        assert!(list.service(0).is_none());
+
+        let mut broken = pat();
+        broken[8] ^= 0xFF;
+        assert!(list.add_pat(&broken).is_err());
+    }
+}
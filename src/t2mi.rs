@@ -0,0 +1,298 @@
+//! DVB-T2 modulator interface (TS 102 773).
+//!
+//! A T2-MI stream feeds DVB-T2 transmitters: the baseband frames of every
+//! PLP, the L1 signalling and timestamps, cut into T2-MI packets and carried
+//! on one PID of a transport stream - on satellite, often the only way a
+//! terrestrial multiplex is distributed. A packet that starts a T2-MI
+//! packet has the payload unit start indicator set and a pointer field to
+//! the start, as a section would.
+//!
+//! [`T2miDecoder`] takes the transport stream of one PLP back out: it
+//! reassembles the T2-MI packets, keeps the baseband frames of the PLP and
+//! rebuilds the transport stream from them.
+
+use libmpegts::utils::crc32b;
+
+use crate::bbframe::{
+    BBHEADER_SIZE,
+    BbFrame,
+    BbHeader,
+    TsPackets,
+    TsReassembler,
+    ts_payload,
+};
+
+/// T2-MI packet type of a baseband frame.
+pub const T2MI_BASEBAND_FRAME: u8 = 0x00;
+
+/// Size of the T2-MI packet header.
+const HEADER_SIZE: usize = 6;
+
+/// Takes the transport stream of one PLP out of a T2-MI stream.
+///
+/// The T2-MI PID is pushed in chunks of any size as a
+/// [`DvrDevice`](crate::DvrDevice) or a [`DmxDevice`](crate::dmx::DmxDevice)
+/// TS tap hands it over; other PIDs in the chunks are passed over. A T2-MI
+/// packet with a bad CRC-32, or one that loses a TS packet on the way, is
+/// dropped, and the decoder waits for the next one to start.
+#[derive(Debug)]
+pub struct T2miDecoder {
+    pid: u16,
+    plp: u8,
+    packets: TsPackets,
+    continuity: Option<u8>,
+    /// T2-MI packets being reassembled, `None` until one starts.
+    buffer: Option<Vec<u8>>,
+    ts: TsReassembler,
+    plps: Vec<u8>,
+    errors: usize,
+}
+
+impl T2miDecoder {
+    /// Decoder of PLP `plp` of the T2-MI stream on `pid`.
+    pub fn new(pid: u16, plp: u8) -> T2miDecoder {
+        T2miDecoder {
+            pid,
+            plp,
+            packets: TsPackets::default(),
+            continuity: None,
+            buffer: None,
+            ts: TsReassembler::new(Some(plp)),
+            plps: Vec::new(),
+            errors: 0,
+        }
+    }
+
+    /// PLPs whose baseband frames arrived so far, in order.
+    pub fn plps(&self) -> &[u8] {
+        &self.plps
+    }
+
+    /// T2-MI packets dropped so far, for a bad CRC-32 or a lost TS packet.
+    pub fn errors(&self) -> usize {
+        self.errors
+    }
+
+    /// TS packets of the PLP whose CRC-8 did not match, so far.
+    pub fn crc_errors(&self) -> usize {
+        self.ts.crc_errors()
+    }
+
+    /// Takes in a chunk of the outer transport stream. Returns the TS
+    /// packets of the PLP it completes, in a row, with the deleted null
+    /// packets put back.
+    pub fn push(&mut self, data: &[u8]) -> Vec<u8> {
+        let mut ts = Vec::new();
+        let mut packets = std::mem::take(&mut self.packets);
+        packets.push(data, |packet| self.push_packet(packet, &mut ts));
+        self.packets = packets;
+
+        ts
+    }
+
+    fn push_packet(&mut self, packet: &[u8], ts: &mut Vec<u8>) {
+        let pid = u16::from_be_bytes([packet[1] & 0x1F, packet[2]]);
+        if pid != self.pid || packet[1] & 0x80 != 0 {
+            return;
+        }
+        let Some(payload) = ts_payload(packet) else {
+            return;
+        };
+
+        let continuity = packet[3] & 0x0F;
+        let lost = self
+            .continuity
+            .is_some_and(|last| (last + 1) & 0x0F != continuity);
+        self.continuity = Some(continuity);
+        if lost && self.buffer.take().is_some_and(|buffer| !buffer.is_empty()) {
+            self.errors += 1;
+        }
+
+        if packet[1] & 0x40 == 0 {
+            if let Some(buffer) = self.buffer.as_mut() {
+                buffer.extend_from_slice(payload);
+            }
+        } else {
+            let Some((&pointer, payload)) = payload.split_first() else {
+                return;
+            };
+            let pointer = usize::from(pointer).min(payload.len());
+            if let Some(buffer) = self.buffer.as_mut() {
+                buffer.extend_from_slice(&payload[.. pointer]);
+                self.drain(ts);
+            }
+            // what is left of the packets before is stuffing
+            self.buffer = Some(payload[pointer ..].to_vec());
+        }
+
+        self.drain(ts);
+    }
+
+    /// Decodes the complete T2-MI packets at the start of the buffer.
+    fn drain(&mut self, ts: &mut Vec<u8>) {
+        let Some(mut buffer) = self.buffer.take() else {
+            return;
+        };
+
+        let mut start = 0;
+        loop {
+            let rest = &buffer[start ..];
+            if rest.len() < HEADER_SIZE {
+                break;
+            }
+            let payload_bits = usize::from(u16::from_be_bytes([rest[4], rest[5]]));
+            let size = HEADER_SIZE + payload_bits.div_ceil(8) + 4;
+            if rest.len() < size {
+                break;
+            }
+
+            let (packet, crc) = rest[.. size].split_at(size - 4);
+            if crc32b(packet) != u32::from_be_bytes([crc[0], crc[1], crc[2], crc[3]]) {
+                // stuffing, or a packet that lost data: wait for the next
+                // start
+                if packet[0] != 0xFF {
+                    self.errors += 1;
+                }
+                return;
+            }
+            self.t2mi_packet(packet[0], &packet[HEADER_SIZE ..], ts);
+            start += size;
+        }
+
+        buffer.drain(.. start);
+        self.buffer = Some(buffer);
+    }
+
+    /// Handles the payload of one T2-MI packet.
+    fn t2mi_packet(&mut self, packet_type: u8, payload: &[u8], ts: &mut Vec<u8>) {
+        // frame_idx, plp_id, intl_frame_start and the baseband frame
+        if packet_type != T2MI_BASEBAND_FRAME || payload.len() < 3 + BBHEADER_SIZE {
+            return;
+        }
+
+        let plp = payload[1];
+        if let Err(i) = self.plps.binary_search(&plp) {
+            self.plps.insert(i, plp);
+        }
+        if plp != self.plp {
+            return;
+        }
+
+        let frame = &payload[3 ..];
+        let Ok(header) = BbHeader::parse(frame) else {
+            self.errors += 1;
+            return;
+        };
+        let Some(data) = frame.get(BBHEADER_SIZE .. header.frame_size()) else {
+            self.errors += 1;
+            return;
+        };
+        ts.extend(self.ts.push(&BbFrame {
+            header,
+            data: data.to_vec(),
+        }));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bbframe::crc8;
+
+    const T2MI_PID: u16 = 0x1000;
+
+    fn ts_packet(pid: u16, fill: u8) -> Vec<u8> {
+        let mut packet = vec![0x47, (pid >> 8) as u8, pid as u8, 0x10];
+        packet.resize(188, fill);
+        packet
+    }
+
+    /// A T2-MI baseband frame packet of PLP `plp` carrying `packets` in
+    /// normal mode, the first of them starting the data field.
+    fn bbframe_packet(plp: u8, packets: &[Vec<u8>]) -> Vec<u8> {
+        let mut data = Vec::new();
+        let mut crc = 0;
+        for packet in packets {
+            data.push(crc);
+            data.extend_from_slice(&packet[1 ..]);
+            crc = crc8(&packet[1 ..]);
+        }
+
+        // TS, multiple PLPs, CCM
+        let mut frame = vec![0xD0, plp, 0x05, 0xE0];
+        frame.extend_from_slice(&((data.len() * 8) as u16).to_be_bytes());
+        frame.extend_from_slice(&[0x47, 0x00, 0x00]);
+        frame.push(crc8(&frame));
+        frame.extend_from_slice(&data);
+
+        let mut payload = vec![0x00, plp, 0x80];
+        payload.extend_from_slice(&frame);
+
+        let mut packet = vec![T2MI_BASEBAND_FRAME, 0x00, 0x00, 0x00];
+        packet.extend_from_slice(&((payload.len() * 8) as u16).to_be_bytes());
+        packet.extend_from_slice(&payload);
+        let crc = crc32b(&packet);
+        packet.extend_from_slice(&crc.to_be_bytes());
+        packet
+    }
+
+    /// The T2-MI packets in TS packets on the T2-MI PID, each starting in a
+    /// new TS packet, with stuffing after the last one.
+    fn outer_ts(t2mi: &[Vec<u8>]) -> Vec<u8> {
+        let mut ts = Vec::new();
+        let mut continuity = 0;
+        for packet in t2mi {
+            let mut data = vec![0x00];
+            data.extend_from_slice(packet);
+            for (i, chunk) in data.chunks(184).enumerate() {
+                let start = if i == 0 { 0x40 } else { 0x00 };
+                ts.extend_from_slice(&[0x47, start | 0x10, 0x00, 0x10 | continuity]);
+                ts.extend_from_slice(chunk);
+                ts.resize(ts.len() + 184 - chunk.len(), 0xFF);
+                continuity = (continuity + 1) & 0x0F;
+            }
+            // a packet of another PID in between
+            ts.extend_from_slice(&ts_packet(0x0100, 0x00));
+        }
+        ts
+    }
+
+    #[test]
+    fn extracts_one_plp() {
+        let plp0: Vec<Vec<u8>> = (0 .. 3).map(|i| ts_packet(0x200 + i, i as u8)).collect();
+        let plp1: Vec<Vec<u8>> = (0 .. 2).map(|i| ts_packet(0x300 + i, 0x80)).collect();
+        let stream = outer_ts(&[
+            bbframe_packet(0, &plp0[.. 2]),
+            bbframe_packet(1, &plp1),
+            bbframe_packet(0, &plp0[2 ..]),
+        ]);
+
+        let mut decoder = T2miDecoder::new(T2MI_PID, 0);
+        let mut ts = Vec::new();
+        for chunk in stream.chunks(1000) {
+            ts.extend(decoder.push(chunk));
+        }
+        assert_eq!(ts, plp0.concat());
+        assert_eq!(decoder.plps(), [0, 1]);
+        assert_eq!(decoder.errors(), 0);
+        assert_eq!(decoder.crc_errors(), 0);
+
+        let mut decoder = T2miDecoder::new(T2MI_PID, 1);
+        assert_eq!(decoder.push(&stream), plp1.concat());
+    }
+
+    #[test]
+    fn lost_packet_drops_the_t2mi_packet() {
+        let plp0: Vec<Vec<u8>> = (0 .. 4).map(|i| ts_packet(0x200 + i, i as u8)).collect();
+        let mut stream = outer_ts(&[
+            bbframe_packet(0, &plp0[.. 2]),
+            bbframe_packet(0, &plp0[2 ..]),
+        ]);
+        // the second TS packet of the first T2-MI packet
+        stream.drain(188 .. 376);
+
+        let mut decoder = T2miDecoder::new(T2MI_PID, 0);
+        assert_eq!(decoder.push(&stream), plp0[2 ..].concat());
+        assert_eq!(decoder.errors(), 1);
+    }
+}
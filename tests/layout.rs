@@ -14,7 +14,11 @@ use libdvb::{
         CaPid,
         CaSlotInfo,
     },
-    dmx::sys::DmxPesFilterParams,
+    dmx::sys::{
+        DmxFilter,
+        DmxPesFilterParams,
+        DmxSctFilterParams,
+    },
     net::sys::DvbNetIf,
 };
 
@@ -24,6 +28,15 @@ fn dmx() {
     assert_eq!(size_of::<DmxPesFilterParams>(), 20);
     assert_eq!(offset_of!(DmxPesFilterParams, input), 4);
     assert_eq!(offset_of!(DmxPesFilterParams, flags), 16);
+    // struct dmx_filter
+    assert_eq!(size_of::<DmxFilter>(), 48);
+    assert_eq!(offset_of!(DmxFilter, mask), 16);
+    assert_eq!(offset_of!(DmxFilter, mode), 32);
+    // struct dmx_sct_filter_params
+    assert_eq!(size_of::<DmxSctFilterParams>(), 60);
+    assert_eq!(offset_of!(DmxSctFilterParams, filter), 2);
+    assert_eq!(offset_of!(DmxSctFilterParams, timeout), 52);
+    assert_eq!(offset_of!(DmxSctFilterParams, flags), 56);
 }
 
 #[test]
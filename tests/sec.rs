@@ -1,13 +1,25 @@
 use std::time::Duration;
 
 use libdvb::fe::{
+    CalibratedLnb,
+    CascadeConfig,
+    CascadeLevel,
     DiseqcSwitchConfig,
+    DualBandSelect,
     Lnb,
+    PositionerCommand,
+    PositionerConfig,
+    PositionerDirection,
+    PositionerDrive,
     SecCommand,
     SecConfig,
+    SecTemplate,
     SecTimings,
     ToneburstConfig,
     UnicableConfig,
+    UsalsConfig,
+    UserBand,
+    format_sec_sequence,
     sec_sequence,
     sys::{
         SecMiniCmd,
@@ -77,6 +89,160 @@ fn lnb_auto_picks_the_lnb_of_the_band() {
     assert_eq!(Lnb::auto(HIGH_BAND), Some(UNIVERSAL));
 }
 
+#[test]
+fn lnb_wideband_and_ka_convert_without_polarization() {
+    let wideband = Lnb::Wideband { lof_mhz: 10_400 };
+    assert_eq!(wideband.intermediate(10_700).unwrap(), (300, SecTone::Off));
+    assert_eq!(
+        wideband.intermediate(12_750).unwrap(),
+        (2_350, SecTone::Off)
+    );
+    assert_eq!(wideband.band_voltage(12_750), None);
+
+    let ka = Lnb::auto(20_185).unwrap();
+    assert_eq!(
+        ka,
+        Lnb::Ka {
+            bands: Lnb::KA_BANDS
+        }
+    );
+    assert_eq!(ka.intermediate(20_185).unwrap(), (1_935, SecTone::Off));
+    assert_eq!(ka.band_voltage(20_185), Some(SecVoltage::V13));
+    assert_eq!(ka.intermediate(21_400).unwrap(), (1_150, SecTone::On));
+    assert_eq!(ka.band_voltage(21_400), Some(SecVoltage::V13));
+    assert!(ka.intermediate(22_200).is_err());
+    assert_eq!(ka.band_voltage(22_200), None);
+
+    // the band voltage replaces the polarization voltage of the config
+    let tune = sec_sequence(
+        18_700,
+        ka,
+        SecConfig::Lnb {
+            voltage: SecVoltage::V13,
+        },
+        SecTimings::default(),
+    )
+    .unwrap();
+    assert_eq!(tune.frontend_frequency_khz, 1_450_000);
+    assert_eq!(
+        tune.sec_sequence
+            .iter()
+            .filter(|c| matches!(c, SecCommand::SetVoltage(_) | SecCommand::SetTone(_)))
+            .collect::<Vec<_>>(),
+        [
+            &SecCommand::SetTone(SecTone::Off),
+            &SecCommand::SetVoltage(SecVoltage::V18),
+            &SecCommand::SetTone(SecTone::On),
+        ]
+    );
+}
+
+#[test]
+fn lnb_dual_band_selects_c_or_ku() {
+    // the tone selects the band, the voltage stays the polarization
+    assert_eq!(
+        Lnb::C_KU.intermediate(3_800).unwrap(),
+        (1_350, SecTone::Off)
+    );
+    assert_eq!(
+        Lnb::C_KU.intermediate(12_000).unwrap(),
+        (1_250, SecTone::On)
+    );
+    assert_eq!(
+        Lnb::C_KU.intermediate_khz(4_100_000).unwrap(),
+        (1_050_000, SecTone::Off)
+    );
+    assert_eq!(Lnb::C_KU.band_voltage(12_000), None);
+    assert!(Lnb::C_KU.intermediate(10_000).is_err());
+
+    // the voltage selects the band, the tone stays off
+    let by_voltage = Lnb::DualBand {
+        c_lof_mhz: 5_150,
+        ku_lof_mhz: 10_750,
+        select: DualBandSelect::Voltage,
+    };
+    assert_eq!(
+        by_voltage.intermediate(3_800).unwrap(),
+        (1_350, SecTone::Off)
+    );
+    assert_eq!(by_voltage.band_voltage(3_800), Some(SecVoltage::V13));
+    assert_eq!(
+        by_voltage.intermediate(12_000).unwrap(),
+        (1_250, SecTone::Off)
+    );
+    assert_eq!(by_voltage.band_voltage(12_000), Some(SecVoltage::V18));
+
+    let tune = sec_sequence(
+        12_000,
+        by_voltage,
+        SecConfig::Lnb {
+            voltage: SecVoltage::V13,
+        },
+        SecTimings::default(),
+    )
+    .unwrap();
+    assert_eq!(tune.frontend_frequency_khz, 1_250_000);
+    assert!(
+        tune.sec_sequence
+            .contains(&SecCommand::SetVoltage(SecVoltage::V18))
+    );
+
+    // no frequency implies a dual-band LNB on its own
+    assert_eq!(Lnb::auto(3_800), Some(Lnb::CBand { lof_mhz: 5_150 }));
+    assert_eq!(Lnb::auto_dual_band(3_800), Some(Lnb::C_KU));
+    assert_eq!(Lnb::auto_dual_band(12_000), Some(Lnb::C_KU));
+    assert_eq!(Lnb::auto_dual_band(LOW_BAND), None);
+}
+
+#[test]
+fn lnb_lo_offset_shifts_the_intermediate_frequency() {
+    // the oscillator runs 1.3 MHz high: everything comes out 1.3 MHz low
+    let lnb = UNIVERSAL.with_lo_offset(1_300);
+    assert_eq!(lnb.lo_offset_khz, 1_300);
+    assert_eq!(
+        lnb.intermediate_khz(11_832_000).unwrap(),
+        (1_230_700, SecTone::On)
+    );
+    assert_eq!(lnb.intermediate(HIGH_BAND).unwrap(), (1_231, SecTone::On));
+
+    // inverted conversion moves the other way, also on a dual-band LNB
+    let cband = Lnb::CBand { lof_mhz: 5_150 }.with_lo_offset(-250);
+    assert_eq!(
+        cband.intermediate_khz(3_800_000).unwrap(),
+        (1_349_750, SecTone::Off)
+    );
+    let dual = Lnb::C_KU.with_lo_offset(300);
+    assert_eq!(
+        dual.intermediate_khz(3_800_000).unwrap(),
+        (1_350_300, SecTone::Off)
+    );
+    assert_eq!(
+        dual.intermediate_khz(12_000_000).unwrap(),
+        (1_249_700, SecTone::On)
+    );
+
+    // nothing to be off without an oscillator, and a plain LNB is off by 0
+    assert_eq!(
+        Lnb::Passthrough
+            .with_lo_offset(1_300)
+            .intermediate_khz(1_232_000)
+            .unwrap(),
+        (1_232_000, SecTone::Off)
+    );
+    assert_eq!(CalibratedLnb::from(UNIVERSAL), UNIVERSAL.with_lo_offset(0));
+
+    let tune = sec_sequence(
+        HIGH_BAND,
+        lnb,
+        SecConfig::Lnb {
+            voltage: SecVoltage::V18,
+        },
+        SecTimings::default(),
+    )
+    .unwrap();
+    assert_eq!(tune.frontend_frequency_khz, 1_230_700);
+}
+
 #[test]
 fn lnb_auto_converts_every_frequency_it_accepts() {
     for transponder in 0 ..= 30_000u32 {
@@ -182,6 +348,207 @@ fn sec_dsl_rejects_invalid_sequences() {
     }
 }
 
+#[test]
+fn sec_dsl_formatter_round_trips_generated_sequences() {
+    let configs = [
+        SecConfig::Lnb {
+            voltage: SecVoltage::V18,
+        },
+        SecConfig::Switch1_0(DiseqcSwitchConfig {
+            port: 2,
+            voltage: SecVoltage::V13,
+        }),
+        SecConfig::Toneburst(ToneburstConfig {
+            burst: SecMiniCmd::B,
+            voltage: SecVoltage::V18,
+        }),
+        SecConfig::Dsl("t V W200 [E0 10 38 F3] W15 T".to_owned()),
+    ];
+
+    for config in configs {
+        let setup = sec_sequence(HIGH_BAND, UNIVERSAL, config, SecTimings::default()).unwrap();
+        let dsl = setup.to_dsl().unwrap();
+        let reparsed = sec_sequence(
+            HIGH_BAND,
+            UNIVERSAL,
+            SecConfig::Dsl(dsl.clone()),
+            SecTimings::default(),
+        )
+        .unwrap();
+
+        assert_eq!(reparsed.sec_sequence, setup.sec_sequence, "{}", dsl);
+    }
+
+    assert_eq!(
+        format_sec_sequence(&[
+            SecCommand::SetTone(SecTone::Off),
+            SecCommand::SetVoltage(SecVoltage::V13),
+            SecCommand::Wait(Duration::from_millis(15)),
+            SecCommand::SendMasterCommand(vec![0xE0, 0x10, 0x38, 0xF0]),
+            SecCommand::SendBurst(SecMiniCmd::A),
+            SecCommand::SetTone(SecTone::On),
+        ])
+        .unwrap(),
+        "t v W15 [E0 10 38 F0] A T"
+    );
+}
+
+#[test]
+fn sec_dsl_formatter_rejects_what_the_dsl_cannot_express() {
+    for sequence in [
+        vec![SecCommand::SetVoltage(SecVoltage::Off)],
+        vec![SecCommand::Wait(Duration::from_micros(1_500))],
+        vec![SecCommand::SendMasterCommand(vec![0xE0, 0x10])],
+    ] {
+        assert!(format_sec_sequence(&sequence).is_err());
+    }
+}
+
+#[test]
+fn sec_timeline_accumulates_the_waits() {
+    let setup = sec_sequence(
+        HIGH_BAND,
+        UNIVERSAL,
+        SecConfig::Dsl("t V W15 [E0 10 38 F3] W15 B W15 T W100".to_owned()),
+        SecTimings::default(),
+    )
+    .unwrap();
+    let timeline = setup.timeline();
+
+    let offsets: Vec<u128> = timeline
+        .entries
+        .iter()
+        .map(|entry| entry.offset.as_millis())
+        .collect();
+    assert_eq!(offsets, [0, 0, 15, 30, 45]);
+    assert_eq!(timeline.duration, Duration::from_millis(145));
+
+    assert_eq!(
+        timeline.to_string(),
+        "     0.0 ms  tone off\n\
+         \x20    0.0 ms  18V\n\
+         \x20   15.0 ms  DiSEqC [E0 10 38 F3] command, any LNB/switcher/SMATV: write N0 F3 \
+         (port 1, 18V, high band)\n\
+         \x20   30.0 ms  burst B\n\
+         \x20   45.0 ms  tone on\n\
+         \x20  145.0 ms  end\n"
+    );
+}
+
+#[test]
+fn sec_template_expands_placeholders_from_the_lnb() {
+    let template = SecTemplate {
+        sequence: "t {pol} W200 [E0 10 38 {port}] W15 {band}".to_owned(),
+        voltage: SecVoltage::V18,
+        port: 4,
+        user_band: None,
+    };
+
+    let tune = sec_sequence(
+        HIGH_BAND,
+        UNIVERSAL,
+        SecConfig::Template(template.clone()),
+        SecTimings::default(),
+    )
+    .unwrap();
+    let switch = sec_sequence(
+        HIGH_BAND,
+        UNIVERSAL,
+        SecConfig::Switch1_0(DiseqcSwitchConfig {
+            port: 4,
+            voltage: SecVoltage::V18,
+        }),
+        SecTimings::default(),
+    )
+    .unwrap();
+    assert_eq!(tune, switch);
+
+    let tune = sec_sequence(
+        LOW_BAND,
+        UNIVERSAL,
+        SecConfig::Template(template),
+        SecTimings::default(),
+    )
+    .unwrap();
+    assert_eq!(
+        tune.sec_sequence[3],
+        SecCommand::SendMasterCommand(vec![0xE0, 0x10, 0x38, 0xFE])
+    );
+    assert_eq!(tune.sec_sequence[5], SecCommand::SetTone(SecTone::Off));
+
+    // the user band code tunes to the user band, as Unicable1 does
+    let template = SecTemplate {
+        sequence: "v t W5 V W15 [E0 10 5A {ub}] W50 v".to_owned(),
+        voltage: SecVoltage::V13,
+        port: 1,
+        user_band: Some(UserBand {
+            slot: 1,
+            frequency_mhz: 1210,
+        }),
+    };
+    let tune = sec_sequence(
+        LOW_BAND,
+        UNIVERSAL,
+        SecConfig::Template(template),
+        SecTimings::default(),
+    )
+    .unwrap();
+    let unicable = sec_sequence(
+        LOW_BAND,
+        UNIVERSAL,
+        SecConfig::Unicable1(UnicableConfig {
+            slot: 1,
+            user_band_frequency_mhz: 1210,
+            position: 0,
+            voltage: SecVoltage::V13,
+            pin: None,
+        }),
+        SecTimings::default(),
+    )
+    .unwrap();
+    assert_eq!(tune, unicable);
+
+    // XX is a user band byte inside a master command only
+    let vdr = |sequence: &str| {
+        sec_sequence(
+            LOW_BAND,
+            UNIVERSAL,
+            SecConfig::Template(SecTemplate {
+                sequence: sequence.to_owned(),
+                voltage: SecVoltage::V13,
+                port: 1,
+                user_band: Some(UserBand {
+                    slot: 1,
+                    frequency_mhz: 1210,
+                }),
+            }),
+            SecTimings::default(),
+        )
+    };
+    assert_eq!(
+        vdr("v t W5 V W15 [E0 10 5A XX XX] W50 v").unwrap(),
+        unicable
+    );
+    assert!(vdr("v t W5 V W15 XX [E0 10 5A XX XX] W50 v").is_err());
+
+    for sequence in ["{lnb}", "[E0 10 38 {port]", "[E0 10 5A {ub}]"] {
+        assert!(
+            sec_sequence(
+                LOW_BAND,
+                UNIVERSAL,
+                SecConfig::Template(SecTemplate {
+                    sequence: sequence.to_owned(),
+                    voltage: SecVoltage::V13,
+                    port: 1,
+                    user_band: None,
+                }),
+                SecTimings::default(),
+            )
+            .is_err()
+        );
+    }
+}
+
 #[test]
 fn lnb_config_generates_voltage_and_band_tone_sequence() {
     let tune = sec_sequence(
@@ -346,6 +713,98 @@ fn diseqc_1_1_builder_generates_uncommitted_switch_bytes() {
     }
 }
 
+#[test]
+fn cascade_builder_sends_every_level_and_the_repeats() {
+    let tune = sec_sequence(
+        HIGH_BAND,
+        UNIVERSAL,
+        SecConfig::Cascade(CascadeConfig {
+            levels: vec![
+                CascadeLevel::Uncommitted { port: 11 },
+                CascadeLevel::Committed { port: 2 },
+                CascadeLevel::Toneburst(SecMiniCmd::B),
+            ],
+            repeat: 1,
+            voltage: SecVoltage::V18,
+        }),
+        SecTimings::default(),
+    )
+    .unwrap();
+
+    assert_eq!(tune.frontend_frequency_khz, 1_232_000);
+    assert_eq!(
+        tune.sec_sequence,
+        vec![
+            SecCommand::SetTone(SecTone::Off),
+            SecCommand::SetVoltage(SecVoltage::V18),
+            SecCommand::Wait(Duration::from_millis(200)),
+            SecCommand::SendMasterCommand(vec![0xE0, 0x10, 0x39, 0xFA]),
+            SecCommand::Wait(Duration::from_millis(15)),
+            SecCommand::SendMasterCommand(vec![0xE0, 0x10, 0x38, 0xF7]),
+            SecCommand::Wait(Duration::from_millis(15)),
+            SecCommand::Wait(Duration::from_millis(200)),
+            SecCommand::SendMasterCommand(vec![0xE1, 0x10, 0x39, 0xFA]),
+            SecCommand::Wait(Duration::from_millis(15)),
+            SecCommand::SendMasterCommand(vec![0xE1, 0x10, 0x38, 0xF7]),
+            SecCommand::Wait(Duration::from_millis(15)),
+            SecCommand::SendBurst(SecMiniCmd::B),
+            SecCommand::Wait(Duration::from_millis(15)),
+            SecCommand::SetTone(SecTone::On),
+        ]
+    );
+
+    // a single level without repeats matches the dedicated configuration
+    let cascade = sec_sequence(
+        LOW_BAND,
+        UNIVERSAL,
+        SecConfig::Cascade(CascadeConfig {
+            levels: vec![CascadeLevel::Committed { port: 3 }],
+            repeat: 0,
+            voltage: SecVoltage::V13,
+        }),
+        SecTimings::default(),
+    )
+    .unwrap();
+    let switch = sec_sequence(
+        LOW_BAND,
+        UNIVERSAL,
+        SecConfig::Switch1_0(DiseqcSwitchConfig {
+            port: 3,
+            voltage: SecVoltage::V13,
+        }),
+        SecTimings::default(),
+    )
+    .unwrap();
+    assert_eq!(cascade, switch);
+}
+
+#[test]
+fn cascade_builder_rejects_invalid_chains() {
+    for levels in [
+        vec![],
+        vec![CascadeLevel::Committed { port: 5 }],
+        vec![CascadeLevel::Uncommitted { port: 0 }],
+        vec![
+            CascadeLevel::Toneburst(SecMiniCmd::A),
+            CascadeLevel::Committed { port: 1 },
+        ],
+    ] {
+        assert!(
+            sec_sequence(
+                LOW_BAND,
+                UNIVERSAL,
+                SecConfig::Cascade(CascadeConfig {
+                    levels,
+                    repeat: 0,
+                    voltage: SecVoltage::V13,
+                }),
+                SecTimings::default(),
+            )
+            .is_err()
+        );
+    }
+}
+
 #[test]
 fn toneburst_builder_generates_mini_burst_sequence() {
     let tune = sec_sequence(
@@ -537,6 +996,186 @@ fn unicable_2_builder_rejects_invalid_values() {
     );
 }
 
+#[test]
+fn positioner_commands_encode_diseqc_1_2_bytes() {
+    for (command, msg) in [
+        (PositionerCommand::Halt, vec![0xE0, 0x31, 0x60]),
+        (PositionerCommand::DisableLimits, vec![0xE0, 0x31, 0x63]),
+        (
+            PositionerCommand::SetLimit(PositionerDirection::East),
+            vec![0xE0, 0x31, 0x66],
+        ),
+        (
+            PositionerCommand::SetLimit(PositionerDirection::West),
+            vec![0xE0, 0x31, 0x67],
+        ),
+        (
+            PositionerCommand::Drive(PositionerDirection::East, PositionerDrive::Continuous),
+            vec![0xE0, 0x31, 0x68, 0x00],
+        ),
+        (
+            PositionerCommand::Drive(
+                PositionerDirection::West,
+                PositionerDrive::Timed { seconds: 10 },
+            ),
+            vec![0xE0, 0x31, 0x69, 0x0A],
+        ),
+        // steps are sent as a negative count
+        (
+            PositionerCommand::Drive(
+                PositionerDirection::East,
+                PositionerDrive::Steps { count: 1 },
+            ),
+            vec![0xE0, 0x31, 0x68, 0xFF],
+        ),
+        (
+            PositionerCommand::Drive(
+                PositionerDirection::West,
+                PositionerDrive::Steps { count: 128 },
+            ),
+            vec![0xE0, 0x31, 0x69, 0x80],
+        ),
+        (PositionerCommand::Store(7), vec![0xE0, 0x31, 0x6A, 0x07]),
+        (PositionerCommand::Goto(0), vec![0xE0, 0x31, 0x6B, 0x00]),
+        (
+            PositionerCommand::Recalculate,
+            vec![0xE0, 0x31, 0x6F, 0x00, 0x00, 0x00],
+        ),
+    ] {
+        assert_eq!(command.encode().unwrap(), msg, "{command:?}");
+        assert_eq!(
+            command.to_sec_command().unwrap(),
+            SecCommand::SendMasterCommand(msg)
+        );
+    }
+}
+
+#[test]
+fn positioner_commands_reject_out_of_range_values() {
+    for command in [
+        PositionerCommand::Store(0),
+        PositionerCommand::Drive(
+            PositionerDirection::East,
+            PositionerDrive::Timed { seconds: 0 },
+        ),
+        PositionerCommand::Drive(
+            PositionerDirection::East,
+            PositionerDrive::Timed { seconds: 128 },
+        ),
+        PositionerCommand::Drive(
+            PositionerDirection::West,
+            PositionerDrive::Steps { count: 0 },
+        ),
+        PositionerCommand::Drive(
+            PositionerDirection::West,
+            PositionerDrive::Steps { count: 129 },
+        ),
+    ] {
+        assert!(command.encode().is_err(), "{command:?}");
+    }
+}
+
+#[test]
+fn positioner_config_moves_the_dish_before_the_next_config() {
+    let tune = sec_sequence(
+        HIGH_BAND,
+        UNIVERSAL,
+        SecConfig::Positioner(PositionerConfig {
+            position: 3,
+            move_time: Duration::from_secs(20),
+            next: Box::new(SecConfig::Lnb {
+                voltage: SecVoltage::V18,
+            }),
+        }),
+        SecTimings::default(),
+    )
+    .unwrap();
+
+    assert_eq!(tune.frontend_frequency_khz, 1_232_000);
+    assert_eq!(
+        tune.sec_sequence,
+        vec![
+            SecCommand::SetTone(SecTone::Off),
+            SecCommand::SetVoltage(SecVoltage::V18),
+            SecCommand::Wait(Duration::from_millis(200)),
+            SecCommand::SendMasterCommand(vec![0xE0, 0x31, 0x6B, 0x03]),
+            SecCommand::Wait(Duration::from_secs(20)),
+            SecCommand::SetTone(SecTone::Off),
+            SecCommand::SetVoltage(SecVoltage::V18),
+            SecCommand::Wait(Duration::from_millis(100)),
+            SecCommand::SetTone(SecTone::On),
+            SecCommand::Wait(Duration::from_millis(100)),
+        ]
+    );
+
+    // the motor runs on the LNB supply a shared LNB does not provide
+    assert!(
+        sec_sequence(
+            HIGH_BAND,
+            UNIVERSAL,
+            SecConfig::Positioner(PositionerConfig {
+                position: 3,
+                move_time: Duration::from_secs(20),
+                next: Box::new(SecConfig::Shared),
+            }),
+            SecTimings::default(),
+        )
+        .is_err()
+    );
+}
+
+#[test]
+fn usals_config_turns_the_dish_to_the_computed_angle() {
+    let config = UsalsConfig {
+        site_latitude: 520,
+        site_longitude: 50,
+        satellite: 192,
+        from_satellite: Some(282),
+        speed: 20,
+        next: Box::new(SecConfig::Lnb {
+            voltage: SecVoltage::V13,
+        }),
+    };
+
+    // 15.64E from 25.50E at 2 degrees per second
+    let move_time = config.move_time().unwrap();
+    assert!(
+        (move_time.as_secs_f64() - 4.93).abs() < 0.01,
+        "{move_time:?}"
+    );
+
+    let tune = sec_sequence(
+        LOW_BAND,
+        UNIVERSAL,
+        SecConfig::Usals(config.clone()),
+        SecTimings::default(),
+    )
+    .unwrap();
+
+    assert_eq!(tune.frontend_frequency_khz, 1_232_000);
+    assert_eq!(
+        tune.sec_sequence[.. 5],
+        [
+            SecCommand::SetTone(SecTone::Off),
+            SecCommand::SetVoltage(SecVoltage::V13),
+            SecCommand::Wait(Duration::from_millis(200)),
+            SecCommand::SendMasterCommand(vec![0xE0, 0x31, 0x6E, 0xE0, 0xFA]),
+            SecCommand::Wait(move_time),
+        ]
+    );
+
+    // an unknown start position is estimated as a move from the far end of
+    // the arc
+    let unknown = UsalsConfig {
+        from_satellite: None,
+        ..config.clone()
+    };
+    assert!(unknown.move_time().unwrap() > Duration::from_secs(45));
+
+    let stopped = UsalsConfig { speed: 0, ..config };
+    assert!(stopped.move_time().is_err());
+}
+
 #[test]
 fn timings_replace_the_generated_waits() {
     let timings = SecTimings {